@@ -0,0 +1,901 @@
+//! Parsing of `#[delta_struct(...)]` attributes.
+
+use crate::{string_to_fieldtype, FieldType, VALID_FIELD_TYPES};
+use proc_macro2::TokenStream;
+use proc_macro_error::emit_error;
+use quote::ToTokens;
+use syn::{
+    parse::Parse, Attribute, Expr, Ident, Lit, LitStr, Member, Meta, MetaNameValue, NestedMeta,
+    Path, Type,
+};
+
+/// Options given on the deriving struct itself.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    /// Field type used for fields without a `field_type`.
+    pub default: Option<FieldType>,
+    /// Tokens emitted before the generated delta struct.
+    pub delta_leader: TokenStream,
+    /// The `repr` of the generated delta struct, e.g. `C` to share deltas
+    /// with C code.
+    pub repr: Option<TokenStream>,
+    /// A user provided delta type, replacing the generated one.
+    pub output: Option<Type>,
+    /// Tokens emitted before the generated change enum.
+    pub change_leader: TokenStream,
+    /// Whether to implement `MaxEncodedSize` for the delta struct.
+    pub max_size: bool,
+    /// Whether to implement `ContentHash` for the delta struct.
+    pub content_hash: bool,
+    /// Whether to implement conversions to and from `serde_json::Value`.
+    pub json: bool,
+    /// Whether to generate `to_sql_update` on the delta struct.
+    pub sql: bool,
+    /// Whether to implement `mongo::UpdateDocument` for the delta struct.
+    pub mongodb: bool,
+    /// Whether to generate the `extern "C"` functions of the C API, and
+    /// the prefix of their names if it isn't the struct's.
+    pub ffi: Option<Option<LitStr>>,
+    /// Whether to generate the JavaScript bindings, and the prefix of the
+    /// exported functions' names if it isn't the struct's.
+    pub wasm: Option<Option<LitStr>>,
+    /// Whether to implement `json::FieldMap` for the delta struct.
+    pub field_map: bool,
+    /// Whether to generate `size_report` on the delta struct.
+    pub size_report: bool,
+    /// Whether to generate `apply_to_json` on the delta struct.
+    pub apply_json: bool,
+    /// Whether to derive serde's traits for the delta struct, with every
+    /// member defaulted when missing.
+    pub serde: bool,
+    /// Whether the delta struct keeps the fields it doesn't know in an
+    /// `unknown_fields` map when deserialized. Implies `serde`.
+    pub unknown_fields: bool,
+    /// Whether to serialize the delta as a list of change records.
+    pub tagged_changes: bool,
+    /// Whether to generate `{Name}Changed` and `changed_flags`.
+    pub changed_flags: bool,
+    /// Whether to generate `record_into` for `tracing` spans.
+    pub tracing: bool,
+    /// Whether to implement `Project` for the delta struct.
+    pub project: bool,
+    /// Whether to implement `PathIds` for the delta struct, with `paths`
+    /// and `changed_path_ids`.
+    pub path_ids: bool,
+    /// Whether every `unordered` field keeps its `_add` and `_remove`
+    /// vectors sorted, so equal deltas serialize identically.
+    pub sort_unordered: bool,
+    /// The default `duplicates` of the `unordered` fields.
+    pub duplicates: Option<Duplicates>,
+    /// Whether to implement `Schema`, describing the fields at runtime.
+    pub schema: bool,
+    /// Whether deltas carry a `base_fingerprint` of the state they were
+    /// computed from, checked by the generated `apply_checked`.
+    pub fingerprint: bool,
+    /// Whether deltas carry a `delta_id`, checked by the generated
+    /// `apply_once`.
+    pub delta_id: bool,
+    /// The field holding the node arena of a tree or graph, diffed by node
+    /// id, with the other `Vec` fields diffed as `ordered` id lists.
+    pub arena: Option<Ident>,
+    /// Whether the delta struct's members are private, read through
+    /// generated getters and set through `with_*` setters instead.
+    pub encapsulated: bool,
+    /// Whether to implement `arbitrary::Arbitrary` for the delta struct.
+    pub arbitrary: bool,
+    /// Whether to implement `AsyncApplyDelta`.
+    pub async_apply: bool,
+    /// Whether to generate `try_apply_delta`, rejecting unknown removals.
+    pub strict: bool,
+    /// Whether to warn about collection fields diffed as scalars only
+    /// because no `field_type` was given.
+    pub lint: bool,
+    /// Whether to generate `from_delta`, building a value from a delta that
+    /// sets every scalar field.
+    pub from_delta: bool,
+    /// Whether to implement only `ComputeDelta`, leaving out applying.
+    pub compute_only: bool,
+    /// Whether the delta is an enum of a patch and a full replacement.
+    pub with_replace: bool,
+    /// Whether the delta struct is `#[non_exhaustive]`, with a `Default`
+    /// impl and `with_*` setters to construct it.
+    pub non_exhaustive: bool,
+    /// Whether scalar fields are tracked in a presence mask instead of
+    /// `Option`s.
+    pub compact: bool,
+    /// The foreign type this struct mirrors, which gets delta functions on
+    /// the mirror instead of a `Delta` impl.
+    pub remote: Option<Type>,
+    /// Context type for the generated `DeltaWith` impl.
+    pub context: Option<Type>,
+    /// Path to the runtime crate, for when it is re-exported elsewhere.
+    pub krate: Option<Path>,
+    /// Fields left out of deltas, like fields marked `skip`.
+    pub exclude: Vec<Path>,
+    /// Additional delta structs limited to some of the fields, by name.
+    /// `None` includes every field.
+    pub views: Vec<(Ident, Option<Vec<Path>>)>,
+}
+
+/// Options given on a variant of a deriving enum.
+#[derive(Default)]
+pub struct VariantAttrs {
+    /// Whether changes within the variant are ignored, like a skipped field.
+    pub skip: bool,
+    /// Whether switching to the variant carries its fields as their delta
+    /// from the default value of its fields struct, instead of in full.
+    pub from_default: bool,
+    /// The options configuring the variant's fields, which are given to the
+    /// struct generated for them.
+    pub fields: Vec<NestedMeta>,
+}
+
+/// The options of a deriving struct that apply to the fields of an enum's
+/// variants as well. Any other option on an enum or variant is an error.
+const VARIANT_OPTIONS: &[&str] = &["default", "sort_unordered", "duplicates"];
+
+/// Options given on a single field.
+#[derive(Clone, Default)]
+pub struct FieldAttrs {
+    pub field_type: Option<FieldType>,
+    /// Whether the field is left out of deltas entirely.
+    pub skip: bool,
+    /// Tokens emitted before the field(s) on the generated delta struct.
+    pub delta_leader: TokenStream,
+    /// Absolute tolerance for `float` fields.
+    pub epsilon: Option<Expr>,
+    /// Tolerance in units in the last place for `float` fields.
+    pub ulps: Option<Expr>,
+    /// Tolerance in nanoseconds for time `scalar` fields.
+    pub tolerance: Option<u64>,
+    /// Whether a nested delta for a missing `Option` value starts from
+    /// `Default::default()` instead of being dropped.
+    pub create_if_missing: bool,
+    /// The element field identifying elements of a keyed `ordered` or a
+    /// `keyed` field.
+    pub key: Option<Member>,
+    /// The column the field is stored in by `to_sql_update`, if not named
+    /// after the field.
+    pub column: Option<LitStr>,
+    /// The type of `key`, which `keyed` fields name on the delta struct.
+    pub key_type: Option<Type>,
+    /// Whether a nested delta field may also be sent as a full replacement.
+    pub coarse: bool,
+    /// A `DiffStrategy` implementing the field's diffing instead of its
+    /// `field_type`.
+    pub strategy: Option<Type>,
+    /// The `DiffStrategy` of the runtime crate implementing the field's
+    /// `field_type`, as a path relative to the crate.
+    pub runtime_strategy: Option<&'static str>,
+    /// Where the field is applied by `apply_delta`, relative to the other
+    /// fields with an `apply_order`. Fields without one come after them, in
+    /// declaration order.
+    pub apply_order: Option<u32>,
+    /// The most character edits between a removed and an added element of
+    /// an `unordered` field sent as a change of one into the other.
+    pub rename_distance: Option<usize>,
+    /// Whether the `_add` and `_remove` vectors of an `unordered` field are
+    /// kept sorted.
+    pub sort_unordered: bool,
+    /// What applying an `unordered` field does with added elements that are
+    /// already present.
+    pub duplicates: Option<Duplicates>,
+    /// Which way a value may move when a delta is applied.
+    pub monotonic: Option<Monotonic>,
+    /// How the field's value is compressed in serialized deltas.
+    pub compress: Option<Compression>,
+    /// The group of fields the field is sent along with whenever any of
+    /// them changes.
+    pub group: Option<Ident>,
+    /// `fn(&T, &T, &Ctx) -> bool` deciding equality in `DeltaWith::delta_with`.
+    pub eq_with: Option<Path>,
+    /// `fn(&mut T, T, &Ctx)` storing a changed value in
+    /// `DeltaWith::apply_delta_with`.
+    pub apply_with: Option<Path>,
+    /// `fn(&'static str, &Change, &Ctx) -> bool` deciding whether a change
+    /// to the field may be applied by the generated `try_apply_delta_with`.
+    pub guard: Option<Path>,
+    /// `fn(&T) -> impl Future<Output = ()>` awaited after a change to the
+    /// field is applied by `AsyncApplyDelta::apply_delta_async`.
+    pub apply_async: Option<Path>,
+}
+
+/// Values of the `compress` field option, each naming the runtime module
+/// that (de)serializes the field.
+#[derive(Clone, Copy)]
+pub enum Compression {
+    Zstd,
+    Deflate,
+}
+
+/// Values of the `monotonic` field option.
+#[derive(Clone, Copy)]
+pub enum Monotonic {
+    /// Only values greater than the current one are applied.
+    Increase,
+    /// Only values less than the current one are applied.
+    Decrease,
+}
+
+/// Values of the `duplicates` option.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Added elements are extended into the collection regardless, so a
+    /// `Vec` holds them again while a set keeps one of them.
+    Keep,
+    /// Added elements that are already present are dropped, giving set
+    /// semantics whatever the collection.
+    Ignore,
+}
+
+impl ContainerAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut container = Self::default();
+        for meta in delta_struct_metas(attrs) {
+            match &meta {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("default") => {
+                    container.default = parse_field_type(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("delta_leader") => {
+                    container.delta_leader = parse_str(nv).unwrap_or_default();
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("repr") => {
+                    container.repr = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("output") => {
+                    container.output = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("change_leader") => {
+                    container.change_leader = parse_str(nv).unwrap_or_default();
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("max_size") => {
+                    container.max_size = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("content_hash") => {
+                    container.content_hash = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sql") => {
+                    if !cfg!(feature = "sql") {
+                        emit_error!(path, "delta_struct(sql) requires the `sql` feature");
+                    }
+                    container.sql = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("ffi") => {
+                    if !cfg!(feature = "ffi") {
+                        emit_error!(path, "delta_struct(ffi) requires the `ffi` feature");
+                    }
+                    container.ffi = Some(None);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("ffi") => {
+                    if !cfg!(feature = "ffi") {
+                        emit_error!(nv.path, "delta_struct(ffi) requires the `ffi` feature");
+                    }
+                    container.ffi = Some(lit_str(nv).cloned());
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("wasm") => {
+                    if !cfg!(feature = "wasm") {
+                        emit_error!(path, "delta_struct(wasm) requires the `wasm` feature");
+                    }
+                    container.serde = true;
+                    container.wasm = Some(None);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("wasm") => {
+                    if !cfg!(feature = "wasm") {
+                        emit_error!(nv.path, "delta_struct(wasm) requires the `wasm` feature");
+                    }
+                    container.serde = true;
+                    container.wasm = Some(lit_str(nv).cloned());
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("mongodb") => {
+                    if !cfg!(feature = "mongodb") {
+                        emit_error!(path, "delta_struct(mongodb) requires the `mongodb` feature");
+                    }
+                    container.mongodb = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("json") => {
+                    if !cfg!(feature = "serde_json") {
+                        emit_error!(path, "delta_struct(json) requires the `serde_json` feature");
+                    }
+                    container.json = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("size_report") => {
+                    if !cfg!(feature = "serde_json") {
+                        emit_error!(
+                            path,
+                            "delta_struct(size_report) requires the `serde_json` feature"
+                        );
+                    }
+                    container.serde = true;
+                    container.size_report = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("apply_json") => {
+                    if !cfg!(feature = "serde_json") {
+                        emit_error!(
+                            path,
+                            "delta_struct(apply_json) requires the `serde_json` feature"
+                        );
+                    }
+                    container.apply_json = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("field_map") => {
+                    if !cfg!(feature = "serde_json") {
+                        emit_error!(
+                            path,
+                            "delta_struct(field_map) requires the `serde_json` feature"
+                        );
+                    }
+                    container.field_map = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("serde") => {
+                    if !cfg!(feature = "serde") {
+                        emit_error!(path, "delta_struct(serde) requires the `serde` feature");
+                    }
+                    container.serde = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unknown_fields") => {
+                    if !cfg!(feature = "serde_json") {
+                        emit_error!(
+                            path,
+                            "delta_struct(unknown_fields) requires the `serde_json` feature"
+                        );
+                    }
+                    container.serde = true;
+                    container.unknown_fields = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("tagged_changes") => {
+                    if !cfg!(feature = "serde") {
+                        emit_error!(
+                            path,
+                            "delta_struct(tagged_changes) requires the `serde` feature"
+                        );
+                    }
+                    container.tagged_changes = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("tracing") => {
+                    if !cfg!(feature = "tracing") {
+                        emit_error!(path, "delta_struct(tracing) requires the `tracing` feature");
+                    }
+                    container.tracing = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("project") => {
+                    container.project = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("path_ids") => {
+                    container.path_ids = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sort_unordered") => {
+                    container.sort_unordered = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("duplicates") => {
+                    container.duplicates = parse_duplicates(nv);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("schema") => {
+                    container.schema = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("fingerprint") => {
+                    container.fingerprint = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("delta_id") => {
+                    container.delta_id = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("arena") => {
+                    container.arena = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("encapsulated") => {
+                    container.encapsulated = true;
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("views") => {
+                    for nested in &list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::List(view))
+                                if view.path.get_ident().is_some() =>
+                            {
+                                let name = view.path.get_ident().unwrap().clone();
+                                let mut fields = vec![];
+                                let mut all = false;
+                                for field in &view.nested {
+                                    match field {
+                                        NestedMeta::Meta(Meta::Path(path))
+                                            if path.is_ident("all") =>
+                                        {
+                                            all = true;
+                                        }
+                                        NestedMeta::Meta(Meta::Path(path))
+                                            if path.get_ident().is_some() =>
+                                        {
+                                            fields.push(path.clone());
+                                        }
+                                        other => emit_error!(other, "expected a field name"),
+                                    }
+                                }
+                                container
+                                    .views
+                                    .push((name, if all { None } else { Some(fields) }));
+                            }
+                            other => emit_error!(other, "expected a view, as `name(fields...)`"),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("exclude") => {
+                    for nested in &list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                                container.exclude.push(path.clone());
+                            }
+                            other => emit_error!(other, "expected a field name"),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("arbitrary") => {
+                    if !cfg!(feature = "arbitrary") {
+                        emit_error!(
+                            path,
+                            "delta_struct(arbitrary) requires the `arbitrary` feature"
+                        );
+                    }
+                    container.arbitrary = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("changed_flags") => {
+                    container.changed_flags = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("async_apply") => {
+                    container.async_apply = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("lint") => {
+                    container.lint = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("strict") => {
+                    container.strict = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("from_delta") => {
+                    container.from_delta = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("compute_only") => {
+                    container.compute_only = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("with_replace") => {
+                    container.with_replace = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("non_exhaustive") => {
+                    container.non_exhaustive = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("compact") => {
+                    container.compact = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("remote") => {
+                    container.remote = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("context") => {
+                    container.context = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => {
+                    container.krate = parse_str(nv);
+                }
+                _ => unrecognized(&meta),
+            }
+        }
+        container
+    }
+}
+
+/// Reports the options of a deriving enum that aren't supported on enums.
+pub fn check_enum_attrs(attrs: &[Attribute]) {
+    for meta in delta_struct_metas(attrs) {
+        let path = match &meta {
+            NestedMeta::Meta(meta) => meta.path(),
+            NestedMeta::Lit(_) => {
+                unrecognized(&meta);
+                continue;
+            }
+        };
+        let supported = ["crate", "delta_leader", "serde"]
+            .iter()
+            .chain(VARIANT_OPTIONS)
+            .any(|option| path.is_ident(option));
+        if !supported {
+            emit_error!(
+                path,
+                "delta_struct({}) is not supported on enums",
+                path.to_token_stream()
+            );
+        }
+    }
+}
+
+/// Whether a field has `#[delta_struct(skip)]`, without reporting errors in
+/// its other options, which are reported when the field is derived.
+pub fn is_skipped(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("delta_struct"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|meta| matches!(meta, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip")))
+}
+
+impl VariantAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut variant = Self::default();
+        for meta in delta_struct_metas(attrs) {
+            match &meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    variant.skip = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("from_default") => {
+                    variant.from_default = true;
+                }
+                NestedMeta::Meta(meta)
+                    if VARIANT_OPTIONS
+                        .iter()
+                        .any(|option| meta.path().is_ident(option)) =>
+                {
+                    variant.fields.push(NestedMeta::Meta(meta.clone()));
+                }
+                NestedMeta::Meta(meta) => emit_error!(
+                    meta.path(),
+                    "delta_struct({}) is not supported on enum variants",
+                    meta.path().to_token_stream()
+                ),
+                NestedMeta::Lit(_) => unrecognized(&meta),
+            }
+        }
+        variant
+    }
+}
+
+impl FieldAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut field = Self::default();
+        let mut arrays = None;
+        let mut ordering = None;
+        let mut inner = None;
+        for meta in delta_struct_metas(attrs) {
+            match &meta {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("field_type") => {
+                    // Implemented by a strategy of the runtime crate, which
+                    // is only named once its path is known.
+                    match &nv.lit {
+                        Lit::Str(s) if runtime_strategy(&s.value()).is_some() => {
+                            if s.value() == "json" && !cfg!(feature = "serde_json") {
+                                emit_error!(
+                                    s,
+                                    "field_type = \"json\" requires the `serde_json` feature"
+                                );
+                            }
+                            field.runtime_strategy = runtime_strategy(&s.value());
+                            field.field_type = Some(FieldType::Strategy);
+                        }
+                        _ => field.field_type = parse_field_type(nv),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("delta_leader") => {
+                    field.delta_leader = parse_str(nv).unwrap_or_default();
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    field.skip = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("epsilon") => {
+                    field.epsilon = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("ulps") => {
+                    field.ulps = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("tolerance") => {
+                    field.tolerance = lit_str(nv).and_then(parse_duration);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("create_if_missing") => {
+                    field.create_if_missing = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sort_unordered") => {
+                    field.sort_unordered = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("duplicates") => {
+                    field.duplicates = parse_duplicates(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("group") => {
+                    field.group = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("compress") => {
+                    field.compress = parse_compression(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("column") => {
+                    field.column = lit_str(nv).cloned();
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key") => {
+                    field.key = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key_type") => {
+                    field.key_type = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("granularity") => {
+                    if let Some(s) = lit_str(nv) {
+                        match s.value().as_str() {
+                            "fine" => field.coarse = false,
+                            "coarse" => field.coarse = true,
+                            other => emit_error!(
+                                s,
+                                "\"{}\" is not an accepted value, expected \"fine\" or \"coarse\".",
+                                other
+                            ),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("arrays") => {
+                    if let Some(s) = lit_str(nv) {
+                        match s.value().as_str() {
+                            "replace" | "splice" => arrays = Some(s.clone()),
+                            other => emit_error!(
+                                s,
+                                "\"{}\" is not an accepted value, expected \"replace\" or \"splice\".",
+                                other
+                            ),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("ordering") => {
+                    ordering = lit_str(nv).cloned();
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("inner") => {
+                    if let Some(s) = lit_str(nv) {
+                        match s.value().as_str() {
+                            "scalar" | "delta" => inner = Some(s.clone()),
+                            other => emit_error!(
+                                s,
+                                "\"{}\" is not an accepted value, expected \"scalar\" or \"delta\".",
+                                other
+                            ),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("monotonic") => {
+                    if let Some(s) = lit_str(nv) {
+                        match s.value().as_str() {
+                            "increase" => field.monotonic = Some(Monotonic::Increase),
+                            "decrease" => field.monotonic = Some(Monotonic::Decrease),
+                            other => emit_error!(
+                                s,
+                                "\"{}\" is not an accepted value, expected \"increase\" or \"decrease\".",
+                                other
+                            ),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("apply_order") => {
+                    field.apply_order = match &nv.lit {
+                        Lit::Int(int) => match int.base10_parse() {
+                            Ok(order) => Some(order),
+                            Err(e) => {
+                                emit_error!(int, "{}", e);
+                                None
+                            }
+                        },
+                        lit => {
+                            emit_error!(lit, "expected an integer literal");
+                            None
+                        }
+                    };
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename_distance") => {
+                    field.rename_distance = match &nv.lit {
+                        Lit::Int(int) => match int.base10_parse() {
+                            Ok(distance) => Some(distance),
+                            Err(e) => {
+                                emit_error!(int, "{}", e);
+                                None
+                            }
+                        },
+                        lit => {
+                            emit_error!(lit, "expected an integer literal");
+                            None
+                        }
+                    };
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("strategy") => {
+                    field.strategy = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("eq_with") => {
+                    field.eq_with = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("apply_with") => {
+                    field.apply_with = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("guard") => {
+                    field.guard = parse_str(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("apply_async") => {
+                    field.apply_async = parse_str(nv);
+                }
+                _ => unrecognized(&meta),
+            }
+        }
+        if let Some(ordering) = ordering {
+            if field.runtime_strategy != runtime_strategy("atomic") {
+                emit_error!(ordering, "ordering is only supported on atomic fields");
+            } else {
+                field.runtime_strategy = parse_ordering(&ordering);
+            }
+        }
+        if let Some(inner) = inner {
+            if field.runtime_strategy != runtime_strategy("locked") {
+                emit_error!(inner, "inner is only supported on locked fields");
+            } else if inner.value() == "delta" {
+                field.runtime_strategy = Some("lock::NestedLockStrategy");
+            }
+        }
+        if let Some(arrays) = arrays {
+            if field.runtime_strategy != runtime_strategy("json") {
+                emit_error!(arrays, "arrays is only supported on json fields");
+            } else if arrays.value() == "splice" {
+                field.runtime_strategy = Some("value::SplicedValueStrategy");
+            }
+        }
+        if let Some(strategy) = &field.strategy {
+            if field.field_type.is_some() {
+                emit_error!(strategy, "strategy can't be combined with field_type");
+            }
+            field.field_type = Some(FieldType::Strategy);
+        }
+        field
+    }
+}
+
+/// Every option inside every `#[delta_struct(...)]` attribute in `attrs`.
+fn delta_struct_metas(attrs: &[Attribute]) -> Vec<NestedMeta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("delta_struct"))
+        .flat_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested.into_iter().collect(),
+            Ok(other) => {
+                emit_error!(other, "expected delta_struct(...)");
+                vec![]
+            }
+            Err(e) => {
+                emit_error!(e.span(), "{}", e);
+                vec![]
+            }
+        })
+        .collect()
+}
+
+fn unrecognized(meta: &NestedMeta) {
+    emit_error!(meta, "unrecognized delta_struct option");
+}
+
+fn lit_str(nv: &MetaNameValue) -> Option<&LitStr> {
+    match &nv.lit {
+        Lit::Str(s) => Some(s),
+        lit => {
+            emit_error!(lit, "expected a string literal");
+            None
+        }
+    }
+}
+
+/// Parses the contents of a string valued option as `T`.
+fn parse_str<T: Parse>(nv: &MetaNameValue) -> Option<T> {
+    let s = lit_str(nv)?;
+    match s.parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            emit_error!(s, "{}", e);
+            None
+        }
+    }
+}
+
+/// Parses a duration like `"5ms"` into nanoseconds.
+fn parse_duration(s: &LitStr) -> Option<u64> {
+    let value = s.value();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let scale: Option<u64> = match unit {
+        "ns" => Some(1),
+        "us" => Some(1_000),
+        "ms" => Some(1_000_000),
+        "s" => Some(1_000_000_000),
+        "m" => Some(60_000_000_000),
+        "h" => Some(3_600_000_000_000),
+        _ => None,
+    };
+    let nanos = scale.and_then(|scale| amount.parse::<u64>().ok()?.checked_mul(scale));
+    if nanos.is_none() {
+        emit_error!(
+            s,
+            "expected a whole number followed by ns, us, ms, s, m or h, e.g. \"5ms\""
+        );
+    }
+    nanos
+}
+
+/// The strategy of the runtime crate implementing `field_type`, for field
+/// types without code generation of their own.
+fn runtime_strategy(field_type: &str) -> Option<&'static str> {
+    match field_type {
+        "set_map" => Some("set_map::SetMapStrategy"),
+        "sorted" => Some("sorted::SortedStrategy"),
+        "json" => Some("value::ValueStrategy"),
+        "atomic" => Some("atomic::AtomicStrategy"),
+        "locked" => Some("lock::LockStrategy"),
+        _ => None,
+    }
+}
+
+fn parse_ordering(ordering: &LitStr) -> Option<&'static str> {
+    match ordering.value().as_str() {
+        "relaxed" => Some("atomic::RelaxedStrategy"),
+        "acq_rel" => Some("atomic::AcqRelStrategy"),
+        "seq_cst" => Some("atomic::AtomicStrategy"),
+        other => {
+            emit_error!(
+                ordering,
+                "\"{}\" is not an accepted value, expected \"relaxed\", \"acq_rel\" or \"seq_cst\".",
+                other
+            );
+            None
+        }
+    }
+}
+
+fn parse_duplicates(nv: &MetaNameValue) -> Option<Duplicates> {
+    let s = lit_str(nv)?;
+    match s.value().as_str() {
+        "keep" => Some(Duplicates::Keep),
+        "ignore" => Some(Duplicates::Ignore),
+        other => {
+            emit_error!(
+                s,
+                "\"{}\" is not an accepted value, expected \"keep\" or \"ignore\".",
+                other
+            );
+            None
+        }
+    }
+}
+
+fn parse_compression(nv: &MetaNameValue) -> Option<Compression> {
+    let s = lit_str(nv)?;
+    let (compression, feature) = match s.value().as_str() {
+        "zstd" => (Compression::Zstd, cfg!(feature = "zstd")),
+        "deflate" => (Compression::Deflate, cfg!(feature = "flate2")),
+        other => {
+            emit_error!(
+                s,
+                "\"{}\" is not an accepted value, expected \"zstd\" or \"deflate\".",
+                other
+            );
+            return None;
+        }
+    };
+    if !feature {
+        emit_error!(
+            s,
+            "compress = \"{}\" requires the `{}` feature",
+            s.value(),
+            match compression {
+                Compression::Zstd => "zstd",
+                Compression::Deflate => "flate2",
+            }
+        );
+    }
+    Some(compression)
+}
+
+fn parse_field_type(nv: &MetaNameValue) -> Option<FieldType> {
+    let s = lit_str(nv)?;
+    let field_type = string_to_fieldtype(&s.value());
+    if field_type.is_none() {
+        emit_error!(
+            s,
+            "\"{}\" is not an accepted value, expected {}.",
+            s.value(),
+            VALID_FIELD_TYPES
+        );
+    }
+    field_type
+}
@@ -0,0 +1,719 @@
+//! `#[derive(Delta)]` for enums. The fields of each variant are diffed as a
+//! struct generated for them, which is derived like any other struct, so
+//! they accept the same options. A delta either patches the fields of the
+//! variant the value is in, or switches it to another variant:
+//!
+//! ```ignore
+//! #[derive(Clone, Delta)]
+//! enum Connection {
+//!     Connected { address: String, rssi: i8 },
+//!     Disconnected(String),
+//!     Idle,
+//! }
+//!
+//! // Generated:
+//! struct ConnectionConnected { address: String, rssi: i8 }
+//! struct ConnectionDisconnected(String);
+//!
+//! enum ConnectionDelta {
+//!     Connected(ConnectionConnectedDelta),
+//!     Disconnected(ConnectionDisconnectedDelta),
+//!     SetVariant(ConnectionVariant),
+//! }
+//!
+//! enum ConnectionVariant {
+//!     Connected { address: String, rssi: i8 },
+//!     Disconnected(String),
+//!     Idle,
+//! }
+//! ```
+//!
+//! The fields are cloned into their struct to be diffed and patched, so
+//! they must implement `Clone`.
+//!
+//! Switching to a variant carries all of its fields. A variant given
+//! `#[delta_struct(from_default)]` carries their delta from the default
+//! value of its fields struct instead, which is `None` when they all have
+//! their default value, so its fields must implement `Default`:
+//!
+//! ```ignore
+//! enum ConnectionVariant {
+//!     Connected(Option<ConnectionConnectedDelta>),
+//!     ...
+//! }
+//! ```
+
+use crate::attr::{check_enum_attrs, is_skipped, ContainerAttrs, VariantAttrs};
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::emit_error;
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{
+    parse_quote, Attribute, Data, DeriveInput, Fields, GenericParam, Generics, Ident, Index,
+    Member, Path, Type, Visibility,
+};
+
+/// How the fields of a variant are declared.
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Named,
+    Tuple,
+    Unit,
+}
+
+/// A variant of the deriving enum.
+struct EnumVariant {
+    ident: Ident,
+    attrs: VariantAttrs,
+    shape: Shape,
+    fields: Vec<VariantField>,
+    /// The struct the fields are diffed as, unless the variant has none or
+    /// is skipped.
+    fields_ident: Option<Ident>,
+}
+
+/// A field of a variant of the deriving enum.
+struct VariantField {
+    member: Member,
+    ty: Type,
+    attrs: Vec<Attribute>,
+    /// Whether the field has `#[delta_struct(skip)]`. A skipped field keeps
+    /// its value when the variant is patched, and takes its default value
+    /// when the value switches to the variant.
+    skip: bool,
+}
+
+impl EnumVariant {
+    fn kept_fields(&self) -> impl Iterator<Item = (usize, &VariantField)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !field.skip)
+    }
+
+    /// `path` followed by `values` for some of the variant's fields, with
+    /// their names or by position. `rest` ends the list, e.g. with `..`.
+    fn construct<'a>(
+        &self,
+        path: TokenStream,
+        values: impl IntoIterator<Item = (&'a VariantField, TokenStream)>,
+        rest: TokenStream,
+    ) -> TokenStream {
+        let (members, values): (Vec<_>, Vec<_>) = values
+            .into_iter()
+            .map(|(field, value)| (&field.member, value))
+            .unzip();
+        match self.shape {
+            Shape::Named => quote!(#path { #(#members: #values,)* #rest }),
+            Shape::Tuple => quote!(#path(#(#values,)* #rest)),
+            Shape::Unit => path,
+        }
+    }
+
+    /// A pattern of `path`, a path to this variant, binding each field that
+    /// isn't skipped to `{prefix}_{index}`.
+    fn pattern(&self, path: TokenStream, prefix: &str) -> TokenStream {
+        self.construct(
+            path,
+            self.fields.iter().enumerate().map(|(i, field)| {
+                if field.skip {
+                    (field, quote!(_))
+                } else {
+                    (field, binding(prefix, i).into_token_stream())
+                }
+            }),
+            quote! {},
+        )
+    }
+
+    /// The variant of the new-variant enum, or of the deriving enum, at
+    /// `path`, from the fields bound to `{prefix}_{index}`. Skipped fields
+    /// are left out of the new-variant enum, and take their default value
+    /// in the deriving enum.
+    fn build(&self, path: TokenStream, prefix: &str, with_skipped: bool) -> TokenStream {
+        self.construct(
+            path,
+            self.fields
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| with_skipped || !field.skip)
+                .map(|(i, field)| {
+                    if field.skip {
+                        (field, quote!(::std::default::Default::default()))
+                    } else {
+                        (field, binding(prefix, i).into_token_stream())
+                    }
+                }),
+            quote! {},
+        )
+    }
+
+    /// The fields struct of the variant, with each field that isn't skipped
+    /// set by `value` given its binding.
+    fn fields_struct(
+        &self,
+        prefix: &str,
+        value: impl Fn(Ident) -> TokenStream,
+        generic: bool,
+    ) -> TokenStream {
+        let fields_ident = self.fields_ident.as_ref().unwrap();
+        let marker = match (generic, self.shape) {
+            (false, _) => quote! {},
+            (true, Shape::Named) => quote!(_delta_struct_marker: ::std::marker::PhantomData),
+            (true, _) => quote!(::std::marker::PhantomData),
+        };
+        self.construct(
+            quote!(#fields_ident),
+            self.kept_fields()
+                .map(|(i, field)| (field, value(binding(prefix, i)))),
+            marker,
+        )
+    }
+
+    /// A pattern of the fields struct of the variant, binding each field to
+    /// `{prefix}_{index}`.
+    fn fields_pattern(&self, prefix: &str) -> TokenStream {
+        let fields_ident = self.fields_ident.as_ref().unwrap();
+        self.construct(
+            quote!(#fields_ident),
+            self.kept_fields()
+                .map(|(i, field)| (field, binding(prefix, i).into_token_stream())),
+            quote!(..),
+        )
+    }
+}
+
+fn binding(prefix: &str, index: usize) -> Ident {
+    format_ident!("{}_{}", prefix, index, span = Span::mixed_site())
+}
+
+pub(crate) fn expand(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        attrs,
+        vis,
+        ident,
+        generics,
+        data,
+    } = input;
+    let data = match data {
+        Data::Enum(data) => data,
+        _ => unreachable!(),
+    };
+    check_enum_attrs(&attrs);
+    let container = ContainerAttrs::from_attrs(&attrs);
+    let krate: Path = container
+        .krate
+        .clone()
+        .unwrap_or_else(|| parse_quote!(::delta_struct));
+    let generic = !generics.params.is_empty();
+    let variants: Vec<EnumVariant> = data
+        .variants
+        .into_iter()
+        .map(|variant| {
+            let attrs = VariantAttrs::from_attrs(&variant.attrs);
+            let shape = match &variant.fields {
+                Fields::Named(_) => Shape::Named,
+                Fields::Unnamed(_) => Shape::Tuple,
+                Fields::Unit => Shape::Unit,
+            };
+            if shape == Shape::Unit && (!attrs.fields.is_empty() || attrs.from_default) {
+                emit_error!(variant.ident, "unit variants have no fields to configure");
+            }
+            if attrs.skip && attrs.from_default {
+                emit_error!(variant.ident, "from_default can't be combined with skip");
+            }
+            let fields = variant
+                .fields
+                .into_iter()
+                .enumerate()
+                .map(|(i, field)| VariantField {
+                    member: match field.ident {
+                        Some(ident) => Member::Named(ident),
+                        None => Member::Unnamed(Index::from(i)),
+                    },
+                    ty: field.ty,
+                    skip: is_skipped(&field.attrs),
+                    attrs: field.attrs,
+                })
+                .collect();
+            let fields_ident = if shape == Shape::Unit || attrs.skip {
+                None
+            } else {
+                Some(format_ident!("{}{}", ident, variant.ident))
+            };
+            EnumVariant {
+                ident: variant.ident,
+                attrs,
+                shape,
+                fields,
+                fields_ident,
+            }
+        })
+        .collect();
+    if let Some(variant) = variants
+        .iter()
+        .find(|variant| variant.ident == "SetVariant")
+    {
+        emit_error!(
+            variant.ident,
+            "a variant named `SetVariant` would clash with the one of the delta"
+        );
+    }
+    let delta_ident = format_ident!("{}Delta", ident);
+    let variant_ident = format_ident!("{}Variant", ident);
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    let mut delta_leader = container.delta_leader;
+    if container.serde {
+        let serde_crate = quote!(#krate::serde).to_string();
+        delta_leader = quote! {
+            #[derive(#krate::serde::Serialize, #krate::serde::Deserialize)]
+            #[serde(crate = #serde_crate)]
+            #delta_leader
+        };
+    }
+
+    let mut delta_generics = generics.clone();
+    let mut compute_generics = generics.clone();
+    let mut apply_generics = generics.clone();
+    for variant in &variants {
+        if let Some(fields_ident) = &variant.fields_ident {
+            let fields_ty = quote!(#fields_ident #ty_generics);
+            delta_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#fields_ty: #krate::ComputeDelta));
+            compute_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#fields_ty: #krate::ComputeDelta));
+            apply_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#fields_ty: #krate::Delta));
+            if variant.attrs.from_default {
+                for generics in [&mut compute_generics, &mut apply_generics] {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(parse_quote!(#fields_ty: ::std::default::Default));
+                }
+            }
+            for (_, field) in variant.kept_fields() {
+                let ty = &field.ty;
+                for generics in [&mut compute_generics, &mut apply_generics] {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(parse_quote!(#ty: ::std::clone::Clone));
+                }
+            }
+        }
+        for field in variant.fields.iter().filter(|field| field.skip) {
+            let ty = &field.ty;
+            apply_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#ty: ::std::default::Default));
+        }
+    }
+
+    let structs = fields_structs(&attrs, &vis, &ident, &generics, &variants);
+    let patches = variants.iter().filter_map(|variant| {
+        let fields_ident = variant.fields_ident.as_ref()?;
+        let variant = &variant.ident;
+        let doc = format!(
+            "The changes to the fields of [`{}::{}`], which the value stays.",
+            ident, variant
+        );
+        Some(quote! {
+            #[doc = #doc]
+            #variant(<#fields_ident #ty_generics as #krate::ComputeDelta>::Output),
+        })
+    });
+    let new_variants = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        if variant.attrs.from_default {
+            let fields_ident = &variant.fields_ident;
+            return quote! {
+                #ident(::std::option::Option<<#fields_ident #ty_generics as #krate::ComputeDelta>::Output>),
+            };
+        }
+        let types = variant.kept_fields().map(|(_, field)| &field.ty);
+        match variant.shape {
+            Shape::Named => {
+                let members = variant.kept_fields().map(|(_, field)| &field.member);
+                quote!(#ident { #(#members: #types,)* },)
+            }
+            Shape::Tuple => quote!(#ident(#(#types,)*),),
+            Shape::Unit => quote!(#ident,),
+        }
+    });
+    let delta_doc = format!("The changes between two [`{}`]s.", ident);
+    let variant_doc = format!(
+        "A [`{}`] variant with the values of its fields, but for skipped \
+         ones, which take their default value. `from_default` variants hold \
+         the delta of their fields from their default value.",
+        ident
+    );
+    let delta_where_clause = &delta_generics.where_clause;
+    let compute = compute_impl(
+        &krate,
+        &ident,
+        &delta_ident,
+        &variant_ident,
+        &variants,
+        &compute_generics,
+        generic,
+    );
+    let apply = apply_impl(
+        &krate,
+        &ident,
+        &delta_ident,
+        &variant_ident,
+        &variants,
+        &apply_generics,
+        generic,
+    );
+    quote! {
+        #structs
+
+        #[doc = #delta_doc]
+        #delta_leader
+        #vis enum #delta_ident #generics #delta_where_clause {
+            #(#patches)*
+            /// The value switched to another variant.
+            SetVariant(#variant_ident #ty_generics),
+        }
+
+        #[doc = #variant_doc]
+        #delta_leader
+        #vis enum #variant_ident #generics #delta_where_clause {
+            #(#new_variants)*
+        }
+
+        #compute
+        #apply
+    }
+}
+
+/// The structs the fields of each diffed variant are diffed as, and their
+/// derived code. The enum's options and the variant's own are given to the
+/// struct, and each field keeps its options.
+fn fields_structs(
+    attrs: &[Attribute],
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    variants: &[EnumVariant],
+) -> TokenStream {
+    let enum_attrs: Vec<&Attribute> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("delta_struct"))
+        .collect();
+    // A variant may not use every parameter of the enum, which its struct
+    // must still use.
+    let marker_types = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            Some(quote!(#ident))
+        }
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            Some(quote!(&#lifetime ()))
+        }
+        GenericParam::Const(_) => None,
+    });
+    let marker_ty = quote!(::std::marker::PhantomData<fn() -> (#(#marker_types,)*)>);
+    let where_clause = &generics.where_clause;
+    variants
+        .iter()
+        .filter_map(|variant| {
+            let fields_ident = variant.fields_ident.as_ref()?;
+            let variant_options = &variant.attrs.fields;
+            // The struct as declared, or as given to the derive, with the
+            // options of the enum, the variant and the fields.
+            let item = |derive: bool| {
+                let fields = variant.kept_fields().map(|(_, field)| {
+                    let attrs = if derive { &field.attrs[..] } else { &[] };
+                    let ty = &field.ty;
+                    match &field.member {
+                        Member::Named(name) => quote!(#(#attrs)* #name: #ty),
+                        Member::Unnamed(_) => quote!(#(#attrs)* #ty),
+                    }
+                });
+                let options = if derive {
+                    quote! {
+                        #(#enum_attrs)*
+                        #[delta_struct(#(#variant_options),*)]
+                    }
+                } else {
+                    quote! {}
+                };
+                let skip = if derive {
+                    quote!(#[delta_struct(skip)])
+                } else {
+                    quote! {}
+                };
+                let marker = match (generics.params.is_empty(), variant.shape) {
+                    (true, _) => quote! {},
+                    (false, Shape::Named) => quote!(#skip _delta_struct_marker: #marker_ty),
+                    (false, _) => quote!(#skip #marker_ty),
+                };
+                match variant.shape {
+                    Shape::Named => quote! {
+                        #options
+                        #vis struct #fields_ident #generics #where_clause {
+                            #(#fields,)*
+                            #marker
+                        }
+                    },
+                    _ => quote! {
+                        #options
+                        #vis struct #fields_ident #generics (#(#fields,)* #marker) #where_clause;
+                    },
+                }
+            };
+            let derived = crate::expand(syn::parse2(item(true)).unwrap());
+            let declared = item(false);
+            let doc = format!(
+                "The fields of a [`{}::{}`], which its delta diffs as a struct.",
+                ident, variant.ident
+            );
+            let derives = if variant.attrs.from_default {
+                quote!(#[derive(Clone, Default)])
+            } else {
+                quote!(#[derive(Clone)])
+            };
+            Some(quote! {
+                #[doc = #doc]
+                #derives
+                #declared
+                #derived
+            })
+        })
+        .collect()
+}
+
+fn compute_impl(
+    krate: &Path,
+    ident: &Ident,
+    delta_ident: &Ident,
+    variant_ident: &Ident,
+    variants: &[EnumVariant],
+    generics: &Generics,
+    generic: bool,
+) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut deltas = TokenStream::new();
+    let mut counts = TokenStream::new();
+    for variant in variants {
+        let variant_name = &variant.ident;
+        let old = variant.pattern(quote!(#ident::#variant_name), "old");
+        let new = variant.pattern(quote!(#ident::#variant_name), "new");
+        match &variant.fields_ident {
+            Some(_) => {
+                let old_fields = variant.fields_struct("old", |field| quote!(#field), generic);
+                let new_fields = variant.fields_struct("new", |field| quote!(#field), generic);
+                let old_cloned = variant.fields_struct(
+                    "old",
+                    |field| quote!(::std::clone::Clone::clone(#field)),
+                    generic,
+                );
+                let new_cloned = variant.fields_struct(
+                    "new",
+                    |field| quote!(::std::clone::Clone::clone(#field)),
+                    generic,
+                );
+                deltas.extend(quote_spanned! {Span::mixed_site()=>
+                    (#old, #new) => #krate::ComputeDelta::delta(#old_fields, #new_fields)
+                        .map(#delta_ident::#variant_name),
+                });
+                counts.extend(quote_spanned! {Span::mixed_site()=>
+                    (#old, #new) => #krate::ComputeDelta::change_count(&#old_cloned, &#new_cloned, limit),
+                });
+            }
+            None => {
+                deltas.extend(quote_spanned! {Span::mixed_site()=>
+                    (#ident::#variant_name { .. }, #ident::#variant_name { .. }) => ::std::option::Option::None,
+                });
+                counts.extend(quote_spanned! {Span::mixed_site()=>
+                    (#ident::#variant_name { .. }, #ident::#variant_name { .. }) => 0,
+                });
+            }
+        }
+    }
+    let switches = variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let new = variant.pattern(quote!(#ident::#variant_name), "new");
+        if variant.attrs.from_default {
+            let fields = variant.fields_struct("new", |field| quote!(#field), generic);
+            return quote_spanned! {Span::mixed_site()=>
+                #new => #variant_ident::#variant_name(#krate::ComputeDelta::delta(
+                    ::std::default::Default::default(),
+                    #fields,
+                )),
+            };
+        }
+        let value = variant.build(quote!(#variant_ident::#variant_name), "new", false);
+        quote_spanned! {Span::mixed_site()=>
+            #new => #value,
+        }
+    });
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::ComputeDelta for #ident #ty_generics #where_clause {
+            type Output = #delta_ident #ty_generics;
+
+            fn delta(old: Self, new: Self) -> ::std::option::Option<Self::Output> {
+                match (old, new) {
+                    #deltas
+                    #[allow(unreachable_patterns)]
+                    (_, new) => ::std::option::Option::Some(#delta_ident::SetVariant(match new {
+                        #(#switches)*
+                    })),
+                }
+            }
+
+            fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+                match (old, new) {
+                    #counts
+                    #[allow(unreachable_patterns)]
+                    _ => 1,
+                }
+            }
+        }
+    }
+}
+
+fn apply_impl(
+    krate: &Path,
+    ident: &Ident,
+    delta_ident: &Ident,
+    variant_ident: &Ident,
+    variants: &[EnumVariant],
+    generics: &Generics,
+    generic: bool,
+) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut patches = TokenStream::new();
+    let mut composed = TokenStream::new();
+    for variant in variants
+        .iter()
+        .filter(|variant| variant.fields_ident.is_some())
+    {
+        let variant_name = &variant.ident;
+        let fields_ident = &variant.fields_ident;
+        let current = variant.pattern(quote!(#ident::#variant_name), "current");
+        let cloned = variant.fields_struct(
+            "current",
+            |field| quote!(::std::clone::Clone::clone(#field)),
+            generic,
+        );
+        let patched = variant.fields_pattern("patched");
+        let assignments = variant.kept_fields().map(|(i, _)| {
+            let current = binding("current", i);
+            let patched = binding("patched", i);
+            quote_spanned! {Span::mixed_site()=>
+                *#current = #patched;
+            }
+        });
+        patches.extend(quote_spanned! {Span::mixed_site()=>
+            #delta_ident::#variant_name(delta) => match self {
+                #current => {
+                    let mut fields = #cloned;
+                    #krate::ApplyDelta::apply_delta(&mut fields, delta);
+                    let #patched = fields;
+                    #(#assignments)*
+                }
+                // A patch of a variant the value is no longer in is skipped.
+                #[allow(unreachable_patterns)]
+                _ => {}
+            },
+        });
+        let compose = quote_spanned! {Span::mixed_site()=>
+            <#fields_ident #ty_generics as #krate::ApplyDelta<_>>::compose_delta
+        };
+        composed.extend(quote_spanned! {Span::mixed_site()=>
+            (#delta_ident::#variant_name(first), #delta_ident::#variant_name(second)) => {
+                #delta_ident::#variant_name(#compose(first, second))
+            }
+        });
+        if variant.attrs.from_default {
+            // The first delta is from the default value, so the second is
+            // too when there is no first.
+            composed.extend(quote_spanned! {Span::mixed_site()=>
+                (
+                    #delta_ident::SetVariant(#variant_ident::#variant_name(first)),
+                    #delta_ident::#variant_name(second),
+                ) => #delta_ident::SetVariant(#variant_ident::#variant_name(
+                    ::std::option::Option::Some(match first {
+                        ::std::option::Option::Some(first) => #compose(first, second),
+                        ::std::option::Option::None => second,
+                    }),
+                )),
+            });
+            continue;
+        }
+        let set = variant.build(quote!(#variant_ident::#variant_name), "set", false);
+        let fields = variant.fields_struct("set", |field| quote!(#field), generic);
+        let value = variant.build(quote!(#variant_ident::#variant_name), "patched", false);
+        composed.extend(quote_spanned! {Span::mixed_site()=>
+            (#delta_ident::SetVariant(#set), #delta_ident::#variant_name(second)) => {
+                let mut fields = #fields;
+                #krate::ApplyDelta::apply_delta(&mut fields, second);
+                let #patched = fields;
+                #delta_ident::SetVariant(#value)
+            }
+        });
+    }
+    let switches = variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        if variant.attrs.from_default {
+            let fields_ident = &variant.fields_ident;
+            let patched = variant.fields_pattern("patched");
+            let value = variant.build(quote!(#ident::#variant_name), "patched", true);
+            return quote_spanned! {Span::mixed_site()=>
+                #variant_ident::#variant_name(delta) => {
+                    let mut fields = <#fields_ident #ty_generics as ::std::default::Default>::default();
+                    if let ::std::option::Option::Some(delta) = delta {
+                        #krate::ApplyDelta::apply_delta(&mut fields, delta);
+                    }
+                    let #patched = fields;
+                    #value
+                }
+            };
+        }
+        let set = variant.build(quote!(#variant_ident::#variant_name), "set", false);
+        let value = variant.build(quote!(#ident::#variant_name), "set", true);
+        quote_spanned! {Span::mixed_site()=>
+            #set => #value,
+        }
+    });
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::ApplyDelta<#delta_ident #ty_generics> for #ident #ty_generics #where_clause {
+            fn apply_delta(&mut self, delta: #delta_ident #ty_generics) {
+                match delta {
+                    #patches
+                    #delta_ident::SetVariant(variant) => {
+                        *self = match variant {
+                            #(#switches)*
+                        };
+                    }
+                }
+            }
+
+            fn compose_delta(
+                first: #delta_ident #ty_generics,
+                second: #delta_ident #ty_generics,
+            ) -> #delta_ident #ty_generics {
+                match (first, second) {
+                    (_, #delta_ident::SetVariant(variant)) => #delta_ident::SetVariant(variant),
+                    #composed
+                    // Applied after `first`, a patch of another variant is
+                    // skipped.
+                    #[allow(unreachable_patterns)]
+                    (first, _) => first,
+                }
+            }
+        }
+    }
+}
@@ -1,238 +1,4117 @@
 extern crate proc_macro;
 
+use attr::{Compression, ContainerAttrs, Duplicates, FieldAttrs, Monotonic};
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenTree};
-use proc_macro_error::abort_call_site;
-use quote::{format_ident, quote};
-use std::{iter::FromIterator, str::FromStr};
+use proc_macro_error::{abort_call_site, abort_if_dirty, emit_error, proc_macro_error};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use std::iter::FromIterator;
 use syn::{
-    parse_macro_input, punctuated::Punctuated, Attribute, Data, DeriveInput, Fields, Ident, Lit,
-    Meta, MetaList, MetaNameValue, NestedMeta, Path, PredicateType, Token, TraitBound,
-    TraitBoundModifier, Type, TypeParamBound, WherePredicate,
+    parse_macro_input, parse_quote, spanned::Spanned, Attribute, Data, DeriveInput, Fields,
+    GenericArgument, Generics, Ident, Index, Member, Path, PathArguments, ReturnType, Type,
+    TypePath, Visibility, WherePredicate,
 };
 
+mod attr;
+mod enums;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum FieldType {
+pub(crate) enum FieldType {
+    Bitset,
+    Float,
     Ordered,
     Unordered,
     Scalar,
+    /// A collection compared as a multiset and replaced wholesale.
+    UnorderedScalar,
+    /// A collection of delta types patched per element, matched by key.
+    Keyed,
     Delta,
+    /// Diffed by the field's `DiffStrategy`.
+    Strategy,
+}
+
+/// A single field of the deriving struct.
+#[derive(Clone)]
+struct DeltaField {
+    /// How the field is accessed on the original struct.
+    member: Member,
+    /// The name of the field (or the prefix of its fields) on the delta
+    /// struct. Carries the span of the original field.
+    ident: Ident,
+    ty: Type,
+    field_type: FieldType,
+    attrs: FieldAttrs,
+    /// The field's bit in the presence mask of a `compact` delta.
+    presence_bit: Option<u32>,
 }
 
-const VALID_FIELD_TYPES: &str = "\"ordered\", \"unordered\", or \"scalar\"";
+pub(crate) const VALID_FIELD_TYPES: &str = "\"ordered\", \"unordered\", \"scalar\", \
+    \"scalar_collection\", \"unordered_scalar\", \"keyed\", \"set_map\", \"sorted\", \"json\", \
+    \"delta\", \"bitset\", \"atomic\", \"locked\", or \"float\"";
 
 #[proc_macro_derive(Delta, attributes(delta_struct))]
+#[proc_macro_error]
 pub fn derive_delta(input: TokenStream) -> TokenStream {
+    TokenStream::from(expand(parse_macro_input!(input as DeriveInput)))
+}
+
+/// The code `#[derive(Delta)]` generates for a struct, as a string literal,
+/// for snapshotting it. Used through `delta_struct_test::expand!`.
+#[doc(hidden)]
+#[proc_macro]
+#[proc_macro_error]
+pub fn expand_to_string(input: TokenStream) -> TokenStream {
+    let expanded = expand(parse_macro_input!(input as DeriveInput)).to_string();
+    TokenStream::from(quote!(#expanded))
+}
+
+fn expand(input: DeriveInput) -> proc_macro2::TokenStream {
+    if let Data::Enum(_) = input.data {
+        return enums::expand(input);
+    }
     let DeriveInput {
         attrs,
         vis,
         ident,
         mut generics,
         data,
-    } = parse_macro_input!(input as DeriveInput);
-    let (default_field_type, delta_leader) = match get_fieldtype_from_attrs(attrs.into_iter(), "default") {
-        Ok((v, delta_leader)) => (v.unwrap_or(FieldType::Scalar), delta_leader),
-        Err(_) => {
-            abort_call_site!(
-                "delta_struct(default = ...) for {} is not an accepted value, expected {}.",
-                ident,
-                VALID_FIELD_TYPES
-            );
+    } = input;
+    let container = ContainerAttrs::from_attrs(&attrs);
+    let default_field_type = container.default.unwrap_or(FieldType::Scalar);
+    let krate = container
+        .krate
+        .clone()
+        .unwrap_or_else(|| parse_quote!(::delta_struct));
+    let field = |member, ident, ty: Type, attrs: &[Attribute]| {
+        let mut attrs = FieldAttrs::from_attrs(attrs);
+        if let Some(strategy) = attrs.runtime_strategy {
+            let strategy: Path = syn::parse_str(strategy).unwrap();
+            attrs.strategy = Some(parse_quote!(#krate::#strategy));
+        }
+        if let (Some(arena), None) = (&container.arena, attrs.field_type) {
+            if ident == *arena {
+                attrs.strategy = Some(parse_quote!(#krate::arena::ArenaStrategy));
+                attrs.field_type = Some(FieldType::Strategy);
+            } else if is_vec(&ty) {
+                attrs.field_type = Some(FieldType::Ordered);
+            }
+        }
+        let mut field_type = attrs.field_type.unwrap_or(default_field_type);
+        if let Some(distance) = attrs.rename_distance {
+            if field_type == FieldType::Unordered {
+                let distance = proc_macro2::Literal::usize_unsuffixed(distance);
+                attrs.strategy = Some(parse_quote!(#krate::unordered::RenameStrategy<#distance>));
+                field_type = FieldType::Strategy;
+            } else {
+                emit_error!(ty, "rename_distance is only supported on unordered fields");
+            }
+        }
+        if field_type == FieldType::Unordered {
+            attrs.sort_unordered |= container.sort_unordered;
+            attrs.duplicates = attrs.duplicates.or(container.duplicates);
+        } else {
+            if attrs.sort_unordered {
+                emit_error!(ty, "sort_unordered is only supported on unordered fields");
+            }
+            if attrs.duplicates.is_some() {
+                emit_error!(ty, "duplicates is only supported on unordered fields");
+            }
+        }
+        DeltaField {
+            member,
+            ident,
+            ty,
+            field_type,
+            attrs,
+            presence_bit: None,
         }
     };
-
-    let (named, fields) = match data {
+    let mut fields: Vec<DeltaField> = match data {
         Data::Struct(strukt) => match strukt.fields {
-            Fields::Named(named) => (
-                true,
-                collect_results(
-                    named.named.into_iter().map(|field| {
-                        (
-                            field.ident.unwrap().to_string(),
-                            field.ty,
-                            get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
-                        )
-                    }),
-                    default_field_type,
-                ),
-            ),
-            Fields::Unnamed(unnamed) => (
-                false,
-                collect_results(
-                    unnamed.unnamed.into_iter().enumerate().map(|(i, field)| {
-                        (
-                            i.to_string(),
-                            field.ty,
-                            get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
-                        )
-                    }),
-                    default_field_type,
-                ),
-            ),
-            Fields::Unit => {
-                (false, Ok(vec![]))
-            }
+            Fields::Named(named) => named
+                .named
+                .into_iter()
+                .map(|f| {
+                    let ident = f.ident.unwrap();
+                    field(Member::Named(ident.clone()), ident, f.ty, &f.attrs)
+                })
+                .collect(),
+            Fields::Unnamed(unnamed) => unnamed
+                .unnamed
+                .into_iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let span = f.ty.span();
+                    let member = Member::Unnamed(Index {
+                        index: i as u32,
+                        span,
+                    });
+                    field(
+                        member,
+                        format_ident!("field_{}", i, span = span),
+                        f.ty,
+                        &f.attrs,
+                    )
+                })
+                .collect(),
+            Fields::Unit => vec![],
         },
         _ => {
             abort_call_site!(
-                "delta_struct::Delta may only be derived for struct types currently. {} is not a struct type."
-            , ident)
+                "delta_struct::Delta may only be derived for structs and enums. {} is a union.",
+                ident
+            )
         }
     };
-    let fields = match fields {
-        Ok(fields) => fields,
-        Err(bad_fields) => {
-            let bad_fields = format!("{:?}", bad_fields);
-            abort_call_site!(
-                "delta_struct(field_type = ...) for fields in {}: {} are not valid values. Expected {}.",
-                ident,
-                bad_fields,
-                VALID_FIELD_TYPES
-            )
+    if let Some(arena) = &container.arena {
+        if !fields.iter().any(|field| field.ident == *arena) {
+            emit_error!(arena, "no field named `{}` to hold the arena", arena);
+        }
+    }
+    for excluded in &container.exclude {
+        let field = fields
+            .iter_mut()
+            .find(|field| matches!(&field.member, Member::Named(name) if excluded.is_ident(name)));
+        match field {
+            Some(field) => field.attrs.skip = true,
+            None => emit_error!(
+                excluded,
+                "no field named `{}` to exclude",
+                quote!(#excluded)
+            ),
         }
+    }
+    // Skipped fields are neither diffed nor applied, so no code is generated
+    // for them at all.
+    fields.retain(|field| !field.attrs.skip);
+    let lints = if container.lint {
+        collection_lints(&fields, container.default)
+    } else {
+        quote! {}
     };
-    let delta_leader = proc_macro2::TokenStream::from_str(&delta_leader).unwrap();
+    for field in &fields {
+        if mentions_trait_object(&field.ty)
+            && !is_unsized(&field.ty)
+            && field.attrs.strategy.is_none()
+            && field.attrs.eq_with.is_none()
+        {
+            emit_error!(
+                field.ty,
+                "field `{}` holds a trait object, which can't be compared or cloned; \
+                 leave it out with `#[delta_struct(skip)]`, compare it with \
+                 `#[delta_struct(eq_with = \"...\")]`, or diff it with \
+                 `#[delta_struct(strategy = \"...\")]`",
+                field.member.to_token_stream()
+            );
+        }
+        if is_unsized(&field.ty) {
+            emit_error!(
+                field.ty,
+                "field `{}` is unsized, but `Delta` moves values of the struct; \
+                 use an owned type such as `Box<[T]>`, `Vec<T>`, `String` or `Box<dyn Trait>`",
+                field.member.to_token_stream()
+            );
+        }
+        if let Some(hook) = &field.attrs.apply_async {
+            if !container.async_apply {
+                emit_error!(hook, "apply_async hooks require delta_struct(async_apply)");
+            }
+        }
+        let hooks = field
+            .attrs
+            .eq_with
+            .iter()
+            .chain(&field.attrs.apply_with)
+            .chain(&field.attrs.guard);
+        for hook in hooks {
+            if container.context.is_none() {
+                emit_error!(
+                    hook,
+                    "context hooks require delta_struct(context = \"...\")"
+                );
+            }
+        }
+        if field.attrs.coarse
+            && (field.field_type != FieldType::Delta || field.attrs.create_if_missing)
+        {
+            emit_error!(
+                field.ty,
+                "granularity = \"coarse\" is only supported on delta fields without create_if_missing"
+            );
+        }
+        if field.attrs.tolerance.is_some() {
+            if field.field_type != FieldType::Scalar {
+                emit_error!(field.ty, "tolerance is only supported on scalar fields");
+            } else if field.attrs.eq_with.is_some() {
+                emit_error!(field.ty, "tolerance can't be combined with eq_with");
+            }
+        }
+        if field.field_type == FieldType::Keyed
+            && (field.attrs.key.is_none() || field.attrs.key_type.is_none())
+        {
+            emit_error!(
+                field.ty,
+                "keyed fields require key = \"...\" and key_type = \"...\""
+            );
+        }
+        if let Some(key_type) = &field.attrs.key_type {
+            if field.field_type != FieldType::Keyed {
+                emit_error!(key_type, "key_type is only supported on keyed fields");
+            }
+        }
+        if container.sql
+            && !matches!(
+                field.field_type,
+                FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+            )
+        {
+            emit_error!(
+                field.ty,
+                "sql only supports scalar and float fields; skip or exclude field `{}`",
+                field_name(&field.member)
+            );
+        }
+        if container.mongodb
+            && !matches!(
+                field.field_type,
+                FieldType::Scalar
+                    | FieldType::Float
+                    | FieldType::UnorderedScalar
+                    | FieldType::Unordered
+                    | FieldType::Delta
+            )
+        {
+            emit_error!(
+                field.ty,
+                "mongodb only supports scalar, float, unordered and delta fields; \
+                 skip or exclude field `{}`",
+                field_name(&field.member)
+            );
+        }
+        if let Some(group) = &field.attrs.group {
+            if !matches!(field.field_type, FieldType::Scalar | FieldType::Float) {
+                emit_error!(group, "group is only supported on scalar and float fields");
+            }
+        }
+        if field.attrs.compress.is_some() {
+            if !container.serde || container.compact {
+                emit_error!(
+                    field.ty,
+                    "compress requires delta_struct(serde), and can't be combined with compact"
+                );
+            }
+            if field.field_type != FieldType::Scalar {
+                emit_error!(field.ty, "compress is only supported on scalar fields");
+            }
+        }
+        if let Some(column) = &field.attrs.column {
+            if !container.sql {
+                emit_error!(column, "column requires delta_struct(sql)");
+            }
+        }
+        if field.attrs.monotonic.is_some()
+            && !matches!(field.field_type, FieldType::Scalar | FieldType::Float)
+        {
+            emit_error!(
+                field.ty,
+                "monotonic is only supported on scalar and float fields"
+            );
+        }
+        if let Some(eq) = &field.attrs.eq_with {
+            if field.field_type != FieldType::Scalar {
+                emit_error!(eq, "eq_with is only supported on scalar fields");
+            }
+        }
+        if let Some(apply) = &field.attrs.apply_with {
+            if !matches!(field.field_type, FieldType::Scalar | FieldType::Float) {
+                emit_error!(
+                    apply,
+                    "apply_with is only supported on scalar and float fields"
+                );
+            }
+        }
+    }
+    if container.compact {
+        check_compact(&container, &fields);
+        for (bit, field) in fields.iter_mut().enumerate() {
+            field.presence_bit = Some(bit as u32);
+        }
+    }
+    if container.with_replace && container.output.is_some() {
+        abort_call_site!("with_replace can't be combined with a custom output type");
+    }
+    if container.remote.is_some()
+        && (container.with_replace || container.context.is_some() || container.output.is_some())
+    {
+        abort_call_site!(
+            "remote can't be combined with with_replace, context or a custom output type"
+        );
+    }
+    if container.remote.is_some()
+        && (container.async_apply || container.strict || container.from_delta)
+    {
+        abort_call_site!("async_apply, strict and from_delta can't be combined with remote");
+    }
+    if container.serde && (container.with_replace || container.output.is_some()) {
+        abort_call_site!(
+            "serde and unknown_fields can't be combined with with_replace or a custom output type"
+        );
+    }
+    if container.serde && container.tagged_changes {
+        abort_call_site!("serde and unknown_fields can't be combined with tagged_changes");
+    }
+    if container.apply_json
+        && (container.with_replace
+            || container.output.is_some()
+            || container.remote.is_some()
+            || container.compute_only)
+    {
+        abort_call_site!(
+            "apply_json can't be combined with with_replace, remote, compute_only or a custom \
+             output type"
+        );
+    }
+    if container.unknown_fields && container.field_map {
+        abort_call_site!("unknown_fields can't be combined with field_map");
+    }
+    if container.unknown_fields {
+        if let Some(field) = fields.iter().find(|field| field.ident == "unknown_fields") {
+            emit_error!(
+                field.ident,
+                "`unknown_fields` is reserved for the fields of a serialized delta this version doesn't know"
+            );
+        }
+    }
+    if container.compute_only
+        && (container.with_replace
+            || container.context.is_some()
+            || container.remote.is_some()
+            || container.async_apply
+            || container.strict
+            || container.from_delta)
+    {
+        abort_call_site!(
+            "compute_only can't be combined with with_replace, context, remote, async_apply, \
+             strict or from_delta"
+        );
+    }
+    if container.output.is_some() && container.repr.is_some() {
+        abort_call_site!("repr can't be combined with a custom output type");
+    }
+    if container.output.is_some() && container.from_delta {
+        abort_call_site!("from_delta can't be combined with a custom output type");
+    }
+    if container.remote.is_some() && fields.iter().any(|field| field.attrs.coarse) {
+        abort_call_site!("granularity = \"coarse\" can't be combined with remote");
+    }
+    if container.output.is_some() && fields.iter().any(|field| field.attrs.coarse) {
+        abort_call_site!("granularity = \"coarse\" can't be combined with a custom output type");
+    }
+    let has_guards = fields.iter().any(|field| field.attrs.guard.is_some());
+    if has_guards && (container.with_replace || container.output.is_some()) {
+        abort_call_site!("guard can't be combined with with_replace or a custom output type");
+    }
+    if !container.views.is_empty()
+        && (container.compact || container.remote.is_some() || !generics.params.is_empty())
+    {
+        abort_call_site!(
+            "views can't be combined with compact or remote, or used on generic structs"
+        );
+    }
+    if container.fingerprint
+        && (container.with_replace
+            || container.output.is_some()
+            || container.remote.is_some()
+            || container.project
+            || container.arbitrary
+            || container.json
+            || container.field_map
+            || container.tagged_changes
+            || container.non_exhaustive)
+    {
+        abort_call_site!(
+            "fingerprint can't be combined with with_replace, remote, project, arbitrary, json, \
+             field_map, tagged_changes, non_exhaustive or a custom output type"
+        );
+    }
+    if container.delta_id
+        && (container.with_replace
+            || container.output.is_some()
+            || container.remote.is_some()
+            || container.project
+            || container.arbitrary
+            || container.json
+            || container.field_map
+            || container.tagged_changes
+            || container.non_exhaustive
+            || container.encapsulated)
+    {
+        abort_call_site!(
+            "delta_id can't be combined with with_replace, remote, project, arbitrary, json, \
+             field_map, tagged_changes, non_exhaustive, encapsulated or a custom output type"
+        );
+    }
+    // Encapsulated deltas are built like `non_exhaustive` ones, which have
+    // no base to fingerprint, and their getters would bypass the presence
+    // mask of compact deltas.
+    if container.encapsulated
+        && (container.compact || container.fingerprint || container.output.is_some())
+    {
+        abort_call_site!(
+            "encapsulated can't be combined with compact, fingerprint or a custom output type"
+        );
+    }
+    if container.sql && (container.compact || container.output.is_some()) {
+        abort_call_site!("sql can't be combined with compact or a custom output type");
+    }
+    if container.ffi.is_some() && (container.remote.is_some() || !generics.params.is_empty()) {
+        abort_call_site!("ffi can't be combined with remote or generic structs");
+    }
+    if container.wasm.is_some() && (container.remote.is_some() || !generics.params.is_empty()) {
+        abort_call_site!("wasm can't be combined with remote or generic structs");
+    }
+    if container.mongodb && (container.compact || container.output.is_some()) {
+        abort_call_site!("mongodb can't be combined with compact or a custom output type");
+    }
+    // The schema is a static, which can't depend on generic parameters.
+    if container.schema && !generics.params.is_empty() {
+        abort_call_site!("schema isn't supported on generic structs");
+    }
+    abort_if_dirty();
     let delta_ident = format_ident!("{}Delta", ident);
-    let delta_fields = delta_fields(named, fields.iter().cloned());
-    let delta_struct = quote! {
-      #delta_leader
-      #vis struct #delta_ident #generics {
-          #delta_fields
-      }
-    };
-    let (delta_compute_let, delta_compute_fields) =
-        delta_compute_fields(named, fields.iter().cloned());
-    let (delta_apply_let, delta_apply_actions) = delta_apply_fields(named, fields.into_iter());
-    let partial_eq_types = generics
-        .type_params()
-        .map(|t| t.ident.clone())
-        .collect::<Vec<_>>();
-    let where_clause = generics.make_where_clause();
-    for ty in partial_eq_types {
-        let mut bounds = Punctuated::new();
-        let mut segments = Punctuated::new();
-        segments.push(Ident::new("std", Span::call_site()).into());
-        segments.push(Ident::new("cmp", Span::call_site()).into());
-        segments.push(Ident::new("PartialEq", Span::call_site()).into());
-        bounds.push(TypeParamBound::Trait(TraitBound {
-            paren_token: None,
-            modifier: TraitBoundModifier::None,
-            lifetimes: None,
-            path: Path {
-                leading_colon: Some(Token!(::)(Span::call_site())),
-                segments,
+    // The delta struct names projections like `<T as Delta>::Output`, which
+    // only resolve with the matching bounds on the struct itself.
+    let type_params: Vec<Ident> = generics.type_params().map(|t| t.ident.clone()).collect();
+    // Const parameters need no bounds, but a field naming one can't be
+    // checked outside the struct's generics either.
+    let generic_params: Vec<Ident> = type_params
+        .iter()
+        .cloned()
+        .chain(generics.const_params().map(|c| c.ident.clone()))
+        .collect();
+    let member_bounds: Vec<WherePredicate> = fields
+        .iter()
+        .filter(|field| mentions_generic_param(&field.ty, &generic_params))
+        .flat_map(|field| member_bounds(&krate, field, container.compute_only))
+        .collect();
+    generics
+        .make_where_clause()
+        .predicates
+        .extend(member_bounds);
+    // Without these, a nested field lacking a `Delta` impl is only reported
+    // against generated code naming `<T as Delta>::Output`.
+    let assert = if container.compute_only {
+        quote!(#krate::assert_compute_delta)
+    } else {
+        quote!(#krate::assert_delta)
+    };
+    let delta_checks: proc_macro2::TokenStream = fields
+        .iter()
+        .filter(|field| field.field_type == FieldType::Delta)
+        .filter(|field| !mentions_generic_param(&field.ty, &generic_params))
+        .map(|field| {
+            let ty = &field.ty;
+            quote_spanned! {ty.span()=>
+                const _: fn() = #assert::<#ty>;
+            }
+        })
+        .collect();
+    // A member missing from a serialized delta didn't change, which lets
+    // older peers read deltas of newer versions and the other way around.
+    let member_leader = if container.serde {
+        quote!(#[serde(default)])
+    } else {
+        quote! {}
+    };
+    let member_vis = if container.encapsulated {
+        quote! {}
+    } else {
+        quote!(pub)
+    };
+    let mut delta_fields = delta_fields(
+        &krate,
+        fields.iter().cloned().map(|mut field| {
+            let leader = std::mem::take(&mut field.attrs.delta_leader);
+            let compress = field.attrs.compress.map(|compression| {
+                let with = match compression {
+                    Compression::Zstd => quote!(#krate::compress::zstd),
+                    Compression::Deflate => quote!(#krate::compress::deflate),
+                }
+                .to_string();
+                quote!(#[serde(with = #with)])
+            });
+            // `null` would read back as unchanged instead of cleared, so an
+            // unchanged `Option` field is left out instead.
+            let nullable = if container.serde && compress.is_none() && is_nullable_member(&field) {
+                let with = quote!(#krate::option::nullable).to_string();
+                quote! {
+                    #[serde(skip_serializing_if = "::std::option::Option::is_none", with = #with)]
+                }
+            } else {
+                quote! {}
+            };
+            field.attrs.delta_leader = quote!(#member_leader #compress #nullable #leader);
+            field
+        }),
+        &member_vis,
+    );
+    if container.compact {
+        delta_fields = quote! {
+            /// Bit `i` is set if the `i`th field changed; unset fields hold
+            /// their default value.
+            #member_leader
+            pub present: u64,
+            #delta_fields
+        };
+    }
+    if container.fingerprint {
+        delta_fields = quote! {
+            #delta_fields
+            /// A hash of the state the delta was computed from, which
+            /// `apply_checked` compares with its target.
+            pub base_fingerprint: u64,
+        };
+    }
+    if container.delta_id {
+        delta_fields = quote! {
+            #delta_fields
+            /// Identifies the delta to `apply_once`, which applies each ID
+            /// at most once.
+            pub delta_id: u64,
+        };
+    }
+    if container.unknown_fields {
+        delta_fields = quote! {
+            #delta_fields
+            /// The fields of a serialized delta this version doesn't know,
+            /// which `apply_delta` ignores.
+            #[serde(flatten)]
+            #member_vis unknown_fields: #krate::json::Map<::std::string::String, #krate::json::Value>,
+        };
+    }
+    let mut delta_leader = container.delta_leader;
+    if container.serde {
+        let serde_crate = quote!(#krate::serde).to_string();
+        delta_leader = quote! {
+            #[derive(#krate::serde::Serialize, #krate::serde::Deserialize)]
+            #[serde(crate = #serde_crate)]
+            #delta_leader
+        };
+    }
+    if container.non_exhaustive {
+        delta_leader = quote! {
+            #delta_leader
+            #[non_exhaustive]
+        };
+    }
+    let views = delta_views(
+        &krate,
+        &vis,
+        &ident,
+        &fields,
+        &container.views,
+        &delta_leader,
+    );
+    // A user provided output type replaces the generated struct entirely.
+    // Declarations keep any default type parameters, but they may not be
+    // repeated where the type is named.
+    let (_, delta_ty_generics, delta_where_clause) = generics.split_for_impl();
+    // With `with_replace` the generated struct is the patch, and the delta
+    // is an enum of it and a full replacement value.
+    let patch_ident = if container.with_replace {
+        format_ident!("{}Patch", ident)
+    } else {
+        delta_ident.clone()
+    };
+    let repr = container.repr.as_ref().map(|repr| quote!(#[repr(#repr)]));
+    let (delta_struct, output_ty) = match &container.output {
+        Some(output) => (quote! {}, quote! { #output }),
+        None => (
+            quote! {
+              #delta_leader
+              #repr
+              #vis struct #patch_ident #generics #delta_where_clause {
+                  #delta_fields
+              }
             },
-        }));
+            quote! { #delta_ident #delta_ty_generics },
+        ),
+    };
+    let replace_enum = if container.with_replace {
+        quote! {
+          #delta_leader
+          #vis enum #delta_ident #generics #delta_where_clause {
+              Patch(#patch_ident #delta_ty_generics),
+              Replace(#ident #delta_ty_generics),
+          }
+        }
+    } else {
+        quote! {}
+    };
+    let max_size = if container.max_size && container.output.is_none() {
+        delta_max_size(&krate, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let content_hash = if container.content_hash && container.output.is_none() {
+        delta_content_hash(&krate, &patch_ident, &generics, &fields, container.compact)
+    } else {
+        quote! {}
+    };
+    let project = if container.project && container.output.is_none() {
+        delta_project(
+            &krate,
+            &patch_ident,
+            &generics,
+            &fields,
+            container.unknown_fields,
+        )
+    } else {
+        quote! {}
+    };
+    let path_ids = if container.path_ids && container.output.is_none() {
+        delta_path_ids(&krate, &vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let getters = if container.encapsulated {
+        delta_getters(
+            &krate,
+            &vis,
+            &patch_ident,
+            &generics,
+            &fields,
+            container.unknown_fields,
+        )
+    } else {
+        quote! {}
+    };
+    let schema = if container.schema {
+        delta_schema(&krate, &ident, &fields)
+    } else {
+        quote! {}
+    };
+    let arbitrary = if container.arbitrary && container.output.is_none() {
+        delta_arbitrary(
+            &krate,
+            &patch_ident,
+            &generics,
+            &fields,
+            container.compact,
+            container.unknown_fields,
+        )
+    } else {
+        quote! {}
+    };
+    let json = if container.json && container.output.is_none() {
+        delta_json(
+            &krate,
+            &patch_ident,
+            &generics,
+            &fields,
+            container.unknown_fields,
+        )
+    } else {
+        quote! {}
+    };
+    let sql = if container.sql {
+        delta_sql(&krate, &vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let mongodb = if container.mongodb {
+        delta_mongodb(&krate, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let ffi = match &container.ffi {
+        Some(prefix) => delta_ffi(&krate, &vis, &ident, &output_ty, prefix.as_ref()),
+        None => quote! {},
+    };
+    let wasm = match &container.wasm {
+        Some(prefix) => delta_wasm(&krate, &vis, &ident, &patch_ident, prefix.as_ref()),
+        None => quote! {},
+    };
+    let field_map = if container.field_map && container.output.is_none() {
+        delta_field_map(&krate, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let apply_json = if container.apply_json {
+        // The members of the delta struct besides those of its fields.
+        let extra = [
+            (container.compact, "present"),
+            (container.fingerprint, "base_fingerprint"),
+            (container.delta_id, "delta_id"),
+            (container.unknown_fields, "unknown_fields"),
+        ];
+        let extra: Vec<_> = extra
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, member)| format_ident!("{}", member))
+            .collect();
+        delta_apply_json(
+            &krate,
+            &vis,
+            &ident,
+            &patch_ident,
+            &generics,
+            &fields,
+            &extra,
+        )
+    } else {
+        quote! {}
+    };
+    let size_report = if container.size_report {
+        delta_size_report(&krate, &vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let tagged = if container.tagged_changes && container.output.is_none() {
+        delta_tagged_serialize(&krate, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let conflicts = if container.output.is_none() {
+        delta_conflicts(&krate, &vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    // Only the first chunk of a split delta applies to the base state.
+    let split = if container.output.is_none() && !container.fingerprint && !container.delta_id {
+        delta_split(
+            &krate,
+            &vis,
+            &patch_ident,
+            &generics,
+            &fields,
+            container.unknown_fields,
+        )
+    } else {
+        quote! {}
+    };
+    let accessors = if container.compact {
+        delta_compact_accessors(&vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let builder =
+        if (container.non_exhaustive || container.encapsulated) && container.output.is_none() {
+            delta_builder(
+                &krate,
+                &vis,
+                &patch_ident,
+                &generics,
+                &fields,
+                container.unknown_fields,
+            )
+        } else {
+            quote! {}
+        };
+    let changed_flags = if container.changed_flags && container.output.is_none() {
+        delta_changed_flags(&krate, &vis, &ident, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let record_into = if container.tracing && container.output.is_none() {
+        delta_record_into(&krate, &vis, &patch_ident, &generics, &fields)
+    } else {
+        quote! {}
+    };
+    let change_enum = if container.output.is_none() {
+        delta_change_enum(
+            &krate,
+            &vis,
+            &ident,
+            &patch_ident,
+            &generics,
+            container.change_leader,
+            fields.iter().cloned(),
+        )
+    } else {
+        quote! {}
+    };
+    let compact = container.compact;
+    let context_impl = container.context.as_ref().map(|context| {
+        let (compute_let, compute_fields) = with_presence(
+            compact,
+            delta_compute_fields(&krate, fields.iter().cloned(), true),
+        );
+        let (apply_let, apply_actions) = delta_apply_fields(
+            &krate,
+            fields.iter().cloned(),
+            true,
+            false,
+            &quote_spanned!(Span::mixed_site()=> self),
+        );
+        let apply_let = if compact {
+            quote_spanned!(Span::mixed_site()=> present, #apply_let)
+        } else {
+            apply_let
+        };
+        (
+            context.clone(),
+            compute_let,
+            compute_fields,
+            apply_let,
+            apply_actions,
+        )
+    });
+    let (delta_compute_let, delta_compute_fields) = with_presence(
+        compact,
+        delta_compute_fields(&krate, fields.iter().cloned(), false),
+    );
+    let (mut delta_compose_let, mut delta_compose_fields) =
+        delta_compose_fields(&krate, fields.iter().cloned());
+    let change_counts = delta_change_counts(&krate, fields.iter().cloned());
+    let has_changes = delta_has_changes(&krate, &fields);
+    // A remote mirror's generated functions apply to a `target` argument of
+    // the foreign type, which they also report to the observer.
+    let (target, observed) = match &container.remote {
+        Some(remote) => (quote_spanned!(Span::mixed_site()=> target), quote!(#remote)),
+        None => (quote_spanned!(Span::mixed_site()=> self), quote!(Self)),
+    };
+    let (mut delta_apply_let, delta_apply_actions) =
+        delta_apply_fields(&krate, fields.iter().cloned(), false, false, &target);
+    let async_apply = container
+        .async_apply
+        .then(|| delta_apply_fields(&krate, fields.iter().cloned(), false, true, &target));
+    if compact {
+        delta_compose_let = quote_spanned! {Span::mixed_site()=>
+            let present = first.present | second.present;
+            #delta_compose_let
+        };
+        delta_compose_fields = quote_spanned!(Span::mixed_site()=> present, #delta_compose_fields);
+        delta_apply_let = quote_spanned!(Span::mixed_site()=> present, #delta_apply_let);
+    }
+    // Unknown fields are only ever read from a serialized delta; composing
+    // keeps those of both deltas, preferring the second.
+    let (mut extra_init, mut extra_ignore) = if container.unknown_fields {
+        delta_compose_let = quote_spanned! {Span::mixed_site()=>
+            let mut unknown_fields = first.unknown_fields;
+            unknown_fields.extend(second.unknown_fields);
+            #delta_compose_let
+        };
+        delta_compose_fields =
+            quote_spanned!(Span::mixed_site()=> unknown_fields, #delta_compose_fields);
+        (
+            quote!(unknown_fields: ::std::default::Default::default(),),
+            quote!(unknown_fields: _,),
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    // A composed delta applies to the base of the first.
+    let fingerprint_let = if container.fingerprint {
+        delta_compose_let = quote_spanned! {Span::mixed_site()=>
+            let base_fingerprint = first.base_fingerprint;
+            #delta_compose_let
+        };
+        delta_compose_fields =
+            quote_spanned!(Span::mixed_site()=> base_fingerprint, #delta_compose_fields);
+        extra_init.extend(quote_spanned!(Span::mixed_site()=> base_fingerprint,));
+        extra_ignore.extend(quote!(base_fingerprint: _,));
+        let hash = fingerprint(&krate, &fields, &quote_spanned!(Span::mixed_site()=> old));
+        quote_spanned! {Span::mixed_site()=>
+            let base_fingerprint = #hash;
+        }
+    } else {
+        quote! {}
+    };
+    // A composed delta is a new delta, so it gets an ID of its own.
+    let delta_id_let = if container.delta_id {
+        delta_compose_fields = quote_spanned!(Span::mixed_site()=> delta_id, #delta_compose_fields);
+        delta_compose_let = quote_spanned! {Span::mixed_site()=>
+            let delta_id = #krate::once::next_id();
+            #delta_compose_let
+        };
+        extra_init.extend(quote_spanned!(Span::mixed_site()=> delta_id,));
+        extra_ignore.extend(quote!(delta_id: _,));
+        quote_spanned! {Span::mixed_site()=>
+            let delta_id = #krate::once::next_id();
+        }
+    } else {
+        quote! {}
+    };
+    let where_clause = generics.make_where_clause();
+    for ty in compared_types(&fields, &type_params) {
         where_clause
             .predicates
-            .push(WherePredicate::Type(PredicateType {
-                lifetimes: None,
-                bounded_ty: Type::Verbatim(<Ident as Into<TokenTree>>::into(ty).into()),
-                colon_token: Token!(:)(Span::call_site()),
-                bounds,
-            }));
+            .push(parse_quote!(#ty: ::std::cmp::PartialEq));
+        if container.fingerprint {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::hash::Hash));
+        }
     }
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    let delta_impl = quote! {
-      impl #impl_generics Delta for #ident #ty_generics #where_clause  {
-          type Output = #delta_ident #generics;
-
-          fn delta(old: Self, new: Self) -> Option<Self::Output> {
-           let mut delta_is_some = false;
-           #delta_compute_let
-           if delta_is_some {
-               Some(Self::Output {
-                #delta_compute_fields
-               })
+    // Struct expressions and patterns for the patch; the bare name lets the
+    // generic arguments be inferred, and also works outside the trait impl.
+    // A custom output type is spelled out, as `Self::Output` only resolves
+    // within the `ComputeDelta` impl.
+    let patch_path = match &container.output {
+        Some(output) => expr_path(output),
+        None => quote!(#patch_ident),
+    };
+    let field_count = fields.len();
+    let with_replace = container.with_replace;
+    let compute_body = |compute_let: proc_macro2::TokenStream,
+                        compute_fields: proc_macro2::TokenStream| {
+        let patch = quote_spanned! {Span::mixed_site()=>
+            #patch_path {
+                #compute_fields
+                #extra_init
+            }
+        };
+        let output = if with_replace {
+            quote_spanned! {Span::mixed_site()=> #delta_ident::Patch(#patch) }
+        } else {
+            patch
+        };
+        quote_spanned! {Span::mixed_site()=>
+           let started = #krate::observer::start();
+           let mut changed_fields = 0usize;
+           #fingerprint_let
+           #delta_id_let
+           #compute_let
+           #krate::observer::finish(
+               started,
+               #krate::observer::Operation::Delta,
+               ::std::any::type_name::<#observed>(),
+               changed_fields,
+           );
+           if changed_fields > 0 {
+               ::std::option::Option::Some(#output)
            } else {
-               None
+               ::std::option::Option::None
            }
+        }
+    };
+    // Every `apply_async` hook, awaited after a full replacement.
+    let replace_hooks: proc_macro2::TokenStream = fields
+        .iter()
+        .filter_map(|field| {
+            let hook = field.attrs.apply_async.as_ref()?;
+            let member = &field.member;
+            Some(quote_spanned!(Span::mixed_site()=> #hook(&self.#member).await;))
+        })
+        .collect();
+    let apply_body = |apply_let: proc_macro2::TokenStream,
+                      apply_actions: proc_macro2::TokenStream,
+                      replaced: proc_macro2::TokenStream| {
+        let apply = if with_replace {
+            quote_spanned! {Span::mixed_site()=>
+                match delta {
+                    #delta_ident::Patch(#patch_ident {
+                        #apply_let
+                        #extra_ignore
+                    }) => {
+                        #apply_actions
+                    }
+                    #delta_ident::Replace(value) => {
+                        applied_fields = #field_count;
+                        *self = value;
+                        #replaced
+                    }
+                }
+            }
+        } else {
+            quote_spanned! {Span::mixed_site()=>
+                let #patch_path {
+                    #apply_let
+                    #extra_ignore
+                } = delta;
+                #apply_actions
+            }
+        };
+        quote_spanned! {Span::mixed_site()=>
+            let started = #krate::observer::start();
+            let mut applied_fields = 0usize;
+            #apply
+            #krate::observer::finish(
+                started,
+                #krate::observer::Operation::Apply,
+                ::std::any::type_name::<#observed>(),
+                applied_fields,
+            );
+        }
+    };
+    let compose_body = if with_replace {
+        quote_spanned! {Span::mixed_site()=>
+            match (first, second) {
+                (#delta_ident::Patch(first), #delta_ident::Patch(second)) => {
+                    #delta_compose_let
+                    #delta_ident::Patch(#patch_ident {
+                        #delta_compose_fields
+                    })
+                }
+                (_, #delta_ident::Replace(value)) => #delta_ident::Replace(value),
+                (#delta_ident::Replace(mut value), patch) => {
+                    #krate::ApplyDelta::apply_delta(&mut value, patch);
+                    #delta_ident::Replace(value)
+                }
+            }
+        }
+    } else {
+        quote_spanned! {Span::mixed_site()=>
+            #delta_compose_let
+            #patch_path {
+                #delta_compose_fields
+            }
+        }
+    };
+    // Coarse fields read `granularity`, which `delta_at` takes from its
+    // caller and everything else fixes to `Fine`.
+    let has_coarse = fields.iter().any(|field| field.attrs.coarse);
+    let (delta_body, delta_at) = if has_coarse {
+        let body = compute_body(delta_compute_let, delta_compute_fields);
+        (
+            quote_spanned! {Span::mixed_site()=>
+                Self::delta_at(old, new, #krate::Granularity::Fine)
+            },
+            quote_spanned! {Span::mixed_site()=>
+                impl #impl_generics #ident #ty_generics #where_clause {
+                    /// Computes the delta from `old` to `new`, with the nested
+                    /// fields marked `granularity = "coarse"` in the given form.
+                    #vis fn delta_at(
+                        old: Self,
+                        new: Self,
+                        granularity: #krate::Granularity,
+                    ) -> ::std::option::Option<#output_ty> {
+                        #body
+                    }
+                }
+            },
+        )
+    } else {
+        (
+            compute_body(delta_compute_let, delta_compute_fields),
+            quote! {},
+        )
+    };
+    let async_impl = async_apply.map(|(apply_let, apply_actions)| {
+        let apply_let = if compact {
+            quote_spanned!(Span::mixed_site()=> present, #apply_let)
+        } else {
+            apply_let
+        };
+        let body = apply_body(apply_let, apply_actions, replace_hooks);
+        quote_spanned! {Span::mixed_site()=>
+          impl #impl_generics #krate::AsyncApplyDelta for #ident #ty_generics #where_clause {
+              async fn apply_delta_async(&mut self, delta: Self::Output) {
+                #body
+              }
           }
-
-          fn apply_delta(&mut self, delta: Self::Output) {
-            let Self::Output {
-                #delta_apply_let
-            } = delta;
-            #delta_apply_actions
-          }
-      }
+        }
+    });
+    let try_apply = if container.strict && container.output.is_none() {
+        let replace = if with_replace {
+            Some(&delta_ident)
+        } else {
+            None
+        };
+        delta_try_apply(
+            &krate, &vis, &ident, &generics, &fields, &output_ty, replace,
+        )
+    } else {
+        quote! {}
     };
-    let output = quote! {
-        #delta_struct
+    let apply_checked = if container.fingerprint {
+        let hash = fingerprint(&krate, &fields, &quote_spanned!(Span::mixed_site()=> self));
+        quote_spanned! {Span::mixed_site()=>
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// A hash of the diffed fields of `self`, as stored in the
+                /// `base_fingerprint` of the deltas computed from it.
+                #vis fn fingerprint(&self) -> u64 {
+                    #hash
+                }
 
-        #delta_impl
+                /// Applies `delta` if `self` is the state it was computed
+                /// from, as far as their fingerprints tell, and otherwise
+                /// leaves `self` as is.
+                #vis fn apply_checked(
+                    &mut self,
+                    delta: #output_ty,
+                ) -> ::std::result::Result<(), #krate::Error> {
+                    let found = self.fingerprint();
+                    if found != delta.base_fingerprint {
+                        return ::std::result::Result::Err(#krate::Error::BaseMismatch {
+                            expected: delta.base_fingerprint,
+                            found,
+                        });
+                    }
+                    #krate::ApplyDelta::apply_delta(self, delta);
+                    ::std::result::Result::Ok(())
+                }
+            }
+        }
+    } else {
+        quote! {}
     };
-    TokenStream::from(output)
-}
+    let apply_once = if container.delta_id {
+        quote_spanned! {Span::mixed_site()=>
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Applies `delta` unless its `delta_id` is already in
+                /// `seen`, recording it there. Returns whether it was
+                /// applied.
+                #vis fn apply_once(
+                    &mut self,
+                    delta: #output_ty,
+                    seen: &mut impl #krate::once::IdSet,
+                ) -> bool {
+                    if !seen.insert(delta.delta_id) {
+                        return false;
+                    }
+                    #krate::ApplyDelta::apply_delta(self, delta);
+                    true
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let from_delta = if container.from_delta {
+        let replace = if with_replace {
+            Some(&delta_ident)
+        } else {
+            None
+        };
+        delta_from_delta(
+            &krate, &vis, &ident, &generics, &fields, &output_ty, replace,
+        )
+    } else {
+        quote! {}
+    };
+    let apply_delta_body = apply_body(delta_apply_let, delta_apply_actions, quote! {});
+    let delta_impl = if let Some(remote) = &container.remote {
+        let members = fields.iter().map(|field| &field.member);
+        quote_spanned! {Span::mixed_site()=>
 
-fn delta_fields(
-    named: bool,
-    iter: impl Iterator<Item = (String, Type, FieldType, String)>,
-) -> proc_macro2::TokenStream {
-    FromIterator::from_iter(iter.map(|(ident, ty, field_ty, field_leader)| {
-        let field_leader = proc_macro2::TokenStream::from_str(&field_leader).unwrap();
-        let ident = if named {
-            format_ident!("{}", ident)
+          impl #impl_generics #ident #ty_generics #where_clause {
+              /// `ComputeDelta::delta` for the foreign type this struct mirrors.
+              #vis fn delta(old: #remote, new: #remote) -> ::std::option::Option<#output_ty> {
+                // The mirror is never constructed, but its fields shouldn't
+                // be reported as unused.
+                let _ = |mirror: &Self| {
+                    #(let _ = &mirror.#members;)*
+                };
+                #delta_body
+              }
+
+              /// `ApplyDelta::apply_delta` for the foreign type this struct mirrors.
+              #vis fn apply_delta(target: &mut #remote, delta: #output_ty) {
+                #apply_delta_body
+              }
+
+              /// `ComputeDelta::change_count` for the foreign type this struct mirrors.
+              #vis fn change_count(old: &#remote, new: &#remote, limit: usize) -> usize {
+                let mut count = 0usize;
+                #change_counts
+                count
+              }
+
+              /// `ApplyDelta::compose_delta` for the foreign type this struct mirrors.
+              #vis fn compose_delta(first: #output_ty, second: #output_ty) -> #output_ty {
+                #compose_body
+              }
+          }
+        }
+    } else {
+        let compute_impl = quote_spanned! {Span::mixed_site()=>
+          impl #impl_generics #krate::ComputeDelta for #ident #ty_generics #where_clause {
+              type Output = #output_ty;
+
+              fn delta(old: Self, new: Self) -> ::std::option::Option<Self::Output> {
+                #delta_body
+              }
+
+              fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+                let mut count = 0usize;
+                #change_counts
+                count
+              }
+
+              #has_changes
+          }
+        };
+        // Custom outputs aren't generated structs.
+        let fields_consts = if container.output.is_none() {
+            let names = fields.iter().map(|field| field_name(&field.member));
+            let field_count = fields.len();
+            quote_spanned! {Span::mixed_site()=>
+              impl #impl_generics #patch_ident #ty_generics #where_clause {
+                  /// The names of the fields, in declaration order.
+                  #vis const FIELDS: &'static [&'static str] = &[#(#names),*];
+
+                  #vis const FIELD_COUNT: usize = #field_count;
+              }
+            }
         } else {
-            format_ident!("field_{}", ident)
+            quote! {}
         };
-        match field_ty {
-            FieldType::Ordered => unimplemented!(),
-            FieldType::Unordered => {
-                let add = format_ident!("{}_add", ident);
-                let remove = format_ident!("{}_remove", ident);
-                quote! {
-                 #field_leader
-                 pub #add: Vec<<#ty as ::std::iter::IntoIterator>::Item>,
-                 #field_leader
-                 pub #remove: Vec<<#ty as ::std::iter::IntoIterator>::Item>,
-                }
+        // The target of a `DeltaOutput` computes and applies it, which
+        // `compute_only` structs don't, and `with_replace` enums aren't
+        // generated structs.
+        let output_impl = if container.output.is_none()
+            && !container.with_replace
+            && !container.compute_only
+        {
+            let field_count = fields.len();
+            let indices = 0..field_count;
+            let changes: Vec<_> = fields
+                .iter()
+                .map(|field| {
+                    field_changed(&krate, field, &quote_spanned!(Span::mixed_site()=> self))
+                })
+                .collect();
+            quote_spanned! {Span::mixed_site()=>
+              impl #impl_generics #krate::Sealed for #patch_ident #ty_generics #where_clause {}
+
+              impl #impl_generics #krate::DeltaOutput for #patch_ident #ty_generics #where_clause {
+                  type Target = #ident #ty_generics;
+
+                  const FIELDS: &'static [&'static str] = Self::FIELDS;
+
+                  fn is_empty(&self) -> bool {
+                    true #(&& !(#changes))*
+                  }
+
+                  #[allow(clippy::match_single_binding)]
+                  fn is_field_changed(&self, index: usize) -> bool {
+                    match index {
+                      #(#indices => #changes,)*
+                      _ => false,
+                    }
+                  }
+              }
             }
-            FieldType::Scalar => {
-                quote! {
-                  #field_leader
-                  pub #ident: ::std::option::Option<#ty>,
-                }
+        } else {
+            quote! {}
+        };
+        if container.compute_only {
+            quote! {
+                #compute_impl
+                #fields_consts
             }
-            FieldType::Delta => {
-                quote! {
-                    #field_leader
-                    pub #ident: ::std::option::Option<<#ty as Delta>::Output>,
-                }
+        } else {
+            quote_spanned! {Span::mixed_site()=>
+              #compute_impl
+
+              #fields_consts
+              #output_impl
+
+              impl #impl_generics #krate::ApplyDelta<#output_ty> for #ident #ty_generics #where_clause {
+                  fn apply_delta(&mut self, delta: #output_ty) {
+                    #apply_delta_body
+                  }
+
+                  fn compose_delta(first: #output_ty, second: #output_ty) -> #output_ty {
+                    #compose_body
+                  }
+              }
+            }
+        }
+    };
+    let context_impl = context_impl.map(
+        |(context, compute_let, compute_fields, apply_let, apply_actions)| {
+            let granularity = if has_coarse {
+                quote_spanned! {Span::mixed_site()=>
+                    let granularity = #krate::Granularity::Fine;
+                }
+            } else {
+                quote! {}
+            };
+            let delta_body = compute_body(compute_let, compute_fields);
+            let apply_delta_body = apply_body(apply_let, apply_actions, quote! {});
+            let try_apply_with = if has_guards {
+                delta_try_apply_with(&krate, &vis, &ident, &generics, &fields, &context)
+            } else {
+                quote! {}
+            };
+            quote_spanned! {Span::mixed_site()=>
+              #try_apply_with
+
+              impl #impl_generics #krate::DeltaWith<#context> for #ident #ty_generics #where_clause {
+                  fn delta_with(old: Self, new: Self, ctx: &#context) -> ::std::option::Option<Self::Output> {
+                    #granularity
+                    #delta_body
+                  }
+
+                  fn apply_delta_with(&mut self, delta: Self::Output, ctx: &#context) {
+                    #apply_delta_body
+                  }
+              }
+            }
+        },
+    );
+    let output = quote! {
+        #delta_checks
+
+        #delta_struct
+
+        #replace_enum
+
+        #accessors
+
+        #builder
+
+        #change_enum
+
+        #changed_flags
+
+        #record_into
+
+        #max_size
+
+        #content_hash
+
+        #arbitrary
+
+        #project
+
+        #path_ids
+
+        #getters
+
+        #lints
+
+        #schema
+
+        #views
+
+        #json
+
+        #sql
+
+        #mongodb
+
+        #ffi
+
+        #wasm
+
+        #field_map
+        #size_report
+        #apply_json
+
+        #tagged
+
+        #conflicts
+
+        #split
+
+        #delta_impl
+
+        #delta_at
+
+        #context_impl
+
+        #async_impl
+
+        #try_apply
+
+        #apply_checked
+
+        #apply_once
+
+        #from_delta
+    };
+    output
+}
+
+/// Generates `try_apply_delta`, which only applies a delta if every element
+/// it removes from an unordered field is present. With `with_replace`, the
+/// delta enum is `replace` and only its patches are checked.
+fn delta_try_apply(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    output_ty: &proc_macro2::TokenStream,
+    replace: Option<&Ident>,
+) -> proc_macro2::TokenStream {
+    let unordered: Vec<&DeltaField> = fields
+        .iter()
+        .filter(|field| field.field_type == FieldType::Unordered)
+        .collect();
+    let checks: proc_macro2::TokenStream = unordered
+        .iter()
+        .map(|field| {
+            let name = field_name(&field.member);
+            let member = &field.member;
+            let remove = format_ident!("{}_remove", field.ident);
+            quote_spanned! {Span::mixed_site()=>
+                let missing = #krate::unordered::missing_removals(&self.#member, &patch.#remove);
+                if !missing.is_empty() {
+                    unknown.push((#name, missing));
+                }
+            }
+        })
+        .collect();
+    let checks = match replace {
+        Some(delta_ident) => quote_spanned! {Span::mixed_site()=>
+            if let #delta_ident::Patch(patch) = &delta {
+                #checks
+            }
+        },
+        None => quote_spanned! {Span::mixed_site()=>
+            let patch = &delta;
+            #checks
+        },
+    };
+    let checks = if unordered.is_empty() {
+        quote! {}
+    } else {
+        quote_spanned! {Span::mixed_site()=>
+            let mut unknown = ::std::vec::Vec::new();
+            #checks
+            if !unknown.is_empty() {
+                return ::std::result::Result::Err(#krate::Error::Apply(
+                    #krate::unordered::UnknownRemovals { fields: unknown },
+                ));
+            }
+        }
+    };
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in &unordered {
+        let ty = &field.ty;
+        where_clause.predicates.push(parse_quote!(
+            <#ty as ::std::iter::IntoIterator>::Item: ::std::fmt::Debug
+        ));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Applies `delta` unless it removes elements from an unordered
+            /// field that aren't present, in which case nothing is applied.
+            #vis fn try_apply_delta(
+                &mut self,
+                delta: #output_ty,
+            ) -> ::std::result::Result<(), #krate::Error> {
+                #checks
+                #krate::ApplyDelta::apply_delta(self, delta);
+                ::std::result::Result::Ok(())
+            }
+        }
+    }
+}
+
+/// Generates `try_apply_delta_with`, which only applies a delta if the
+/// guard of every field it changes allows the change.
+fn delta_try_apply_with(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    context: &Type,
+) -> proc_macro2::TokenStream {
+    let checks: proc_macro2::TokenStream = fields
+        .iter()
+        .filter_map(|field| {
+            let guard = field.attrs.guard.as_ref()?;
+            let name = field_name(&field.member);
+            let ident = &field.ident;
+            let veto = quote_spanned! {Span::mixed_site()=>
+                if !#guard(#name, change, ctx) {
+                    denied.push(#name);
+                }
+            };
+            let changed = field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> delta));
+            Some(match field.field_type {
+                FieldType::Bitset => {
+                    let set = format_ident!("{}_set", ident);
+                    let clear = format_ident!("{}_clear", ident);
+                    quote_spanned! {Span::mixed_site()=>
+                        if #changed {
+                            let change = &(delta.#set, delta.#clear);
+                            #veto
+                        }
+                    }
+                }
+                FieldType::Unordered => {
+                    let add = format_ident!("{}_add", ident);
+                    let remove = format_ident!("{}_remove", ident);
+                    quote_spanned! {Span::mixed_site()=>
+                        if #changed {
+                            let change = &(&delta.#add[..], &delta.#remove[..]);
+                            #veto
+                        }
+                    }
+                }
+                FieldType::Ordered | FieldType::Keyed => quote_spanned! {Span::mixed_site()=>
+                    if #changed {
+                        let change = &delta.#ident;
+                        #veto
+                    }
+                },
+                _ if field.presence_bit.is_some() => quote_spanned! {Span::mixed_site()=>
+                    if #changed {
+                        let change = &delta.#ident;
+                        #veto
+                    }
+                },
+                FieldType::Scalar
+                | FieldType::Float
+                | FieldType::UnorderedScalar
+                | FieldType::Delta
+                | FieldType::Strategy => quote_spanned! {Span::mixed_site()=>
+                    if let ::std::option::Option::Some(change) = &delta.#ident {
+                        #veto
+                    }
+                },
+            })
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Applies `delta` with `DeltaWith::apply_delta_with`, unless the
+            /// guard of a field it changes rejects the change, in which case
+            /// nothing is applied.
+            #vis fn try_apply_delta_with(
+                &mut self,
+                delta: <Self as #krate::ComputeDelta>::Output,
+                ctx: &#context,
+            ) -> ::std::result::Result<(), #krate::Error> {
+                let mut denied = ::std::vec::Vec::new();
+                #checks
+                if !denied.is_empty() {
+                    return ::std::result::Result::Err(#krate::Error::Denied(denied));
+                }
+                #krate::DeltaWith::apply_delta_with(self, delta, ctx);
+                ::std::result::Result::Ok(())
+            }
+        }
+    }
+}
+
+/// Generates `from_delta`, which builds a value by applying a delta to
+/// `Self::default()`, as long as the delta sets every scalar field. With
+/// `with_replace`, a replacement is returned as is.
+fn delta_from_delta(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    output_ty: &proc_macro2::TokenStream,
+    replace: Option<&Ident>,
+) -> proc_macro2::TokenStream {
+    let checks: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .filter(|field| {
+            matches!(
+                field.field_type,
+                FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+            )
+        })
+        .map(|field| {
+            let ident = &field.ident;
+            match field.presence_bit {
+                Some(bit) => quote_spanned! {Span::mixed_site()=>
+                    patch.present & (1u64 << #bit) != 0
+                },
+                None => quote_spanned! {Span::mixed_site()=>
+                    patch.#ident.is_some()
+                },
+            }
+        })
+        .collect();
+    let complete = quote_spanned! {Span::mixed_site()=>
+        #(#checks)&&*
+    };
+    let build = quote_spanned! {Span::mixed_site()=>
+        let mut value = <Self as ::std::default::Default>::default();
+        #krate::ApplyDelta::apply_delta(&mut value, delta);
+        ::std::option::Option::Some(value)
+    };
+    let body = match replace {
+        _ if checks.is_empty() => build,
+        Some(delta_ident) => quote_spanned! {Span::mixed_site()=>
+            let complete = match &delta {
+                #delta_ident::Patch(patch) => #complete,
+                #delta_ident::Replace(_) => true,
+            };
+            if !complete {
+                return ::std::option::Option::None;
+            }
+            #build
+        },
+        None => quote_spanned! {Span::mixed_site()=>
+            let patch = &delta;
+            if !(#complete) {
+                return ::std::option::Option::None;
+            }
+            #build
+        },
+    };
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let mut generics = generics.clone();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(#ident #ty_generics: ::std::default::Default));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Builds a value from `delta` alone, e.g. from the first delta
+            /// seen for an entity. Returns `None` unless `delta` sets every
+            /// scalar field; other fields start from their default.
+            #vis fn from_delta(delta: #output_ty) -> ::std::option::Option<Self> {
+                #body
+            }
+        }
+    }
+}
+
+/// Checks that a `compact` struct only has fields a presence bit can track.
+fn check_compact(container: &ContainerAttrs, fields: &[DeltaField]) {
+    if container.output.is_some()
+        || container.json
+        || container.field_map
+        || container.tagged_changes
+        || container.max_size
+    {
+        abort_call_site!(
+            "compact can't be combined with a custom output type, json, field_map, \
+             tagged_changes or max_size"
+        );
+    }
+    if fields.len() > 64 {
+        abort_call_site!("compact supports at most 64 fields");
+    }
+    for field in fields {
+        if !matches!(
+            field.field_type,
+            FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+        ) {
+            emit_error!(
+                field.ty,
+                "compact only supports scalar, float and unordered_scalar fields"
+            );
+        }
+        if field.ident == "present" {
+            emit_error!(
+                field.ident,
+                "`present` is reserved for the presence mask of a compact delta"
+            );
+        }
+    }
+}
+
+/// Adds the presence mask to the output of `delta_compute_fields`.
+fn with_presence(
+    compact: bool,
+    (compute_let, compute_fields): (proc_macro2::TokenStream, proc_macro2::TokenStream),
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if !compact {
+        return (compute_let, compute_fields);
+    }
+    (
+        quote_spanned! {Span::mixed_site()=>
+            let mut present = 0u64;
+            #compute_let
+        },
+        quote_spanned! {Span::mixed_site()=>
+            present,
+            #compute_fields
+        },
+    )
+}
+
+/// Generates an `Option` returning getter per field of a `compact` delta.
+fn delta_compact_accessors(
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let getters: proc_macro2::TokenStream = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            let bit = field.presence_bit;
+            quote_spanned! {Span::mixed_site()=>
+                #vis fn #ident(&self) -> ::std::option::Option<&#ty> {
+                    if self.present & (1u64 << #bit) != 0 {
+                        ::std::option::Option::Some(&self.#ident)
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            #getters
+        }
+    }
+}
+
+/// Implements `Default` as the empty delta and generates a `with_*` setter
+/// per delta field, for constructing a `non_exhaustive` delta struct.
+fn delta_builder(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let mut empties = proc_macro2::TokenStream::new();
+    let mut setters = proc_macro2::TokenStream::new();
+    if fields.iter().any(|field| field.presence_bit.is_some()) {
+        empties.extend(quote! { present: 0, });
+    }
+    if unknown_fields {
+        empties.extend(quote! { unknown_fields: ::std::default::Default::default(), });
+    }
+    for field in fields {
+        let ty = &field.ty;
+        let members = delta_members(krate, field);
+        match field.field_type {
+            // Masks have no empty value without an instance to clear, and
+            // unset compact fields hold their default.
+            FieldType::Bitset => {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(parse_quote!(#ty: ::std::default::Default));
+            }
+            _ if field.presence_bit.is_some() => {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(parse_quote!(#ty: ::std::default::Default));
+            }
+            _ => {}
+        }
+        for (member, member_ty) in members {
+            let setter = format_ident!("with_{}", member);
+            let (empty, setter) = match field.field_type {
+                FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+                    if field.presence_bit.is_some() =>
+                {
+                    let bit = field.presence_bit;
+                    (
+                        quote!(::std::default::Default::default()),
+                        quote_spanned! {Span::mixed_site()=>
+                            #vis fn #setter(mut self, value: #ty) -> Self {
+                                self.#member = value;
+                                self.present |= 1u64 << #bit;
+                                self
+                            }
+                        },
+                    )
+                }
+                FieldType::Scalar
+                | FieldType::Float
+                | FieldType::UnorderedScalar
+                | FieldType::Delta
+                | FieldType::Strategy => {
+                    let inner = match field.field_type {
+                        FieldType::Delta if field.attrs.coarse => {
+                            quote!(#krate::Granular<#ty>)
+                        }
+                        FieldType::Delta => quote!(<#ty as #krate::ComputeDelta>::Output),
+                        FieldType::Strategy => {
+                            let strategy = &field.attrs.strategy;
+                            quote!(<#strategy as #krate::DiffStrategy<#ty>>::Delta)
+                        }
+                        _ => quote!(#ty),
+                    };
+                    (
+                        quote!(::std::option::Option::None),
+                        quote_spanned! {Span::mixed_site()=>
+                            #vis fn #setter(mut self, value: #inner) -> Self {
+                                self.#member = ::std::option::Option::Some(value);
+                                self
+                            }
+                        },
+                    )
+                }
+                FieldType::Bitset
+                | FieldType::Ordered
+                | FieldType::Unordered
+                | FieldType::Keyed => (
+                    quote!(::std::default::Default::default()),
+                    quote_spanned! {Span::mixed_site()=>
+                        #vis fn #setter(mut self, value: #member_ty) -> Self {
+                            self.#member = value;
+                            self
+                        }
+                    },
+                ),
+            };
+            empties.extend(quote! { #member: #empty, });
+            setters.extend(setter);
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::std::default::Default for #delta_ident #ty_generics #where_clause {
+            /// The delta changing nothing.
+            fn default() -> Self {
+                Self { #empties }
+            }
+        }
+
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            #setters
+        }
+    }
+}
+
+/// The operator a new value of a `monotonic` field must satisfy against the
+/// current one.
+fn monotonic_op(monotonic: Monotonic) -> proc_macro2::TokenStream {
+    match monotonic {
+        Monotonic::Increase => quote!(>),
+        Monotonic::Decrease => quote!(<),
+    }
+}
+
+/// Generates `{Name}Change`, an enum with one variant per field, and an
+/// `IntoIterator` impl on the delta struct yielding one change per changed
+/// field.
+fn delta_change_enum(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    delta_ident: &Ident,
+    generics: &Generics,
+    leader: proc_macro2::TokenStream,
+    iter: impl Iterator<Item = DeltaField>,
+) -> proc_macro2::TokenStream {
+    let change_ident = format_ident!("{}Change", ident);
+    let (variants, pushes): (proc_macro2::TokenStream, proc_macro2::TokenStream) = iter
+        .map(
+            |DeltaField {
+                 ident,
+                 ty,
+                 field_type,
+                 attrs,
+                 presence_bit,
+                 ..
+             }| {
+                let variant = variant_ident(&ident);
+                match field_type {
+                    FieldType::Bitset => {
+                        let set = format_ident!("{}_set", ident);
+                        let clear = format_ident!("{}_clear", ident);
+                        (
+                            quote! {
+                                #variant { set: #ty, clear: #ty },
+                            },
+                            quote_spanned! {Span::mixed_site()=>
+                                if !#krate::bitset::is_empty(self.#set)
+                                    || !#krate::bitset::is_empty(self.#clear)
+                                {
+                                    changes.push(#change_ident::#variant {
+                                        set: self.#set,
+                                        clear: self.#clear,
+                                    });
+                                }
+                            },
+                        )
+                    }
+                    FieldType::Ordered if attrs.key.is_some() => (
+                        quote! {
+                            #variant(::std::vec::Vec<#krate::KeyedOp<<#ty as ::std::iter::IntoIterator>::Item>>),
+                        },
+                        quote_spanned! {Span::mixed_site()=>
+                            if !self.#ident.is_empty() {
+                                changes.push(#change_ident::#variant(self.#ident));
+                            }
+                        },
+                    ),
+                    FieldType::Keyed => {
+                        let key_ty = attrs.key_type.unwrap();
+                        (
+                            quote! {
+                                #variant(#krate::KeyedVecDelta<#key_ty, <#ty as ::std::iter::IntoIterator>::Item>),
+                            },
+                            quote_spanned! {Span::mixed_site()=>
+                                if !self.#ident.is_empty() {
+                                    changes.push(#change_ident::#variant(self.#ident));
+                                }
+                            },
+                        )
+                    }
+                    FieldType::Ordered => (
+                        quote! {
+                            #variant(::std::vec::Vec<#krate::Splice<<#ty as ::std::iter::IntoIterator>::Item>>),
+                        },
+                        quote_spanned! {Span::mixed_site()=>
+                            if !self.#ident.is_empty() {
+                                changes.push(#change_ident::#variant(self.#ident));
+                            }
+                        },
+                    ),
+                    FieldType::Unordered => {
+                        let add = format_ident!("{}_add", ident);
+                        let remove = format_ident!("{}_remove", ident);
+                        (
+                            quote! {
+                                #variant {
+                                    add: ::std::vec::Vec<<#ty as ::std::iter::IntoIterator>::Item>,
+                                    remove: ::std::vec::Vec<<#ty as ::std::iter::IntoIterator>::Item>,
+                                },
+                            },
+                            quote_spanned! {Span::mixed_site()=>
+                                if !self.#add.is_empty() || !self.#remove.is_empty() {
+                                    changes.push(#change_ident::#variant {
+                                        add: self.#add,
+                                        remove: self.#remove,
+                                    });
+                                }
+                            },
+                        )
+                    }
+                    FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => (
+                        quote! {
+                            #variant(#ty),
+                        },
+                        match presence_bit {
+                            Some(bit) => quote_spanned! {Span::mixed_site()=>
+                                if self.present & (1u64 << #bit) != 0 {
+                                    changes.push(#change_ident::#variant(self.#ident));
+                                }
+                            },
+                            None => quote_spanned! {Span::mixed_site()=>
+                                if let ::std::option::Option::Some(v) = self.#ident {
+                                    changes.push(#change_ident::#variant(v));
+                                }
+                            },
+                        },
+                    ),
+                    FieldType::Delta if attrs.coarse => (
+                        quote! {
+                            #variant(#krate::Granular<#ty>),
+                        },
+                        quote_spanned! {Span::mixed_site()=>
+                            if let ::std::option::Option::Some(v) = self.#ident {
+                                changes.push(#change_ident::#variant(v));
+                            }
+                        },
+                    ),
+                    FieldType::Strategy => {
+                        let strategy = attrs.strategy.unwrap();
+                        (
+                            quote! {
+                                #variant(<#strategy as #krate::DiffStrategy<#ty>>::Delta),
+                            },
+                            quote_spanned! {Span::mixed_site()=>
+                                if let ::std::option::Option::Some(v) = self.#ident {
+                                    changes.push(#change_ident::#variant(v));
+                                }
+                            },
+                        )
+                    }
+                    FieldType::Delta => (
+                        quote! {
+                            #variant(<#ty as #krate::ComputeDelta>::Output),
+                        },
+                        quote_spanned! {Span::mixed_site()=>
+                            if let ::std::option::Option::Some(v) = self.#ident {
+                                changes.push(#change_ident::#variant(v));
+                            }
+                        },
+                    ),
+                }
+            },
+        )
+        .unzip();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        #leader
+        // Only reading the payloads counts as use, and many users of the
+        // delta never iterate over it.
+        #[allow(dead_code)]
+        #vis enum #change_ident #generics #where_clause {
+            #variants
+        }
+
+        impl #impl_generics ::std::iter::IntoIterator for #delta_ident #ty_generics #where_clause {
+            type Item = #change_ident #ty_generics;
+            type IntoIter = ::std::vec::IntoIter<Self::Item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                let mut changes = ::std::vec::Vec::new();
+                #pushes
+                changes.into_iter()
+            }
+        }
+    }
+}
+
+/// The `extern "C"` functions of the C API, named after `prefix` or the
+/// struct in snake case.
+fn delta_ffi(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    output_ty: &proc_macro2::TokenStream,
+    prefix: Option<&syn::LitStr>,
+) -> proc_macro2::TokenStream {
+    let prefix = match prefix {
+        Some(prefix) => prefix.value(),
+        None => snake_case(ident),
+    };
+    let compute = format_ident!("{}_delta_compute", prefix);
+    let apply = format_ident!("{}_delta_apply", prefix);
+    let free = format_ident!("{}_delta_free", prefix);
+    let compute_doc = format!(
+        "The delta from `*old` to `*new`, or null if they are equal. It must \
+         be passed to `{}` or `{}`.",
+        apply, free
+    );
+    let apply_doc = format!(
+        "Applies `delta`, returned by `{}`, to `*target` and frees it. Does \
+         nothing if `delta` is null.",
+        compute
+    );
+    let free_doc = format!(
+        "Frees `delta`, returned by `{}`. Does nothing if it is null.",
+        compute
+    );
+    quote_spanned! {Span::mixed_site()=>
+        #[doc = #compute_doc]
+        ///
+        /// # Safety
+        ///
+        /// `old` and `new` must point to valid values.
+        #[no_mangle]
+        #vis unsafe extern "C" fn #compute(old: *const #ident, new: *const #ident) -> *mut #output_ty {
+            #krate::ffi::into_raw(#krate::ComputeDelta::delta(
+                ::std::clone::Clone::clone(&*old),
+                ::std::clone::Clone::clone(&*new),
+            ))
+        }
+
+        #[doc = #apply_doc]
+        ///
+        /// # Safety
+        ///
+        /// `target` must point to a valid value, and `delta` must not be
+        /// used afterwards.
+        #[no_mangle]
+        #vis unsafe extern "C" fn #apply(target: *mut #ident, delta: *mut #output_ty) {
+            if let ::std::option::Option::Some(delta) = #krate::ffi::from_raw(delta) {
+                #krate::ApplyDelta::apply_delta(&mut *target, delta);
+            }
+        }
+
+        #[doc = #free_doc]
+        ///
+        /// # Safety
+        ///
+        /// `delta` must not be used afterwards.
+        #[no_mangle]
+        #vis unsafe extern "C" fn #free(delta: *mut #output_ty) {
+            #krate::ffi::free(delta);
+        }
+    }
+}
+
+/// The JavaScript bindings: conversions of the delta struct, and exported
+/// functions named after `prefix` or the struct in snake case.
+fn delta_wasm(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    delta_ident: &Ident,
+    prefix: Option<&syn::LitStr>,
+) -> proc_macro2::TokenStream {
+    let prefix = match prefix {
+        Some(prefix) => prefix.value(),
+        None => snake_case(ident),
+    };
+    let compute = format_ident!("{}_delta_compute", prefix);
+    let apply = format_ident!("{}_delta_apply", prefix);
+    quote_spanned! {Span::mixed_site()=>
+        impl #delta_ident {
+            /// The delta as a JavaScript object.
+            #vis fn to_js(&self) -> ::std::result::Result<#krate::wasm::JsValue, #krate::wasm::JsValue> {
+                #krate::wasm::to_js(self)
+            }
+
+            /// The delta in a JavaScript object.
+            #vis fn from_js(
+                value: &#krate::wasm::JsValue,
+            ) -> ::std::result::Result<Self, #krate::wasm::JsValue> {
+                #krate::wasm::from_js(value)
+            }
+        }
+
+        /// The delta from `old` to `new`, or `null` if they are equal.
+        #[#krate::wasm::wasm_bindgen::prelude::wasm_bindgen(wasm_bindgen = #krate::wasm::wasm_bindgen)]
+        pub fn #compute(
+            old: #krate::wasm::JsValue,
+            new: #krate::wasm::JsValue,
+        ) -> ::std::result::Result<#krate::wasm::JsValue, #krate::wasm::JsValue> {
+            #krate::wasm::compute::<#ident>(&old, &new)
+        }
+
+        /// `target` with `delta` applied.
+        #[#krate::wasm::wasm_bindgen::prelude::wasm_bindgen(wasm_bindgen = #krate::wasm::wasm_bindgen)]
+        pub fn #apply(
+            target: #krate::wasm::JsValue,
+            delta: #krate::wasm::JsValue,
+        ) -> ::std::result::Result<#krate::wasm::JsValue, #krate::wasm::JsValue> {
+            #krate::wasm::apply::<#ident>(&target, &delta)
+        }
+    }
+}
+
+/// The `snake_case` name for an `UpperCamelCase` type identifier.
+fn snake_case(ident: &Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident
+        .to_string()
+        .trim_start_matches("r#")
+        .chars()
+        .enumerate()
+    {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// The `UpperCamelCase` variant name for a (snake case) field identifier.
+fn variant_ident(ident: &Ident) -> Ident {
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#");
+    let variant: String = name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    Ident::new(&variant, ident.span())
+}
+
+/// The bounds `field`'s delta members need to be well formed.
+fn member_bounds(krate: &Path, field: &DeltaField, compute_only: bool) -> Vec<WherePredicate> {
+    let ty = &field.ty;
+    // Without applying code, nested deltas only need to be computed.
+    let delta_trait: Path = if compute_only {
+        parse_quote!(#krate::ComputeDelta)
+    } else {
+        parse_quote!(#krate::Delta)
+    };
+    match field.field_type {
+        FieldType::Delta => vec![parse_quote!(#ty: #delta_trait)],
+        FieldType::Strategy => {
+            let strategy = &field.attrs.strategy;
+            vec![parse_quote!(#strategy: #krate::DiffStrategy<#ty>)]
+        }
+        // Bounding the collection itself by `IntoIterator` would hide the
+        // impl's `Item` from inference, so only the element is bounded.
+        FieldType::Ordered | FieldType::Keyed if field.attrs.key.is_some() => vec![parse_quote!(
+            <#ty as ::std::iter::IntoIterator>::Item: #delta_trait
+        )],
+        _ => vec![],
+    }
+}
+
+/// A closure cloning the `key` of an element of the collection `ty`.
+fn key_extractor(ty: &Type, attrs: &FieldAttrs) -> proc_macro2::TokenStream {
+    let key = &attrs.key;
+    quote_spanned! {Span::mixed_site()=>
+        |item: &<#ty as ::std::iter::IntoIterator>::Item| ::std::clone::Clone::clone(&item.#key)
+    }
+}
+
+/// A closure comparing the `key`s of two elements of the collection `ty`
+/// without cloning them.
+fn key_comparer(ty: &Type, attrs: &FieldAttrs) -> proc_macro2::TokenStream {
+    let key = &attrs.key;
+    quote_spanned! {Span::mixed_site()=>
+        |a: &<#ty as ::std::iter::IntoIterator>::Item, b: &<#ty as ::std::iter::IntoIterator>::Item| a.#key == b.#key
+    }
+}
+
+/// `ty` as a path usable in struct expressions and patterns, with its
+/// generic arguments in turbofish form.
+fn expr_path(ty: &Type) -> proc_macro2::TokenStream {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => {
+            let mut path = path.clone();
+            for segment in &mut path.segments {
+                if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                    args.colon2_token = Some(Default::default());
+                }
+            }
+            quote!(#path)
+        }
+        _ => quote!(#ty),
+    }
+}
+
+/// Whether `ty` is syntactically a dynamically sized type.
+fn is_unsized(ty: &Type) -> bool {
+    match ty {
+        Type::Slice(_) | Type::TraitObject(_) => true,
+        Type::Path(path) => path.qself.is_none() && path.path.is_ident("str"),
+        Type::Paren(paren) => is_unsized(&paren.elem),
+        Type::Group(group) => is_unsized(&group.elem),
+        _ => false,
+    }
+}
+
+/// Warns about each collection field that is replaced wholesale only
+/// because it defaulted to `scalar`. Proc macros can't emit warnings on
+/// stable, so the warning is the deprecation of a constant named after the
+/// field, used at the field's type.
+fn collection_lints(fields: &[DeltaField], default: Option<FieldType>) -> proc_macro2::TokenStream {
+    const COLLECTIONS: &[&str] = &[
+        "Vec", "VecDeque", "HashSet", "BTreeSet", "HashMap", "BTreeMap",
+    ];
+    if default.is_some() {
+        return quote! {};
+    }
+    fields
+        .iter()
+        .filter(|field| field.attrs.field_type.is_none())
+        .filter(|field| match &field.ty {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| COLLECTIONS.iter().any(|c| segment.ident == c)),
+            _ => false,
+        })
+        .map(|field| {
+            let name = format_ident!(
+                "field_{}_is_diffed_as_a_scalar",
+                field_name(&field.member),
+                span = field.ty.span()
+            );
+            let note = format!(
+                "field `{}` is a collection diffed as a scalar, so any change replaces all of it; \
+                 give it a `field_type` such as \"unordered\" or \"ordered\", or \"scalar\" to keep this",
+                field_name(&field.member)
+            );
+            quote! {
+                const _: () = {
+                    #[deprecated(note = #note)]
+                    #[allow(non_upper_case_globals)]
+                    const #name: () = ();
+                    #name
+                };
+            }
+        })
+        .collect()
+}
+
+/// Whether `ty` is written as a `Vec`.
+fn is_vec(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Vec"),
+        _ => false,
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Whether the field's delta member is an `Option<Option<_>>`, which
+/// serializes both "unchanged" and "cleared" as `null`.
+fn is_nullable_member(field: &DeltaField) -> bool {
+    matches!(field.field_type, FieldType::Scalar | FieldType::Float)
+        && field.presence_bit.is_none()
+        && is_option(&field.ty)
+}
+
+/// Whether `ty` contains `f32` or `f64` anywhere.
+fn mentions_float(ty: &Type) -> bool {
+    fn walk(tokens: proc_macro2::TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ident == "f32" || ident == "f64",
+            TokenTree::Group(group) => walk(group.stream()),
+            _ => false,
+        })
+    }
+    walk(quote!(#ty))
+}
+
+/// Whether `ty` contains a `dyn Trait` type anywhere.
+fn mentions_trait_object(ty: &Type) -> bool {
+    fn walk(tokens: proc_macro2::TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ident == "dyn",
+            TokenTree::Group(group) => walk(group.stream()),
+            _ => false,
+        })
+    }
+    walk(quote!(#ty))
+}
+
+/// The types the generated impls compare, and so bound by `PartialEq`:
+/// every type parameter a field uses directly, and every associated type of
+/// one, such as `T::Reading`, without bounding the parameter itself.
+fn compared_types(fields: &[DeltaField], params: &[Ident]) -> Vec<Type> {
+    fn walk(ty: &Type, params: &[Ident], found: &mut Vec<Type>) {
+        let mut push = |ty: Type| {
+            let tokens = ty.to_token_stream().to_string();
+            if !found
+                .iter()
+                .any(|found| found.to_token_stream().to_string() == tokens)
+            {
+                found.push(ty);
+            }
+        };
+        match ty {
+            Type::Path(TypePath { qself, path }) => {
+                let projection = match qself {
+                    Some(qself) => mentions_generic_param(&qself.ty, params),
+                    None => path.segments.len() > 1 && params.contains(&path.segments[0].ident),
+                };
+                if projection {
+                    return push(ty.clone());
+                }
+                if let Some(param) = path.get_ident().filter(|ident| params.contains(ident)) {
+                    return push(parse_quote!(#param));
+                }
+                for segment in &path.segments {
+                    match &segment.arguments {
+                        PathArguments::AngleBracketed(args) => {
+                            for arg in &args.args {
+                                if let GenericArgument::Type(ty) = arg {
+                                    walk(ty, params, found);
+                                }
+                            }
+                        }
+                        PathArguments::Parenthesized(args) => {
+                            for ty in &args.inputs {
+                                walk(ty, params, found);
+                            }
+                            if let ReturnType::Type(_, ty) = &args.output {
+                                walk(ty, params, found);
+                            }
+                        }
+                        PathArguments::None => {}
+                    }
+                }
+            }
+            Type::Array(array) => walk(&array.elem, params, found),
+            Type::Slice(slice) => walk(&slice.elem, params, found),
+            Type::Reference(reference) => walk(&reference.elem, params, found),
+            Type::Ptr(ptr) => walk(&ptr.elem, params, found),
+            Type::Paren(paren) => walk(&paren.elem, params, found),
+            Type::Group(group) => walk(&group.elem, params, found),
+            Type::Tuple(tuple) => {
+                for ty in &tuple.elems {
+                    walk(ty, params, found);
+                }
+            }
+            // Function pointers, trait objects and macros are bounded by
+            // the parameters they mention.
+            _ => {
+                for param in params {
+                    if mentions_generic_param(ty, std::slice::from_ref(param)) {
+                        push(parse_quote!(#param));
+                    }
+                }
+            }
+        }
+    }
+    let mut found = vec![];
+    for field in fields {
+        walk(&field.ty, params, &mut found);
+    }
+    found
+}
+
+/// Whether any of `params` appears in `ty`.
+fn mentions_generic_param(ty: &Type, params: &[Ident]) -> bool {
+    fn walk(tokens: proc_macro2::TokenStream, params: &[Ident]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => params.contains(&ident),
+            TokenTree::Group(group) => walk(group.stream(), params),
+            _ => false,
+        })
+    }
+    walk(quote!(#ty), params)
+}
+
+/// The fields a single source field maps to on the delta struct, with
+/// their types.
+fn delta_members(krate: &Path, field: &DeltaField) -> Vec<(Ident, proc_macro2::TokenStream)> {
+    let DeltaField { ident, ty, .. } = field;
+    match field.field_type {
+        FieldType::Bitset => vec![
+            (format_ident!("{}_set", ident), quote!(#ty)),
+            (format_ident!("{}_clear", ident), quote!(#ty)),
+        ],
+        FieldType::Ordered if field.attrs.key.is_some() => vec![(
+            ident.clone(),
+            quote! {
+                ::std::vec::Vec<#krate::KeyedOp<<#ty as ::std::iter::IntoIterator>::Item>>
+            },
+        )],
+        FieldType::Ordered => vec![(
+            ident.clone(),
+            quote! {
+                ::std::vec::Vec<#krate::Splice<<#ty as ::std::iter::IntoIterator>::Item>>
+            },
+        )],
+        FieldType::Keyed => {
+            let key_ty = &field.attrs.key_type;
+            vec![(
+                ident.clone(),
+                quote! {
+                    #krate::KeyedVecDelta<#key_ty, <#ty as ::std::iter::IntoIterator>::Item>
+                },
+            )]
+        }
+        FieldType::Unordered => {
+            let items = quote!(::std::vec::Vec<<#ty as ::std::iter::IntoIterator>::Item>);
+            vec![
+                (format_ident!("{}_add", ident), items.clone()),
+                (format_ident!("{}_remove", ident), items),
+            ]
+        }
+        FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+            if field.presence_bit.is_some() =>
+        {
+            vec![(ident.clone(), quote!(#ty))]
+        }
+        FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => {
+            vec![(ident.clone(), quote!(::std::option::Option<#ty>))]
+        }
+        FieldType::Delta if field.attrs.coarse => vec![(
+            ident.clone(),
+            quote!(::std::option::Option<#krate::Granular<#ty>>),
+        )],
+        FieldType::Delta => vec![(
+            ident.clone(),
+            quote!(::std::option::Option<<#ty as #krate::ComputeDelta>::Output>),
+        )],
+        FieldType::Strategy => {
+            let strategy = &field.attrs.strategy;
+            vec![(
+                ident.clone(),
+                quote!(::std::option::Option<<#strategy as #krate::DiffStrategy<#ty>>::Delta>),
+            )]
+        }
+    }
+}
+
+/// The type of the new value of a field whose delta member is an `Option`
+/// of it, as opposed to the parts of a collection's delta.
+fn changed_value_type(krate: &Path, field: &DeltaField) -> Option<proc_macro2::TokenStream> {
+    let ty = &field.ty;
+    match field.field_type {
+        FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+            if field.presence_bit.is_none() =>
+        {
+            Some(quote!(#ty))
+        }
+        FieldType::Delta if field.attrs.coarse => Some(quote!(#krate::Granular<#ty>)),
+        FieldType::Strategy => {
+            let strategy = &field.attrs.strategy;
+            Some(quote!(<#strategy as #krate::DiffStrategy<#ty>>::Delta))
+        }
+        _ => None,
+    }
+}
+
+fn delta_fields(
+    krate: &Path,
+    iter: impl Iterator<Item = DeltaField>,
+    member_vis: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    iter.flat_map(|field| {
+        let leader = field.attrs.delta_leader.clone();
+        delta_members(krate, &field)
+            .into_iter()
+            .map(move |(ident, ty)| {
+                quote! {
+                    #leader
+                    #member_vis #ident: #ty,
+                }
+            })
+    })
+    .collect()
+}
+
+/// Generates a getter per member of an `encapsulated` delta struct. Its
+/// members are set through the same `with_*` setters as `non_exhaustive`
+/// deltas.
+fn delta_getters(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    let mut members: Vec<(Ident, proc_macro2::TokenStream)> = fields
+        .iter()
+        .flat_map(|field| delta_members(krate, field))
+        .collect();
+    if unknown_fields {
+        members.push((
+            format_ident!("unknown_fields"),
+            quote!(#krate::json::Map<::std::string::String, #krate::json::Value>),
+        ));
+    }
+    let getters: proc_macro2::TokenStream = members
+        .iter()
+        .map(|(ident, ty)| {
+            quote_spanned! {Span::mixed_site()=>
+                #vis fn #ident(&self) -> &#ty {
+                    &self.#ident
+                }
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            #getters
+        }
+    }
+}
+
+/// Generates `to_sql_update` on the delta struct, setting the column of
+/// each changed field.
+fn delta_sql(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    let mut columns = proc_macro2::TokenStream::new();
+    for field in fields {
+        let ty = &field.ty;
+        let ident = &field.ident;
+        let column = match &field.attrs.column {
+            Some(column) => column.value(),
+            None => field_name(&field.member),
+        };
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::sql::ToSqlValue));
+        columns.extend(quote_spanned! {Span::mixed_site()=>
+            if let ::std::option::Option::Some(value) = &self.#ident {
+                columns.push(#column);
+                values.push(#krate::sql::ToSqlValue::to_sql_value(value));
+            }
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// An `UPDATE` of `table` setting the columns of the changed
+            /// fields, without a `WHERE` clause, and the values to bind to
+            /// its placeholders. `None` if no field changed.
+            #vis fn to_sql_update(
+                &self,
+                table: &str,
+            ) -> ::std::option::Option<(::std::string::String, ::std::vec::Vec<#krate::sql::SqlValue>)> {
+                let mut columns: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+                let mut values = ::std::vec::Vec::new();
+                #columns
+                if columns.is_empty() {
+                    ::std::option::Option::None
+                } else {
+                    ::std::option::Option::Some((#krate::sql::update(table, &columns), values))
+                }
+            }
+        }
+    }
+}
+
+/// Implements `mongo::UpdateDocument` for the delta struct, updating the
+/// path of each changed field.
+fn delta_mongodb(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    let mut updates = proc_macro2::TokenStream::new();
+    for field in fields {
+        let ty = &field.ty;
+        let ident = &field.ident;
+        let name = field_name(&field.member);
+        let path = quote_spanned!(Span::mixed_site()=> #krate::path_ids::join(prefix, #name));
+        match field.field_type {
+            FieldType::Unordered => {
+                let add = format_ident!("{}_add", ident);
+                let remove = format_ident!("{}_remove", ident);
+                where_clause.predicates.push(
+                    parse_quote!(<#ty as ::std::iter::IntoIterator>::Item: #krate::serde::Serialize),
+                );
+                updates.extend(quote_spanned! {Span::mixed_site()=>
+                    update.pull(#path, &self.#remove)?;
+                    update.add_to_set(#path, &self.#add)?;
+                });
+            }
+            FieldType::Delta => {
+                let inner = if field.attrs.coarse {
+                    quote!(#krate::Granular<#ty>)
+                } else {
+                    quote!(<#ty as #krate::ComputeDelta>::Output)
+                };
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#inner: #krate::mongo::UpdateDocument));
+                updates.extend(quote_spanned! {Span::mixed_site()=>
+                    if let ::std::option::Option::Some(delta) = &self.#ident {
+                        #krate::mongo::UpdateDocument::push_update(delta, &#path, update)?;
+                    }
+                });
+            }
+            _ => {
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#ty: #krate::serde::Serialize));
+                updates.extend(quote_spanned! {Span::mixed_site()=>
+                    if let ::std::option::Option::Some(value) = &self.#ident {
+                        update.set(#path, value)?;
+                    }
+                });
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::mongo::UpdateDocument for #delta_ident #ty_generics #where_clause {
+            fn push_update(
+                &self,
+                prefix: &str,
+                update: &mut #krate::mongo::Update,
+            ) -> ::std::result::Result<(), #krate::mongo::Error> {
+                #updates
+                ::std::result::Result::Ok(())
+            }
+        }
+    }
+}
+
+/// Implements `MaxEncodedSize` for the delta struct as the sum of its
+/// fields' sizes.
+fn delta_max_size(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let types: Vec<_> = fields
+        .iter()
+        .flat_map(|field| delta_members(krate, field))
+        .map(|(_, ty)| ty)
+        .collect();
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::MaxEncodedSize));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::MaxEncodedSize for #delta_ident #ty_generics #where_clause {
+            const MAX_ENCODED_SIZE: usize = 0 #(+ <#types as #krate::MaxEncodedSize>::MAX_ENCODED_SIZE)*;
+        }
+    }
+}
+
+/// Hashes the diffed fields of `value` with `StableHasher`, leaving out
+/// skipped fields, which may differ between replicas.
+fn fingerprint(
+    krate: &Path,
+    fields: &[DeltaField],
+    value: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let members = fields.iter().map(|field| &field.member);
+    quote_spanned! {Span::mixed_site()=>
+        {
+            let mut hasher = <#krate::StableHasher as ::std::default::Default>::default();
+            #(::std::hash::Hash::hash(&#value.#members, &mut hasher);)*
+            ::std::hash::Hasher::finish(&hasher)
+        }
+    }
+}
+
+/// Implements `ContentHash` for the delta struct by hashing every field.
+fn delta_content_hash(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    compact: bool,
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    let mut hashes = proc_macro2::TokenStream::new();
+    if compact {
+        hashes.extend(quote_spanned! {Span::mixed_site()=>
+            ::std::hash::Hash::hash(&self.present, &mut hasher);
+        });
+    }
+    for field in fields {
+        // Floats aren't `Hash`, so members holding them hash their bits.
+        let (bound, hash) = if mentions_float(&field.ty) {
+            (
+                quote!(#krate::FloatHash),
+                quote!(#krate::FloatHash::float_hash),
+            )
+        } else {
+            (quote!(::std::hash::Hash), quote!(::std::hash::Hash::hash))
+        };
+        for (ident, ty) in delta_members(krate, field) {
+            where_clause.predicates.push(parse_quote!(#ty: #bound));
+            hashes.extend(quote_spanned! {Span::mixed_site()=>
+                #hash(&self.#ident, &mut hasher);
+            });
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::ContentHash for #delta_ident #ty_generics #where_clause {
+            fn content_hash(&self) -> u64 {
+                let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+                #hashes
+                ::std::hash::Hasher::finish(&hasher)
+            }
+        }
+    }
+}
+
+/// Implements `Project` for the delta struct. Nested delta fields are
+/// projected in turn; every other field is kept or dropped as a whole.
+fn delta_project(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let mut selections = proc_macro2::TokenStream::new();
+    let mut members = proc_macro2::TokenStream::new();
+    if unknown_fields {
+        members.extend(quote_spanned! {Span::mixed_site()=>
+            unknown_fields: self
+                .unknown_fields
+                .iter()
+                .filter(|(name, _)| {
+                    #krate::project::select(paths, name) != #krate::project::Selection::None
+                })
+                .map(|(name, value)| {
+                    (::std::clone::Clone::clone(name), ::std::clone::Clone::clone(value))
+                })
+                .collect(),
+        });
+    }
+    for field in fields {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let name = field_name(&field.member);
+        let where_clause = generics.make_where_clause();
+        for (_, member_ty) in delta_members(krate, field) {
+            where_clause
+                .predicates
+                .push(parse_quote!(#member_ty: ::std::clone::Clone));
+        }
+        if field.field_type == FieldType::Delta {
+            let patch_ty = if field.attrs.coarse {
+                quote!(#krate::Granular<#ty>)
+            } else {
+                quote!(<#ty as #krate::ComputeDelta>::Output)
+            };
+            where_clause
+                .predicates
+                .push(parse_quote!(#patch_ty: #krate::project::Project));
+            members.extend(quote_spanned! {Span::mixed_site()=>
+                #ident: match #krate::project::select(paths, #name) {
+                    #krate::project::Selection::None => ::std::option::Option::None,
+                    #krate::project::Selection::All => ::std::clone::Clone::clone(&self.#ident),
+                    #krate::project::Selection::Nested(paths) => self
+                        .#ident
+                        .as_ref()
+                        .map(|patch| #krate::project::Project::project(patch, &paths)),
+                },
+            });
+            continue;
+        }
+        let selected = format_ident!("{}_selected", ident, span = Span::mixed_site());
+        selections.extend(quote_spanned! {Span::mixed_site()=>
+            let #selected = #krate::project::select(paths, #name)
+                != #krate::project::Selection::None;
+        });
+        if let Some(bit) = field.presence_bit {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::default::Default));
+            selections.extend(quote_spanned! {Span::mixed_site()=>
+                if !#selected {
+                    present &= !(1u64 << #bit);
+                }
+            });
+        }
+        for (member, _) in delta_members(krate, field) {
+            let empty = match field.field_type {
+                FieldType::Bitset => quote_spanned! {Span::mixed_site()=>
+                    self.#member & !self.#member
+                },
+                FieldType::Ordered | FieldType::Unordered => quote_spanned! {Span::mixed_site()=>
+                    ::std::vec::Vec::new()
+                },
+                FieldType::Keyed => quote_spanned! {Span::mixed_site()=>
+                    ::std::default::Default::default()
+                },
+                _ if field.presence_bit.is_some() => quote_spanned! {Span::mixed_site()=>
+                    ::std::default::Default::default()
+                },
+                _ => quote_spanned! {Span::mixed_site()=>
+                    ::std::option::Option::None
+                },
+            };
+            members.extend(quote_spanned! {Span::mixed_site()=>
+                #member: if #selected {
+                    ::std::clone::Clone::clone(&self.#member)
+                } else {
+                    #empty
+                },
+            });
+        }
+    }
+    let present = if fields.iter().any(|field| field.presence_bit.is_some()) {
+        selections = quote_spanned! {Span::mixed_site()=>
+            let mut present = self.present;
+            #selections
+        };
+        quote_spanned!(Span::mixed_site()=> present,)
+    } else {
+        quote!()
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::project::Project for #delta_ident #ty_generics #where_clause {
+            fn project(&self, paths: &[#krate::project::DeltaPath]) -> Self {
+                #selections
+                Self {
+                    #present
+                    #members
+                }
+            }
+        }
+    }
+}
+
+/// Implements `PathIds` for the delta struct, numbering each field and then
+/// the paths under nested delta fields, and generates `paths` and
+/// `changed_path_ids`.
+fn delta_path_ids(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let mut counts = proc_macro2::TokenStream::new();
+    let mut paths = proc_macro2::TokenStream::new();
+    let mut changed = proc_macro2::TokenStream::new();
+    for field in fields {
+        let ident = &field.ident;
+        let name = field_name(&field.member);
+        let is_changed = field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> self));
+        changed.extend(quote_spanned! {Span::mixed_site()=>
+            if #is_changed {
+                ids.push(id);
+            }
+        });
+        paths.extend(quote_spanned! {Span::mixed_site()=>
+            let path = #krate::path_ids::join(prefix, #name);
+        });
+        if field.field_type == FieldType::Delta {
+            let ty = &field.ty;
+            let patch_ty = if field.attrs.coarse {
+                quote!(#krate::Granular<#ty>)
+            } else {
+                quote!(<#ty as #krate::ComputeDelta>::Output)
+            };
+            generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#patch_ty: #krate::path_ids::PathIds));
+            let nested = quote_spanned! {Span::mixed_site()=>
+                <#patch_ty as #krate::path_ids::PathIds>::path_count()
+            };
+            counts.extend(quote_spanned! {Span::mixed_site()=> + 1 + #nested});
+            paths.extend(quote_spanned! {Span::mixed_site()=>
+                paths.push(::std::clone::Clone::clone(&path));
+                <#patch_ty as #krate::path_ids::PathIds>::push_paths(&path, paths);
+            });
+            changed.extend(quote_spanned! {Span::mixed_site()=>
+                if let ::std::option::Option::Some(patch) = &self.#ident {
+                    #krate::path_ids::PathIds::push_changed_path_ids(patch, id + 1, ids);
+                }
+                id += 1 + #nested;
+            });
+        } else {
+            counts.extend(quote_spanned! {Span::mixed_site()=> + 1});
+            paths.extend(quote_spanned! {Span::mixed_site()=>
+                paths.push(path);
+            });
+            changed.extend(quote_spanned! {Span::mixed_site()=>
+                id += 1;
+            });
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::path_ids::PathIds for #delta_ident #ty_generics #where_clause {
+            fn path_count() -> u32 {
+                0 #counts
+            }
+
+            fn push_paths(prefix: &str, paths: &mut ::std::vec::Vec<::std::string::String>) {
+                #paths
+            }
+
+            #[allow(unused_assignments)]
+            fn push_changed_path_ids(&self, first: u32, ids: &mut ::std::vec::Vec<u32>) {
+                let mut id = first;
+                #changed
+            }
+        }
+
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// The dotted path of each field, indexed by its path ID.
+            #vis fn paths() -> ::std::vec::Vec<::std::string::String> {
+                let mut paths = ::std::vec::Vec::new();
+                <Self as #krate::path_ids::PathIds>::push_paths("", &mut paths);
+                paths
+            }
+
+            /// The path IDs of the fields `self` changes, in ID order.
+            #vis fn changed_path_ids(&self) -> ::std::vec::Vec<u32> {
+                let mut ids = ::std::vec::Vec::new();
+                #krate::path_ids::PathIds::push_changed_path_ids(self, 0, &mut ids);
+                ids
+            }
+        }
+    }
+}
+
+/// Generates a delta struct per view, holding only the members of the
+/// view's fields, along with `delta_{view}` and `apply_delta_{view}` on the
+/// struct.
+fn delta_views(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    fields: &[DeltaField],
+    views: &[(Ident, Option<Vec<Path>>)],
+    delta_leader: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut output = proc_macro2::TokenStream::new();
+    for (view, names) in views {
+        let view_fields: Vec<DeltaField> = match names {
+            Some(names) => names
+                .iter()
+                .filter_map(|name| {
+                    let field = fields.iter().find(
+                        |field| matches!(&field.member, Member::Named(member) if name.is_ident(member)),
+                    );
+                    if field.is_none() {
+                        emit_error!(name, "no field named `{}` in the view", quote!(#name));
+                    }
+                    field.cloned()
+                })
+                .collect(),
+            None => fields.to_vec(),
+        };
+        let view_ident = format_ident!("{}{}Delta", ident, variant_ident(view));
+        let delta_fn = format_ident!("delta_{}", view);
+        let apply_fn = format_ident!("apply_delta_{}", view);
+        let members = delta_fields(krate, view_fields.iter().cloned(), &quote!(pub));
+        let (compute_let, compute_fields) =
+            delta_compute_fields(krate, view_fields.iter().cloned(), false);
+        let target = quote_spanned!(Span::mixed_site()=> self);
+        let (apply_let, apply_actions) =
+            delta_apply_fields(krate, view_fields.iter().cloned(), false, false, &target);
+        let granularity = if view_fields.iter().any(|field| field.attrs.coarse) {
+            quote_spanned! {Span::mixed_site()=>
+                let granularity = #krate::Granularity::Fine;
+            }
+        } else {
+            quote! {}
+        };
+        let doc = format!("The delta of the `{}` view of [`{}`].", view, ident);
+        output.extend(quote_spanned! {Span::mixed_site()=>
+            #[doc = #doc]
+            #delta_leader
+            #vis struct #view_ident {
+                #members
+            }
+
+            impl #ident {
+                /// `ComputeDelta::delta`, limited to the fields of the view.
+                #vis fn #delta_fn(old: Self, new: Self) -> ::std::option::Option<#view_ident> {
+                    let started = #krate::observer::start();
+                    let mut changed_fields = 0usize;
+                    #granularity
+                    #compute_let
+                    #krate::observer::finish(
+                        started,
+                        #krate::observer::Operation::Delta,
+                        ::std::any::type_name::<Self>(),
+                        changed_fields,
+                    );
+                    if changed_fields > 0 {
+                        ::std::option::Option::Some(#view_ident {
+                            #compute_fields
+                        })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+
+                /// `ApplyDelta::apply_delta` for a delta of the view.
+                #vis fn #apply_fn(&mut self, delta: #view_ident) {
+                    let started = #krate::observer::start();
+                    let mut applied_fields = 0usize;
+                    let #view_ident {
+                        #apply_let
+                    } = delta;
+                    #apply_actions
+                    #krate::observer::finish(
+                        started,
+                        #krate::observer::Operation::Apply,
+                        ::std::any::type_name::<Self>(),
+                        applied_fields,
+                    );
+                }
+            }
+        });
+    }
+    output
+}
+
+/// Implements `Schema` for the struct, as a static describing each field.
+fn delta_schema(krate: &Path, ident: &Ident, fields: &[DeltaField]) -> proc_macro2::TokenStream {
+    let name = ident.to_string();
+    let fields = fields.iter().map(|field| {
+        let name = field_name(&field.member);
+        let ty = &field.ty;
+        let type_name = quote!(#ty).to_string();
+        let kind = format_ident!(
+            "{}",
+            match field.field_type {
+                FieldType::Scalar => "Scalar",
+                FieldType::Float => "Float",
+                FieldType::Bitset => "Bitset",
+                FieldType::Ordered => "Ordered",
+                FieldType::Unordered => "Unordered",
+                FieldType::UnorderedScalar => "UnorderedScalar",
+                FieldType::Keyed => "Keyed",
+                FieldType::Delta => "Delta",
+                FieldType::Strategy => "Strategy",
+            }
+        );
+        let nested = if field.field_type == FieldType::Delta {
+            quote_spanned! {Span::mixed_site()=>
+                ::std::option::Option::Some(<#ty as #krate::schema::Schema>::delta_schema)
+            }
+        } else {
+            quote_spanned!(Span::mixed_site()=> ::std::option::Option::None)
+        };
+        quote_spanned! {Span::mixed_site()=>
+            #krate::schema::FieldSchema {
+                name: #name,
+                type_name: #type_name,
+                kind: #krate::schema::FieldKind::#kind,
+                nested: #nested,
+            }
+        }
+    });
+    quote_spanned! {Span::mixed_site()=>
+        impl #krate::schema::Schema for #ident {
+            fn delta_schema() -> &'static #krate::schema::DeltaSchema {
+                static SCHEMA: #krate::schema::DeltaSchema = #krate::schema::DeltaSchema {
+                    name: #name,
+                    fields: &[#(#fields),*],
+                };
+                &SCHEMA
+            }
+        }
+    }
+}
+
+/// Implements `arbitrary::Arbitrary` for the delta struct, for fuzzing
+/// `apply_delta` with random deltas.
+fn delta_arbitrary(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    compact: bool,
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    // `serde_json::Value` has no `Arbitrary` impl, and random deltas only
+    // need the fields this version knows.
+    let unknown = if unknown_fields {
+        quote!(unknown_fields: ::std::default::Default::default(),)
+    } else {
+        quote! {}
+    };
+    let (mut idents, types): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .flat_map(|field| delta_members(krate, field))
+        .unzip();
+    if compact {
+        idents.insert(0, format_ident!("present"));
+    }
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let mut generics = generics.clone();
+    generics.params.insert(0, parse_quote!('arbitrary));
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::arbitrary::Arbitrary<'arbitrary>));
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::arbitrary::Arbitrary<'arbitrary> for #delta_ident #ty_generics #where_clause {
+            fn arbitrary(
+                u: &mut #krate::arbitrary::Unstructured<'arbitrary>,
+            ) -> #krate::arbitrary::Result<Self> {
+                ::std::result::Result::Ok(Self {
+                    #(#idents: #krate::arbitrary::Arbitrary::arbitrary(u)?,)*
+                    #unknown
+                })
+            }
+        }
+    }
+}
+
+/// Implements conversions between the delta struct and a
+/// `serde_json::Value` object with one key per delta field.
+fn delta_json(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    // Keys left after taking every field are kept in `unknown_fields`, or
+    // are an error without it.
+    let (take_unknown, deny_unknown, insert_unknown) = if unknown_fields {
+        (
+            quote_spanned!(Span::mixed_site()=> unknown_fields: object,),
+            quote! {},
+            quote_spanned!(Span::mixed_site()=> object.extend(delta.unknown_fields);),
+        )
+    } else {
+        (
+            quote! {},
+            quote_spanned!(Span::mixed_site()=> #krate::json::deny_unknown(object)?;),
+            quote! {},
+        )
+    };
+    let (idents, types): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .flat_map(|field| delta_members(krate, field))
+        .unzip();
+    let names: Vec<_> = idents
+        .iter()
+        .map(|ident| ident.to_string().trim_start_matches("r#").to_string())
+        .collect();
+    // A cleared `Option` field is `null`, so an unchanged one is left out.
+    let (takes, inserts): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .flat_map(|field| {
+            let nullable = is_nullable_member(field);
+            delta_members(krate, field)
+                .into_iter()
+                .map(move |_| nullable)
+        })
+        .map(|nullable| {
+            if nullable {
+                (
+                    quote_spanned!(Span::mixed_site()=> #krate::json::take_nullable),
+                    quote_spanned!(Span::mixed_site()=> #krate::json::insert_nullable),
+                )
+            } else {
+                (
+                    quote_spanned!(Span::mixed_site()=> #krate::json::take_field),
+                    quote_spanned!(Span::mixed_site()=> #krate::json::insert),
+                )
+            }
+        })
+        .unzip();
+    let mut de_generics = generics.clone();
+    let de_where_clause = de_generics.make_where_clause();
+    let mut ser_generics = generics.clone();
+    let ser_where_clause = ser_generics.make_where_clause();
+    for ty in &types {
+        de_where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::serde::de::DeserializeOwned));
+        ser_where_clause
+            .predicates
+            .push(parse_quote!(#ty: #krate::serde::Serialize));
+    }
+    let (de_impl_generics, ty_generics, de_where_clause) = de_generics.split_for_impl();
+    let (ser_impl_generics, _, ser_where_clause) = ser_generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #de_impl_generics ::std::convert::TryFrom<#krate::json::Value> for #delta_ident #ty_generics #de_where_clause {
+            type Error = #krate::Error;
+
+            fn try_from(value: #krate::json::Value) -> ::std::result::Result<Self, Self::Error> {
+                let mut object = #krate::json::object(value)?;
+                let delta = Self {
+                    #(#idents: #takes(&mut object, #names)?,)*
+                    #take_unknown
+                };
+                #deny_unknown
+                ::std::result::Result::Ok(delta)
+            }
+        }
+
+        impl #ser_impl_generics ::std::convert::From<#delta_ident #ty_generics> for #krate::json::Value #ser_where_clause {
+            fn from(delta: #delta_ident #ty_generics) -> Self {
+                let mut object = #krate::json::Map::new();
+                #insert_unknown
+                #(#inserts(&mut object, #names, delta.#idents);)*
+                #krate::json::Value::Object(object)
+            }
+        }
+    }
+}
+
+/// Generates `apply_to_json` on the delta struct, which applies a clone of
+/// the delta to the struct a document deserializes to.
+fn delta_apply_json(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    extra: &[Ident],
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let target_ty: Type = parse_quote!(#ident #ty_generics);
+    let where_clause = generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(parse_quote!(#target_ty: #krate::serde::Serialize));
+    where_clause
+        .predicates
+        .push(parse_quote!(#target_ty: #krate::serde::de::DeserializeOwned));
+    let mut members = Vec::new();
+    for (member, member_ty) in fields.iter().flat_map(|field| delta_members(krate, field)) {
+        where_clause
+            .predicates
+            .push(parse_quote!(#member_ty: ::std::clone::Clone));
+        members.push(member);
+    }
+    members.extend(extra.iter().cloned());
+    // Fields this version doesn't know are set on the document as they are.
+    let unknown = if extra.iter().any(|member| member == "unknown_fields") {
+        quote_spanned! {Span::mixed_site()=>
+            if let #krate::json::Value::Object(object) = target {
+                object.extend(::std::clone::Clone::clone(&self.unknown_fields));
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let doc = format!(
+        "Applies the delta to `target`, a document holding a `{}` as untyped \
+         JSON. Only the keys whose serialized value the delta changes are \
+         written, so the others are left as they are.",
+        ident
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            #[doc = #doc]
+            #vis fn apply_to_json(
+                &self,
+                target: &mut #krate::json::Value,
+            ) -> ::std::result::Result<(), #krate::Error> {
+                let delta = Self {
+                    #(#members: ::std::clone::Clone::clone(&self.#members),)*
+                };
+                #krate::json::apply_to_json::<#target_ty, Self>(delta, target)?;
+                #unknown
+                ::std::result::Result::Ok(())
+            }
+        }
+    }
+}
+
+/// Generates `size_report` on the delta struct.
+fn delta_size_report(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let entries = fields.iter().map(|field| {
+        let name = field_name(&field.member);
+        let members = delta_members(krate, field)
+            .into_iter()
+            .map(|(member, _)| member.to_string().trim_start_matches("r#").to_string());
+        quote_spanned!(Span::mixed_site()=> (#name, &[#(#members),*]))
+    });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// How many bytes of the delta, serialized as JSON, each of its
+            /// fields takes.
+            #vis fn size_report(&self) -> #krate::DeltaSizeReport
+            where
+                Self: #krate::serde::Serialize,
+            {
+                #krate::json::size_report(self, &[#(#entries),*])
+            }
+        }
+    }
+}
+
+/// Implements `json::FieldMap` for the delta struct, flattening nested
+/// delta fields into dotted paths.
+fn delta_field_map(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    let mut inserts = proc_macro2::TokenStream::new();
+    let mut takes = proc_macro2::TokenStream::new();
+    for field in fields {
+        let ty = &field.ty;
+        if field.field_type == FieldType::Delta && !field.attrs.coarse {
+            let ident = &field.ident;
+            let name = ident.to_string().trim_start_matches("r#").to_string();
+            where_clause
+                .predicates
+                .push(parse_quote!(<#ty as #krate::ComputeDelta>::Output: #krate::json::FieldMap));
+            inserts.extend(quote_spanned! {Span::mixed_site()=>
+                if let ::std::option::Option::Some(patch) = self.#ident {
+                    #krate::json::FieldMap::insert_fields(patch, &#krate::json::join(path, #name), map);
+                }
+            });
+            takes.extend(quote_spanned! {Span::mixed_site()=>
+                #ident: {
+                    let path = #krate::json::join(path, #name);
+                    if #krate::json::has_fields(map, &path) {
+                        ::std::option::Option::Some(#krate::json::FieldMap::take_fields(&path, map)?)
+                    } else {
+                        ::std::option::Option::None
+                    }
+                },
+            });
+            continue;
+        }
+        // A changed value is kept even if it is `null` or empty, unlike the
+        // empty parts of a collection's delta.
+        if let Some(value_ty) = changed_value_type(krate, field) {
+            let member = &field.ident;
+            let name = member.to_string().trim_start_matches("r#").to_string();
+            where_clause
+                .predicates
+                .push(parse_quote!(#value_ty: #krate::serde::Serialize));
+            where_clause
+                .predicates
+                .push(parse_quote!(#value_ty: #krate::serde::de::DeserializeOwned));
+            inserts.extend(quote_spanned! {Span::mixed_site()=>
+                #krate::json::insert_changed(map, #krate::json::join(path, #name), self.#member);
+            });
+            takes.extend(quote_spanned! {Span::mixed_site()=>
+                #member: #krate::json::take_changed(map, #krate::json::join(path, #name))?,
+            });
+            continue;
+        }
+        for (member, member_ty) in delta_members(krate, field) {
+            let name = member.to_string().trim_start_matches("r#").to_string();
+            where_clause
+                .predicates
+                .push(parse_quote!(#member_ty: #krate::serde::Serialize));
+            where_clause
+                .predicates
+                .push(parse_quote!(#member_ty: #krate::serde::de::DeserializeOwned));
+            inserts.extend(quote_spanned! {Span::mixed_site()=>
+                #krate::json::insert_path(map, #krate::json::join(path, #name), self.#member);
+            });
+            takes.extend(quote_spanned! {Span::mixed_site()=>
+                #member: #krate::json::take_path(map, #krate::json::join(path, #name))?,
+            });
+        }
+    }
+    // Keeps a struct without fields from warning about unused arguments.
+    let unused = if fields.is_empty() {
+        quote_spanned!(Span::mixed_site()=> let _ = (path, &map);)
+    } else {
+        quote! {}
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::json::FieldMap for #delta_ident #ty_generics #where_clause {
+            fn insert_fields(
+                self,
+                path: &str,
+                map: &mut ::std::collections::HashMap<::std::string::String, #krate::json::Value>,
+            ) {
+                #unused
+                #inserts
+            }
+
+            fn take_fields(
+                path: &str,
+                map: &mut ::std::collections::HashMap<::std::string::String, #krate::json::Value>,
+            ) -> ::std::result::Result<Self, #krate::json::Error> {
+                #unused
+                ::std::result::Result::Ok(Self {
+                    #takes
+                })
+            }
+        }
+    }
+}
+
+/// Generates `conflicts_with` on the delta struct, listing the fields two
+/// deltas both modify. Bitset fields only conflict on shared bits.
+fn delta_conflicts(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let checks: proc_macro2::TokenStream = fields
+        .iter()
+        .map(|field| {
+            let name = field_name(&field.member);
+            let ident = &field.ident;
+            let conflict = match field.field_type {
+                FieldType::Bitset => {
+                    let set = format_ident!("{}_set", ident);
+                    let clear = format_ident!("{}_clear", ident);
+                    quote_spanned! {Span::mixed_site()=>
+                        #krate::bitset::overlaps((self.#set, self.#clear), (other.#set, other.#clear))
+                    }
+                }
+                _ => {
+                    let ours = field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> self));
+                    let theirs =
+                        field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> other));
+                    quote_spanned! {Span::mixed_site()=>
+                        (#ours) && (#theirs)
+                    }
+                }
+            };
+            quote_spanned! {Span::mixed_site()=>
+                if #conflict {
+                    conflicts.push(#name);
+                }
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// The names of the fields both `self` and `other` modify.
+            #vis fn conflicts_with(&self, other: &Self) -> ::std::vec::Vec<&'static str> {
+                let mut conflicts = ::std::vec::Vec::new();
+                #checks
+                conflicts
+            }
+        }
+    }
+}
+
+/// Generates `split` on the delta struct, breaking it into deltas with at
+/// most `max_items` collection elements each.
+fn delta_split(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+    unknown_fields: bool,
+) -> proc_macro2::TokenStream {
+    let mut takes = proc_macro2::TokenStream::new();
+    let mut empties = proc_macro2::TokenStream::new();
+    // Unknown fields stay in the first chunk.
+    if unknown_fields {
+        empties.extend(quote_spanned! {Span::mixed_site()=>
+            unknown_fields: ::std::default::Default::default(),
+        });
+    }
+    let mut pushes = proc_macro2::TokenStream::new();
+    let vec_new = quote!(::std::vec::Vec::new());
+    for field in fields {
+        let ident = &field.ident;
+        // Moves a collection member out of the first chunk and pushes its
+        // elements one by one.
+        let mut spread = |member: Ident, empty: &proc_macro2::TokenStream, elements: &[&str]| {
+            takes.extend(quote_spanned! {Span::mixed_site()=>
+                let #member = ::std::mem::take(&mut first.#member);
+            });
+            empties.extend(quote_spanned! {Span::mixed_site()=>
+                #member: #empty,
+            });
+            if elements.is_empty() {
+                pushes.extend(quote_spanned! {Span::mixed_site()=>
+                    for item in #member {
+                        chunks.take(1).#member.push(item);
+                    }
+                });
+            }
+            for element in elements {
+                let element = format_ident!("{}", element);
+                pushes.extend(quote_spanned! {Span::mixed_site()=>
+                    for item in #member.#element {
+                        chunks.take(1).#member.#element.push(item);
+                    }
+                });
+            }
+        };
+        match field.field_type {
+            FieldType::Unordered => {
+                spread(format_ident!("{}_remove", ident), &vec_new, &[]);
+                spread(format_ident!("{}_add", ident), &vec_new, &[]);
+            }
+            FieldType::Ordered if field.attrs.key.is_some() => {
+                spread(ident.clone(), &vec_new, &[]);
+            }
+            FieldType::Keyed => spread(
+                ident.clone(),
+                &quote!(::std::default::Default::default()),
+                &["remove", "patch", "add"],
+            ),
+            FieldType::Ordered => {
+                takes.extend(quote_spanned! {Span::mixed_site()=>
+                    let #ident = ::std::mem::take(&mut first.#ident);
+                });
+                empties.extend(quote_spanned! {Span::mixed_site()=>
+                    #ident: ::std::vec::Vec::new(),
+                });
+                pushes.extend(quote_spanned! {Span::mixed_site()=>
+                    for splice in #ident {
+                        for part in splice.split(max_items) {
+                            chunks.take(part.insert.len().max(1)).#ident.push(part);
+                        }
+                    }
+                });
+            }
+            FieldType::Bitset => {
+                for mask in &[
+                    format_ident!("{}_set", ident),
+                    format_ident!("{}_clear", ident),
+                ] {
+                    let empty = format_ident!("{}_empty", mask, span = Span::mixed_site());
+                    takes.extend(quote_spanned! {Span::mixed_site()=>
+                        let #empty = first.#mask & !first.#mask;
+                    });
+                    empties.extend(quote_spanned! {Span::mixed_site()=>
+                        #mask: #empty,
+                    });
+                }
+            }
+            FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+                if field.presence_bit.is_some() =>
+            {
+                empties.extend(quote_spanned! {Span::mixed_site()=>
+                    #ident: ::std::default::Default::default(),
+                });
+            }
+            FieldType::Scalar
+            | FieldType::Float
+            | FieldType::UnorderedScalar
+            | FieldType::Delta
+            | FieldType::Strategy => {
+                empties.extend(quote_spanned! {Span::mixed_site()=>
+                    #ident: ::std::option::Option::None,
+                });
+            }
+        }
+    }
+    if fields.iter().any(|field| field.presence_bit.is_some()) {
+        empties.extend(quote_spanned! {Span::mixed_site()=>
+            present: 0,
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = if pushes.is_empty() {
+        quote_spanned! {Span::mixed_site()=>
+            let _ = max_items;
+            ::std::vec![self]
+        }
+    } else {
+        quote_spanned! {Span::mixed_site()=>
+            let mut first = self;
+            #takes
+            let mut chunks = #krate::split::Chunks::new(first, max_items, || Self {
+                #empties
+            });
+            #pushes
+            chunks.finish()
+        }
+    };
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// Splits `self` into deltas holding at most `max_items` collection
+            /// elements each, which applied in order are equivalent to `self`.
+            /// All other changes are kept in the first delta.
+            #vis fn split(self, max_items: usize) -> ::std::vec::Vec<Self> {
+                #body
+            }
+        }
+    }
+}
+
+/// Whether the delta struct `delta` changes `field`.
+fn field_changed(
+    krate: &Path,
+    field: &DeltaField,
+    delta: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let ident = &field.ident;
+    match field.field_type {
+        FieldType::Bitset => {
+            let set = format_ident!("{}_set", ident);
+            let clear = format_ident!("{}_clear", ident);
+            quote_spanned! {Span::mixed_site()=>
+                !#krate::bitset::is_empty(#delta.#set) || !#krate::bitset::is_empty(#delta.#clear)
+            }
+        }
+        FieldType::Ordered | FieldType::Keyed => quote_spanned! {Span::mixed_site()=>
+            !#delta.#ident.is_empty()
+        },
+        FieldType::Unordered => {
+            let add = format_ident!("{}_add", ident);
+            let remove = format_ident!("{}_remove", ident);
+            quote_spanned! {Span::mixed_site()=>
+                !#delta.#add.is_empty() || !#delta.#remove.is_empty()
+            }
+        }
+        FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar
+            if field.presence_bit.is_some() =>
+        {
+            let bit = field.presence_bit;
+            quote_spanned! {Span::mixed_site()=>
+                #delta.present & (1u64 << #bit) != 0
+            }
+        }
+        FieldType::Scalar
+        | FieldType::Float
+        | FieldType::UnorderedScalar
+        | FieldType::Delta
+        | FieldType::Strategy => {
+            quote_spanned! {Span::mixed_site()=>
+                #delta.#ident.is_some()
+            }
+        }
+    }
+}
+
+/// Generates `{Name}Changed`, a struct with a `bool` per field, and
+/// `changed_flags` on the delta struct returning it.
+fn delta_changed_flags(
+    krate: &Path,
+    vis: &Visibility,
+    ident: &Ident,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let flags_ident = format_ident!("{}Changed", ident);
+    let flag_fields: proc_macro2::TokenStream = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            quote! {
+                pub #ident: bool,
+            }
+        })
+        .collect();
+    let flag_values: proc_macro2::TokenStream = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let changed = field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> self));
+            quote_spanned! {Span::mixed_site()=>
+                #ident: #changed,
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        /// Which fields a delta changes.
+        #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+        #vis struct #flags_ident {
+            #flag_fields
+        }
+
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// Which fields `self` changes.
+            #vis fn changed_flags(&self) -> #flags_ident {
+                #flags_ident {
+                    #flag_values
+                }
+            }
+        }
+    }
+}
+
+/// Generates `record_into` on the delta struct, recording the changed
+/// fields on a `tracing` span.
+fn delta_record_into(
+    krate: &Path,
+    vis: &Visibility,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let checks: proc_macro2::TokenStream = fields
+        .iter()
+        .map(|field| {
+            let name = field_name(&field.member);
+            let changed = field_changed(krate, field, &quote_spanned!(Span::mixed_site()=> self));
+            quote_spanned! {Span::mixed_site()=>
+                if #changed {
+                    changed.push(#name);
+                }
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// Records the names and number of the fields `self` changes on
+            /// `span`, as `delta.changed_fields` and `delta.change_count`.
+            #vis fn record_into(&self, span: &#krate::trace::Span) {
+                let mut changed = ::std::vec::Vec::new();
+                #checks
+                #krate::trace::record(span, &changed);
+            }
+        }
+    }
+}
+
+/// The name of a field on the deriving struct, as used in runtime output.
+fn field_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.to_string().trim_start_matches("r#").to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// Implements `Serialize` for the delta struct as a sequence of
+/// `tagged::Record`s, one per changed field and operation.
+fn delta_tagged_serialize(
+    krate: &Path,
+    delta_ident: &Ident,
+    generics: &Generics,
+    fields: &[DeltaField],
+) -> proc_macro2::TokenStream {
+    let mut records = Vec::new();
+    for field in fields {
+        let name = field_name(&field.member);
+        let ident = &field.ident;
+        let mut record = |value: proc_macro2::TokenStream, op: &str| {
+            records.push((value, name.clone(), op.to_string()));
+        };
+        match field.field_type {
+            FieldType::Bitset => {
+                let set = format_ident!("{}_set", ident);
+                let clear = format_ident!("{}_clear", ident);
+                record(
+                    quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty_bits(&self.#set)},
+                    "set",
+                );
+                record(
+                    quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty_bits(&self.#clear)},
+                    "clear",
+                );
+            }
+            FieldType::Ordered => record(
+                quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty(&self.#ident)},
+                "splice",
+            ),
+            FieldType::Keyed => {
+                for op in &["remove", "patch", "add"] {
+                    let member = format_ident!("{}", op);
+                    record(
+                        quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty(&self.#ident.#member)},
+                        op,
+                    );
+                }
+            }
+            FieldType::Unordered => {
+                let add = format_ident!("{}_add", ident);
+                let remove = format_ident!("{}_remove", ident);
+                record(
+                    quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty(&self.#add)},
+                    "add",
+                );
+                record(
+                    quote_spanned! {Span::mixed_site()=> #krate::tagged::non_empty(&self.#remove)},
+                    "remove",
+                );
+            }
+            FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => record(
+                quote_spanned! {Span::mixed_site()=> self.#ident.as_ref()},
+                "set",
+            ),
+            FieldType::Delta | FieldType::Strategy => record(
+                quote_spanned! {Span::mixed_site()=> self.#ident.as_ref()},
+                "patch",
+            ),
+        }
+    }
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in fields {
+        for (_, ty) in delta_members(krate, field) {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: #krate::serde::Serialize));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let values: Vec<_> = records.iter().map(|(value, _, _)| value).collect();
+    let names: Vec<_> = records.iter().map(|(_, name, _)| name).collect();
+    let ops: Vec<_> = records.iter().map(|(_, _, op)| op).collect();
+    quote_spanned! {Span::mixed_site()=>
+        impl #impl_generics #krate::serde::Serialize for #delta_ident #ty_generics #where_clause {
+            fn serialize<__S>(&self, serializer: __S) -> ::std::result::Result<__S::Ok, __S::Error>
+            where
+                __S: #krate::serde::Serializer,
+            {
+                let mut len = 0usize;
+                #(
+                    if #values.is_some() {
+                        len += 1;
+                    }
+                )*
+                let mut seq = #krate::serde::Serializer::serialize_seq(
+                    serializer,
+                    ::std::option::Option::Some(len),
+                )?;
+                #(
+                    if let ::std::option::Option::Some(value) = #values {
+                        #krate::serde::ser::SerializeSeq::serialize_element(
+                            &mut seq,
+                            &#krate::tagged::Record {
+                                field: #names,
+                                op: #ops,
+                                value,
+                            },
+                        )?;
+                    }
+                )*
+                #krate::serde::ser::SerializeSeq::end(seq)
             }
         }
-    }))
+    }
 }
 
+/// The statements computing each delta member and the members themselves.
+/// With `with_context`, `ctx` is in scope and field hooks are used.
 fn delta_compute_fields(
-    named: bool,
-    iter: impl Iterator<Item = (String, Type, FieldType, String)>,
+    krate: &Path,
+    iter: impl Iterator<Item = DeltaField>,
+    with_context: bool,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    iter.map(|(og_ident, _ty, field_ty, _field_leader)| {
-        let ident = if named {
-            format_ident!("{}", og_ident)
-        } else {
-            format_ident!("field_{}", og_ident)
-        };
-        let og_ident: proc_macro2::TokenStream = FromStr::from_str(&og_ident).unwrap();
-        match field_ty {
-            FieldType::Ordered => unimplemented!(),
+    let fields: Vec<DeltaField> = iter.collect();
+    // The fields of a group are all sent when any of them changed.
+    let mut groups: Vec<&Ident> = vec![];
+    for group in fields.iter().filter_map(|field| field.attrs.group.as_ref()) {
+        if !groups.contains(&group) {
+            groups.push(group);
+        }
+    }
+    let group_flags: proc_macro2::TokenStream = groups
+        .iter()
+        .map(|group| {
+            let flag = group_flag(group);
+            let differs = fields
+                .iter()
+                .filter(|field| field.attrs.group.as_ref() == Some(group))
+                .map(|field| scalar_differs(krate, &field.member, field.field_type, &field.attrs));
+            quote_spanned! {Span::mixed_site()=>
+                let #flag = false #(|| (#differs))*;
+            }
+        })
+        .collect();
+    let (lets, members): (proc_macro2::TokenStream, proc_macro2::TokenStream) = fields
+        .into_iter()
+        .map(
+        |DeltaField {
+             member,
+             ident,
+             ty,
+             field_type,
+             attrs,
+             presence_bit,
+         }| match field_type {
+            FieldType::Bitset => {
+                let set = format_ident!("{}_set", ident);
+                let clear = format_ident!("{}_clear", ident);
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let (#set, #clear) = #krate::bitset::diff(old.#member, new.#member);
+                        if !#krate::bitset::is_empty(#set) || !#krate::bitset::is_empty(#clear) {
+                            changed_fields += 1;
+                        }
+                    },
+                    quote! {
+                        #set,
+                        #clear,
+                    },
+                )
+            }
+            FieldType::Ordered if attrs.key.is_some() => {
+                let key = key_extractor(&ty, &attrs);
+                let same_key = key_comparer(&ty, &attrs);
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let #ident = if #krate::keyed_vec::unchanged_in_order(&old.#member, &new.#member, #same_key) {
+                            ::std::vec::Vec::new()
+                        } else {
+                            #krate::KeyedOp::diff(
+                                old.#member.into_iter().collect(),
+                                new.#member.into_iter().collect(),
+                                #key,
+                            )
+                        };
+                        if !#ident.is_empty() {
+                            changed_fields += 1;
+                        }
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
+            FieldType::Keyed => {
+                let key = key_extractor(&ty, &attrs);
+                let same_key = key_comparer(&ty, &attrs);
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let #ident = if #krate::keyed_vec::unchanged_in_order(&old.#member, &new.#member, #same_key) {
+                            ::std::default::Default::default()
+                        } else {
+                            #krate::KeyedVecDelta::diff(
+                                old.#member.into_iter().collect(),
+                                new.#member.into_iter().collect(),
+                                #key,
+                            )
+                        };
+                        if !#ident.is_empty() {
+                            changed_fields += 1;
+                        }
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
+            FieldType::Ordered => (
+                quote_spanned! {Span::mixed_site()=>
+                    let #ident = if #krate::unordered::in_order_eq(&old.#member, &new.#member) {
+                        ::std::vec::Vec::new()
+                    } else {
+                        #krate::Splice::diff(
+                            old.#member.into_iter().collect(),
+                            new.#member.into_iter().collect(),
+                        )
+                    };
+                    if !#ident.is_empty() {
+                        changed_fields += 1;
+                    }
+                },
+                quote! {
+                    #ident,
+                },
+            ),
             FieldType::Unordered => {
                 let add = format_ident!("{}_add", ident);
                 let remove = format_ident!("{}_remove", ident);
-
+                let sort = sort_unordered(&attrs, &add, &remove);
+                let remove_mut = if attrs.sort_unordered {
+                    quote!(mut)
+                } else {
+                    quote!()
+                };
                 (
-                    quote! {
-                        let mut #add = new.#og_ident.into_iter().collect::<::std::vec::Vec<_>>();
-                        let #remove = old.#og_ident.into_iter().filter_map(|i| {
-                            if let Some(index) = #add.iter().position(|a| a == &i) {
-                                #add.remove(index);
-                                None
-                            } else {
-                                Some(i)
-                            }
-                        }).collect::<::std::vec::Vec<_>>();
-                        delta_is_some = delta_is_some || !#add.is_empty() || !#remove.is_empty();
+                    // Unchanged collections are caught before anything is
+                    // collected, so they don't allocate.
+                    quote_spanned! {Span::mixed_site()=>
+                        let (#remove_mut #add, #remove_mut #remove) = if #krate::unordered::in_order_eq(&old.#member, &new.#member) {
+                            (::std::vec::Vec::new(), ::std::vec::Vec::new())
+                        } else {
+                            let mut #add = new.#member.into_iter().collect::<::std::vec::Vec<_>>();
+                            let #remove = old.#member.into_iter().filter_map(|i| {
+                                if let ::std::option::Option::Some(index) = #add.iter().position(|a| a == &i) {
+                                    #add.remove(index);
+                                    ::std::option::Option::None
+                                } else {
+                                    ::std::option::Option::Some(i)
+                                }
+                            }).collect::<::std::vec::Vec<_>>();
+                            (#add, #remove)
+                        };
+                        #sort
+                        if !#add.is_empty() || !#remove.is_empty() {
+                            changed_fields += 1;
+                        }
                     },
                     quote! {
                         #add,
@@ -240,187 +4119,641 @@ fn delta_compute_fields(
                     },
                 )
             }
-            FieldType::Scalar => (
-                quote! {
-                   let #ident = if old.#og_ident != new.#og_ident {
-                       delta_is_some = true;
-                       Some(new.#og_ident)
-                   } else {
-                       None
-                   };
+            FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => {
+                let changed = match field_type {
+                    _ if attrs.group.is_some() => {
+                        let flag = group_flag(attrs.group.as_ref().unwrap());
+                        quote!(#flag)
+                    }
+                    FieldType::Float => {
+                        let epsilon = attrs.epsilon.map_or_else(|| quote!(0.0), |e| quote!(#e));
+                        let ulps = attrs.ulps.map_or_else(|| quote!(0), |u| quote!(#u));
+                        quote_spanned! {Span::mixed_site()=>
+                            !#krate::float::Float::approx_eq(old.#member, new.#member, #epsilon, #ulps)
+                        }
+                    }
+                    FieldType::UnorderedScalar => quote_spanned! {Span::mixed_site()=>
+                        !#krate::unordered::multiset_eq(&old.#member, &new.#member)
+                    },
+                    _ => match (attrs.eq_with.filter(|_| with_context), attrs.tolerance) {
+                        (Some(eq), _) => quote_spanned! {Span::mixed_site()=>
+                            !#eq(&old.#member, &new.#member, ctx)
+                        },
+                        (None, Some(nanos)) => quote_spanned! {Span::mixed_site()=>
+                            !#krate::time::within(&old.#member, &new.#member, #nanos)
+                        },
+                        (None, None) => quote_spanned! {Span::mixed_site()=>
+                            old.#member != new.#member
+                        },
+                    },
+                };
+                let value = match presence_bit {
+                    Some(bit) => quote_spanned! {Span::mixed_site()=>
+                        if #changed {
+                            changed_fields += 1;
+                            present |= 1u64 << #bit;
+                            new.#member
+                        } else {
+                            ::std::default::Default::default()
+                        }
+                    },
+                    None => quote_spanned! {Span::mixed_site()=>
+                        if #changed {
+                            changed_fields += 1;
+                            ::std::option::Option::Some(new.#member)
+                        } else {
+                            ::std::option::Option::None
+                        }
+                    },
+                };
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                       let #ident = #value;
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
+            FieldType::Delta if attrs.coarse => (
+                quote_spanned! {Span::mixed_site()=>
+                    let #ident = #krate::Granular::diff(old.#member, new.#member, granularity);
+                    if #ident.is_some() {
+                        changed_fields += 1;
+                    }
                 },
                 quote! {
                     #ident,
                 },
             ),
+            FieldType::Strategy => {
+                let strategy = attrs.strategy.unwrap();
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let #ident = <#strategy as #krate::DiffStrategy<#ty>>::diff(old.#member, new.#member);
+                        if #ident.is_some() {
+                            changed_fields += 1;
+                        }
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
             FieldType::Delta => (
-                quote! {
-                    let #ident = Delta::delta(old.#og_ident, new.#og_ident);
-                    delta_is_some = delta_is_some || #ident.is_some();
-
+                quote_spanned! {Span::mixed_site()=>
+                    let #ident = #krate::ComputeDelta::delta(old.#member, new.#member);
+                    if #ident.is_some() {
+                        changed_fields += 1;
+                    }
                 },
                 quote! {
                     #ident,
                 },
             ),
+        },
+    )
+    .unzip();
+    (quote!(#group_flags #lets), members)
+}
+
+/// The local holding whether any field of `group` changed.
+fn group_flag(group: &Ident) -> Ident {
+    format_ident!("{}_group_changed", group)
+}
+
+/// Statements adding each field's change count to `count`, returning early
+/// once it exceeds `limit`.
+fn delta_change_counts(
+    krate: &Path,
+    iter: impl Iterator<Item = DeltaField>,
+) -> proc_macro2::TokenStream {
+    FromIterator::from_iter(iter.map(
+        |DeltaField {
+             member,
+             ty,
+             field_type,
+             attrs,
+             ..
+         }| {
+            let field_count = match field_type {
+                FieldType::Bitset => quote_spanned! {Span::mixed_site()=>
+                    {
+                        let (set, clear) = #krate::bitset::diff(old.#member, new.#member);
+                        (!#krate::bitset::is_empty(set) || !#krate::bitset::is_empty(clear)) as usize
+                    }
+                },
+                FieldType::Ordered if attrs.key.is_some() => {
+                    let key = key_extractor(&ty, &attrs);
+                    let same_key = key_comparer(&ty, &attrs);
+                    quote_spanned! {Span::mixed_site()=>
+                        if #krate::keyed_vec::unchanged_in_order(&old.#member, &new.#member, #same_key) {
+                            0
+                        } else {
+                            #krate::ordered::keyed_change_count(
+                                &old.#member,
+                                &new.#member,
+                                #key,
+                                limit - count,
+                            )
+                        }
+                    }
+                }
+                FieldType::Ordered => quote_spanned! {Span::mixed_site()=>
+                    #krate::ordered::change_count(&old.#member, &new.#member)
+                },
+                FieldType::Keyed => {
+                    let key = key_extractor(&ty, &attrs);
+                    let same_key = key_comparer(&ty, &attrs);
+                    quote_spanned! {Span::mixed_site()=>
+                        if #krate::keyed_vec::unchanged_in_order(&old.#member, &new.#member, #same_key) {
+                            0
+                        } else {
+                            #krate::keyed_vec::change_count(&old.#member, &new.#member, #key, limit - count)
+                        }
+                    }
+                }
+                FieldType::Unordered => quote_spanned! {Span::mixed_site()=>
+                    #krate::unordered::change_count(&old.#member, &new.#member, limit - count)
+                },
+                FieldType::Scalar | FieldType::Float => {
+                    let differs = scalar_differs(krate, &member, field_type, &attrs);
+                    quote_spanned! {Span::mixed_site()=>
+                        (#differs) as usize
+                    }
+                }
+                FieldType::UnorderedScalar => quote_spanned! {Span::mixed_site()=>
+                    (!#krate::unordered::multiset_eq(&old.#member, &new.#member)) as usize
+                },
+                FieldType::Delta => quote_spanned! {Span::mixed_site()=>
+                    <#ty as #krate::ComputeDelta>::change_count(&old.#member, &new.#member, limit - count)
+                },
+                FieldType::Strategy => {
+                    let strategy = attrs.strategy.unwrap();
+                    quote_spanned! {Span::mixed_site()=>
+                        <#strategy as #krate::DiffStrategy<#ty>>::change_count(&old.#member, &new.#member, limit - count)
+                    }
+                }
+            };
+            quote_spanned! {Span::mixed_site()=>
+                count += #field_count;
+                if count > limit {
+                    return count;
+                }
+            }
+        },
+    ))
+}
+
+/// Whether a `scalar` or `float` field differs between `old` and `new`.
+fn scalar_differs(
+    krate: &Path,
+    member: &Member,
+    field_type: FieldType,
+    attrs: &FieldAttrs,
+) -> proc_macro2::TokenStream {
+    match (field_type, attrs.tolerance) {
+        (FieldType::Float, _) => {
+            let epsilon = attrs
+                .epsilon
+                .as_ref()
+                .map_or_else(|| quote!(0.0), |e| quote!(#e));
+            let ulps = attrs
+                .ulps
+                .as_ref()
+                .map_or_else(|| quote!(0), |u| quote!(#u));
+            quote_spanned! {Span::mixed_site()=>
+                !#krate::float::Float::approx_eq(old.#member, new.#member, #epsilon, #ulps)
+            }
         }
-    })
-    .unzip()
+        (_, Some(nanos)) => quote_spanned! {Span::mixed_site()=>
+            !#krate::time::within(&old.#member, &new.#member, #nanos)
+        },
+        (_, None) => quote_spanned! {Span::mixed_site()=>
+            old.#member != new.#member
+        },
+    }
 }
 
-fn delta_apply_fields(
-    named: bool,
-    iter: impl Iterator<Item = (String, Type, FieldType, String)>,
+/// Overrides `ComputeDelta::has_changes` for structs with only `scalar` and
+/// `float` fields, comparing the fields in place until one differs.
+fn delta_has_changes(krate: &Path, fields: &[DeltaField]) -> proc_macro2::TokenStream {
+    let scalar_only = fields
+        .iter()
+        .all(|field| matches!(field.field_type, FieldType::Scalar | FieldType::Float));
+    if !scalar_only {
+        return quote! {};
+    }
+    let differs = fields
+        .iter()
+        .map(|field| scalar_differs(krate, &field.member, field.field_type, &field.attrs));
+    quote_spanned! {Span::mixed_site()=>
+        fn has_changes(old: &Self, new: &Self) -> bool {
+            false #(|| #differs)*
+        }
+    }
+}
+
+/// Sorts the `_add` and `_remove` vectors of an unordered field marked
+/// `sort_unordered`.
+fn sort_unordered(attrs: &FieldAttrs, add: &Ident, remove: &Ident) -> proc_macro2::TokenStream {
+    if attrs.sort_unordered {
+        quote_spanned! {Span::mixed_site()=>
+            #add.sort();
+            #remove.sort();
+        }
+    } else {
+        quote! {}
+    }
+}
+
+fn delta_compose_fields(
+    krate: &Path,
+    iter: impl Iterator<Item = DeltaField>,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    iter.map(|(og_ident, ty, field_ty, _field_leader)| {
-        let ident = if named {
-            format_ident!("{}", og_ident)
-        } else {
-            format_ident!("field_{}", og_ident)
-        };
-        let og_ident: proc_macro2::TokenStream = FromStr::from_str(&og_ident).unwrap();
-        match field_ty {
-            FieldType::Ordered => unimplemented!(),
+    iter.map(
+        |DeltaField {
+             ident,
+             ty,
+             field_type,
+             attrs,
+             presence_bit,
+             ..
+         }| match field_type {
+            FieldType::Bitset => {
+                let set = format_ident!("{}_set", ident);
+                let clear = format_ident!("{}_clear", ident);
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let (#set, #clear) = #krate::bitset::compose(
+                            (first.#set, first.#clear),
+                            (second.#set, second.#clear),
+                        );
+                    },
+                    quote! {
+                        #set,
+                        #clear,
+                    },
+                )
+            }
+            FieldType::Keyed => {
+                let key = key_extractor(&ty, &attrs);
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let #ident = #krate::KeyedVecDelta::compose(first.#ident, second.#ident, #key);
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
+            FieldType::Ordered => (
+                quote_spanned! {Span::mixed_site()=>
+                    let mut #ident = first.#ident;
+                    #ident.extend(second.#ident);
+                },
+                quote! {
+                    #ident,
+                },
+            ),
             FieldType::Unordered => {
                 let add = format_ident!("{}_add", ident);
                 let remove = format_ident!("{}_remove", ident);
+                let sort = sort_unordered(&attrs, &add, &remove);
                 (
+                    // Removals in the second delta first cancel out additions
+                    // made by the first.
+                    quote_spanned! {Span::mixed_site()=>
+                        let mut #add = first.#add;
+                        let mut #remove = first.#remove;
+                        for i in second.#remove {
+                            if let ::std::option::Option::Some(index) = #add.iter().position(|a| a == &i) {
+                                #add.remove(index);
+                            } else {
+                                #remove.push(i);
+                            }
+                        }
+                        #add.extend(second.#add);
+                        #sort
+                    },
                     quote! {
                         #add,
-                        mut #remove,
+                        #remove,
                     },
-                    quote! {
-                        {
-                            let og = ::std::mem::replace(&mut self.#og_ident, ::std::iter::FromIterator::from_iter(vec![]));
-                            let mut #ident: #ty = ::std::iter::FromIterator::from_iter(og.into_iter().filter_map(|i| {
-                               if let Some(index) = #remove.iter().position(|a| a == &i) {
-                                 #remove.remove(index);
-                                 None
-                               } else {
-                                 Some(i)
-                               }
-                            }));
-                            #ident.extend(#add.into_iter());
-                            self.#og_ident = #ident;
-                        }
-                    }
                 )
             }
-            FieldType::Scalar => 
-            (
+            FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => (
+                match (presence_bit, attrs.monotonic.map(monotonic_op)) {
+                    // The composed value is the one a sequential apply would
+                    // leave, as far as the two deltas tell.
+                    (Some(bit), Some(op)) => quote_spanned! {Span::mixed_site()=>
+                        let #ident = if second.present & (1u64 << #bit) != 0
+                            && (first.present & (1u64 << #bit) == 0 || second.#ident #op first.#ident)
+                        {
+                            second.#ident
+                        } else {
+                            first.#ident
+                        };
+                    },
+                    (None, Some(op)) => quote_spanned! {Span::mixed_site()=>
+                        let #ident = match (first.#ident, second.#ident) {
+                            (::std::option::Option::Some(a), ::std::option::Option::Some(b)) if !(b #op a) => {
+                                ::std::option::Option::Some(a)
+                            }
+                            (a, b) => b.or(a),
+                        };
+                    },
+                    (Some(bit), None) => quote_spanned! {Span::mixed_site()=>
+                        let #ident = if second.present & (1u64 << #bit) != 0 {
+                            second.#ident
+                        } else {
+                            first.#ident
+                        };
+                    },
+                    (None, None) => quote_spanned! {Span::mixed_site()=>
+                        let #ident = second.#ident.or(first.#ident);
+                    },
+                },
                 quote! {
                     #ident,
                 },
+            ),
+            FieldType::Delta | FieldType::Strategy => {
+                let compose = if let Some(strategy) = attrs.strategy {
+                    quote!(<#strategy as #krate::DiffStrategy<#ty>>::compose)
+                } else if attrs.coarse {
+                    quote!(#krate::Granular::compose)
+                } else if attrs.create_if_missing {
+                    quote!(#krate::option::compose_or_default)
+                } else {
+                    quote!(<#ty as #krate::ApplyDelta<<#ty as #krate::ComputeDelta>::Output>>::compose_delta)
+                };
+                (
+                    quote_spanned! {Span::mixed_site()=>
+                        let #ident = match (first.#ident, second.#ident) {
+                            (::std::option::Option::Some(a), ::std::option::Option::Some(b)) => {
+                                ::std::option::Option::Some(#compose(a, b))
+                            }
+                            (a, b) => b.or(a),
+                        };
+                    },
+                    quote! {
+                        #ident,
+                    },
+                )
+            }
+        },
+    )
+    .unzip()
+}
+
+/// The delta members to destructure and the statements applying them to
+/// `target`. With `with_context`, `ctx` is in scope and field hooks are used.
+/// With `with_async`, the statements are the body of an async fn and
+/// `apply_async` hooks are awaited after each changed field. Fields are
+/// applied by their `apply_order`, then in declaration order.
+fn delta_apply_fields(
+    krate: &Path,
+    iter: impl Iterator<Item = DeltaField>,
+    with_context: bool,
+    with_async: bool,
+    target: &proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut fields: Vec<DeltaField> = iter.collect();
+    fields.sort_by_key(|field| (field.attrs.apply_order.is_none(), field.attrs.apply_order));
+    fields
+        .into_iter()
+        .map(|field| {
+            let hook = field.attrs.apply_async.clone().filter(|_| with_async);
+            let member = field.member.clone();
+            let (members, apply) = delta_apply_field(krate, field, with_context, target);
+            let apply = match hook {
+                // Every field counts itself in `applied_fields` when it changes.
+                Some(hook) => quote_spanned! {Span::mixed_site()=>
+                    let applied_before = applied_fields;
+                    #apply
+                    if applied_fields != applied_before {
+                        #hook(&#target.#member).await;
+                    }
+                },
+                None => apply,
+            };
+            (members, apply)
+        })
+        .unzip()
+}
+
+/// The delta members of a single field and the statements applying them.
+fn delta_apply_field(
+    krate: &Path,
+    field: DeltaField,
+    with_context: bool,
+    target: &proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let DeltaField {
+        member,
+        ident,
+        ty,
+        field_type,
+        attrs,
+        presence_bit,
+    } = field;
+    match field_type {
+        FieldType::Bitset => {
+            let set = format_ident!("{}_set", ident);
+            let clear = format_ident!("{}_clear", ident);
+            (
                 quote! {
-                   if let Some(v) = #ident {
-                       self.#og_ident = v; 
-                   }
+                    #set,
+                    #clear,
+                },
+                quote_spanned! {Span::mixed_site()=>
+                    if !#krate::bitset::is_empty(#set) || !#krate::bitset::is_empty(#clear) {
+                        applied_fields += 1;
+                    }
+                    #krate::bitset::apply(&mut #target.#member, #set, #clear);
+                },
+            )
+        }
+        FieldType::Ordered => (
+            quote! {
+                #ident,
+            },
+            quote_spanned! {Span::mixed_site()=>
+                if !#ident.is_empty() {
+                    applied_fields += 1;
+                    let og = ::std::mem::replace(&mut #target.#member, ::std::iter::FromIterator::from_iter(::std::vec::Vec::new()));
+                    let mut items = og.into_iter().collect::<::std::vec::Vec<_>>();
+                    for splice in #ident {
+                        splice.apply(&mut items);
+                    }
+                    #target.#member = ::std::iter::FromIterator::from_iter(items);
                 }
-            ),
-            FieldType::Delta => 
+            },
+        ),
+        FieldType::Keyed => {
+            let key = key_extractor(&ty, &attrs);
             (
                 quote! {
                     #ident,
                 },
-                quote!{
-                   if let Some(v) = #ident {
-                       self.#og_ident.apply_delta(v); 
-                   }
-                }
-            ),
-        }
-    }).unzip()
-}
-
-fn collect_results(
-    iter: impl Iterator<Item = (String, Type, Result<(Option<FieldType>, String), FieldTypeError>)>,
-    default_field_type: FieldType,
-) -> Result<Vec<(String, Type, FieldType, String)>, Vec<String>> {
-    iter.fold(Ok(vec![]), |v, i| match (v, i) {
-        (Ok(mut v), (ident, b, Ok((c, d)))) => {
-            v.push((ident, b, c.unwrap_or(default_field_type), d));
-            Ok(v)
-        }
-        (Ok(_), (ident, _, Err(_))) => Err(vec![ident]),
-        (Err(mut v), (ident, _, Err(_))) => {
-            v.push(ident);
-            Err(v)
+                quote_spanned! {Span::mixed_site()=>
+                    if !#ident.is_empty() {
+                        applied_fields += 1;
+                        #ident.apply(&mut #target.#member, #key);
+                    }
+                },
+            )
         }
-        (v @ Err(_), _) => v,
-    })
-}
-
-enum FieldTypeError {
-    UnrecognizedJunkFound(Vec<NestedMeta>),
-}
-
-fn get_fieldtype_from_attrs(
-    iter: impl Iterator<Item = Attribute>,
-    attr_name: &str,
-) -> Result<(Option<FieldType>, String), FieldTypeError> {
-    for attr in iter {
-        if let Ok(Meta::List(MetaList { path, nested, .. })) = attr.parse_meta() {
-            let Path { segments, .. } = path;
-            if segments
-                .iter()
-                .map(|p| &p.ident)
-                .eq(["delta_struct"].iter().cloned())
-            {
-                let values: Result<Vec<_>, Vec<NestedMeta>> = nested
-                    .iter()
-                    .map(|nested_meta| match nested_meta {
-                        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
-                            path,
-                            lit: Lit::Str(s),
-                            ..
-                        })) => Ok((path.get_ident().map(|i| i.to_string()), s.value())),
-                        e @ _ => Err(e),
-                    })
-                    .fold(Ok(vec![]), |v, i| match (v, i) {
-                        (Ok(mut v), Ok(i)) => {
-                            v.push(i);
-                            Ok(v)
-                        }
-                        (Ok(_), Err(e)) => Err(vec![e.clone()]),
-                        (Err(mut v), Err(e)) => {
-                            v.push(e.clone());
-                            Err(v)
-                        }
-                        (v @ Err(_), _) => v,
-                    });
-                return match values {
-                    Ok(v) => {
-                        let mut field_type = None;
-                        let mut delta_leader = String::new();
-                        for i in v {
-                            match i.0.as_deref() {
-                                Some("delta_leader") => {
-                                    delta_leader = i.1;
-                                },
-                                a @ _ if Some(attr_name) == a => {
-                                   field_type = string_to_fieldtype(&i.1); 
-                                },
-                                a @ _ => {
-                                    abort_call_site!("Unrecognized value {:?}", a);
-                                }
-                            }
+        FieldType::Unordered => {
+            let add = format_ident!("{}_add", ident);
+            let remove = format_ident!("{}_remove", ident);
+            let extend = if attrs.duplicates == Some(Duplicates::Ignore) {
+                quote_spanned! {Span::mixed_site()=>
+                    let mut updated = kept.collect::<::std::vec::Vec<_>>();
+                    for item in #add {
+                        if !updated.contains(&item) {
+                            updated.push(item);
                         }
-                        Ok((field_type, delta_leader))
                     }
-                    Err(v) => Err(FieldTypeError::UnrecognizedJunkFound(v)),
-                };
-            }
+                    #target.#member = ::std::iter::FromIterator::from_iter(updated);
+                }
+            } else {
+                quote_spanned! {Span::mixed_site()=>
+                    let mut updated: #ty = ::std::iter::FromIterator::from_iter(kept);
+                    updated.extend(#add.into_iter());
+                    #target.#member = updated;
+                }
+            };
+            (
+                quote! {
+                    #add,
+                    mut #remove,
+                },
+                quote_spanned! {Span::mixed_site()=>
+                    if !#add.is_empty() || !#remove.is_empty() {
+                        applied_fields += 1;
+                    }
+                    {
+                        let og = ::std::mem::replace(&mut #target.#member, ::std::iter::FromIterator::from_iter(::std::vec::Vec::new()));
+                        let kept = og.into_iter().filter_map(|i| {
+                           if let ::std::option::Option::Some(index) = #remove.iter().position(|a| a == &i) {
+                             #remove.remove(index);
+                             ::std::option::Option::None
+                           } else {
+                             ::std::option::Option::Some(i)
+                           }
+                        });
+                        #extend
+                    }
+                },
+            )
+        }
+        FieldType::Scalar | FieldType::Float | FieldType::UnorderedScalar => {
+            let store = match attrs.apply_with.filter(|_| with_context) {
+                Some(apply) => quote_spanned! {Span::mixed_site()=>
+                    #apply(&mut #target.#member, v, ctx);
+                },
+                None => quote_spanned! {Span::mixed_site()=>
+                    #target.#member = v;
+                },
+            };
+            // Stale values of a monotonic field are dropped.
+            let store = match attrs.monotonic.map(monotonic_op) {
+                Some(op) => quote_spanned! {Span::mixed_site()=>
+                    if v #op #target.#member {
+                        applied_fields += 1;
+                        #store
+                    }
+                },
+                None => quote_spanned! {Span::mixed_site()=>
+                    applied_fields += 1;
+                    #store
+                },
+            };
+            let apply = match presence_bit {
+                Some(bit) => quote_spanned! {Span::mixed_site()=>
+                   if present & (1u64 << #bit) != 0 {
+                       let v = #ident;
+                       #store
+                   }
+                },
+                None => quote_spanned! {Span::mixed_site()=>
+                   if let ::std::option::Option::Some(v) = #ident {
+                       #store
+                   }
+                },
+            };
+            (
+                quote! {
+                    #ident,
+                },
+                apply,
+            )
+        }
+        FieldType::Delta if attrs.coarse => (
+            quote! {
+                #ident,
+            },
+            quote_spanned! {Span::mixed_site()=>
+               if let ::std::option::Option::Some(v) = #ident {
+                   applied_fields += 1;
+                   #krate::Granular::apply(v, &mut #target.#member);
+               }
+            },
+        ),
+        FieldType::Delta if attrs.create_if_missing => (
+            quote! {
+                #ident,
+            },
+            quote_spanned! {Span::mixed_site()=>
+               if let ::std::option::Option::Some(v) = #ident {
+                   applied_fields += 1;
+                   #krate::option::apply_or_default(&mut #target.#member, v);
+               }
+            },
+        ),
+        FieldType::Delta => (
+            quote! {
+                #ident,
+            },
+            quote_spanned! {Span::mixed_site()=>
+               if let ::std::option::Option::Some(v) = #ident {
+                   applied_fields += 1;
+                   #krate::ApplyDelta::apply_delta(&mut #target.#member, v);
+               }
+            },
+        ),
+        FieldType::Strategy => {
+            let strategy = attrs.strategy.unwrap();
+            (
+                quote! {
+                    #ident,
+                },
+                quote_spanned! {Span::mixed_site()=>
+                   if let ::std::option::Option::Some(v) = #ident {
+                       applied_fields += 1;
+                       <#strategy as #krate::DiffStrategy<#ty>>::apply(&mut #target.#member, v);
+                   }
+                },
+            )
         }
     }
-    Ok((None, String::new()))
 }
 
-fn string_to_fieldtype(s: &str) -> Option<FieldType> {
+pub(crate) fn string_to_fieldtype(s: &str) -> Option<FieldType> {
     match s {
         "ordered" => Some(FieldType::Ordered),
         "unordered" => Some(FieldType::Unordered),
         "scalar" => Some(FieldType::Scalar),
+        // Collections compared by equality and replaced wholesale are just
+        // scalars; the name exists so the intent is visible at the field.
+        "scalar_collection" => Some(FieldType::Scalar),
+        "unordered_scalar" => Some(FieldType::UnorderedScalar),
+        "keyed" => Some(FieldType::Keyed),
         "delta" => Some(FieldType::Delta),
+        "bitset" => Some(FieldType::Bitset),
+        "float" => Some(FieldType::Float),
         _ => None,
     }
 }
@@ -0,0 +1,190 @@
+//! Snapshot testing of the code `#[derive(Delta)]` generates, so crates can
+//! catch unintended changes to the expansion of their types when upgrading
+//! delta-struct.
+//!
+//! ```ignore
+//! #[test]
+//! fn thermostat_expansion() {
+//!     delta_struct_test::assert_snapshot(
+//!         "thermostat",
+//!         delta_struct_test::expand! {
+//!             #[delta_struct(default = "scalar")]
+//!             struct Thermostat {
+//!                 target: u8,
+//!             }
+//!         },
+//!     );
+//! }
+//! ```
+//!
+//! Snapshots are kept in `tests/snapshots/{name}.expanded.rs` of the crate
+//! under test. A missing snapshot is written and the test passes, unless
+//! the `CI` environment variable is set, which fails it instead. A
+//! snapshot that differs fails the test, unless the `DELTA_STRUCT_BLESS`
+//! environment variable is set, which overwrites it instead. Expansions
+//! are formatted with prettyplease, so snapshots don't depend on which
+//! tools are installed.
+//!
+//! With the `macrotest` feature, [`assert_expansions`] snapshots whole test
+//! files through cargo-expand instead, and with the `trybuild` feature,
+//! [`assert_compile_errors`] checks the errors the derive reports for
+//! invalid input.
+
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Expands to the code `#[derive(Delta)]` generates for the given struct,
+/// as a `&'static str`. The struct's `#[delta_struct(...)]` attributes are
+/// honored, and a `#[derive(...)]` on it is ignored.
+pub use delta_struct_macros::expand_to_string as expand;
+
+/// Compares `expanded` with the snapshot `name` in the `tests/snapshots`
+/// directory of the crate being tested. See the [crate docs](crate).
+pub fn assert_snapshot(name: &str, expanded: &str) {
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")
+        .expect("CARGO_MANIFEST_DIR is not set; run the snapshot test through cargo");
+    assert_snapshot_in(
+        Path::new(&manifest_dir).join("tests").join("snapshots"),
+        name,
+        expanded,
+    );
+}
+
+/// [`assert_snapshot`] with the snapshots kept in `dir`.
+pub fn assert_snapshot_in(dir: impl AsRef<Path>, name: &str, expanded: &str) {
+    let bless = env::var_os("DELTA_STRUCT_BLESS").is_some();
+    let ci = env::var_os("CI").is_some();
+    if let Err(message) = check(dir.as_ref(), name, &format(expanded), bless, ci) {
+        panic!("{}", message);
+    }
+}
+
+/// `expanded` formatted by prettyplease.
+///
+/// # Panics
+///
+/// If `expanded` isn't a sequence of Rust items.
+pub fn format(expanded: &str) -> String {
+    match syn::parse_file(expanded) {
+        Ok(file) => prettyplease::unparse(&file),
+        Err(e) => panic!("the expansion is not valid Rust: {}\n\n{}", e, expanded),
+    }
+}
+
+/// Expands every file matching `glob` with cargo-expand and compares it
+/// with the `.expanded.rs` file next to it, like [`assert_snapshot`] does.
+/// Missing expansions are written, unless the `CI` environment variable is
+/// set.
+#[cfg(feature = "macrotest")]
+pub fn assert_expansions(glob: impl AsRef<Path>) {
+    if env::var_os("CI").is_some() {
+        macrotest::expand_without_refresh(glob);
+    } else {
+        macrotest::expand(glob);
+    }
+}
+
+/// Checks that every file matching `glob` fails to compile with the errors
+/// in the `.stderr` file next to it.
+#[cfg(feature = "trybuild")]
+pub fn assert_compile_errors(glob: impl AsRef<Path>) {
+    trybuild::TestCases::new().compile_fail(glob);
+}
+
+fn snapshot_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.expanded.rs", name))
+}
+
+fn check(dir: &Path, name: &str, actual: &str, bless: bool, ci: bool) -> Result<(), String> {
+    let path = snapshot_path(dir, name);
+    match fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => Ok(()),
+        Ok(expected) if !bless => Err(format!(
+            "the expansion differs from the snapshot {}; rerun with DELTA_STRUCT_BLESS=1 to \
+             update it\n\n{}",
+            path.display(),
+            first_difference(&expected, actual)
+        )),
+        Err(_) if ci && !bless => Err(format!(
+            "the snapshot {} is missing; run the test outside of CI to write it",
+            path.display()
+        )),
+        _ => fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&path, actual))
+            .map_err(|e| format!("could not write the snapshot {}: {}", path.display(), e)),
+    }
+}
+
+/// The first line that differs between `expected` and `actual`.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (e, a) => {
+                return format!(
+                    "line {}:\n  snapshot:  {}\n  expansion: {}",
+                    line,
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("delta-struct-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn missing_snapshots_are_written_and_differences_fail() {
+        let dir = scratch_dir("check");
+        let error = check(&dir, "lamp", "struct A;\n", false, true).unwrap_err();
+        assert!(error.contains("lamp.expanded.rs is missing"), "{}", error);
+        assert!(!snapshot_path(&dir, "lamp").exists());
+        assert_eq!(check(&dir, "lamp", "struct A;\n", false, false), Ok(()));
+        assert_eq!(check(&dir, "lamp", "struct A;\n", false, true), Ok(()));
+        let error = check(&dir, "lamp", "struct B;\n", false, false).unwrap_err();
+        assert!(error.ends_with("line 1:\n  snapshot:  struct A;\n  expansion: struct B;"));
+        assert_eq!(check(&dir, "lamp", "struct B;\n", true, false), Ok(()));
+        assert_eq!(
+            fs::read_to_string(snapshot_path(&dir, "lamp")).unwrap(),
+            "struct B;\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expansions_are_strings() {
+        let expanded: &'static str = expand! {
+            #[derive(Delta)]
+            struct Lamp {
+                on: bool,
+            }
+        };
+        assert!(expanded.contains("LampDelta"));
+    }
+
+    #[test]
+    fn formatting_is_independent_of_the_input_layout() {
+        let formatted = format("struct   Lamp { on : bool , }");
+        assert_eq!(formatted, "struct Lamp {\n    on: bool,\n}\n");
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    #[should_panic(expected = "the expansion is not valid Rust")]
+    fn invalid_expansions_fail_loudly() {
+        format("struct Lamp {");
+    }
+}
@@ -0,0 +1,7 @@
+// Checks the errors the derive reports for invalid input. Rerun with
+// TRYBUILD=overwrite after intended changes to them.
+#[cfg(feature = "trybuild")]
+#[test]
+fn invalid_derives() {
+    delta_struct_test::assert_compile_errors("tests/ui/*.rs");
+}
@@ -0,0 +1,235 @@
+use delta_struct::Delta;
+struct Lamp {
+    on: bool,
+    #[delta_struct(field_type = "unordered")]
+    scenes: Vec<u8>,
+}
+struct LampDelta {
+    pub on: ::std::option::Option<bool>,
+    pub scenes_add: ::std::vec::Vec<<Vec<u8> as ::std::iter::IntoIterator>::Item>,
+    pub scenes_remove: ::std::vec::Vec<<Vec<u8> as ::std::iter::IntoIterator>::Item>,
+}
+#[allow(dead_code)]
+enum LampChange {
+    On(bool),
+    Scenes {
+        add: ::std::vec::Vec<<Vec<u8> as ::std::iter::IntoIterator>::Item>,
+        remove: ::std::vec::Vec<<Vec<u8> as ::std::iter::IntoIterator>::Item>,
+    },
+}
+impl ::std::iter::IntoIterator for LampDelta {
+    type Item = LampChange;
+    type IntoIter = ::std::vec::IntoIter<Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+        let mut changes = ::std::vec::Vec::new();
+        if let ::std::option::Option::Some(v) = self.on {
+            changes.push(LampChange::On(v));
+        }
+        if !self.scenes_add.is_empty() || !self.scenes_remove.is_empty() {
+            changes
+                .push(LampChange::Scenes {
+                    add: self.scenes_add,
+                    remove: self.scenes_remove,
+                });
+        }
+        changes.into_iter()
+    }
+}
+impl LampDelta {
+    /// The names of the fields both `self` and `other` modify.
+    fn conflicts_with(&self, other: &Self) -> ::std::vec::Vec<&'static str> {
+        let mut conflicts = ::std::vec::Vec::new();
+        if (self.on.is_some()) && (other.on.is_some()) {
+            conflicts.push("on");
+        }
+        if (!self.scenes_add.is_empty() || !self.scenes_remove.is_empty())
+            && (!other.scenes_add.is_empty() || !other.scenes_remove.is_empty())
+        {
+            conflicts.push("scenes");
+        }
+        conflicts
+    }
+}
+impl LampDelta {
+    /// Splits `self` into deltas holding at most `max_items` collection
+    /// elements each, which applied in order are equivalent to `self`.
+    /// All other changes are kept in the first delta.
+    fn split(self, max_items: usize) -> ::std::vec::Vec<Self> {
+        let mut first = self;
+        let scenes_remove = ::std::mem::take(&mut first.scenes_remove);
+        let scenes_add = ::std::mem::take(&mut first.scenes_add);
+        let mut chunks = ::delta_struct::split::Chunks::new(
+            first,
+            max_items,
+            || Self {
+                on: ::std::option::Option::None,
+                scenes_remove: ::std::vec::Vec::new(),
+                scenes_add: ::std::vec::Vec::new(),
+            },
+        );
+        for item in scenes_remove {
+            chunks.take(1).scenes_remove.push(item);
+        }
+        for item in scenes_add {
+            chunks.take(1).scenes_add.push(item);
+        }
+        chunks.finish()
+    }
+}
+impl ::delta_struct::ComputeDelta for Lamp {
+    type Output = LampDelta;
+    fn delta(old: Self, new: Self) -> ::std::option::Option<Self::Output> {
+        let started = ::delta_struct::observer::start();
+        let mut changed_fields = 0usize;
+        let on = if old.on != new.on {
+            changed_fields += 1;
+            ::std::option::Option::Some(new.on)
+        } else {
+            ::std::option::Option::None
+        };
+        let (scenes_add, scenes_remove) = if ::delta_struct::unordered::in_order_eq(
+            &old.scenes,
+            &new.scenes,
+        ) {
+            (::std::vec::Vec::new(), ::std::vec::Vec::new())
+        } else {
+            let mut scenes_add = new.scenes.into_iter().collect::<::std::vec::Vec<_>>();
+            let scenes_remove = old
+                .scenes
+                .into_iter()
+                .filter_map(|i| {
+                    if let ::std::option::Option::Some(index) = scenes_add
+                        .iter()
+                        .position(|a| a == &i)
+                    {
+                        scenes_add.remove(index);
+                        ::std::option::Option::None
+                    } else {
+                        ::std::option::Option::Some(i)
+                    }
+                })
+                .collect::<::std::vec::Vec<_>>();
+            (scenes_add, scenes_remove)
+        };
+        if !scenes_add.is_empty() || !scenes_remove.is_empty() {
+            changed_fields += 1;
+        }
+        ::delta_struct::observer::finish(
+            started,
+            ::delta_struct::observer::Operation::Delta,
+            ::std::any::type_name::<Self>(),
+            changed_fields,
+        );
+        if changed_fields > 0 {
+            ::std::option::Option::Some(LampDelta {
+                on,
+                scenes_add,
+                scenes_remove,
+            })
+        } else {
+            ::std::option::Option::None
+        }
+    }
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+        let mut count = 0usize;
+        count += (old.on != new.on) as usize;
+        if count > limit {
+            return count;
+        }
+        count
+            += ::delta_struct::unordered::change_count(
+                &old.scenes,
+                &new.scenes,
+                limit - count,
+            );
+        if count > limit {
+            return count;
+        }
+        count
+    }
+}
+impl LampDelta {
+    /// The names of the fields, in declaration order.
+    const FIELDS: &'static [&'static str] = &["on", "scenes"];
+    const FIELD_COUNT: usize = 2usize;
+}
+impl ::delta_struct::Sealed for LampDelta {}
+impl ::delta_struct::DeltaOutput for LampDelta {
+    type Target = Lamp;
+    const FIELDS: &'static [&'static str] = Self::FIELDS;
+    fn is_empty(&self) -> bool {
+        true && !(self.on.is_some())
+            && !(!self.scenes_add.is_empty() || !self.scenes_remove.is_empty())
+    }
+    #[allow(clippy::match_single_binding)]
+    fn is_field_changed(&self, index: usize) -> bool {
+        match index {
+            0usize => self.on.is_some(),
+            1usize => !self.scenes_add.is_empty() || !self.scenes_remove.is_empty(),
+            _ => false,
+        }
+    }
+}
+impl ::delta_struct::ApplyDelta<LampDelta> for Lamp {
+    fn apply_delta(&mut self, delta: LampDelta) {
+        let started = ::delta_struct::observer::start();
+        let mut applied_fields = 0usize;
+        let LampDelta { on, scenes_add, mut scenes_remove } = delta;
+        if let ::std::option::Option::Some(v) = on {
+            applied_fields += 1;
+            self.on = v;
+        }
+        if !scenes_add.is_empty() || !scenes_remove.is_empty() {
+            applied_fields += 1;
+        }
+        {
+            let og = ::std::mem::replace(
+                &mut self.scenes,
+                ::std::iter::FromIterator::from_iter(::std::vec::Vec::new()),
+            );
+            let kept = og
+                .into_iter()
+                .filter_map(|i| {
+                    if let ::std::option::Option::Some(index) = scenes_remove
+                        .iter()
+                        .position(|a| a == &i)
+                    {
+                        scenes_remove.remove(index);
+                        ::std::option::Option::None
+                    } else {
+                        ::std::option::Option::Some(i)
+                    }
+                });
+            let mut updated: Vec<u8> = ::std::iter::FromIterator::from_iter(kept);
+            updated.extend(scenes_add.into_iter());
+            self.scenes = updated;
+        }
+        ::delta_struct::observer::finish(
+            started,
+            ::delta_struct::observer::Operation::Apply,
+            ::std::any::type_name::<Self>(),
+            applied_fields,
+        );
+    }
+    fn compose_delta(first: LampDelta, second: LampDelta) -> LampDelta {
+        let on = second.on.or(first.on);
+        let mut scenes_add = first.scenes_add;
+        let mut scenes_remove = first.scenes_remove;
+        for i in second.scenes_remove {
+            if let ::std::option::Option::Some(index) = scenes_add
+                .iter()
+                .position(|a| a == &i)
+            {
+                scenes_add.remove(index);
+            } else {
+                scenes_remove.push(i);
+            }
+        }
+        scenes_add.extend(second.scenes_add);
+        LampDelta {
+            on,
+            scenes_add,
+            scenes_remove,
+        }
+    }
+}
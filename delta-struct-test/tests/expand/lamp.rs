@@ -0,0 +1,8 @@
+use delta_struct::Delta;
+
+#[derive(Delta)]
+struct Lamp {
+    on: bool,
+    #[delta_struct(field_type = "unordered")]
+    scenes: Vec<u8>,
+}
@@ -0,0 +1,8 @@
+// Catches unintended changes to the expansion of whole files. Delete the
+// `.expanded.rs` files to regenerate them after intended ones.
+#[cfg(feature = "macrotest")]
+#[test]
+#[ignore = "needs cargo-expand"]
+fn file_expansions() {
+    delta_struct_test::assert_expansions("tests/expand/*.rs");
+}
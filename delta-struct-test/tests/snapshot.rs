@@ -0,0 +1,18 @@
+// Catches unintended changes to the code generated for a representative
+// struct. Rerun with DELTA_STRUCT_BLESS=1 after intended ones.
+#[test]
+fn thermostat_expansion() {
+    delta_struct_test::assert_snapshot(
+        "thermostat",
+        delta_struct_test::expand! {
+            #[delta_struct(delta_leader = "#[derive(Debug)]")]
+            pub struct Thermostat {
+                target: u8,
+                #[delta_struct(field_type = "float", epsilon = "0.1")]
+                temperature: f32,
+                #[delta_struct(field_type = "unordered")]
+                schedules: Vec<u32>,
+            }
+        },
+    );
+}
@@ -0,0 +1,279 @@
+#[derive(Debug)]
+pub struct ThermostatDelta {
+    pub target: ::std::option::Option<u8>,
+    pub temperature: ::std::option::Option<f32>,
+    pub schedules_add: ::std::vec::Vec<<Vec<u32> as ::std::iter::IntoIterator>::Item>,
+    pub schedules_remove: ::std::vec::Vec<<Vec<u32> as ::std::iter::IntoIterator>::Item>,
+}
+#[allow(dead_code)]
+pub enum ThermostatChange {
+    Target(u8),
+    Temperature(f32),
+    Schedules {
+        add: ::std::vec::Vec<<Vec<u32> as ::std::iter::IntoIterator>::Item>,
+        remove: ::std::vec::Vec<<Vec<u32> as ::std::iter::IntoIterator>::Item>,
+    },
+}
+impl ::std::iter::IntoIterator for ThermostatDelta {
+    type Item = ThermostatChange;
+    type IntoIter = ::std::vec::IntoIter<Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+        let mut changes = ::std::vec::Vec::new();
+        if let ::std::option::Option::Some(v) = self.target {
+            changes.push(ThermostatChange::Target(v));
+        }
+        if let ::std::option::Option::Some(v) = self.temperature {
+            changes.push(ThermostatChange::Temperature(v));
+        }
+        if !self.schedules_add.is_empty() || !self.schedules_remove.is_empty() {
+            changes
+                .push(ThermostatChange::Schedules {
+                    add: self.schedules_add,
+                    remove: self.schedules_remove,
+                });
+        }
+        changes.into_iter()
+    }
+}
+impl ThermostatDelta {
+    /// The names of the fields both `self` and `other` modify.
+    pub fn conflicts_with(&self, other: &Self) -> ::std::vec::Vec<&'static str> {
+        let mut conflicts = ::std::vec::Vec::new();
+        if (self.target.is_some()) && (other.target.is_some()) {
+            conflicts.push("target");
+        }
+        if (self.temperature.is_some()) && (other.temperature.is_some()) {
+            conflicts.push("temperature");
+        }
+        if (!self.schedules_add.is_empty() || !self.schedules_remove.is_empty())
+            && (!other.schedules_add.is_empty() || !other.schedules_remove.is_empty())
+        {
+            conflicts.push("schedules");
+        }
+        conflicts
+    }
+}
+impl ThermostatDelta {
+    /// Splits `self` into deltas holding at most `max_items` collection
+    /// elements each, which applied in order are equivalent to `self`.
+    /// All other changes are kept in the first delta.
+    pub fn split(self, max_items: usize) -> ::std::vec::Vec<Self> {
+        let mut first = self;
+        let schedules_remove = ::std::mem::take(&mut first.schedules_remove);
+        let schedules_add = ::std::mem::take(&mut first.schedules_add);
+        let mut chunks = ::delta_struct::split::Chunks::new(
+            first,
+            max_items,
+            || Self {
+                target: ::std::option::Option::None,
+                temperature: ::std::option::Option::None,
+                schedules_remove: ::std::vec::Vec::new(),
+                schedules_add: ::std::vec::Vec::new(),
+            },
+        );
+        for item in schedules_remove {
+            chunks.take(1).schedules_remove.push(item);
+        }
+        for item in schedules_add {
+            chunks.take(1).schedules_add.push(item);
+        }
+        chunks.finish()
+    }
+}
+impl ::delta_struct::ComputeDelta for Thermostat {
+    type Output = ThermostatDelta;
+    fn delta(old: Self, new: Self) -> ::std::option::Option<Self::Output> {
+        let started = ::delta_struct::observer::start();
+        let mut changed_fields = 0usize;
+        let target = if old.target != new.target {
+            changed_fields += 1;
+            ::std::option::Option::Some(new.target)
+        } else {
+            ::std::option::Option::None
+        };
+        let temperature = if !::delta_struct::float::Float::approx_eq(
+            old.temperature,
+            new.temperature,
+            0.1,
+            0,
+        ) {
+            changed_fields += 1;
+            ::std::option::Option::Some(new.temperature)
+        } else {
+            ::std::option::Option::None
+        };
+        let (schedules_add, schedules_remove) = if ::delta_struct::unordered::in_order_eq(
+            &old.schedules,
+            &new.schedules,
+        ) {
+            (::std::vec::Vec::new(), ::std::vec::Vec::new())
+        } else {
+            let mut schedules_add = new
+                .schedules
+                .into_iter()
+                .collect::<::std::vec::Vec<_>>();
+            let schedules_remove = old
+                .schedules
+                .into_iter()
+                .filter_map(|i| {
+                    if let ::std::option::Option::Some(index) = schedules_add
+                        .iter()
+                        .position(|a| a == &i)
+                    {
+                        schedules_add.remove(index);
+                        ::std::option::Option::None
+                    } else {
+                        ::std::option::Option::Some(i)
+                    }
+                })
+                .collect::<::std::vec::Vec<_>>();
+            (schedules_add, schedules_remove)
+        };
+        if !schedules_add.is_empty() || !schedules_remove.is_empty() {
+            changed_fields += 1;
+        }
+        ::delta_struct::observer::finish(
+            started,
+            ::delta_struct::observer::Operation::Delta,
+            ::std::any::type_name::<Self>(),
+            changed_fields,
+        );
+        if changed_fields > 0 {
+            ::std::option::Option::Some(ThermostatDelta {
+                target,
+                temperature,
+                schedules_add,
+                schedules_remove,
+            })
+        } else {
+            ::std::option::Option::None
+        }
+    }
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+        let mut count = 0usize;
+        count += (old.target != new.target) as usize;
+        if count > limit {
+            return count;
+        }
+        count
+            += (!::delta_struct::float::Float::approx_eq(
+                old.temperature,
+                new.temperature,
+                0.1,
+                0,
+            )) as usize;
+        if count > limit {
+            return count;
+        }
+        count
+            += ::delta_struct::unordered::change_count(
+                &old.schedules,
+                &new.schedules,
+                limit - count,
+            );
+        if count > limit {
+            return count;
+        }
+        count
+    }
+}
+impl ThermostatDelta {
+    /// The names of the fields, in declaration order.
+    pub const FIELDS: &'static [&'static str] = &["target", "temperature", "schedules"];
+    pub const FIELD_COUNT: usize = 3usize;
+}
+impl ::delta_struct::Sealed for ThermostatDelta {}
+impl ::delta_struct::DeltaOutput for ThermostatDelta {
+    type Target = Thermostat;
+    const FIELDS: &'static [&'static str] = Self::FIELDS;
+    fn is_empty(&self) -> bool {
+        true && !(self.target.is_some()) && !(self.temperature.is_some())
+            && !(!self.schedules_add.is_empty() || !self.schedules_remove.is_empty())
+    }
+    #[allow(clippy::match_single_binding)]
+    fn is_field_changed(&self, index: usize) -> bool {
+        match index {
+            0usize => self.target.is_some(),
+            1usize => self.temperature.is_some(),
+            2usize => !self.schedules_add.is_empty() || !self.schedules_remove.is_empty(),
+            _ => false,
+        }
+    }
+}
+impl ::delta_struct::ApplyDelta<ThermostatDelta> for Thermostat {
+    fn apply_delta(&mut self, delta: ThermostatDelta) {
+        let started = ::delta_struct::observer::start();
+        let mut applied_fields = 0usize;
+        let ThermostatDelta {
+            target,
+            temperature,
+            schedules_add,
+            mut schedules_remove,
+        } = delta;
+        if let ::std::option::Option::Some(v) = target {
+            applied_fields += 1;
+            self.target = v;
+        }
+        if let ::std::option::Option::Some(v) = temperature {
+            applied_fields += 1;
+            self.temperature = v;
+        }
+        if !schedules_add.is_empty() || !schedules_remove.is_empty() {
+            applied_fields += 1;
+        }
+        {
+            let og = ::std::mem::replace(
+                &mut self.schedules,
+                ::std::iter::FromIterator::from_iter(::std::vec::Vec::new()),
+            );
+            let kept = og
+                .into_iter()
+                .filter_map(|i| {
+                    if let ::std::option::Option::Some(index) = schedules_remove
+                        .iter()
+                        .position(|a| a == &i)
+                    {
+                        schedules_remove.remove(index);
+                        ::std::option::Option::None
+                    } else {
+                        ::std::option::Option::Some(i)
+                    }
+                });
+            let mut updated: Vec<u32> = ::std::iter::FromIterator::from_iter(kept);
+            updated.extend(schedules_add.into_iter());
+            self.schedules = updated;
+        }
+        ::delta_struct::observer::finish(
+            started,
+            ::delta_struct::observer::Operation::Apply,
+            ::std::any::type_name::<Self>(),
+            applied_fields,
+        );
+    }
+    fn compose_delta(
+        first: ThermostatDelta,
+        second: ThermostatDelta,
+    ) -> ThermostatDelta {
+        let target = second.target.or(first.target);
+        let temperature = second.temperature.or(first.temperature);
+        let mut schedules_add = first.schedules_add;
+        let mut schedules_remove = first.schedules_remove;
+        for i in second.schedules_remove {
+            if let ::std::option::Option::Some(index) = schedules_add
+                .iter()
+                .position(|a| a == &i)
+            {
+                schedules_add.remove(index);
+            } else {
+                schedules_remove.push(i);
+            }
+        }
+        schedules_add.extend(second.schedules_add);
+        ThermostatDelta {
+            target,
+            temperature,
+            schedules_add,
+            schedules_remove,
+        }
+    }
+}
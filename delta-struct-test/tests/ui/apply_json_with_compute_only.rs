@@ -0,0 +1,10 @@
+use delta_struct::Delta;
+
+#[derive(Delta)]
+#[delta_struct(apply_json, compute_only)]
+struct Lamp {
+    name: String,
+    brightness: u8,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use delta_struct::Delta;
+
+#[derive(Delta)]
+#[delta_struct(compact)]
+struct Lamp {
+    present: bool,
+    #[delta_struct(field_type = "unordered")]
+    scenes: Vec<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use delta_struct::Delta;
+
+#[derive(Delta)]
+#[delta_struct(sql, compact)]
+struct Lamp {
+    name: String,
+    brightness: u8,
+}
+
+fn main() {}
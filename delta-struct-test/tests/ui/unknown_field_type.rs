@@ -0,0 +1,9 @@
+use delta_struct::Delta;
+
+#[derive(Delta)]
+struct Lamp {
+    #[delta_struct(field_type = "dimmable")]
+    brightness: u8,
+}
+
+fn main() {}
@@ -0,0 +1,111 @@
+//! Compares diffing large collections as `field_type = "sorted"` against
+//! `field_type = "unordered"`, which matches every element against every
+//! other.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use delta_struct::{ApplyDelta, ComputeDelta, Delta};
+use std::collections::BTreeSet;
+use std::hint::black_box;
+
+#[derive(Clone, Delta)]
+#[delta_struct(delta_leader = "#[derive(Clone)]")]
+struct Unordered {
+    #[delta_struct(field_type = "unordered")]
+    ids: Vec<u32>,
+}
+
+#[derive(Clone, Delta)]
+#[delta_struct(delta_leader = "#[derive(Clone)]")]
+struct SortedVec {
+    #[delta_struct(field_type = "sorted")]
+    ids: Vec<u32>,
+}
+
+#[derive(Clone, Delta)]
+struct SortedSet {
+    #[delta_struct(field_type = "sorted")]
+    ids: BTreeSet<u32>,
+}
+
+/// `len` sorted ids, and the same ids with every 10th one replaced.
+fn ids(len: u32) -> (Vec<u32>, Vec<u32>) {
+    let old: Vec<u32> = (0..len).map(|i| i * 2).collect();
+    let new = old
+        .iter()
+        .map(|&id| if id % 20 == 0 { id + 1 } else { id })
+        .collect();
+    (old, new)
+}
+
+fn diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    for len in [100, 1_000, 10_000] {
+        let (old, new) = ids(len);
+        group.bench_with_input(BenchmarkId::new("unordered", len), &len, |b, _| {
+            b.iter(|| {
+                Unordered::delta(
+                    black_box(Unordered { ids: old.clone() }),
+                    black_box(Unordered { ids: new.clone() }),
+                )
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("sorted_vec", len), &len, |b, _| {
+            b.iter(|| {
+                SortedVec::delta(
+                    black_box(SortedVec { ids: old.clone() }),
+                    black_box(SortedVec { ids: new.clone() }),
+                )
+            })
+        });
+        let (old_set, new_set): (BTreeSet<u32>, BTreeSet<u32>) =
+            (old.iter().copied().collect(), new.iter().copied().collect());
+        group.bench_with_input(BenchmarkId::new("sorted_set", len), &len, |b, _| {
+            b.iter(|| {
+                SortedSet::delta(
+                    black_box(SortedSet {
+                        ids: old_set.clone(),
+                    }),
+                    black_box(SortedSet {
+                        ids: new_set.clone(),
+                    }),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn apply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply");
+    for len in [100, 1_000, 10_000] {
+        let (old, new) = ids(len);
+        let unordered = Unordered::delta(
+            Unordered { ids: old.clone() },
+            Unordered { ids: new.clone() },
+        )
+        .unwrap();
+        group.bench_with_input(BenchmarkId::new("unordered", len), &len, |b, _| {
+            b.iter(|| {
+                let mut value = Unordered { ids: old.clone() };
+                value.apply_delta(black_box(unordered.clone()));
+                value
+            })
+        });
+        let sorted = SortedVec::delta(
+            SortedVec { ids: old.clone() },
+            SortedVec { ids: new.clone() },
+        )
+        .unwrap();
+        group.bench_with_input(BenchmarkId::new("sorted_vec", len), &len, |b, _| {
+            b.iter(|| {
+                let mut value = SortedVec { ids: old.clone() };
+                value.apply_delta(black_box(sorted.clone()));
+                value
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, diff, apply);
+criterion_main!(benches);
@@ -0,0 +1,363 @@
+//! Diffing of node arenas, for tree and graph structures whose nodes refer
+//! to each other by id instead of owning each other, e.g. scene graphs.
+//! Nodes are matched by id, so a changed node is patched in place, and the
+//! lists of ids are diffed positionally, as `ordered` fields.
+//!
+//! With `#[delta_struct(arena = "nodes")]`, the `nodes` field is diffed
+//! with [`ArenaStrategy`] and every other `Vec` field without a
+//! `field_type` as `ordered`:
+//!
+//! ```
+//! use delta_struct::{ComputeDelta, Delta};
+//! use std::collections::HashMap;
+//!
+//! #[derive(Clone, Debug, Delta, PartialEq)]
+//! struct Node {
+//!     name: String,
+//!     #[delta_struct(field_type = "ordered")]
+//!     children: Vec<u32>,
+//! }
+//!
+//! #[derive(Clone, Debug, Delta, PartialEq)]
+//! #[delta_struct(arena = "nodes")]
+//! struct Scene {
+//!     nodes: HashMap<u32, Node>,
+//!     roots: Vec<u32>,
+//! }
+//! ```
+//!
+//! Arenas are `HashMap`s or `BTreeMap`s keyed by id, or `Vec`s indexed by
+//! it. A `Vec` arena only grows and shrinks at the end, so removing a node
+//! truncates the arena to it.
+
+use crate::strategy::DiffStrategy;
+use crate::Delta;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// The delta of an arena. Applying it removes the `remove` ids, patches the
+/// nodes with the `patch` ids in place, and then inserts the `insert`
+/// nodes, replacing any node with the same id.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: serde::Serialize, T: serde::Serialize, T::Output: serde::Serialize",
+        deserialize = "K: serde::Deserialize<'de>, T: serde::Deserialize<'de>, \
+                       T::Output: serde::Deserialize<'de>"
+    ))
+)]
+pub struct ArenaDelta<K, T: Delta> {
+    pub remove: Vec<K>,
+    pub patch: Vec<(K, T::Output)>,
+    pub insert: Vec<(K, T)>,
+}
+
+impl<K, T: Delta> ArenaDelta<K, T> {
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.patch.is_empty() && self.insert.is_empty()
+    }
+
+    fn non_empty(self) -> Option<Self> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self)
+        }
+    }
+}
+
+impl<K, T: Delta> Default for ArenaDelta<K, T> {
+    fn default() -> Self {
+        ArenaDelta {
+            remove: vec![],
+            patch: vec![],
+            insert: vec![],
+        }
+    }
+}
+
+impl<K: Clone, T: Delta + Clone> Clone for ArenaDelta<K, T>
+where
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        ArenaDelta {
+            remove: self.remove.clone(),
+            patch: self.patch.clone(),
+            insert: self.insert.clone(),
+        }
+    }
+}
+
+impl<K: std::fmt::Debug, T: Delta + std::fmt::Debug> std::fmt::Debug for ArenaDelta<K, T>
+where
+    T::Output: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArenaDelta")
+            .field("remove", &self.remove)
+            .field("patch", &self.patch)
+            .field("insert", &self.insert)
+            .finish()
+    }
+}
+
+impl<K: PartialEq, T: Delta + PartialEq> PartialEq for ArenaDelta<K, T>
+where
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.remove == other.remove && self.patch == other.patch && self.insert == other.insert
+    }
+}
+
+/// Diffs an arena by node id. See the [module docs](self).
+pub struct ArenaStrategy;
+
+macro_rules! impl_map_arena {
+    ($map:ident, $($bound:tt)+) => {
+        impl<K: $($bound)+, T: Delta> DiffStrategy<$map<K, T>> for ArenaStrategy {
+            type Delta = ArenaDelta<K, T>;
+
+            fn diff(mut old: $map<K, T>, new: $map<K, T>) -> Option<ArenaDelta<K, T>> {
+                let mut delta = ArenaDelta::default();
+                for (id, node) in new {
+                    match old.remove(&id) {
+                        Some(previous) => {
+                            if let Some(patch) = T::delta(previous, node) {
+                                delta.patch.push((id, patch));
+                            }
+                        }
+                        None => delta.insert.push((id, node)),
+                    }
+                }
+                delta.remove = old.into_keys().collect();
+                delta.non_empty()
+            }
+
+            fn apply(target: &mut $map<K, T>, delta: ArenaDelta<K, T>) {
+                for id in &delta.remove {
+                    target.remove(id);
+                }
+                for (id, patch) in delta.patch {
+                    if let Some(node) = target.get_mut(&id) {
+                        node.apply_delta(patch);
+                    }
+                }
+                target.extend(delta.insert);
+            }
+
+            fn change_count(old: &$map<K, T>, new: &$map<K, T>, limit: usize) -> usize {
+                let mut count = 0;
+                for (id, node) in new {
+                    count += match old.get(id) {
+                        Some(previous) => T::change_count(previous, node, limit - count),
+                        None => 1,
+                    };
+                    if count > limit {
+                        return count;
+                    }
+                }
+                count + old.keys().filter(|id| !new.contains_key(*id)).count()
+            }
+
+            fn compose(first: ArenaDelta<K, T>, second: ArenaDelta<K, T>) -> ArenaDelta<K, T> {
+                compose(first, second)
+            }
+        }
+    };
+}
+
+impl_map_arena!(HashMap, Hash + Eq);
+impl_map_arena!(BTreeMap, Ord);
+
+/// A `Vec` arena, whose ids are indices.
+impl<T: Delta> DiffStrategy<Vec<T>> for ArenaStrategy {
+    type Delta = ArenaDelta<usize, T>;
+
+    fn diff(old: Vec<T>, new: Vec<T>) -> Option<ArenaDelta<usize, T>> {
+        let mut delta = ArenaDelta::default();
+        let (old_len, new_len) = (old.len(), new.len());
+        delta.remove = (new_len..old_len).collect();
+        let mut old = old.into_iter();
+        for (id, node) in new.into_iter().enumerate() {
+            match old.next() {
+                Some(previous) => {
+                    if let Some(patch) = T::delta(previous, node) {
+                        delta.patch.push((id, patch));
+                    }
+                }
+                None => delta.insert.push((id, node)),
+            }
+        }
+        delta.non_empty()
+    }
+
+    fn apply(target: &mut Vec<T>, delta: ArenaDelta<usize, T>) {
+        if let Some(&first) = delta.remove.iter().min() {
+            target.truncate(first);
+        }
+        for (id, patch) in delta.patch {
+            if let Some(node) = target.get_mut(id) {
+                node.apply_delta(patch);
+            }
+        }
+        for (id, node) in delta.insert {
+            if id < target.len() {
+                target[id] = node;
+            } else if id == target.len() {
+                target.push(node);
+            }
+        }
+    }
+
+    fn change_count(old: &Vec<T>, new: &Vec<T>, limit: usize) -> usize {
+        let mut count = old.len().abs_diff(new.len());
+        for (previous, node) in old.iter().zip(new) {
+            if count > limit {
+                break;
+            }
+            count += T::change_count(previous, node, limit - count);
+        }
+        count
+    }
+
+    fn compose(first: ArenaDelta<usize, T>, second: ArenaDelta<usize, T>) -> ArenaDelta<usize, T> {
+        compose(first, second)
+    }
+}
+
+fn compose<K: PartialEq, T: Delta>(
+    first: ArenaDelta<K, T>,
+    second: ArenaDelta<K, T>,
+) -> ArenaDelta<K, T> {
+    let ArenaDelta {
+        mut remove,
+        mut patch,
+        mut insert,
+    } = first;
+    for id in second.remove {
+        patch.retain(|(k, _)| *k != id);
+        insert.retain(|(k, _)| *k != id);
+        if !remove.contains(&id) {
+            remove.push(id);
+        }
+    }
+    for (id, second_patch) in second.patch {
+        if let Some((_, node)) = insert.iter_mut().find(|(k, _)| *k == id) {
+            node.apply_delta(second_patch);
+        } else if let Some(index) = patch.iter().position(|(k, _)| *k == id) {
+            let (id, first_patch) = patch.remove(index);
+            patch.insert(index, (id, T::compose_delta(first_patch, second_patch)));
+        } else {
+            patch.push((id, second_patch));
+        }
+    }
+    for (id, node) in second.insert {
+        patch.retain(|(k, _)| *k != id);
+        insert.retain(|(k, _)| *k != id);
+        insert.push((id, node));
+    }
+    ArenaDelta {
+        remove,
+        patch,
+        insert,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApplyDelta, ComputeDelta};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Node {
+        name: String,
+        #[delta_struct(field_type = "ordered")]
+        children: Vec<u32>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(arena = "nodes", delta_leader = "#[derive(Debug)]")]
+    struct Scene {
+        nodes: HashMap<u32, Node>,
+        roots: Vec<u32>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(arena = "nodes")]
+    struct Layers {
+        nodes: Vec<Node>,
+        #[delta_struct(field_type = "unordered")]
+        hidden: Vec<usize>,
+    }
+
+    fn node(name: &str, children: &[u32]) -> Node {
+        Node {
+            name: name.into(),
+            children: children.to_vec(),
+        }
+    }
+
+    #[test]
+    fn nodes_are_patched_by_id() {
+        let old = Scene {
+            nodes: vec![
+                (1, node("root", &[2])),
+                (2, node("lamp", &[])),
+                (3, node("old", &[])),
+            ]
+            .into_iter()
+            .collect(),
+            roots: vec![1],
+        };
+        let mut new = old.clone();
+        new.nodes.get_mut(&1).unwrap().children.push(4);
+        new.nodes.insert(4, node("fan", &[]));
+        new.nodes.remove(&3);
+        new.roots.push(4);
+        let delta = Scene::delta(old.clone(), new.clone()).unwrap();
+        let nodes = delta.nodes.as_ref().unwrap();
+        assert_eq!(nodes.remove, vec![3]);
+        assert_eq!(nodes.patch.len(), 1);
+        assert_eq!(nodes.insert, vec![(4, node("fan", &[]))]);
+        assert_eq!(delta.roots.len(), 1);
+        assert_eq!(Scene::change_count(&old, &new, usize::MAX), 4);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn vec_arenas_grow_and_shrink_at_the_end() {
+        let a = Layers {
+            nodes: vec![node("a", &[]), node("b", &[]), node("c", &[])],
+            hidden: vec![],
+        };
+        let b = Layers {
+            nodes: vec![node("a", &[1]), node("b", &[])],
+            hidden: vec![1],
+        };
+        let c = Layers {
+            nodes: vec![
+                node("a", &[1, 2]),
+                node("b", &[]),
+                node("d", &[]),
+                node("e", &[]),
+            ],
+            hidden: vec![1],
+        };
+        let mut applied = a.clone();
+        applied.apply_delta(Layers::delta(a.clone(), b.clone()).unwrap());
+        assert_eq!(applied, b);
+        let composed = Layers::compose_delta(
+            Layers::delta(a.clone(), b.clone()).unwrap(),
+            Layers::delta(b, c.clone()).unwrap(),
+        );
+        let mut applied = a;
+        applied.apply_delta(composed);
+        assert_eq!(applied, c);
+    }
+}
@@ -0,0 +1,169 @@
+//! Diffing of atomic fields, for `field_type = "atomic"`, so structs of
+//! shared state such as counters can derive `Delta`. Deltas hold the plain
+//! value, loaded with the field's `ordering` when the fields are compared
+//! by reference, and stored with it when applied.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::sync::atomic::{AtomicBool, AtomicU64};
+//!
+//! #[derive(Delta)]
+//! struct Stats {
+//!     #[delta_struct(field_type = "atomic", ordering = "relaxed")]
+//!     requests: AtomicU64,
+//!     #[delta_struct(field_type = "atomic")]
+//!     healthy: AtomicBool,
+//! }
+//! ```
+//!
+//! `ordering` is `"relaxed"`, `"acq_rel"` (loads acquire and stores
+//! release), or `"seq_cst"`, the default.
+
+use crate::strategy::DiffStrategy;
+use std::marker::PhantomData;
+use std::sync::atomic::*;
+
+/// An atomic type, as its operations aren't described by a std trait.
+pub trait Atomic {
+    type Value: Copy + PartialEq;
+
+    fn load(&self, ordering: Ordering) -> Self::Value;
+
+    fn store(&self, value: Self::Value, ordering: Ordering);
+
+    fn into_inner(self) -> Self::Value;
+}
+
+macro_rules! impl_atomic {
+    ($($atomic:ident: $value:ty),+ $(,)?) => {
+        $(
+            impl Atomic for $atomic {
+                type Value = $value;
+
+                fn load(&self, ordering: Ordering) -> $value {
+                    $atomic::load(self, ordering)
+                }
+
+                fn store(&self, value: $value, ordering: Ordering) {
+                    $atomic::store(self, value, ordering)
+                }
+
+                fn into_inner(self) -> $value {
+                    $atomic::into_inner(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_atomic!(
+    AtomicBool: bool,
+    AtomicU8: u8,
+    AtomicU16: u16,
+    AtomicU32: u32,
+    AtomicU64: u64,
+    AtomicUsize: usize,
+    AtomicI8: i8,
+    AtomicI16: i16,
+    AtomicI32: i32,
+    AtomicI64: i64,
+    AtomicIsize: isize,
+);
+
+/// The orderings an atomic field is loaded and stored with.
+pub trait AtomicOrdering {
+    const LOAD: Ordering;
+    const STORE: Ordering;
+}
+
+pub struct Relaxed;
+
+impl AtomicOrdering for Relaxed {
+    const LOAD: Ordering = Ordering::Relaxed;
+    const STORE: Ordering = Ordering::Relaxed;
+}
+
+pub struct AcqRel;
+
+impl AtomicOrdering for AcqRel {
+    const LOAD: Ordering = Ordering::Acquire;
+    const STORE: Ordering = Ordering::Release;
+}
+
+pub struct SeqCst;
+
+impl AtomicOrdering for SeqCst {
+    const LOAD: Ordering = Ordering::SeqCst;
+    const STORE: Ordering = Ordering::SeqCst;
+}
+
+/// Diffs an atomic by its value. See the [module docs](self).
+pub struct AtomicStrategy<O = SeqCst>(PhantomData<O>);
+
+pub type RelaxedStrategy = AtomicStrategy<Relaxed>;
+
+pub type AcqRelStrategy = AtomicStrategy<AcqRel>;
+
+impl<A: Atomic, O: AtomicOrdering> DiffStrategy<A> for AtomicStrategy<O> {
+    type Delta = A::Value;
+
+    fn diff(old: A, new: A) -> Option<A::Value> {
+        let new = new.into_inner();
+        if old.into_inner() != new {
+            Some(new)
+        } else {
+            None
+        }
+    }
+
+    fn apply(target: &mut A, delta: A::Value) {
+        target.store(delta, O::STORE);
+    }
+
+    fn change_count(old: &A, new: &A, _limit: usize) -> usize {
+        (old.load(O::LOAD) != new.load(O::LOAD)) as usize
+    }
+
+    fn compose(_first: A::Value, second: A::Value) -> A::Value {
+        second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    #[derive(Debug, Default, Delta)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Counters {
+        #[delta_struct(field_type = "atomic", ordering = "relaxed")]
+        events: AtomicU64,
+        #[delta_struct(field_type = "atomic", ordering = "acq_rel")]
+        online: AtomicBool,
+        label: String,
+    }
+
+    #[test]
+    fn atomics_are_diffed_by_value() {
+        let old = Counters::default();
+        let new = Counters {
+            events: AtomicU64::new(7),
+            ..Counters::default()
+        };
+        assert_eq!(Counters::change_count(&old, &new, usize::MAX), 1);
+        let delta = Counters::delta(old, new).unwrap();
+        assert_eq!(
+            delta,
+            CountersDelta {
+                events: Some(7),
+                online: None,
+                label: None,
+            }
+        );
+        let mut target = Counters::default();
+        target.apply_delta(delta);
+        assert_eq!(target.events.load(Ordering::Relaxed), 7);
+        assert!(Counters::delta(Counters::default(), Counters::default()).is_none());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::ComputeDelta;
+
+/// Picks the candidate `new` has the fewest changes from, as counted by
+/// `ComputeDelta::change_count`, and computes the delta from it, e.g. to
+/// delta-encode a value against whichever of a cache of recent snapshots
+/// it is closest to.
+///
+/// Returns the index of the candidate along with the delta, which is `None`
+/// if the candidate equals `new`, or `None` if there are no candidates.
+/// Ties go to the earliest candidate.
+pub fn best_base<'a, T, I>(new: &T, candidates: I) -> Option<(usize, Option<T::Output>)>
+where
+    T: ComputeDelta + Clone + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut best: Option<(usize, &T, usize)> = None;
+    for (index, candidate) in candidates.into_iter().enumerate() {
+        // Counting stops once a candidate is known to be worse.
+        let limit = best.map_or(usize::MAX, |(_, _, count)| count.saturating_sub(1));
+        let count = T::change_count(candidate, new, limit);
+        if best.is_none_or(|(_, _, best)| count < best) {
+            best = Some((index, candidate, count));
+            if count == 0 {
+                break;
+            }
+        }
+    }
+    let (index, base, _) = best?;
+    Some((index, T::delta(base.clone(), new.clone())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Delta;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Snapshot {
+        mode: u8,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+    }
+
+    fn snapshot(mode: u8, zones: &[u8]) -> Snapshot {
+        Snapshot {
+            mode,
+            zones: zones.to_vec(),
+        }
+    }
+
+    #[test]
+    fn the_closest_candidate_is_the_base() {
+        let cache = vec![
+            snapshot(1, &[1, 2, 3]),
+            snapshot(2, &[1, 2, 4, 5]),
+            snapshot(2, &[1]),
+        ];
+        let new = snapshot(2, &[1, 2, 4]);
+        let (index, delta) = best_base(&new, &cache).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(
+            delta,
+            Some(SnapshotDelta {
+                mode: None,
+                zones_add: vec![],
+                zones_remove: vec![5],
+            })
+        );
+        assert_eq!(best_base(&cache[2], &cache), Some((2, None)));
+        assert_eq!(best_base(&new, &[]), None);
+    }
+}
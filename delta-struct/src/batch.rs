@@ -0,0 +1,128 @@
+use crate::Delta;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Accumulates deltas for many entities, keyed by an entity id.
+///
+/// Pushing a delta for a key that already has one pending composes the two,
+/// so each key appears at most once when the batch is drained. Keys are
+/// drained in the order they were first pushed.
+pub struct DeltaBatch<K, T: Delta> {
+    order: Vec<K>,
+    deltas: HashMap<K, T::Output>,
+}
+
+impl<K: Eq + Hash + Clone, T: Delta> DeltaBatch<K, T> {
+    pub fn new() -> Self {
+        DeltaBatch {
+            order: Vec::new(),
+            deltas: HashMap::new(),
+        }
+    }
+
+    /// Adds `delta` for `key`, composing it with any delta already pending
+    /// for that key.
+    pub fn push(&mut self, key: K, delta: T::Output) {
+        let delta = match self.deltas.remove(&key) {
+            Some(pending) => T::compose_delta(pending, delta),
+            None => {
+                self.order.push(key.clone());
+                delta
+            }
+        };
+        self.deltas.insert(key, delta);
+    }
+
+    /// Computes the delta between `old` and `new` and adds it for `key`.
+    /// Nothing is recorded when the two are equal.
+    pub fn push_change(&mut self, key: K, old: T, new: T) {
+        if let Some(delta) = T::delta(old, new) {
+            self.push(key, delta);
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&T::Output> {
+        self.deltas.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Removes and returns every pending delta, leaving the batch empty.
+    pub fn drain(&mut self) -> Vec<(K, T::Output)> {
+        let deltas = &mut self.deltas;
+        self.order
+            .drain(..)
+            .map(|key| {
+                let delta = deltas.remove(&key).expect("every ordered key has a delta");
+                (key, delta)
+            })
+            .collect()
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: Delta> Default for DeltaBatch<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Device {
+        name: String,
+        online: bool,
+    }
+
+    #[test]
+    fn deltas_for_the_same_key_are_composed() {
+        let a = Device {
+            name: "a".to_string(),
+            online: false,
+        };
+        let b = Device {
+            name: "b".to_string(),
+            online: false,
+        };
+        let c = Device {
+            name: "b".to_string(),
+            online: true,
+        };
+        let mut batch = DeltaBatch::<u32, Device>::new();
+        batch.push_change(7, a.clone(), b.clone());
+        batch.push_change(3, a.clone(), a.clone());
+        batch.push_change(9, b.clone(), c.clone());
+        batch.push_change(7, b, c);
+        assert_eq!(batch.len(), 2);
+        let drained = batch.drain();
+        assert!(batch.is_empty());
+        assert_eq!(
+            drained,
+            vec![
+                (
+                    7,
+                    DeviceDelta {
+                        name: Some("b".to_string()),
+                        online: Some(true),
+                    }
+                ),
+                (
+                    9,
+                    DeviceDelta {
+                        name: None,
+                        online: Some(true),
+                    }
+                ),
+            ]
+        );
+    }
+}
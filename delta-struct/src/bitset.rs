@@ -0,0 +1,80 @@
+//! Support for `field_type = "bitset"` fields.
+//!
+//! A bitset delta is a pair of masks: bits to set and bits to clear.
+//! Applying only touches those bits, so concurrent changes to other bits
+//! in the target survive. Any `Copy` type with the `&`, `|` and `!`
+//! operators qualifies, which covers the integer primitives as well as
+//! types generated by the `bitflags` crate.
+
+use std::ops::{BitAnd, BitOr, Not};
+
+/// Types that can be diffed bit by bit.
+pub trait BitSet:
+    Copy + PartialEq + BitAnd<Output = Self> + BitOr<Output = Self> + Not<Output = Self>
+{
+}
+
+impl<T> BitSet for T where
+    T: Copy + PartialEq + BitAnd<Output = T> + BitOr<Output = T> + Not<Output = T>
+{
+}
+
+/// Returns the `(set, clear)` masks turning `old` into `new`.
+pub fn diff<T: BitSet>(old: T, new: T) -> (T, T) {
+    (new & !old, old & !new)
+}
+
+/// Whether `mask` has no bits set.
+pub fn is_empty<T: BitSet>(mask: T) -> bool {
+    mask == (mask & !mask)
+}
+
+pub fn apply<T: BitSet>(target: &mut T, set: T, clear: T) {
+    *target = (*target | set) & !clear;
+}
+
+/// Combines two consecutive `(set, clear)` pairs.
+pub fn compose<T: BitSet>(first: (T, T), second: (T, T)) -> (T, T) {
+    let (first_set, first_clear) = first;
+    let (second_set, second_clear) = second;
+    (
+        (first_set & !second_clear) | second_set,
+        (first_clear & !second_set) | second_clear,
+    )
+}
+
+/// Whether two `(set, clear)` pairs touch any of the same bits.
+pub fn overlaps<T: BitSet>(a: (T, T), b: (T, T)) -> bool {
+    !is_empty((a.0 | a.1) & (b.0 | b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untouched_bits_survive_apply() {
+        let (set, clear) = diff(0b0011u8, 0b0110u8);
+        assert_eq!((set, clear), (0b0100, 0b0001));
+        let mut concurrent = 0b1011u8;
+        apply(&mut concurrent, set, clear);
+        assert_eq!(concurrent, 0b1110);
+        assert!(is_empty(diff(5u32, 5u32).0));
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let first = diff(0b0011u8, 0b0110u8);
+        let second = diff(0b0110u8, 0b1001u8);
+        let (set, clear) = compose(first, second);
+        let mut target = 0b0011u8;
+        apply(&mut target, set, clear);
+        assert_eq!(target, 0b1001);
+    }
+
+    #[test]
+    fn overlap_is_per_bit() {
+        assert!(!overlaps((0b0001u8, 0b0010), (0b0100, 0b1000)));
+        assert!(overlaps((0b0001u8, 0b0000), (0b0000, 0b0001)));
+    }
+}
@@ -0,0 +1,306 @@
+//! Compression of large fields in serialized deltas. A scalar field of a
+//! struct deriving with `#[delta_struct(serde)]` can be given
+//! `compress = "zstd"` or `compress = "deflate"`, so its new value is
+//! compressed when the delta is serialized and decompressed when it is
+//! deserialized, before it is applied:
+//!
+//! ```ignore
+//! #[derive(Clone, Delta)]
+//! #[delta_struct(serde)]
+//! struct Firmware {
+//!     version: u32,
+//!     #[delta_struct(compress = "zstd")]
+//!     image: Vec<u8>,
+//! }
+//! ```
+//!
+//! The field's type must implement [`Blob`]. Requires the `zstd` or
+//! `flate2` feature.
+//!
+//! A few bytes of compressed input can expand to gigabytes, so a field
+//! whose decompressed value is larger than [`max_decompressed_size`] fails
+//! to deserialize instead. The limit defaults to
+//! [`DEFAULT_MAX_DECOMPRESSED_SIZE`] and is changed with
+//! [`set_max_decompressed_size`].
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{self, Serializer};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The default of [`max_decompressed_size`]: 16 MiB.
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 16 << 20;
+
+static MAX_DECOMPRESSED_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_DECOMPRESSED_SIZE);
+
+/// The most bytes a compressed field may decompress to when deserialized.
+pub fn max_decompressed_size() -> usize {
+    MAX_DECOMPRESSED_SIZE.load(Ordering::Relaxed)
+}
+
+/// Sets [`max_decompressed_size`] for every compressed field from now on.
+pub fn set_max_decompressed_size(bytes: usize) {
+    MAX_DECOMPRESSED_SIZE.store(bytes, Ordering::Relaxed);
+}
+
+/// Reads all of `decoder`, failing once it yields more than
+/// [`max_decompressed_size`] bytes.
+fn read_limited(decoder: impl Read) -> io::Result<Vec<u8>> {
+    let limit = max_decompressed_size();
+    let mut decompressed = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompressed field is larger than {} bytes", limit),
+        ));
+    }
+    Ok(decompressed)
+}
+
+/// A field type held as raw bytes, which can be compressed.
+pub trait Blob: Sized {
+    fn as_bytes(&self) -> &[u8];
+
+    /// The value held in `bytes`, or why they don't hold one.
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, String>;
+}
+
+impl Blob for Vec<u8> {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(bytes)
+    }
+}
+
+impl Blob for Box<[u8]> {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(bytes.into_boxed_slice())
+    }
+}
+
+impl Blob for String {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }
+}
+
+fn serialize<T, S>(
+    value: &Option<T>,
+    serializer: S,
+    compress: fn(&[u8]) -> io::Result<Vec<u8>>,
+) -> Result<S::Ok, S::Error>
+where
+    T: Blob,
+    S: Serializer,
+{
+    match value {
+        Some(value) => {
+            let compressed = compress(value.as_bytes()).map_err(ser::Error::custom)?;
+            serializer.serialize_some(&Bytes(&compressed))
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize<'de, T, D>(
+    deserializer: D,
+    decompress: fn(&[u8]) -> io::Result<Vec<u8>>,
+) -> Result<Option<T>, D::Error>
+where
+    T: Blob,
+    D: Deserializer<'de>,
+{
+    match de::Deserialize::deserialize(deserializer)? {
+        Some(ByteBuf(compressed)) => {
+            let bytes = decompress(&compressed).map_err(de::Error::custom)?;
+            T::from_bytes(bytes).map(Some).map_err(de::Error::custom)
+        }
+        None => Ok(None),
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl ser::Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Bytes serialized by [`Bytes`], which formats without a bytes type, such
+/// as JSON, write as a sequence.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> de::Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteBufVisitor;
+
+        impl<'de> Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("compressed bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(bytes))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(ByteBuf(bytes))
+            }
+        }
+
+        deserializer.deserialize_bytes(ByteBufVisitor)
+    }
+}
+
+/// `serde(with)` module of `compress = "zstd"` fields.
+#[cfg(feature = "zstd")]
+pub mod zstd {
+    use super::Blob;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<T: Blob, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize(value, serializer, |bytes| ::zstd::bulk::compress(bytes, 0))
+    }
+
+    pub fn deserialize<'de, T: Blob, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        super::deserialize(deserializer, |bytes| {
+            super::read_limited(::zstd::stream::read::Decoder::new(bytes)?)
+        })
+    }
+}
+
+/// `serde(with)` module of `compress = "deflate"` fields.
+#[cfg(feature = "flate2")]
+pub mod deflate {
+    use super::Blob;
+    use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+    use serde::{Deserializer, Serializer};
+    use std::io::Write;
+
+    pub fn serialize<T: Blob, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize(value, serializer, |bytes| {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        })
+    }
+
+    pub fn deserialize<'de, T: Blob, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        super::deserialize(deserializer, |bytes| {
+            super::read_limited(DeflateDecoder::new(bytes))
+        })
+    }
+}
+
+#[cfg(all(test, feature = "zstd", feature = "flate2"))]
+mod tests {
+    use super::{max_decompressed_size, set_max_decompressed_size};
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+    use std::sync::Mutex;
+
+    /// Held by tests that depend on the decompressed size limit.
+    static LIMIT: Mutex<()> = Mutex::new(());
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    #[delta_struct(serde)]
+    struct Firmware {
+        version: u32,
+        #[delta_struct(compress = "zstd")]
+        image: Vec<u8>,
+        #[delta_struct(compress = "deflate")]
+        notes: String,
+    }
+
+    #[test]
+    fn compressed_fields_round_trip() {
+        let _limit = LIMIT.lock().unwrap_or_else(|e| e.into_inner());
+        let new = Firmware {
+            version: 2,
+            image: vec![7; 4096],
+            notes: "fixes ".repeat(100),
+        };
+        let delta = Firmware::delta(Firmware::default(), new.clone()).unwrap();
+        let json = serde_json::to_string(&delta).unwrap();
+        assert!(json.len() < 1000, "{} bytes", json.len());
+        let mut applied = Firmware::default();
+        applied.apply_delta(serde_json::from_str(&json).unwrap());
+        assert_eq!(applied, new);
+
+        let unchanged = serde_json::from_str::<FirmwareDelta>(r#"{"version":3,"image":null}"#);
+        assert_eq!(unchanged.unwrap().image, None);
+    }
+
+    #[test]
+    fn corrupt_payloads_fail_to_deserialize() {
+        let corrupt = serde_json::from_str::<FirmwareDelta>(r#"{"image":[1,2,3]}"#);
+        assert!(corrupt.is_err());
+    }
+
+    #[test]
+    fn oversized_payloads_fail_to_deserialize() {
+        let _limit = LIMIT.lock().unwrap_or_else(|e| e.into_inner());
+        let bomb = Firmware {
+            version: 1,
+            image: vec![0; max_decompressed_size() + 1],
+            notes: "a".repeat(max_decompressed_size() + 1),
+        };
+        let json =
+            serde_json::to_string(&Firmware::delta(Firmware::default(), bomb).unwrap()).unwrap();
+        assert!(json.len() < 100_000, "{} bytes", json.len());
+        let err = serde_json::from_str::<FirmwareDelta>(&json)
+            .err()
+            .expect("oversized payload deserialized");
+        assert!(err.to_string().contains("larger than"), "{}", err);
+
+        let small = Firmware {
+            version: 1,
+            image: vec![0; 2048],
+            notes: "a".repeat(1024),
+        };
+        let json =
+            serde_json::to_string(&Firmware::delta(Firmware::default(), small).unwrap()).unwrap();
+        set_max_decompressed_size(1024);
+        let image = serde_json::from_str::<FirmwareDelta>(&json);
+        let notes =
+            serde_json::from_str::<FirmwareDelta>(&json.replace("\"image\"", "\"skipped\""));
+        set_max_decompressed_size(super::DEFAULT_MAX_DECOMPRESSED_SIZE);
+        assert!(image.is_err());
+        assert_eq!(notes.unwrap().notes.unwrap().len(), 1024);
+    }
+}
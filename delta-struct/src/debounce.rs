@@ -0,0 +1,287 @@
+use crate::{Delta, DeltaOutput};
+use std::time::{Duration, Instant};
+
+/// Coalesces deltas arriving in quick succession, e.g. one per keystroke,
+/// so the target is only applied to once per window.
+///
+/// The window starts when a delta is buffered with nothing else pending, so
+/// a steady stream of deltas is still applied once per window instead of
+/// being held back until it stops.
+pub struct Debouncer<T: Delta> {
+    window: Duration,
+    pending: Option<(T::Output, Instant)>,
+}
+
+impl<T: Delta> Debouncer<T> {
+    pub fn new(window: Duration) -> Self {
+        Debouncer {
+            window,
+            pending: None,
+        }
+    }
+
+    /// Buffers `delta`, composing it with any delta already pending.
+    pub fn push(&mut self, delta: T::Output) {
+        self.push_at(delta, Instant::now())
+    }
+
+    /// [`Debouncer::push`] with an explicit current time.
+    pub fn push_at(&mut self, delta: T::Output, now: Instant) {
+        self.pending = Some(match self.pending.take() {
+            Some((pending, since)) => (T::compose_delta(pending, delta), since),
+            None => (delta, now),
+        });
+    }
+
+    /// When the pending delta is due to be applied, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|(_, since)| *since + self.window)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Applies the pending delta to `target` if its window has passed.
+    /// Returns whether anything was applied.
+    pub fn apply_due(&mut self, target: &mut T) -> bool {
+        self.apply_due_at(target, Instant::now())
+    }
+
+    /// [`Debouncer::apply_due`] with an explicit current time.
+    pub fn apply_due_at(&mut self, target: &mut T, now: Instant) -> bool {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.flush(target),
+            _ => false,
+        }
+    }
+
+    /// Applies the pending delta to `target` right away, e.g. before
+    /// shutting down. Returns whether anything was applied.
+    pub fn flush(&mut self, target: &mut T) -> bool {
+        match self.pending.take() {
+            Some((delta, _)) => {
+                target.apply_delta(delta);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A [`Debouncer`] that lets deltas changing urgent fields through right
+/// away, e.g. alarm state changes arriving amid telemetry noise.
+///
+/// Urgent fields are named as in [`DeltaOutput::changed_fields`]. An
+/// urgent delta is applied together with anything pending, composed in
+/// the order they arrived, so nothing is applied out of order.
+pub struct PrioritizedDebounce<T: Delta> {
+    debouncer: Debouncer<T>,
+    urgent: Vec<String>,
+}
+
+impl<T: Delta> PrioritizedDebounce<T>
+where
+    T::Output: DeltaOutput,
+{
+    pub fn new<I>(window: Duration, urgent: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        PrioritizedDebounce {
+            debouncer: Debouncer::new(window),
+            urgent: urgent.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Whether `delta` changes any urgent field.
+    pub fn is_urgent(&self, delta: &T::Output) -> bool {
+        delta
+            .changed_fields()
+            .iter()
+            .any(|field| self.urgent.iter().any(|urgent| urgent == field))
+    }
+
+    /// Applies `delta` to `target` right away if it is urgent, and buffers
+    /// it otherwise. Returns whether anything was applied.
+    pub fn push(&mut self, delta: T::Output, target: &mut T) -> bool {
+        self.push_at(delta, target, Instant::now())
+    }
+
+    /// [`PrioritizedDebounce::push`] with an explicit current time.
+    pub fn push_at(&mut self, delta: T::Output, target: &mut T, now: Instant) -> bool {
+        let urgent = self.is_urgent(&delta);
+        self.debouncer.push_at(delta, now);
+        urgent && self.debouncer.flush(target)
+    }
+
+    /// When the pending delta is due to be applied, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.debouncer.deadline()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.debouncer.is_pending()
+    }
+
+    /// Applies the pending delta to `target` if its window has passed.
+    /// Returns whether anything was applied.
+    pub fn apply_due(&mut self, target: &mut T) -> bool {
+        self.debouncer.apply_due(target)
+    }
+
+    /// [`PrioritizedDebounce::apply_due`] with an explicit current time.
+    pub fn apply_due_at(&mut self, target: &mut T, now: Instant) -> bool {
+        self.debouncer.apply_due_at(target, now)
+    }
+
+    /// Applies the pending delta to `target` right away. Returns whether
+    /// anything was applied.
+    pub fn flush(&mut self, target: &mut T) -> bool {
+        self.debouncer.flush(target)
+    }
+}
+
+/// A [`Debouncer`] fed through a channel, for applying deltas from a tokio
+/// task. Requires the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub struct AsyncDebouncer<T: Delta> {
+    window: Duration,
+    receiver: tokio::sync::mpsc::UnboundedReceiver<T::Output>,
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Delta> AsyncDebouncer<T> {
+    /// Creates a debouncer along with the sender to send it deltas through.
+    pub fn new(window: Duration) -> (tokio::sync::mpsc::UnboundedSender<T::Output>, Self) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        (sender, AsyncDebouncer { window, receiver })
+    }
+
+    /// Waits for a delta, composes it with the deltas received within the
+    /// window after it, and applies the result to `target`. Returns `false`
+    /// without applying anything once every sender has been dropped.
+    pub async fn apply_next(&mut self, target: &mut T) -> bool {
+        let mut pending = match self.receiver.recv().await {
+            Some(delta) => delta,
+            None => return false,
+        };
+        let deadline = tokio::time::Instant::now() + self.window;
+        while let Ok(Some(delta)) = tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+            pending = T::compose_delta(pending, delta);
+        }
+        target.apply_delta(pending);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ComputeDelta;
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct SearchBox {
+        text: String,
+        focused: bool,
+    }
+
+    fn typed(text: &str) -> SearchBoxDelta {
+        SearchBoxDelta {
+            text: Some(text.to_string()),
+            focused: None,
+        }
+    }
+
+    #[test]
+    fn deltas_within_the_window_are_applied_once() {
+        let start = Instant::now();
+        let window = Duration::from_millis(100);
+        let mut debouncer = Debouncer::<SearchBox>::new(window);
+        let mut target = SearchBox::default();
+        debouncer.push_at(typed("h"), start);
+        debouncer.push_at(
+            SearchBox::delta(
+                SearchBox::default(),
+                SearchBox {
+                    text: String::new(),
+                    focused: true,
+                },
+            )
+            .unwrap(),
+            start + Duration::from_millis(30),
+        );
+        debouncer.push_at(typed("hi"), start + Duration::from_millis(60));
+        assert_eq!(debouncer.deadline(), Some(start + window));
+        assert!(!debouncer.apply_due_at(&mut target, start + Duration::from_millis(90)));
+        assert_eq!(target, SearchBox::default());
+        assert!(debouncer.apply_due_at(&mut target, start + window));
+        assert_eq!(
+            target,
+            SearchBox {
+                text: "hi".to_string(),
+                focused: true,
+            }
+        );
+        assert!(!debouncer.is_pending());
+        assert!(!debouncer.flush(&mut target));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn async_debouncer_composes_until_the_window_ends() {
+        let (sender, mut debouncer) = AsyncDebouncer::<SearchBox>::new(Duration::from_millis(100));
+        let mut target = SearchBox::default();
+        sender.send(typed("h")).unwrap();
+        sender.send(typed("he")).unwrap();
+        let late = sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            late.send(typed("hey")).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            late.send(typed("hey!")).unwrap();
+        });
+        drop(sender);
+        assert!(debouncer.apply_next(&mut target).await);
+        assert_eq!(target.text, "hey");
+        assert!(debouncer.apply_next(&mut target).await);
+        assert_eq!(target.text, "hey!");
+        assert!(!debouncer.apply_next(&mut target).await);
+    }
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    struct Panel {
+        armed: bool,
+        temperature: u8,
+    }
+
+    #[test]
+    fn urgent_deltas_are_applied_with_everything_pending() {
+        let start = Instant::now();
+        let mut debounce =
+            PrioritizedDebounce::<Panel>::new(Duration::from_millis(100), vec!["armed"]);
+        let mut target = Panel::default();
+        let warmer = PanelDelta {
+            armed: None,
+            temperature: Some(21),
+        };
+        assert!(!debounce.push_at(warmer, &mut target, start));
+        assert_eq!(target, Panel::default());
+        let armed = PanelDelta {
+            armed: Some(true),
+            temperature: None,
+        };
+        assert!(debounce.is_urgent(&armed));
+        assert!(debounce.push_at(armed, &mut target, start + Duration::from_millis(10)));
+        assert_eq!(
+            target,
+            Panel {
+                armed: true,
+                temperature: 21,
+            }
+        );
+        assert!(!debounce.is_pending());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::unordered::UnknownRemovals;
+use std::fmt;
+
+/// The error of the crate's fallible operations, so callers can match on
+/// what went wrong regardless of which operation failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A delta could not be applied, as returned by a generated
+    /// `try_apply_delta`. Nothing was applied.
+    Apply(UnknownRemovals),
+    /// A delta, or a document it was applied to, could not be decoded from
+    /// its serialized form.
+    #[cfg(feature = "serde_json")]
+    Decode(crate::json::Error),
+    /// A delta could not be converted to BSON for a MongoDB update.
+    #[cfg(feature = "mongodb")]
+    Encode(crate::mongo::Error),
+    /// Deltas could not be merged, since they both modify the named fields.
+    Conflict(Vec<&'static str>),
+    /// A delta was not applied by a generated `try_apply_delta_with`, since
+    /// the guards of the named fields rejected their changes.
+    Denied(Vec<&'static str>),
+    /// A delta was not applied by a generated `apply_checked`, since its
+    /// target isn't the state the delta was computed from.
+    BaseMismatch { expected: u64, found: u64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Apply(e) => e.fmt(f),
+            #[cfg(feature = "serde_json")]
+            Error::Decode(e) => e.fmt(f),
+            #[cfg(feature = "mongodb")]
+            Error::Encode(e) => e.fmt(f),
+            Error::Conflict(fields) => write!(
+                f,
+                "the deltas both modify the fields `{}`",
+                fields.join("`, `")
+            ),
+            Error::Denied(fields) => write!(
+                f,
+                "the changes to the fields `{}` were denied",
+                fields.join("`, `")
+            ),
+            Error::BaseMismatch { expected, found } => write!(
+                f,
+                "the delta was computed from a state with fingerprint {:#x}, not {:#x}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Apply(e) => Some(e),
+            #[cfg(feature = "serde_json")]
+            Error::Decode(e) => Some(e),
+            #[cfg(feature = "mongodb")]
+            Error::Encode(e) => Some(e),
+            Error::Conflict(_) | Error::Denied(_) | Error::BaseMismatch { .. } => None,
+        }
+    }
+}
+
+impl From<UnknownRemovals> for Error {
+    fn from(e: UnknownRemovals) -> Self {
+        Error::Apply(e)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<crate::json::Error> for Error {
+    fn from(e: crate::json::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+#[cfg(feature = "mongodb")]
+impl From<crate::mongo::Error> for Error {
+    fn from(e: crate::mongo::Error) -> Self {
+        Error::Encode(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn errors_describe_their_kind() {
+        let apply = Error::from(UnknownRemovals {
+            fields: vec![("codes", vec!["1234".to_string()])],
+        });
+        assert_eq!(
+            apply.to_string(),
+            "the delta removes elements that aren't present: `codes`: [1234]"
+        );
+        assert!(apply.source().is_some());
+        let conflict = Error::Conflict(vec!["volume", "muted"]);
+        assert_eq!(
+            conflict.to_string(),
+            "the deltas both modify the fields `volume`, `muted`"
+        );
+        assert!(conflict.source().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+//! A C API for delta structs, so components written in other languages can
+//! compute and apply deltas. Structs deriving with `#[delta_struct(ffi)]`
+//! get three `extern "C"` functions, named after the struct in snake case,
+//! or after the prefix given with `ffi = "prefix"`:
+//!
+//! ```c
+//! FooDelta *foo_delta_compute(const Foo *old, const Foo *new);
+//! void foo_delta_apply(Foo *target, FooDelta *delta);
+//! void foo_delta_free(FooDelta *delta);
+//! ```
+//!
+//! Deltas are handed out as owned pointers, null when nothing changed.
+//! Every delta must be passed to either `_apply`, which consumes it, or
+//! `_free`. To generate a header with cbindgen, enable its `parse.expand`
+//! option so it sees the derived functions. Requires the `ffi` feature.
+
+/// Moves `delta` to the heap for handing it to C, or returns null if there
+/// is none.
+pub fn into_raw<T>(delta: Option<T>) -> *mut T {
+    match delta {
+        Some(delta) => Box::into_raw(Box::new(delta)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Takes back a delta handed out by [`into_raw`].
+///
+/// # Safety
+///
+/// `delta` must be null or returned by [`into_raw`], and not taken back
+/// before.
+pub unsafe fn from_raw<T>(delta: *mut T) -> Option<T> {
+    if delta.is_null() {
+        None
+    } else {
+        Some(*Box::from_raw(delta))
+    }
+}
+
+/// Frees a delta handed out by [`into_raw`].
+///
+/// # Safety
+///
+/// As for [`from_raw`].
+pub unsafe fn free<T>(delta: *mut T) {
+    if !delta.is_null() {
+        drop(Box::from_raw(delta));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, crate::Delta, PartialEq)]
+    #[delta_struct(ffi, repr = "C")]
+    #[repr(C)]
+    struct Shade {
+        position: u8,
+        moving: bool,
+    }
+
+    #[derive(Clone, crate::Delta)]
+    #[delta_struct(ffi = "blind")]
+    struct Blind {
+        tilt: i16,
+    }
+
+    #[test]
+    fn deltas_round_trip_through_the_c_api() {
+        let old = Shade {
+            position: 0,
+            moving: false,
+        };
+        let new = Shade {
+            position: 80,
+            moving: true,
+        };
+        let mut target = old.clone();
+        unsafe {
+            assert!(shade_delta_compute(&old, &old).is_null());
+            let delta = shade_delta_compute(&old, &new);
+            assert!(!delta.is_null());
+            shade_delta_apply(&mut target, delta);
+            shade_delta_free(std::ptr::null_mut());
+            shade_delta_free(shade_delta_compute(&new, &old));
+            shade_delta_apply(&mut target, std::ptr::null_mut());
+        }
+        assert_eq!(target, new);
+    }
+
+    #[test]
+    fn prefixes_rename_the_functions() {
+        let delta = unsafe { blind_delta_compute(&Blind { tilt: 0 }, &Blind { tilt: 30 }) };
+        assert_eq!(unsafe { from_raw(delta) }.unwrap().tilt, Some(30));
+    }
+}
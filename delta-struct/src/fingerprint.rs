@@ -0,0 +1,85 @@
+use std::hash::Hasher;
+
+/// The hasher of `#[delta_struct(fingerprint)]`, whose fingerprints are
+/// compared between processes, e.g. a server and a device. Unlike std's
+/// `DefaultHasher`, its algorithm is fixed: 64 bit FNV-1a over integers
+/// written little endian, with `usize` and `isize` widened to 64 bits, so
+/// builds with different toolchains or for different targets agree as long
+/// as the hashed types' `Hash` impls do.
+#[derive(Clone, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+macro_rules! write_le {
+    ($($method:ident: $ty:ty,)+) => {
+        $(
+            fn $method(&mut self, i: $ty) {
+                self.write(&i.to_le_bytes());
+            }
+        )+
+    };
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    write_le! {
+        write_u16: u16,
+        write_u32: u32,
+        write_u64: u64,
+        write_u128: u128,
+        write_i16: i16,
+        write_i32: i32,
+        write_i64: i64,
+        write_i128: i128,
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::Hash;
+
+    fn hash<T: Hash>(value: T) -> u64 {
+        let mut hasher = StableHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hashes_are_fixed() {
+        // The 64 bit FNV-1a test vectors.
+        assert_eq!(hash(()), 0xcbf2_9ce4_8422_2325);
+        let mut hasher = StableHasher::default();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(7usize), hash(7u64));
+        assert_eq!(hash(0x0102u16), {
+            let mut hasher = StableHasher::default();
+            hasher.write(&[2, 1]);
+            hasher.finish()
+        });
+    }
+}
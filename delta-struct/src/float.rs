@@ -0,0 +1,52 @@
+//! Support for `field_type = "float"` fields.
+
+/// Floating point types that can be compared with a tolerance.
+pub trait Float: Copy {
+    /// Whether `self` and `other` are close enough to not warrant a delta.
+    ///
+    /// Values are considered equal when they are within `epsilon` of each
+    /// other, or at most `ulps` representable values apart. Two NaNs are
+    /// considered equal, so a field holding NaN does not produce a delta
+    /// every time it is diffed.
+    fn approx_eq(self, other: Self, epsilon: Self, ulps: u32) -> bool;
+}
+
+macro_rules! impl_float {
+    ($float:ty, $bits:ty) => {
+        impl Float for $float {
+            fn approx_eq(self, other: Self, epsilon: Self, ulps: u32) -> bool {
+                if self.is_nan() || other.is_nan() {
+                    return self.is_nan() && other.is_nan();
+                }
+                if self == other || (self - other).abs() <= epsilon {
+                    return true;
+                }
+                if self.is_sign_positive() != other.is_sign_positive() {
+                    return false;
+                }
+                let distance = (self.to_bits() as $bits - other.to_bits() as $bits).unsigned_abs();
+                distance <= ulps.into()
+            }
+        }
+    };
+}
+
+impl_float!(f32, i64);
+impl_float!(f64, i128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerances() {
+        assert!(f64::NAN.approx_eq(f64::NAN, 0.0, 0));
+        assert!(!f64::NAN.approx_eq(1.0, 0.0, 0));
+        assert!(1.0f64.approx_eq(1.0 + 1e-9, 1e-6, 0));
+        assert!(!1.0f64.approx_eq(1.1, 1e-6, 0));
+        let next = f32::from_bits(1.0f32.to_bits() + 2);
+        assert!(!1.0f32.approx_eq(next, 0.0, 1));
+        assert!(1.0f32.approx_eq(next, 0.0, 2));
+        assert!(0.0f32.approx_eq(-0.0, 0.0, 0));
+    }
+}
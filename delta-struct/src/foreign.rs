@@ -0,0 +1,56 @@
+//! `Delta` for widely used third party types that are compared by value and
+//! replaced wholesale, so they can be nested like any other delta type, e.g.
+//! as `field_type = "delta"` fields or in a `Vec`. Each is behind a feature
+//! named after its crate.
+
+#[cfg(feature = "uuid")]
+crate::impl_delta_scalar!(uuid::Uuid);
+
+#[cfg(feature = "url")]
+crate::impl_delta_scalar!(url::Url);
+
+#[cfg(feature = "ipnet")]
+crate::impl_delta_scalar!(ipnet::IpNet, ipnet::Ipv4Net, ipnet::Ipv6Net);
+
+#[cfg(all(test, feature = "uuid", feature = "url", feature = "ipnet"))]
+mod tests {
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+    use ipnet::IpNet;
+    use url::Url;
+    use uuid::Uuid;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Endpoint {
+        id: Uuid,
+        #[delta_struct(field_type = "delta")]
+        subnet: Option<IpNet>,
+        #[delta_struct(field_type = "delta")]
+        mirrors: Vec<Url>,
+    }
+
+    #[test]
+    fn foreign_types_nest_as_scalars() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let old = Endpoint {
+            id: Uuid::from_u128(1),
+            subnet: None,
+            mirrors: vec![url("https://a.example/"), url("https://b.example/")],
+        };
+        let new = Endpoint {
+            id: Uuid::from_u128(2),
+            subnet: Some("10.0.0.0/24".parse().unwrap()),
+            mirrors: vec![url("https://a.example/"), url("https://c.example/")],
+        };
+        let delta = Endpoint::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.id, Some(Uuid::from_u128(2)));
+        assert_eq!(
+            delta.mirrors.as_ref().unwrap().patch,
+            vec![(1, url("https://c.example/"))]
+        );
+        assert_eq!(Endpoint::change_count(&old, &new, usize::MAX), 3);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+}
@@ -0,0 +1,112 @@
+//! Nested deltas that can also be sent as a full replacement, for fields
+//! marked `granularity = "coarse"`.
+
+use crate::Delta;
+use std::fmt;
+
+/// Which form [`Granular`] fields take when a delta is computed with the
+/// generated `delta_at`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    /// Nested patches, as computed by `ComputeDelta::delta`.
+    Fine,
+    /// Changed nested values are replaced in full, for consumers that can't
+    /// apply nested patches.
+    Coarse,
+}
+
+/// The delta of a nested field: either a patch or the new value.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Granular<T: Delta> {
+    Patch(T::Output),
+    Replace(T),
+}
+
+impl<T: Delta> Granular<T> {
+    /// Computes the delta between `old` and `new` in the given form.
+    pub fn diff(old: T, new: T, granularity: Granularity) -> Option<Self> {
+        match granularity {
+            Granularity::Fine => T::delta(old, new).map(Granular::Patch),
+            Granularity::Coarse if T::change_count(&old, &new, 0) > 0 => {
+                Some(Granular::Replace(new))
+            }
+            Granularity::Coarse => None,
+        }
+    }
+
+    pub fn apply(self, target: &mut T) {
+        match self {
+            Granular::Patch(patch) => target.apply_delta(patch),
+            Granular::Replace(value) => *target = value,
+        }
+    }
+
+    /// Combines two consecutive deltas into one.
+    pub fn compose(first: Self, second: Self) -> Self {
+        match (first, second) {
+            (Granular::Patch(first), Granular::Patch(second)) => {
+                Granular::Patch(T::compose_delta(first, second))
+            }
+            (Granular::Replace(mut value), Granular::Patch(patch)) => {
+                value.apply_delta(patch);
+                Granular::Replace(value)
+            }
+            (_, replace) => replace,
+        }
+    }
+}
+
+impl<T: Delta> Clone for Granular<T>
+where
+    T: Clone,
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Granular::Patch(patch) => Granular::Patch(patch.clone()),
+            Granular::Replace(value) => Granular::Replace(value.clone()),
+        }
+    }
+}
+
+impl<T: Delta> fmt::Debug for Granular<T>
+where
+    T: fmt::Debug,
+    T::Output: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Granular::Patch(patch) => f.debug_tuple("Patch").field(patch).finish(),
+            Granular::Replace(value) => f.debug_tuple("Replace").field(value).finish(),
+        }
+    }
+}
+
+impl<T: Delta> PartialEq for Granular<T>
+where
+    T: PartialEq,
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Granular::Patch(a), Granular::Patch(b)) => a == b,
+            (Granular::Replace(a), Granular::Replace(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: Delta> arbitrary::Arbitrary<'a> for Granular<T>
+where
+    T: arbitrary::Arbitrary<'a>,
+    T::Output: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(if u.arbitrary()? {
+            Granular::Patch(u.arbitrary()?)
+        } else {
+            Granular::Replace(u.arbitrary()?)
+        })
+    }
+}
@@ -0,0 +1,980 @@
+//! Conversions between generated delta structs and `serde_json::Value`, for
+//! handling patches whose shape is only known at runtime. Enabled per struct
+//! with `#[delta_struct(json)]`.
+//!
+//! Each field of the delta struct becomes a key of a JSON object. Keys may
+//! be omitted if the field accepts `null`, so scalar changes can be left out
+//! of a patch; unknown keys are rejected. The key of an `Option` field is
+//! only present if it changed, so that `null` clears it.
+//!
+//! With `#[delta_struct(field_map)]`, a delta can also be converted to and
+//! from a flat [`FieldMap`], keyed by dotted field paths such as
+//! `"climate.temperature"`.
+
+use crate::{ApplyDelta, Delta, OptionDelta};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+pub use serde_json::{Map, Value};
+
+/// Why a `serde_json::Value` could not be converted into a delta, as
+/// [`Error::Decode`](crate::Error::Decode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    field: Option<String>,
+    message: String,
+}
+
+impl Error {
+    /// The delta field the error is about, if it is about a single field.
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "field `{}`: {}", field, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The object a delta is read from.
+pub fn object(value: Value) -> Result<Map<String, Value>, Error> {
+    match value {
+        Value::Object(object) => Ok(object),
+        other => Err(Error {
+            field: None,
+            message: format!("expected a JSON object, found {}", kind(&other)),
+        }),
+    }
+}
+
+/// Removes `field` from `object` and deserializes it. A missing field is
+/// read as `null`.
+pub fn take_field<T: DeserializeOwned>(
+    object: &mut Map<String, Value>,
+    field: &str,
+) -> Result<T, Error> {
+    let error = |message: String| Error {
+        field: Some(field.to_string()),
+        message,
+    };
+    match object.remove(field) {
+        Some(value) => serde_json::from_value(value).map_err(|e| error(e.to_string())),
+        None => serde_json::from_value(Value::Null).map_err(|_| error("missing field".into())),
+    }
+}
+
+/// Removes the member of an `Option` field from `object`. A missing key is
+/// unchanged, and `null` clears the field.
+pub fn take_nullable<T: DeserializeOwned>(
+    object: &mut Map<String, Value>,
+    field: &str,
+) -> Result<Option<Option<T>>, Error> {
+    match object.remove(field) {
+        Some(value) => serde_json::from_value(value).map(Some).map_err(|e| Error {
+            field: Some(field.to_string()),
+            message: e.to_string(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Fails if any key is left in `object` after all fields were taken.
+pub fn deny_unknown(object: Map<String, Value>) -> Result<(), Error> {
+    match object.into_iter().next() {
+        Some((key, _)) => Err(Error {
+            field: Some(key),
+            message: "unknown field".into(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Serializes `value` into `object` under `field`.
+///
+/// # Panics
+///
+/// If `value` can't be represented as JSON, e.g. a map with non-string keys.
+pub fn insert<T: Serialize>(object: &mut Map<String, Value>, field: &str, value: T) {
+    match serde_json::to_value(value) {
+        Ok(value) => {
+            object.insert(field.to_string(), value);
+        }
+        Err(e) => panic!(
+            "delta field `{}` can't be represented as JSON: {}",
+            field, e
+        ),
+    }
+}
+
+/// Conversions between a delta and a flat map from dotted field paths to
+/// values. Nested delta fields are flattened into paths below their own;
+/// unchanged fields and the empty parts of a collection's delta are left
+/// out. A changed value is kept even if it is `null` or empty, so clearing
+/// an `Option` field is a `null`.
+pub trait FieldMap: Sized {
+    /// Inserts the changes into `map`, with paths below `path`.
+    fn insert_fields(self, path: &str, map: &mut HashMap<String, Value>);
+
+    /// Takes the changes with paths below `path` out of `map`.
+    fn take_fields(path: &str, map: &mut HashMap<String, Value>) -> Result<Self, Error>;
+
+    fn to_field_map(self) -> HashMap<String, Value> {
+        let mut map = HashMap::new();
+        self.insert_fields("", &mut map);
+        map
+    }
+
+    /// Fails on paths that don't name a field of the delta.
+    fn from_field_map(mut map: HashMap<String, Value>) -> Result<Self, crate::Error> {
+        let delta = Self::take_fields("", &mut map)?;
+        match map.into_iter().next() {
+            Some((path, _)) => Err(crate::Error::Decode(Error {
+                field: Some(path),
+                message: "unknown field".into(),
+            })),
+            None => Ok(delta),
+        }
+    }
+}
+
+/// The path of the field `name` below `path`.
+pub fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// Whether `map` has the path `path` or any path below it.
+pub fn has_fields(map: &HashMap<String, Value>, path: &str) -> bool {
+    map.keys().any(|key| {
+        key.strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Serializes the new value of a changed field into `map` under `path`.
+///
+/// # Panics
+///
+/// If `value` can't be represented as JSON, e.g. a map with non-string keys.
+pub fn insert_changed<T: Serialize>(
+    map: &mut HashMap<String, Value>,
+    path: String,
+    value: Option<T>,
+) {
+    if let Some(value) = value {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                map.insert(path, value);
+            }
+            Err(e) => panic!("delta field `{}` can't be represented as JSON: {}", path, e),
+        }
+    }
+}
+
+/// Removes the new value of a field from `map`. A missing path is
+/// unchanged.
+pub fn take_changed<T: DeserializeOwned>(
+    map: &mut HashMap<String, Value>,
+    path: String,
+) -> Result<Option<T>, Error> {
+    match map.remove(&path) {
+        Some(value) => serde_json::from_value(value).map(Some).map_err(|e| Error {
+            field: Some(path),
+            message: e.to_string(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Serializes a part of a collection's delta into `map` under `path`,
+/// unless it is `null` or an empty array.
+///
+/// # Panics
+///
+/// If `value` can't be represented as JSON, e.g. a map with non-string keys.
+pub fn insert_path<T: Serialize>(map: &mut HashMap<String, Value>, path: String, value: T) {
+    match serde_json::to_value(value) {
+        Ok(Value::Null) => {}
+        Ok(Value::Array(items)) if items.is_empty() => {}
+        Ok(value) => {
+            map.insert(path, value);
+        }
+        Err(e) => panic!("delta field `{}` can't be represented as JSON: {}", path, e),
+    }
+}
+
+/// Removes `path` from `map` and deserializes it. A missing path is read as
+/// `null` or an empty array.
+pub fn take_path<T: DeserializeOwned>(
+    map: &mut HashMap<String, Value>,
+    path: String,
+) -> Result<T, Error> {
+    match map.remove(&path) {
+        Some(value) => serde_json::from_value(value).map_err(|e| Error {
+            field: Some(path),
+            message: e.to_string(),
+        }),
+        None => serde_json::from_value(Value::Null)
+            .or_else(|_| serde_json::from_value(Value::Array(vec![])))
+            .map_err(|_| Error {
+                field: Some(path),
+                message: "missing field".into(),
+            }),
+    }
+}
+
+/// A value being set or cleared is stored whole at the field's own path,
+/// as `null` when cleared; a patch is flattened below it.
+impl<T: Delta + Serialize + DeserializeOwned> FieldMap for OptionDelta<T>
+where
+    T::Output: FieldMap,
+{
+    fn insert_fields(self, path: &str, map: &mut HashMap<String, Value>) {
+        match self {
+            OptionDelta::Patch(patch) => patch.insert_fields(path, map),
+            OptionDelta::Set(value) => insert_changed(map, path.to_string(), Some(value)),
+            OptionDelta::Clear => {
+                map.insert(path.to_string(), Value::Null);
+            }
+        }
+    }
+
+    fn take_fields(path: &str, map: &mut HashMap<String, Value>) -> Result<Self, Error> {
+        match map.remove(path) {
+            Some(Value::Null) => Ok(OptionDelta::Clear),
+            Some(value) => serde_json::from_value(value)
+                .map(OptionDelta::Set)
+                .map_err(|e| Error {
+                    field: Some(path.to_string()),
+                    message: e.to_string(),
+                }),
+            None => T::Output::take_fields(path, map).map(OptionDelta::Patch),
+        }
+    }
+}
+
+/// Applies `delta` to the document `target` by deserializing it as a `T`,
+/// for generated `apply_to_json`s. `T` is serialized before and after the
+/// delta is applied, and only the keys whose value differs are written
+/// back into `target`, so every other key, including the unknown keys
+/// nested under an unchanged field, is kept as it was. Keys are compared
+/// as serialized, so fields renamed by serde are written under their
+/// serialized names.
+pub fn apply_to_json<T, D>(delta: D, target: &mut Value) -> Result<(), crate::Error>
+where
+    T: ApplyDelta<D> + Serialize + DeserializeOwned,
+{
+    let error = |message: String| Error {
+        field: None,
+        message,
+    };
+    let mut typed = T::deserialize(&*target).map_err(|e| error(e.to_string()))?;
+    let before = serde_json::to_value(&typed).map_err(|e| error(e.to_string()))?;
+    typed.apply_delta(delta);
+    let after = serde_json::to_value(typed).map_err(|e| error(e.to_string()))?;
+    match (target, before, after) {
+        (Value::Object(target), Value::Object(mut before), Value::Object(after)) => {
+            for (key, value) in after {
+                if before.remove(&key).as_ref() != Some(&value) {
+                    target.insert(key, value);
+                }
+            }
+            // Keys no longer serialized, e.g. skipped when `None`.
+            for key in before.keys() {
+                target.remove(key);
+            }
+        }
+        (target, before, after) => {
+            if before != after {
+                *target = after;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serializes the member of an `Option` field into `object` under `field`
+/// if it changed, as `null` if it was cleared.
+///
+/// # Panics
+///
+/// If `value` can't be represented as JSON, e.g. a map with non-string keys.
+pub fn insert_nullable<T: Serialize>(
+    object: &mut Map<String, Value>,
+    field: &str,
+    value: Option<Option<T>>,
+) {
+    if let Some(value) = value {
+        insert(object, field, value);
+    }
+}
+
+/// How many bytes of a delta each of its fields takes, generated as
+/// `size_report` with `#[delta_struct(size_report)]`, to find the fields
+/// that dominate the bandwidth deltas take. Sizes are of the delta
+/// serialized as JSON.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeltaSizeReport {
+    /// The size of the whole delta, including its field names and the
+    /// punctuation around them.
+    pub total: usize,
+    /// The size of each field's serialized members, in declaration order.
+    pub fields: Vec<(&'static str, usize)>,
+}
+
+impl DeltaSizeReport {
+    /// The fields by size, largest first.
+    pub fn largest(&self) -> Vec<(&'static str, usize)> {
+        let mut fields = self.fields.clone();
+        fields.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        fields
+    }
+}
+
+impl fmt::Display for DeltaSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes", self.total)?;
+        for (name, size) in self.largest() {
+            write!(f, "\n  {}: {} bytes", name, size)?;
+        }
+        Ok(())
+    }
+}
+
+/// The size report of `delta`, whose `fields` are named along with the
+/// names of their serialized members.
+///
+/// # Panics
+///
+/// If `delta` can't be represented as JSON, e.g. a map with non-string keys.
+pub fn size_report<D: Serialize>(delta: &D, fields: &[(&'static str, &[&str])]) -> DeltaSizeReport {
+    let value = match serde_json::to_value(delta) {
+        Ok(value) => value,
+        Err(e) => panic!("the delta can't be represented as JSON: {}", e),
+    };
+    let size = |value: &Value| serde_json::to_vec(value).map_or(0, |bytes| bytes.len());
+    DeltaSizeReport {
+        total: size(&value),
+        fields: fields
+            .iter()
+            .map(|(name, members)| {
+                let members = members.iter().filter_map(|member| value.get(member));
+                (*name, members.map(size).sum())
+            })
+            .collect(),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldMap;
+    use crate::{ApplyDelta, ComputeDelta, Delta, Splice};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(json, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Device {
+        name: String,
+        level: u8,
+        #[delta_struct(field_type = "unordered")]
+        tags: Vec<String>,
+        #[delta_struct(field_type = "ordered")]
+        scenes: Vec<u32>,
+    }
+
+    fn device() -> Device {
+        Device {
+            name: "lamp".into(),
+            level: 10,
+            tags: vec!["hall".into()],
+            scenes: vec![1, 2],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_value() {
+        let new = Device {
+            level: 80,
+            tags: vec!["porch".into()],
+            scenes: vec![1, 3],
+            ..device()
+        };
+        let delta = Device::delta(device(), new).unwrap();
+        let value = Value::from(delta);
+        assert_eq!(
+            value,
+            json!({
+                "name": null,
+                "level": 80,
+                "tags_add": ["porch"],
+                "tags_remove": ["hall"],
+                "scenes": [{ "start": 1, "delete_count": 1, "insert": [3] }],
+            })
+        );
+        assert_eq!(
+            DeviceDelta::try_from(value).unwrap(),
+            DeviceDelta {
+                name: None,
+                level: Some(80),
+                tags_add: vec!["porch".into()],
+                tags_remove: vec!["hall".into()],
+                scenes: vec![Splice {
+                    start: 1,
+                    delete_count: 1,
+                    insert: vec![3],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn omitted_nullable_fields_are_unchanged() {
+        let delta = DeviceDelta::try_from(json!({
+            "level": 5,
+            "tags_add": [],
+            "tags_remove": [],
+            "scenes": [],
+        }))
+        .unwrap();
+        let mut value = device();
+        value.apply_delta(delta);
+        assert_eq!(
+            value,
+            Device {
+                level: 5,
+                ..device()
+            }
+        );
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let wrong_type = DeviceDelta::try_from(json!({
+            "level": "high",
+            "tags_add": [],
+            "tags_remove": [],
+            "scenes": [],
+        }))
+        .unwrap_err();
+        let crate::Error::Decode(wrong_type) = wrong_type else {
+            panic!("expected a decode error, got {:?}", wrong_type);
+        };
+        assert_eq!(wrong_type.field(), Some("level"));
+        assert!(wrong_type
+            .to_string()
+            .starts_with("field `level`: invalid type"));
+
+        let missing = DeviceDelta::try_from(json!({ "tags_add": [] })).unwrap_err();
+        assert_eq!(missing.to_string(), "field `tags_remove`: missing field");
+
+        let unknown = DeviceDelta::try_from(json!({
+            "tags_add": [],
+            "tags_remove": [],
+            "scenes": [],
+            "colour": "red",
+        }))
+        .unwrap_err();
+        assert_eq!(unknown.to_string(), "field `colour`: unknown field");
+
+        let not_object = DeviceDelta::try_from(json!([1, 2])).unwrap_err();
+        let crate::Error::Decode(not_object) = not_object else {
+            panic!("expected a decode error, got {:?}", not_object);
+        };
+        assert_eq!(not_object.field(), None);
+        assert_eq!(
+            not_object.to_string(),
+            "expected a JSON object, found an array"
+        );
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize, Deserialize)]
+    #[delta_struct(field_map, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Dimmer {
+        level: u8,
+        ramp: Option<u16>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(field_map, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Fixture {
+        name: String,
+        #[delta_struct(field_type = "delta")]
+        dimmer: Dimmer,
+        #[delta_struct(field_type = "delta")]
+        backup: Option<Dimmer>,
+        #[delta_struct(field_type = "unordered")]
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn field_maps_use_dotted_paths() {
+        let old = Fixture {
+            name: "porch".into(),
+            dimmer: Dimmer {
+                level: 10,
+                ramp: None,
+            },
+            backup: None,
+            tags: vec!["outside".into()],
+        };
+        let new = Fixture {
+            dimmer: Dimmer {
+                level: 60,
+                ramp: Some(500),
+            },
+            backup: Some(Dimmer {
+                level: 1,
+                ramp: None,
+            }),
+            ..old.clone()
+        };
+        let map = Fixture::delta(old.clone(), new.clone())
+            .unwrap()
+            .to_field_map();
+        let expected: HashMap<String, Value> = vec![
+            ("dimmer.level".to_string(), json!(60)),
+            ("dimmer.ramp".to_string(), json!(500)),
+            ("backup".to_string(), json!({ "level": 1, "ramp": null })),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(map, expected);
+        let mut applied = old;
+        applied.apply_delta(FixtureDelta::from_field_map(map).unwrap());
+        assert_eq!(applied, new);
+
+        let unknown = vec![("dimmer.colour".to_string(), json!("red"))];
+        let error = FixtureDelta::from_field_map(unknown.into_iter().collect()).unwrap_err();
+        assert_eq!(error.to_string(), "field `dimmer.colour`: unknown field");
+    }
+
+    /// A newer version of `Siren`, with a field `Siren` doesn't know.
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(serde)]
+    struct SirenV2 {
+        volume: u8,
+        tone: String,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(unknown_fields, json, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Siren {
+        volume: u8,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+    }
+
+    #[test]
+    fn unknown_fields_are_kept_and_forwarded() {
+        let old = SirenV2 {
+            volume: 5,
+            tone: "chime".into(),
+            zones: vec![1],
+        };
+        let new = SirenV2 {
+            volume: 9,
+            tone: "alarm".into(),
+            ..old.clone()
+        };
+        let sent = serde_json::to_value(SirenV2::delta(old, new).unwrap()).unwrap();
+        let delta: SirenDelta = serde_json::from_value(sent.clone()).unwrap();
+        assert_eq!(delta.volume, Some(9));
+        assert_eq!(delta.unknown_fields["tone"], json!("alarm"));
+        assert_eq!(serde_json::to_value(&delta).unwrap(), sent);
+        let mut siren = Siren {
+            volume: 5,
+            zones: vec![1],
+        };
+        siren.apply_delta(delta);
+        assert_eq!(siren.volume, 9);
+
+        // Members an older peer doesn't send are unchanged.
+        let delta: SirenV2Delta = serde_json::from_value(json!({ "volume": 3 })).unwrap();
+        assert_eq!(
+            (delta.volume, delta.tone, delta.zones_add),
+            (Some(3), None, vec![])
+        );
+
+        let delta = SirenDelta::try_from(json!({
+            "volume": 1,
+            "zones_add": [],
+            "zones_remove": [],
+            "strobe": true,
+        }))
+        .unwrap();
+        assert_eq!(delta.unknown_fields["strobe"], json!(true));
+        assert_eq!(Value::from(delta)["strobe"], json!(true));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Outlet {
+        name: String,
+        #[delta_struct(field_type = "json")]
+        attributes: Value,
+        #[delta_struct(field_type = "json", arrays = "splice")]
+        readings: Value,
+    }
+
+    #[test]
+    fn json_fields_are_diffed_structurally() {
+        use crate::value::ValueDelta;
+
+        let old = Outlet {
+            name: "desk".into(),
+            attributes: json!({ "watts": 40, "schedule": { "on": "08:00" } }),
+            readings: json!([1, 2, 3]),
+        };
+        let new = Outlet {
+            // Keys in sorted order, so the changes come out in the same order
+            // when serde_json preserves insertion order.
+            attributes: json!({ "eco": true, "schedule": { "on": "07:30" }, "watts": 40 }),
+            readings: json!([1, 2, 3, 4]),
+            ..old.clone()
+        };
+        let delta = Outlet::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta.attributes,
+            Some(ValueDelta::Object {
+                remove: vec![],
+                change: vec![
+                    ("eco".into(), ValueDelta::Replace(json!(true))),
+                    (
+                        "schedule".into(),
+                        ValueDelta::Object {
+                            remove: vec![],
+                            change: vec![("on".into(), ValueDelta::Replace(json!("07:30")))],
+                        }
+                    ),
+                ],
+            })
+        );
+        assert_eq!(
+            delta.readings,
+            Some(ValueDelta::Array(vec![Splice {
+                start: 3,
+                delete_count: 0,
+                insert: vec![json!(4)],
+            }]))
+        );
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(size_report)]
+    struct Camera {
+        name: String,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<String>,
+        snapshot: Vec<u8>,
+    }
+
+    #[test]
+    fn size_report_breaks_down_fields() {
+        let old = Camera {
+            name: "porch".into(),
+            zones: vec!["steps".into()],
+            snapshot: vec![],
+        };
+        let new = Camera {
+            zones: vec!["drive".into()],
+            snapshot: vec![255; 10],
+            ..old.clone()
+        };
+        let delta = Camera::delta(old, new).unwrap();
+        let report = delta.size_report();
+        assert_eq!(
+            report.fields,
+            vec![("name", 4), ("zones", 9 + 9), ("snapshot", 41)]
+        );
+        assert_eq!(report.total, serde_json::to_vec(&delta).unwrap().len());
+        assert_eq!(report.largest()[0], ("snapshot", 41));
+        assert!(report
+            .to_string()
+            .ends_with("bytes\n  snapshot: 41 bytes\n  zones: 18 bytes\n  name: 4 bytes"));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize, Deserialize)]
+    #[delta_struct(apply_json)]
+    struct Lock {
+        name: String,
+        locked: bool,
+        #[delta_struct(field_type = "unordered")]
+        codes: Vec<u16>,
+    }
+
+    #[test]
+    fn deltas_apply_to_untyped_documents() {
+        let old = Lock {
+            name: "front".into(),
+            locked: false,
+            codes: vec![1234, 4321],
+        };
+        let new = Lock {
+            locked: true,
+            codes: vec![4321, 1111],
+            ..old.clone()
+        };
+        let delta = Lock::delta(old, new).unwrap();
+        let mut document = json!({
+            "name": "front",
+            "locked": false,
+            "codes": [1234, 4321],
+            "firmware": "2.1",
+        });
+        delta.apply_to_json(&mut document).unwrap();
+        assert_eq!(
+            document,
+            json!({
+                "name": "front",
+                "locked": true,
+                "codes": [4321, 1111],
+                "firmware": "2.1",
+            })
+        );
+        let mut invalid = json!({ "name": "front" });
+        let error = delta.apply_to_json(&mut invalid).unwrap_err();
+        assert!(matches!(error, crate::Error::Decode(_)));
+        assert_eq!(invalid, json!({ "name": "front" }));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(serde)]
+    struct Fader {
+        ramp: Option<u8>,
+        level: u8,
+    }
+
+    #[test]
+    fn cleared_options_survive_serialization() {
+        let old = Fader {
+            ramp: Some(5),
+            level: 1,
+        };
+        let new = Fader {
+            ramp: None,
+            ..old.clone()
+        };
+        let sent = serde_json::to_value(Fader::delta(old.clone(), new.clone()).unwrap()).unwrap();
+        assert_eq!(sent, json!({ "ramp": null, "level": null }));
+        let mut applied = old.clone();
+        applied.apply_delta(serde_json::from_value::<FaderDelta>(sent).unwrap());
+        assert_eq!(applied, new);
+
+        let level = Fader {
+            level: 2,
+            ..old.clone()
+        };
+        let sent = serde_json::to_value(Fader::delta(old.clone(), level.clone()).unwrap()).unwrap();
+        assert_eq!(sent, json!({ "level": 2 }));
+        let mut applied = old;
+        applied.apply_delta(serde_json::from_value::<FaderDelta>(sent).unwrap());
+        assert_eq!(applied, level);
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(json, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Chime {
+        ramp: Option<u8>,
+        tone: String,
+    }
+
+    #[test]
+    fn values_clear_option_fields() {
+        let old = Chime {
+            ramp: Some(5),
+            tone: "ding".into(),
+        };
+        let new = Chime {
+            ramp: None,
+            ..old.clone()
+        };
+        let value = Value::from(Chime::delta(old.clone(), new.clone()).unwrap());
+        assert_eq!(value, json!({ "ramp": null, "tone": null }));
+        let delta = ChimeDelta::try_from(value).unwrap();
+        assert_eq!(delta.ramp, Some(None));
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let delta = ChimeDelta::try_from(json!({ "tone": "dong" })).unwrap();
+        assert_eq!(delta.ramp, None);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied.ramp, Some(5));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(field_map)]
+    struct Spotlight {
+        ramp: Option<u8>,
+        tags: Vec<String>,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+    }
+
+    #[test]
+    fn field_maps_keep_cleared_and_emptied_values() {
+        let old = Spotlight {
+            ramp: Some(5),
+            tags: vec!["x".into()],
+            zones: vec![1],
+        };
+        let new = Spotlight {
+            ramp: None,
+            tags: vec![],
+            ..old.clone()
+        };
+        let map = Spotlight::delta(old.clone(), new.clone())
+            .unwrap()
+            .to_field_map();
+        let expected: HashMap<String, Value> = vec![
+            ("ramp".to_string(), json!(null)),
+            ("tags".to_string(), json!([])),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(map, expected);
+        let mut applied = old;
+        applied.apply_delta(SpotlightDelta::from_field_map(map).unwrap());
+        assert_eq!(applied, new);
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Radio {
+        retries: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize, Deserialize)]
+    #[delta_struct(apply_json)]
+    struct Keypad {
+        label: String,
+        level: f64,
+        config: Radio,
+    }
+
+    #[test]
+    fn untyped_documents_keep_unchanged_fields_as_they_were() {
+        let old = Keypad {
+            label: "hall".into(),
+            level: 1.0,
+            config: Radio { retries: 3 },
+        };
+        let new = Keypad {
+            label: "stairs".into(),
+            ..old.clone()
+        };
+        let mut document = json!({
+            "label": "hall",
+            "level": 1,
+            "config": { "retries": 3, "vendor": "acme" },
+        });
+        Keypad::delta(old, new)
+            .unwrap()
+            .apply_to_json(&mut document)
+            .unwrap();
+        assert_eq!(
+            document.to_string(),
+            json!({
+                "label": "stairs",
+                "level": 1,
+                "config": { "retries": 3, "vendor": "acme" },
+            })
+            .to_string()
+        );
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    #[delta_struct(apply_json)]
+    struct Sconce {
+        set_point: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fade_ms: Option<u16>,
+        room_name: String,
+    }
+
+    #[test]
+    fn untyped_documents_use_the_serialized_field_names() {
+        let old = Sconce {
+            set_point: 10,
+            fade_ms: Some(250),
+            room_name: "den".into(),
+        };
+        let new = Sconce {
+            set_point: 80,
+            fade_ms: None,
+            ..old.clone()
+        };
+        let mut document = json!({
+            "setPoint": 10,
+            "fadeMs": 250,
+            "roomName": "den",
+            "zone": 4,
+        });
+        Sconce::delta(old, new)
+            .unwrap()
+            .apply_to_json(&mut document)
+            .unwrap();
+        assert_eq!(
+            document,
+            json!({
+                "setPoint": 80,
+                "roomName": "den",
+                "zone": 4,
+            })
+        );
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(serde)]
+    enum Power {
+        Mains { volts: u16 },
+        Battery(u8, #[delta_struct(field_type = "unordered")] Vec<String>),
+    }
+
+    #[test]
+    fn enum_deltas_round_trip_through_json() {
+        let battery = |level, alerts: &[&str]| {
+            Power::Battery(level, alerts.iter().map(|&alert| alert.into()).collect())
+        };
+        for (old, new) in [
+            (battery(90, &[]), battery(80, &["low"])),
+            (Power::Mains { volts: 120 }, battery(80, &[])),
+        ] {
+            let delta = Power::delta(old.clone(), new.clone()).unwrap();
+            let json = serde_json::to_string(&delta).unwrap();
+            let mut applied = old;
+            applied.apply_delta(serde_json::from_str(&json).unwrap());
+            assert_eq!(applied, new);
+        }
+    }
+}
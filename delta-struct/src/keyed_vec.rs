@@ -0,0 +1,357 @@
+//! Child-level patching of collections whose elements are identified by a
+//! key, for `field_type = "keyed"` fields. Unlike keyed `ordered` fields,
+//! the position of elements isn't tracked.
+
+use crate::{ComputeDelta, Delta};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+
+/// The delta of a collection of `T`s keyed by `K`. Applying it removes the
+/// elements with the `remove` keys, patches the elements with the `patch`
+/// keys, and then adds the `add` elements, replacing any element with the
+/// same key in place.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: serde::Serialize, T: serde::Serialize, T::Output: serde::Serialize",
+        deserialize = "K: serde::Deserialize<'de>, T: serde::Deserialize<'de>, \
+                       T::Output: serde::Deserialize<'de>"
+    ))
+)]
+pub struct KeyedVecDelta<K, T: Delta> {
+    pub remove: Vec<K>,
+    pub patch: Vec<(K, T::Output)>,
+    pub add: Vec<T>,
+}
+
+impl<K, T: Delta> KeyedVecDelta<K, T> {
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.patch.is_empty() && self.add.is_empty()
+    }
+}
+
+impl<K: Eq + Hash, T: Delta> KeyedVecDelta<K, T> {
+    /// Computes the delta turning `old` into `new`, matching elements by
+    /// `key`. Keys are expected to be unique within each collection.
+    pub fn diff<F>(old: Vec<T>, new: Vec<T>, key: F) -> Self
+    where
+        F: Fn(&T) -> K,
+    {
+        let index: HashMap<K, usize> = old
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (key(item), i))
+            .collect();
+        let mut old: Vec<Option<T>> = old.into_iter().map(Some).collect();
+        let mut delta = KeyedVecDelta::default();
+        for item in new {
+            let item_key = key(&item);
+            match index.get(&item_key).and_then(|&i| old[i].take()) {
+                Some(previous) => {
+                    if let Some(patch) = T::delta(previous, item) {
+                        delta.patch.push((item_key, patch));
+                    }
+                }
+                None => delta.add.push(item),
+            }
+        }
+        delta.remove = old.into_iter().flatten().map(|item| key(&item)).collect();
+        delta
+    }
+
+    /// Applies the delta to `target`, patching the elements with the
+    /// `patch` keys where they are instead of taking them out.
+    pub fn apply<C, F>(self, target: &mut C, key: F)
+    where
+        C: KeyedCollection<T>,
+        F: Fn(&T) -> K,
+    {
+        if !self.remove.is_empty() {
+            let removed: HashSet<K> = self.remove.into_iter().collect();
+            target.retain_items(|item| !removed.contains(&key(item)));
+        }
+        if !self.patch.is_empty() {
+            let mut patches: HashMap<K, T::Output> = self.patch.into_iter().collect();
+            target.patch_items(|item| {
+                if let Some(patch) = patches.remove(&key(item)) {
+                    item.apply_delta(patch);
+                }
+            });
+        }
+        for item in self.add {
+            let item_key = key(&item);
+            target.upsert_item(item, |existing| key(existing) == item_key);
+        }
+    }
+
+    /// Combines two consecutive deltas into one, such that applying the
+    /// result is equivalent to applying `first` and then `second`.
+    pub fn compose<F>(first: Self, second: Self, key: F) -> Self
+    where
+        F: Fn(&T) -> K,
+    {
+        let KeyedVecDelta {
+            mut remove,
+            mut patch,
+            mut add,
+        } = first;
+        for removed in second.remove {
+            patch.retain(|(k, _)| *k != removed);
+            add.retain(|item| key(item) != removed);
+            // `first` may have added it in place of an existing element.
+            if !remove.contains(&removed) {
+                remove.push(removed);
+            }
+        }
+        for (patched, delta) in second.patch {
+            if let Some(item) = add.iter_mut().find(|item| key(item) == patched) {
+                item.apply_delta(delta);
+            } else if let Some(index) = patch.iter().position(|(k, _)| *k == patched) {
+                let (k, previous) = patch.remove(index);
+                patch.insert(index, (k, T::compose_delta(previous, delta)));
+            } else {
+                patch.push((patched, delta));
+            }
+        }
+        for item in second.add {
+            let item_key = key(&item);
+            patch.retain(|(k, _)| *k != item_key);
+            match add.iter_mut().find(|existing| key(existing) == item_key) {
+                Some(existing) => *existing = item,
+                None => add.push(item),
+            }
+        }
+        KeyedVecDelta { remove, patch, add }
+    }
+}
+
+/// The collections of `keyed` fields, which [`KeyedVecDelta::apply`]
+/// updates in place.
+pub trait KeyedCollection<T> {
+    fn retain_items<F: FnMut(&T) -> bool>(&mut self, keep: F);
+
+    /// Calls `patch` on every element, which may modify it in place.
+    fn patch_items<F: FnMut(&mut T)>(&mut self, patch: F);
+
+    /// Replaces the element `matches` accepts with `item`, or adds `item`
+    /// if there is none.
+    fn upsert_item<F: FnMut(&T) -> bool>(&mut self, item: T, matches: F);
+}
+
+impl<T> KeyedCollection<T> for Vec<T> {
+    fn retain_items<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+        self.retain(keep);
+    }
+
+    fn patch_items<F: FnMut(&mut T)>(&mut self, patch: F) {
+        self.iter_mut().for_each(patch);
+    }
+
+    fn upsert_item<F: FnMut(&T) -> bool>(&mut self, item: T, mut matches: F) {
+        match self.iter_mut().find(|existing| matches(existing)) {
+            Some(existing) => *existing = item,
+            None => self.push(item),
+        }
+    }
+}
+
+impl<T> KeyedCollection<T> for VecDeque<T> {
+    fn retain_items<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+        self.retain(keep);
+    }
+
+    fn patch_items<F: FnMut(&mut T)>(&mut self, patch: F) {
+        self.iter_mut().for_each(patch);
+    }
+
+    fn upsert_item<F: FnMut(&T) -> bool>(&mut self, item: T, mut matches: F) {
+        match self.iter_mut().find(|existing| matches(existing)) {
+            Some(existing) => *existing = item,
+            None => self.push_back(item),
+        }
+    }
+}
+
+/// The elements of sets can't be modified where they are, so patching takes
+/// them all out and inserts them again.
+macro_rules! impl_keyed_set {
+    ($set:ident, $($bound:tt)+) => {
+        impl<T: $($bound)+> KeyedCollection<T> for $set<T> {
+            fn retain_items<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+                self.retain(keep);
+            }
+
+            fn patch_items<F: FnMut(&mut T)>(&mut self, mut patch: F) {
+                let items = std::mem::take(self);
+                self.extend(items.into_iter().map(|mut item| {
+                    patch(&mut item);
+                    item
+                }));
+            }
+
+            fn upsert_item<F: FnMut(&T) -> bool>(&mut self, item: T, mut matches: F) {
+                self.retain(|existing| !matches(existing));
+                self.insert(item);
+            }
+        }
+    };
+}
+
+impl_keyed_set!(HashSet, Hash + Eq);
+impl_keyed_set!(BTreeSet, Ord);
+
+/// Whether `old` and `new` hold elements with the same keys in the same
+/// order, none of which changed, which an unchanged collection usually
+/// does. Checked without allocating, so keyed diffs only allocate once
+/// something changed. `same_key` compares the keys of two elements.
+pub fn unchanged_in_order<'a, C, T, F>(old: &'a C, new: &'a C, same_key: F) -> bool
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: ComputeDelta + 'a,
+    F: Fn(&T, &T) -> bool,
+{
+    let mut old = old.into_iter();
+    let mut new = new.into_iter();
+    loop {
+        match (old.next(), new.next()) {
+            (Some(a), Some(b)) if same_key(a, b) && !T::has_changes(a, b) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Counts the changes [`KeyedVecDelta::diff`] would produce: one per added
+/// or removed element, and the nested count for patched elements.
+pub fn change_count<'a, C, T, K, F>(old: &'a C, new: &'a C, key: F, limit: usize) -> usize
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Delta + 'a,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let mut old: HashMap<K, &T> = old.into_iter().map(|item| (key(item), item)).collect();
+    let mut count = 0;
+    for item in new {
+        if count > limit {
+            return count;
+        }
+        count += match old.remove(&key(item)) {
+            Some(previous) => T::change_count(previous, item, limit - count),
+            None => 1,
+        };
+    }
+    count + old.len()
+}
+
+impl<K: Clone, T: Delta> Clone for KeyedVecDelta<K, T>
+where
+    T: Clone,
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        KeyedVecDelta {
+            remove: self.remove.clone(),
+            patch: self.patch.clone(),
+            add: self.add.clone(),
+        }
+    }
+}
+
+impl<K, T: Delta> Default for KeyedVecDelta<K, T> {
+    fn default() -> Self {
+        KeyedVecDelta {
+            remove: vec![],
+            patch: vec![],
+            add: vec![],
+        }
+    }
+}
+
+impl<K: fmt::Debug, T: Delta> fmt::Debug for KeyedVecDelta<K, T>
+where
+    T: fmt::Debug,
+    T::Output: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedVecDelta")
+            .field("remove", &self.remove)
+            .field("patch", &self.patch)
+            .field("add", &self.add)
+            .finish()
+    }
+}
+
+impl<K: PartialEq, T: Delta> PartialEq for KeyedVecDelta<K, T>
+where
+    T: PartialEq,
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.remove == other.remove && self.patch == other.patch && self.add == other.add
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, T: Delta> arbitrary::Arbitrary<'a> for KeyedVecDelta<K, T>
+where
+    K: arbitrary::Arbitrary<'a>,
+    T: arbitrary::Arbitrary<'a>,
+    T::Output: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(KeyedVecDelta {
+            remove: u.arbitrary()?,
+            patch: u.arbitrary()?,
+            add: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Zone {
+        id: u8,
+        armed: bool,
+    }
+
+    fn zone(id: u8, armed: bool) -> Zone {
+        Zone { id, armed }
+    }
+
+    fn diff(old: &[Zone], new: &[Zone]) -> KeyedVecDelta<u8, Zone> {
+        KeyedVecDelta::diff(old.to_vec(), new.to_vec(), |zone| zone.id)
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let a = vec![zone(1, false), zone(2, false), zone(3, false)];
+        let b = vec![zone(1, true), zone(3, false), zone(4, false)];
+        let c = vec![zone(3, true), zone(4, true), zone(2, false)];
+        let mut sequential = a.clone();
+        diff(&a, &b).apply(&mut sequential, |zone| zone.id);
+        diff(&b, &c).apply(&mut sequential, |zone| zone.id);
+        let mut composed = a.clone();
+        KeyedVecDelta::compose(diff(&a, &b), diff(&b, &c), |zone| zone.id)
+            .apply(&mut composed, |zone| zone.id);
+        assert_eq!(composed, sequential);
+        assert_eq!(composed, vec![zone(3, true), zone(4, true), zone(2, false)]);
+    }
+
+    #[test]
+    fn reordering_is_not_a_change() {
+        let old = vec![zone(1, false), zone(2, false)];
+        let new = vec![zone(2, false), zone(1, false)];
+        assert!(diff(&old, &new).is_empty());
+        assert_eq!(
+            change_count(&old, &new, |zone: &Zone| zone.id, usize::MAX),
+            0
+        );
+    }
+}
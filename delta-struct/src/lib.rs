@@ -1,23 +1,317 @@
+// Lets generated code refer to this crate as `::delta_struct` from within.
+extern crate self as delta_struct;
+
+use std::future::Future;
+
+pub use base::best_base;
+pub use batch::DeltaBatch;
+#[cfg(feature = "tokio")]
+pub use debounce::AsyncDebouncer;
+pub use debounce::{Debouncer, PrioritizedDebounce};
 pub use delta_struct_macros::Delta;
+pub use error::Error;
+pub use fingerprint::StableHasher;
+pub use granular::{Granular, Granularity};
+#[cfg(feature = "serde_json")]
+pub use json::DeltaSizeReport;
+pub use keyed_vec::KeyedVecDelta;
+pub use observer::set_delta_observer;
+pub use option::OptionDelta;
+pub use ordered::{KeyedOp, Splice};
+pub use output::DeltaOutput;
+#[doc(hidden)]
+pub use output::Sealed;
+#[cfg(feature = "tokio")]
+pub use publish::{DeltaPublisher, DeltaSubscriber};
+pub use set_map::SetMapDelta;
+pub use size::MaxEncodedSize;
+pub use strategy::DiffStrategy;
+pub use sync::{reconcile, reconcile_with, ConflictPolicy, PreferFirst, PreferSecond};
+#[doc(hidden)]
+pub use throttle::FloatHash;
+pub use throttle::{ContentHash, DeltaThrottle};
+pub use vec::VecDelta;
+
+// Used by generated code to name serde's traits.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
+
+// Used by generated code to name `Arbitrary`.
+#[cfg(feature = "arbitrary")]
+#[doc(hidden)]
+pub use arbitrary;
 
-pub trait Delta {
+pub mod arena;
+pub mod atomic;
+mod base;
+mod batch;
+pub mod bitset;
+#[cfg(any(feature = "zstd", feature = "flate2"))]
+pub mod compress;
+mod debounce;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fingerprint;
+pub mod float;
+mod foreign;
+pub mod granular;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod keyed_vec;
+pub mod lock;
+#[cfg(feature = "mongodb")]
+pub mod mongo;
+pub mod observer;
+pub mod once;
+pub mod option;
+pub mod ordered;
+mod output;
+pub mod path_ids;
+#[cfg(feature = "im")]
+pub mod persistent;
+mod pointer;
+pub mod project;
+#[cfg(feature = "tokio")]
+mod publish;
+pub mod schema;
+pub mod set_map;
+mod size;
+pub mod sorted;
+pub mod split;
+#[cfg(feature = "sql")]
+pub mod sql;
+#[cfg(feature = "serde_json")]
+pub mod store;
+pub mod strategy;
+mod sync;
+#[cfg(feature = "serde")]
+pub mod tagged;
+pub mod testing;
+mod throttle;
+pub mod time;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod unordered;
+#[cfg(feature = "serde_json")]
+pub mod value;
+pub mod vec;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// A type that can be diffed into deltas and patched by them. Implemented
+/// for every type implementing both [`ComputeDelta`] and [`ApplyDelta`] of
+/// its own deltas, which is what `#[derive(Delta)]` generates.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `Delta`",
+    label = "used as a nested delta here",
+    note = "derive `Delta` for it, implement it with `impl_delta_scalar!`, \
+            or give the field a different `field_type`"
+)]
+pub trait Delta: ComputeDelta + ApplyDelta<<Self as ComputeDelta>::Output> {}
+
+impl<T> Delta for T where T: ComputeDelta + ApplyDelta<<T as ComputeDelta>::Output> + ?Sized {}
+
+/// The computing half of [`Delta`]. Types that only ever produce deltas,
+/// such as read-only publishers, can derive it alone with
+/// `#[delta_struct(compute_only)]`, which generates no applying code, so
+/// field types only need to support diffing.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `ComputeDelta`",
+    note = "derive `Delta` for it, or implement it with `impl_delta_scalar!`"
+)]
+pub trait ComputeDelta {
     type Output;
 
     fn delta(old: Self, new: Self) -> Option<Self::Output>;
 
-    fn apply_delta(&mut self, delta: Self::Output);
+    /// Counts the individual changes between `old` and `new`: one per
+    /// changed scalar, one per added or removed collection element, and
+    /// the nested count for delta fields. Implementations may stop counting
+    /// once the count exceeds `limit`.
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize;
+
+    /// Whether `old` and `new` differ at all, stopping at the first
+    /// difference. Derived for structs with only `scalar` and `float`
+    /// fields as a direct comparison of the fields, whose deltas also hold
+    /// no heap allocations of their own.
+    fn has_changes(old: &Self, new: &Self) -> bool {
+        Self::change_count(old, new, 0) > 0
+    }
+
+    /// Computes the delta between `old` and `new`, unless it would contain
+    /// more than `max_changes` changes, in which case `new` is returned for
+    /// a full replacement. Diffing stops as soon as the budget is exceeded.
+    fn delta_bounded(old: Self, new: Self, max_changes: usize) -> DeltaOrFull<Self>
+    where
+        Self: Sized,
+    {
+        if Self::change_count(&old, &new, max_changes) > max_changes {
+            return DeltaOrFull::Full(new);
+        }
+        match Self::delta(old, new) {
+            Some(delta) => DeltaOrFull::Delta(delta),
+            None => DeltaOrFull::Unchanged,
+        }
+    }
+
+    /// Expresses `new` as its changes from `Self::default()`, or `None` if
+    /// it is the default.
+    fn delta_from_default(new: Self) -> Option<Self::Output>
+    where
+        Self: Default + Sized,
+    {
+        Self::delta(Self::default(), new)
+    }
+}
+
+/// The applying half of [`Delta`], patching a value with deltas of type `D`.
+/// Usually `D` is the type's own [`ComputeDelta::Output`], but a different
+/// representation of the same data, such as a view model, can implement it
+/// for the deltas of the type they are computed from.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't apply deltas of type `{D}`",
+    note = "derive `Delta` for it, or implement `ApplyDelta` by hand"
+)]
+pub trait ApplyDelta<D> {
+    fn apply_delta(&mut self, delta: D);
+
+    /// Combines two consecutive deltas into one, such that applying the
+    /// result is equivalent to applying `first` and then `second`.
+    fn compose_delta(first: D, second: D) -> D;
+
+    /// Applies a borrowed `delta`, so the same delta can be applied to many
+    /// values.
+    fn apply_delta_ref(&mut self, delta: &D)
+    where
+        D: Clone,
+    {
+        self.apply_delta(delta.clone());
+    }
+
+    /// Returns the value `self` would have after applying `delta`, leaving
+    /// `self` untouched.
+    fn preview(&self, delta: &D) -> Self
+    where
+        Self: Clone,
+        D: Clone,
+    {
+        let mut preview = self.clone();
+        preview.apply_delta_ref(delta);
+        preview
+    }
+}
+
+/// Used by generated code to report a missing `Delta` impl at the field
+/// that needs it.
+#[doc(hidden)]
+pub fn assert_delta<T: Delta + ?Sized>() {}
+
+/// Like [`assert_delta`], for the fields of `compute_only` structs.
+#[doc(hidden)]
+pub fn assert_compute_delta<T: ComputeDelta + ?Sized>() {}
+
+/// A `Delta` whose diffing and applying can consult a context, such as
+/// tolerances or permissions. Derived with `#[delta_struct(context = "Ctx")]`;
+/// scalar fields then accept `eq_with = "path"`, a `fn(&T, &T, &Ctx) -> bool`
+/// used instead of `PartialEq`, and `apply_with = "path"`, a
+/// `fn(&mut T, T, &Ctx)` used instead of assignment.
+///
+/// Any field may also have a `guard = "path"`, a
+/// `fn(&'static str, &Change, &Ctx) -> bool` given the field's name and
+/// change, which can reject the change in the generated
+/// `try_apply_delta_with`. The change is what the delta holds for the
+/// field: the new value of a scalar, the nested delta of a delta field, and
+/// `(added, removed)` slices for an unordered field.
+pub trait DeltaWith<Ctx: ?Sized>: Delta {
+    fn delta_with(old: Self, new: Self, ctx: &Ctx) -> Option<Self::Output>;
+
+    fn apply_delta_with(&mut self, delta: Self::Output, ctx: &Ctx);
+}
+
+/// A `Delta` that can be applied asynchronously, e.g. to persist each change
+/// as it is made. Derived with `#[delta_struct(async_apply)]`; fields then
+/// accept `apply_async = "path"`, a `fn(&T) -> impl Future<Output = ()>`
+/// awaited with the updated value after a change to the field is applied.
+pub trait AsyncApplyDelta: Delta {
+    fn apply_delta_async(&mut self, delta: Self::Output) -> impl Future<Output = ()>;
+}
+
+/// The result of [`ComputeDelta::delta_bounded`].
+pub enum DeltaOrFull<T: ComputeDelta> {
+    Unchanged,
+    Delta(T::Output),
+    /// The delta exceeded its budget; this is the new value in full.
+    Full(T),
+}
+
+/// Implements `Delta` for types compared by `PartialEq` and replaced
+/// wholesale, so they can be used as `field_type = "delta"` fields. Useful
+/// for enums that are only ever replaced, and for types the derive doesn't
+/// support.
+///
+/// The orphan rule still applies: a third party type has to be wrapped in a
+/// local newtype first.
+///
+/// ```
+/// #[derive(Clone, Debug, PartialEq)]
+/// enum Mode {
+///     Heat,
+///     Cool,
+/// }
+///
+/// delta_struct::impl_delta_scalar!(Mode);
+/// ```
+#[macro_export]
+macro_rules! impl_delta_scalar {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl $crate::ComputeDelta for $ty {
+                type Output = Self;
+
+                fn delta(old: Self, new: Self) -> ::std::option::Option<Self> {
+                    if old != new {
+                        ::std::option::Option::Some(new)
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+
+                fn change_count(old: &Self, new: &Self, _limit: usize) -> usize {
+                    (old != new) as usize
+                }
+            }
+
+            impl $crate::ApplyDelta<Self> for $ty {
+                fn apply_delta(&mut self, delta: Self) {
+                    *self = delta;
+                }
+
+                fn compose_delta(_first: Self, second: Self) -> Self {
+                    second
+                }
+            }
+        )+
+    };
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[allow(dead_code)]
     #[derive(Delta)]
     struct UnitType;
 
     #[derive(Delta, Clone, Debug, PartialEq, Eq)]
-    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq, Eq)]")]
+    #[delta_struct(
+        delta_leader = "#[derive(Clone, Debug, PartialEq, Eq)]",
+        change_leader = "#[derive(Debug, PartialEq)]"
+    )]
     struct NewType(i32);
 
+    #[allow(dead_code)]
     #[derive(Delta)]
     struct NewTypeWithGeneric<T>(T);
 
@@ -28,12 +322,23 @@ mod tests {
         bar: bool,
     }
 
+    #[allow(dead_code)]
     #[derive(Delta)]
     struct SimpleTypeWithGeneric<T> {
         foo: T,
         bar: bool,
     }
 
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct DefaultTypeParameter<T = u32>
+    where
+        T: Copy,
+    {
+        value: T,
+        #[delta_struct(field_type = "unordered")]
+        values: Vec<T>,
+    }
+
     #[derive(Delta)]
     struct SimpleCollectionWithGeneric<T> {
         #[delta_struct(
@@ -44,6 +349,207 @@ mod tests {
         bar: bool,
     }
 
+    struct Locale {
+        case_insensitive: bool,
+        max_volume: u8,
+    }
+
+    fn names_eq(a: &String, b: &String, locale: &Locale) -> bool {
+        if locale.case_insensitive {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    }
+
+    fn clamp_volume(volume: &mut u8, new: u8, locale: &Locale) {
+        *volume = new.min(locale.max_volume);
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(context = "Locale")]
+    struct Speaker {
+        #[delta_struct(eq_with = "names_eq")]
+        name: String,
+        #[delta_struct(apply_with = "clamp_volume")]
+        volume: u8,
+    }
+
+    struct Role {
+        admin: bool,
+    }
+
+    fn admin_only<C: ?Sized>(_field: &'static str, _change: &C, role: &Role) -> bool {
+        role.admin
+    }
+
+    fn keep_pins(_field: &'static str, change: &(&[u16], &[u16]), role: &Role) -> bool {
+        let (_, removed) = change;
+        role.admin || removed.is_empty()
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(context = "Role")]
+    struct Alarm {
+        label: String,
+        #[delta_struct(guard = "admin_only")]
+        armed: bool,
+        #[delta_struct(field_type = "unordered", guard = "keep_pins")]
+        pins: Vec<u16>,
+    }
+
+    thread_local! {
+        static PERSISTED: std::cell::RefCell<Vec<String>> = Default::default();
+    }
+
+    async fn persist_name(name: &str) {
+        PERSISTED.with(|persisted| persisted.borrow_mut().push(name.to_owned()));
+    }
+
+    async fn persist_volume(volume: &u8) {
+        PERSISTED.with(|persisted| persisted.borrow_mut().push(volume.to_string()));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(async_apply)]
+    struct Thermostat {
+        #[delta_struct(apply_async = "persist_name")]
+        name: String,
+        #[delta_struct(apply_async = "persist_volume")]
+        volume: u8,
+        muted: bool,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(async_apply)]
+    struct Valve {
+        #[delta_struct(apply_async = "persist_name")]
+        name: String,
+        #[delta_struct(apply_async = "persist_volume", apply_order = 2)]
+        flow: u8,
+        #[delta_struct(apply_async = "persist_volume", apply_order = 1)]
+        pressure: u8,
+    }
+
+    struct NoopWaker;
+
+    impl std::task::Wake for NoopWaker {
+        fn wake(self: std::sync::Arc<Self>) {}
+    }
+
+    /// Polls a future that never waits on anything to completion.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = std::task::Waker::from(std::sync::Arc::new(NoopWaker));
+        let mut cx = std::task::Context::from_waker(&waker);
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(with_replace, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Snapshot {
+        level: u8,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+    }
+
+    /// Mirrors `Range`, which can't derive `Delta` here.
+    #[derive(Delta)]
+    #[delta_struct(remote = "std::ops::Range<u32>")]
+    struct RangeDef {
+        start: u32,
+        end: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(strict)]
+    struct Keypad {
+        #[delta_struct(field_type = "unordered")]
+        codes: Vec<u16>,
+        #[delta_struct(field_type = "unordered")]
+        users: Vec<String>,
+        locked: bool,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(changed_flags)]
+    struct Camera {
+        name: String,
+        #[delta_struct(field_type = "unordered")]
+        zones: Vec<u8>,
+        #[delta_struct(field_type = "bitset")]
+        features: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(compact)]
+    struct Reading {
+        temperature: i32,
+        humidity: u32,
+        #[delta_struct(field_type = "float")]
+        pressure: f32,
+        online: bool,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Mode {
+        Heat,
+        Cool,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Label(String);
+
+    impl_delta_scalar!(Mode, Label);
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Hvac {
+        #[delta_struct(field_type = "delta")]
+        mode: Mode,
+        #[delta_struct(field_type = "delta")]
+        label: Option<Label>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Room {
+        #[delta_struct(field_type = "delta", granularity = "coarse")]
+        settings: Settings,
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Schedule {
+        #[delta_struct(field_type = "unordered_scalar")]
+        days: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct GenericNested<T> {
+        #[delta_struct(field_type = "delta")]
+        inner: T,
+        #[delta_struct(field_type = "delta")]
+        maybe: Option<T>,
+        count: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Buffer<const N: usize> {
+        data: [u8; N],
+        len: usize,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(max_size, changed_flags)]
+    struct Frames<T: Copy, const N: usize = 2> {
+        #[delta_struct(field_type = "delta")]
+        buffer: Buffer<N>,
+        samples: [T; N],
+    }
+
     #[derive(Delta)]
     struct DeltaRecursion {
         #[delta_struct(field_type = "delta")]
@@ -62,6 +568,10 @@ mod tests {
     }
 
     #[derive(Delta, Clone, Debug, PartialEq, Eq)]
+    #[delta_struct(
+        delta_leader = "#[derive(Clone)]",
+        change_leader = "#[derive(Debug, PartialEq)]"
+    )]
     struct AllFieldTypes {
         #[delta_struct(field_type = "scalar")]
         scalar: i32,
@@ -72,107 +582,2524 @@ mod tests {
     }
 
     #[derive(Clone, Debug, Delta, PartialEq)]
-    struct DeviceConfig {
-        #[delta_struct(field_type = "unordered")]
-        pub services: Vec<String>,
-        #[delta_struct(field_type = "unordered")]
-        pub settings: Vec<String>,
-        pub thumbnail_request: i32,
-        pub speedtest_request: i32,
+    struct OrderedList {
+        #[delta_struct(field_type = "ordered")]
+        items: Vec<char>,
+        #[delta_struct(field_type = "ordered")]
+        queue: std::collections::VecDeque<u8>,
+    }
+
+    bitflags::bitflags! {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        struct Permissions: u8 {
+            const READ = 1;
+            const WRITE = 2;
+            const ADMIN = 4;
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Flags {
+        #[delta_struct(field_type = "bitset")]
+        raw: u16,
+        #[delta_struct(field_type = "bitset")]
+        permissions: Permissions,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Measurement {
+        #[delta_struct(field_type = "float", epsilon = "0.01")]
+        temperature: f64,
+        #[delta_struct(field_type = "float", ulps = "4")]
+        level: f32,
+    }
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    struct Settings {
+        volume: u8,
+        muted: bool,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct OptionalSettings {
         #[delta_struct(field_type = "delta")]
-        pub features: AllFieldTypes,
-        pub deprovision: bool,
+        dropped: Option<Settings>,
+        #[delta_struct(field_type = "delta", create_if_missing)]
+        created: Option<Settings>,
     }
 
-    #[test]
-    fn unordered_with_scalar() {
-        let old = SimpleCollectionWithGeneric {
-            foo: vec![1, 2, 3],
-            bar: false,
-        };
-        let new = SimpleCollectionWithGeneric {
-            foo: vec![3, 4, 5],
-            bar: true,
-        };
-        let delta = Delta::delta(old, new).unwrap();
-        assert_eq!(delta.foo_add, vec![4, 5]);
-        assert_eq!(delta.foo_remove, vec![1, 2]);
-        assert_eq!(delta.bar, Some(true));
+    #[derive(Delta)]
+    #[delta_struct(max_size)]
+    struct CompactInner(i32);
+
+    #[allow(dead_code)]
+    #[derive(Delta)]
+    #[delta_struct(max_size)]
+    struct Compact {
+        flag: bool,
+        level: u32,
+        #[delta_struct(field_type = "bitset")]
+        mask: u8,
+        #[delta_struct(field_type = "delta")]
+        inner: CompactInner,
     }
 
-    #[test]
-    fn delta_false_positive_check() {
-        let old = NewType(5);
-        let new = NewType(5);
-        let delta = Delta::delta(old, new);
-        assert!(delta.is_none());
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Child {
+        id: u32,
+        label: &'static str,
     }
 
-    #[test]
-    fn scalar_delta_false_positive_check() {
-        let old = SimpleType { foo: 5, bar: false };
-        let new = SimpleType { foo: 5, bar: true };
-        let delta = Delta::delta(old, new).unwrap();
-        assert!(delta.foo.is_none());
-        assert_eq!(delta.bar, Some(true));
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Floorplan<T> {
+        #[delta_struct(field_type = "delta")]
+        rooms: Vec<Box<T>>,
+        #[delta_struct(field_type = "delta")]
+        primary: Option<std::sync::Arc<T>>,
+        #[delta_struct(field_type = "delta")]
+        wings: std::rc::Rc<Vec<Option<Box<T>>>>,
     }
 
-    #[test]
-    fn delta_field() {
-        let old = DeltaRecursion {
-            foo: NewType(5),
-            bar: false,
-        };
-        let new = DeltaRecursion {
-            foo: NewType(6),
-            bar: true,
-        };
-        let delta = Delta::delta(old, new).unwrap();
-        // TODO: Use assert_eq when we build out delta struct
-        // attributes.
-        if let Some(NewTypeDelta { field_0: Some(6) }) = delta.foo {
-            // Do nothing, this is the pass case.
-        } else {
-            panic!();
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Parent {
+        #[delta_struct(field_type = "ordered", key = "id")]
+        children: Vec<Child>,
+    }
+
+    /// A snapshot of sensor readings, which can be iterated but not built
+    /// from an iterator.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Readings(Vec<u16>);
+
+    impl IntoIterator for Readings {
+        type Item = u16;
+        type IntoIter = std::vec::IntoIter<u16>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.into_iter()
         }
-        assert_eq!(delta.bar, Some(true));
     }
 
-    #[test]
-    fn default_type_respected() {
-        let old = AttributeTest {
-            foo: 5,
-            bar: 4,
-            baz: vec![],
-        };
-        let new = AttributeTest {
-            foo: 5,
-            bar: 4,
-            baz: vec![9, 4, 5],
-        };
-        let delta = Delta::delta(old, new).unwrap();
-        assert!(delta.foo.is_none());
-        assert!(delta.bar.is_none());
-        assert_eq!(delta.baz_add, vec![9, 4, 5]);
-        assert_eq!(delta.baz_remove, vec![]);
+    impl<'a> IntoIterator for &'a Readings {
+        type Item = &'a u16;
+        type IntoIter = std::slice::Iter<'a, u16>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.iter()
+        }
     }
 
-    #[test]
-    fn apply_delta_all_field_types() {
-        let old = AllFieldTypes {
-            scalar: 1,
-            delta: NewType(3),
-            unordered: vec![1, 2, 3, 3],
-        };
-        let new = AllFieldTypes {
-            scalar: 2,
-            delta: NewType(4),
-            unordered: vec![3, 4, 5],
-        };
-        let new_clone = new.clone();
-        let mut old_delta_applied = old.clone();
-        let delta = Delta::delta(old, new);
-        old_delta_applied.apply_delta(delta.unwrap());
-        assert_eq!(new_clone, old_delta_applied);
+    #[derive(Delta)]
+    #[delta_struct(compute_only)]
+    struct SensorReport {
+        station: String,
+        #[delta_struct(field_type = "unordered")]
+        readings: Readings,
+    }
+
+    /// How a subscriber displays a `SensorReport`, kept up to date by its
+    /// deltas.
+    #[derive(Debug, PartialEq)]
+    struct SensorDisplay {
+        title: String,
+        reading_count: usize,
+    }
+
+    impl ApplyDelta<SensorReportDelta> for SensorDisplay {
+        fn apply_delta(&mut self, delta: SensorReportDelta) {
+            if let Some(station) = delta.station {
+                self.title = format!("Station {}", station);
+            }
+            self.reading_count += delta.readings_add.len();
+            self.reading_count -= delta.readings_remove.len();
+        }
+
+        fn compose_delta(
+            mut first: SensorReportDelta,
+            second: SensorReportDelta,
+        ) -> SensorReportDelta {
+            first.station = second.station.or(first.station);
+            first.readings_add.extend(second.readings_add);
+            first.readings_remove.extend(second.readings_remove);
+            first
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Household {
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "u32")]
+        members: Vec<Child>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Daycare {
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "u32")]
+        children: std::collections::VecDeque<Child>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(repr = "C, packed")]
+    struct Dimmer {
+        level: u8,
+        on: bool,
+    }
+
+    trait Sensor {
+        type Reading;
+        type Config;
+    }
+
+    /// Deliberately not `PartialEq`, as only its associated types are
+    /// compared.
+    struct Probe;
+
+    impl Sensor for Probe {
+        type Reading = u16;
+        type Config = Child;
+    }
+
+    #[derive(Delta)]
+    struct SensorAdapter<S: Sensor> {
+        reading: S::Reading,
+        #[delta_struct(field_type = "delta")]
+        config: <S as Sensor>::Config,
+        #[delta_struct(field_type = "unordered")]
+        history: Vec<S::Reading>,
+    }
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    struct Drone {
+        #[delta_struct(group = "position")]
+        x: i32,
+        #[delta_struct(group = "position")]
+        y: i32,
+        #[delta_struct(group = "position", field_type = "float", epsilon = "0.5")]
+        z: f32,
+        battery: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Strategies {
+        #[delta_struct(strategy = "strategy::UnorderedStrategy")]
+        zones: Vec<u8>,
+        #[delta_struct(strategy = "strategy::NestedStrategy")]
+        settings: Settings,
+        #[delta_struct(strategy = "strategy::ScalarStrategy")]
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(non_exhaustive)]
+    struct Furnace {
+        setpoint: u8,
+        settings: Settings,
+        #[delta_struct(field_type = "unordered")]
+        schedules: Vec<u8>,
+        #[delta_struct(field_type = "bitset")]
+        modes: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Beacon {
+        #[delta_struct(monotonic = "increase")]
+        last_seen: u64,
+        #[delta_struct(monotonic = "decrease")]
+        min_rssi: i8,
+        name: String,
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[derive(Clone, Debug, Delta, PartialEq, arbitrary::Arbitrary)]
+    #[delta_struct(arbitrary)]
+    struct Chime {
+        tone: u8,
+        #[delta_struct(field_type = "ordered")]
+        melody: Vec<u8>,
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(arbitrary)]
+    struct Doorbell {
+        volume: u8,
+        #[delta_struct(field_type = "unordered")]
+        contacts: Vec<u16>,
+        #[delta_struct(field_type = "delta")]
+        chime: Option<Chime>,
+    }
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    #[delta_struct(from_delta)]
+    struct Contact {
+        name: String,
+        phone: Option<String>,
+        #[delta_struct(field_type = "unordered")]
+        groups: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(
+        project,
+        path_ids,
+        schema,
+        delta_leader = "#[derive(Clone, Debug, PartialEq)]"
+    )]
+    struct Climate {
+        temperature: u8,
+        humidity: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(
+        project,
+        path_ids,
+        schema,
+        delta_leader = "#[derive(Debug, PartialEq)]"
+    )]
+    struct Nursery {
+        #[delta_struct(field_type = "delta")]
+        climate: Climate,
+        lights: bool,
+        #[delta_struct(field_type = "unordered")]
+        occupants: Vec<u8>,
+    }
+
+    /// Compares rendered text, for fields holding `dyn Display`.
+    struct DisplayStrategy;
+
+    impl strategy::DiffStrategy<Box<dyn std::fmt::Display>> for DisplayStrategy {
+        type Delta = Box<dyn std::fmt::Display>;
+
+        fn diff(
+            old: Box<dyn std::fmt::Display>,
+            new: Box<dyn std::fmt::Display>,
+        ) -> Option<Self::Delta> {
+            if old.to_string() != new.to_string() {
+                Some(new)
+            } else {
+                None
+            }
+        }
+
+        fn apply(target: &mut Box<dyn std::fmt::Display>, delta: Self::Delta) {
+            *target = delta;
+        }
+
+        fn change_count(
+            old: &Box<dyn std::fmt::Display>,
+            new: &Box<dyn std::fmt::Display>,
+            _limit: usize,
+        ) -> usize {
+            (old.to_string() != new.to_string()) as usize
+        }
+
+        fn compose(_first: Self::Delta, second: Self::Delta) -> Self::Delta {
+            second
+        }
+    }
+
+    #[derive(Delta)]
+    struct Widget {
+        id: u32,
+        #[delta_struct(skip)]
+        on_click: Box<dyn Fn(u32) -> u32>,
+        #[delta_struct(strategy = "DisplayStrategy")]
+        label: Box<dyn std::fmt::Display>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(
+        exclude(created_at, internal_cache),
+        delta_leader = "#[derive(Debug, PartialEq)]"
+    )]
+    struct Account {
+        name: String,
+        created_at: u64,
+        internal_cache: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(
+        views(public(name, status), admin(all)),
+        delta_leader = "#[derive(Debug, PartialEq)]"
+    )]
+    struct Profile {
+        name: String,
+        status: String,
+        #[delta_struct(field_type = "unordered")]
+        permissions: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(default = "unordered", duplicates = "ignore")]
+    struct Guestlist {
+        invited: Vec<String>,
+        #[delta_struct(duplicates = "keep")]
+        arrivals: Vec<String>,
+    }
+
+    /// Every collection has a `field_type`, so `lint` has nothing to warn
+    /// about.
+    #[allow(dead_code)]
+    #[derive(Clone, Delta)]
+    #[delta_struct(lint)]
+    struct Pantry {
+        #[delta_struct(field_type = "unordered")]
+        shelves: Vec<String>,
+        #[delta_struct(field_type = "scalar")]
+        labels: std::collections::HashMap<u8, String>,
+        #[delta_struct(skip)]
+        scratch: Vec<u8>,
+        count: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_id, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Tally {
+        #[delta_struct(field_type = "unordered")]
+        visitors: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct AccessPolicy {
+        #[delta_struct(field_type = "set_map")]
+        scopes: std::collections::HashMap<String, std::collections::HashSet<String>>,
+        version: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Calendar {
+        #[delta_struct(field_type = "sorted")]
+        holidays: std::collections::BTreeSet<u16>,
+        #[delta_struct(field_type = "sorted")]
+        setpoints: std::collections::BTreeMap<u16, i8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Roster {
+        #[delta_struct(field_type = "unordered", rename_distance = 2)]
+        rooms: Vec<String>,
+        #[delta_struct(field_type = "unordered", rename_distance = 1)]
+        members: std::collections::HashSet<String>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(sort_unordered, default = "unordered")]
+    struct Badges {
+        earned: std::collections::HashSet<u32>,
+        pinned: Vec<u32>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(fingerprint)]
+    struct Porch {
+        chime: u8,
+        #[delta_struct(field_type = "unordered")]
+        contacts: Vec<u32>,
+        /// Replica-local, so it doesn't change the fingerprint.
+        #[delta_struct(skip)]
+        seen: std::time::Instant,
+    }
+
+    /// Counts the allocations made by the current thread.
+    mod allocations {
+        use std::alloc::{GlobalAlloc, Layout, System};
+        use std::cell::Cell;
+
+        thread_local! {
+            static COUNT: Cell<usize> = const { Cell::new(0) };
+        }
+
+        struct Counting;
+
+        unsafe impl GlobalAlloc for Counting {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                let _ = COUNT.try_with(|count| count.set(count.get() + 1));
+                System.alloc(layout)
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                System.dealloc(ptr, layout)
+            }
+        }
+
+        #[global_allocator]
+        static ALLOCATOR: Counting = Counting;
+
+        /// Runs `f`, returning its result and the allocations it made.
+        pub fn counted<R>(f: impl FnOnce() -> R) -> (R, usize) {
+            let before = COUNT.with(Cell::get);
+            let result = f();
+            (result, COUNT.with(Cell::get) - before)
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Jukebox {
+        #[delta_struct(field_type = "unordered")]
+        queued: std::collections::VecDeque<u8>,
+        #[delta_struct(field_type = "ordered")]
+        history: std::collections::VecDeque<u8>,
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "u32")]
+        requests: Vec<Child>,
+        #[delta_struct(field_type = "ordered", key = "id")]
+        lineup: Vec<Child>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Playlist {
+        name: String,
+        #[delta_struct(field_type = "unordered")]
+        favorites: Vec<u8>,
+        #[delta_struct(field_type = "ordered")]
+        tracks: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    enum Link {
+        #[delta_struct(default = "unordered")]
+        Connected {
+            #[delta_struct(field_type = "scalar")]
+            address: String,
+            channels: Vec<u8>,
+            #[delta_struct(skip)]
+            retries: u8,
+        },
+        Disconnected(String, #[delta_struct(field_type = "ordered")] Vec<u8>),
+        #[delta_struct(skip)]
+        Pairing(u32),
+        Idle,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    enum Presence {
+        #[delta_struct(from_default)]
+        Away {
+            message: String,
+            minutes: u32,
+        },
+        Home,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    enum Sample<T> {
+        Value(T),
+        Missing,
+    }
+
+    #[derive(Delta)]
+    struct ScalarCollection {
+        #[delta_struct(field_type = "scalar_collection")]
+        tags: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(output = "HandwrittenDelta")]
+    struct Handwritten {
+        name: String,
+        #[delta_struct(field_type = "unordered")]
+        tags: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct HandwrittenDelta {
+        name: Option<String>,
+        tags_add: Vec<u8>,
+        tags_remove: Vec<u8>,
+    }
+
+    impl HandwrittenDelta {
+        fn renamed(&self) -> bool {
+            self.name.is_some()
+        }
+    }
+
+    mod facade {
+        pub mod vendored {
+            pub use crate as delta_struct;
+        }
+    }
+
+    /// Nothing from the runtime crate is in scope here, so every generated
+    /// reference has to go through the configured path.
+    mod through_facade {
+        #[derive(super::facade::vendored::delta_struct::Delta, Clone, Debug, PartialEq)]
+        #[delta_struct(crate = "super::facade::vendored::delta_struct")]
+        pub struct Reexported {
+            pub name: String,
+            #[delta_struct(field_type = "ordered")]
+            pub items: Vec<u8>,
+            #[delta_struct(field_type = "delta")]
+            pub inner: Option<super::Settings>,
+        }
+    }
+
+    /// The delta's members are private outside this module.
+    mod encapsulated {
+        use crate::Delta;
+
+        #[derive(Clone, Debug, Delta, PartialEq)]
+        #[delta_struct(encapsulated, delta_leader = "#[derive(Debug, PartialEq)]")]
+        pub struct Lockbox {
+            pub code: u16,
+            #[delta_struct(field_type = "unordered")]
+            pub users: Vec<u8>,
+        }
+    }
+
+    macro_rules! generated_struct {
+        ($name:ident { $($field:ident: $ty:ty),* }) => {
+            #[derive(Clone, Debug, Delta, PartialEq)]
+            struct $name {
+                $($field: $ty,)*
+                #[delta_struct(field_type = "unordered")]
+                items: Vec<i32>,
+            }
+        };
+    }
+
+    generated_struct!(MacroGenerated {
+        old: i32,
+        new: i32,
+        delta: bool,
+        delta_is_some: bool,
+        v: u8
+    });
+
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct DeviceConfig {
+        #[delta_struct(field_type = "unordered")]
+        pub services: Vec<String>,
+        #[delta_struct(field_type = "unordered")]
+        pub settings: Vec<String>,
+        pub thumbnail_request: i32,
+        pub speedtest_request: i32,
+        #[delta_struct(field_type = "delta")]
+        pub features: AllFieldTypes,
+        pub deprovision: bool,
+    }
+
+    #[test]
+    fn unordered_with_scalar() {
+        let old = SimpleCollectionWithGeneric {
+            foo: vec![1, 2, 3],
+            bar: false,
+        };
+        let new = SimpleCollectionWithGeneric {
+            foo: vec![3, 4, 5],
+            bar: true,
+        };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert_eq!(delta.foo_add, vec![4, 5]);
+        assert_eq!(delta.foo_remove, vec![1, 2]);
+        assert_eq!(delta.bar, Some(true));
+    }
+
+    #[test]
+    fn delta_false_positive_check() {
+        let old = NewType(5);
+        let new = NewType(5);
+        let delta = ComputeDelta::delta(old, new);
+        assert!(delta.is_none());
+    }
+
+    #[test]
+    fn scalar_delta_false_positive_check() {
+        let old = SimpleType { foo: 5, bar: false };
+        let new = SimpleType { foo: 5, bar: true };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert!(delta.foo.is_none());
+        assert_eq!(delta.bar, Some(true));
+    }
+
+    #[test]
+    fn delta_field() {
+        let old = DeltaRecursion {
+            foo: NewType(5),
+            bar: false,
+        };
+        let new = DeltaRecursion {
+            foo: NewType(6),
+            bar: true,
+        };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        // TODO: Use assert_eq when we build out delta struct
+        // attributes.
+        if let Some(NewTypeDelta { field_0: Some(6) }) = delta.foo {
+            // Do nothing, this is the pass case.
+        } else {
+            panic!();
+        }
+        assert_eq!(delta.bar, Some(true));
+    }
+
+    #[test]
+    fn default_type_respected() {
+        let old = AttributeTest {
+            foo: 5,
+            bar: 4,
+            baz: vec![],
+        };
+        let new = AttributeTest {
+            foo: 5,
+            bar: 4,
+            baz: vec![9, 4, 5],
+        };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert!(delta.foo.is_none());
+        assert!(delta.bar.is_none());
+        assert_eq!(delta.baz_add, vec![9, 4, 5]);
+        assert!(delta.baz_remove.is_empty());
+    }
+
+    #[test]
+    fn apply_delta_all_field_types() {
+        let old = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2, 3, 3],
+        };
+        let new = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(4),
+            unordered: vec![3, 4, 5],
+        };
+        let new_clone = new.clone();
+        let mut old_delta_applied = old.clone();
+        let delta = ComputeDelta::delta(old, new);
+        old_delta_applied.apply_delta(delta.unwrap());
+        assert_eq!(new_clone, old_delta_applied);
+    }
+
+    #[test]
+    fn preview_leaves_original_untouched() {
+        let old = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2, 3],
+        };
+        let new = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(4),
+            unordered: vec![3, 4],
+        };
+        let delta = ComputeDelta::delta(old.clone(), new.clone()).unwrap();
+        let preview = old.preview(&delta);
+        assert_eq!(preview, new);
+        assert_eq!(old.scalar, 1);
+    }
+
+    #[test]
+    fn apply_delta_ref_applies_to_many_values() {
+        let old = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2, 3],
+        };
+        let new = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(4),
+            unordered: vec![3, 4],
+        };
+        let delta = ComputeDelta::delta(old.clone(), new.clone()).unwrap();
+        let mut replicas = vec![old.clone(), old];
+        for replica in &mut replicas {
+            replica.apply_delta_ref(&delta);
+        }
+        assert_eq!(replicas, vec![new.clone(), new]);
+    }
+
+    #[test]
+    fn context_hooks_are_used_by_delta_with() {
+        let locale = Locale {
+            case_insensitive: true,
+            max_volume: 50,
+        };
+        let old = Speaker {
+            name: "Kitchen".into(),
+            volume: 10,
+        };
+        let new = Speaker {
+            name: "KITCHEN".into(),
+            volume: 90,
+        };
+        let delta = Speaker::delta_with(old.clone(), new.clone(), &locale).unwrap();
+        assert_eq!(delta.name, None);
+        let mut applied = old.clone();
+        applied.apply_delta_with(delta, &locale);
+        assert_eq!(applied.volume, 50);
+
+        // The plain `Delta` impl ignores the hooks.
+        let delta = Speaker::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.name.as_deref(), Some("KITCHEN"));
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn guards_veto_changes_in_try_apply_delta_with() {
+        let user = Role { admin: false };
+        let old = Alarm {
+            label: "Front".into(),
+            armed: false,
+            pins: vec![1234],
+        };
+        let new = Alarm {
+            label: "Front door".into(),
+            armed: true,
+            pins: vec![5678],
+        };
+        let mut alarm = old.clone();
+        let delta = Alarm::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            alarm.try_apply_delta_with(delta, &user),
+            Err(Error::Denied(vec!["armed", "pins"]))
+        );
+        assert_eq!(alarm, old);
+        let added = Alarm {
+            label: "Front door".into(),
+            pins: vec![1234, 5678],
+            ..old.clone()
+        };
+        let delta = Alarm::delta(old.clone(), added.clone()).unwrap();
+        assert_eq!(alarm.try_apply_delta_with(delta, &user), Ok(()));
+        assert_eq!(alarm, added);
+        let mut alarm = old.clone();
+        let delta = Alarm::delta(old, new.clone()).unwrap();
+        assert_eq!(
+            alarm.try_apply_delta_with(delta, &Role { admin: true }),
+            Ok(())
+        );
+        assert_eq!(alarm, new);
+    }
+
+    #[test]
+    fn async_apply_awaits_hooks_of_changed_fields() {
+        let old = Thermostat {
+            name: "hall".into(),
+            volume: 3,
+            muted: false,
+        };
+        let new = Thermostat {
+            volume: 7,
+            muted: true,
+            ..old.clone()
+        };
+        let delta = Thermostat::delta(old.clone(), new.clone()).unwrap();
+        let mut applied = old;
+        block_on(applied.apply_delta_async(delta));
+        assert_eq!(applied, new);
+        assert_eq!(PERSISTED.with(|persisted| persisted.take()), vec!["7"]);
+    }
+
+    #[test]
+    fn remote_mirrors_diff_the_foreign_type() {
+        let delta = RangeDef::delta(0..10, 0..12).unwrap();
+        assert_eq!((delta.start, delta.end), (None, Some(12)));
+        assert_eq!(RangeDef::change_count(&(0..10), &(1..12), usize::MAX), 2);
+        let mut range = 0..10;
+        RangeDef::apply_delta(&mut range, delta);
+        assert_eq!(range, 0..12);
+        assert!(RangeDef::delta(0..10, 0..10).is_none());
+    }
+
+    #[test]
+    fn strict_apply_rejects_unknown_removals() {
+        let old = Keypad {
+            codes: vec![1234, 1234, 5678],
+            users: vec!["ann".into()],
+            locked: false,
+        };
+        let new = Keypad {
+            codes: vec![5678],
+            users: vec![],
+            locked: true,
+        };
+        let delta = Keypad::delta(old.clone(), new.clone()).unwrap();
+        let mut diverged = Keypad {
+            codes: vec![1234, 5678],
+            ..old.clone()
+        };
+        let error = diverged.try_apply_delta(delta).unwrap_err();
+        assert_eq!(
+            error,
+            Error::Apply(unordered::UnknownRemovals {
+                fields: vec![("codes", vec!["1234".to_string()])],
+            })
+        );
+        assert!(!diverged.locked);
+
+        let mut applied = old.clone();
+        applied
+            .try_apply_delta(Keypad::delta(old, new.clone()).unwrap())
+            .unwrap();
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn changed_flags_mirror_the_fields() {
+        let old = Camera {
+            name: "porch".into(),
+            zones: vec![1],
+            features: 0b01,
+        };
+        let new = Camera {
+            zones: vec![1, 2],
+            features: 0b11,
+            ..old.clone()
+        };
+        let delta = Camera::delta(old, new).unwrap();
+        assert_eq!(
+            delta.changed_flags(),
+            CameraChanged {
+                name: false,
+                zones: true,
+                features: true,
+            }
+        );
+    }
+
+    #[test]
+    fn compact_deltas_use_a_presence_mask() {
+        // The same fields as `Option`s, as a delta without `compact` has.
+        type Sparse = (Option<i32>, Option<u32>, Option<f32>, Option<bool>);
+        assert!(std::mem::size_of::<ReadingDelta>() < std::mem::size_of::<Sparse>());
+        let old = Reading {
+            temperature: 20,
+            humidity: 40,
+            pressure: 1013.0,
+            online: true,
+        };
+        let new = Reading {
+            humidity: 45,
+            online: false,
+            ..old.clone()
+        };
+        let delta = Reading::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.present, 0b1010);
+        assert_eq!(delta.temperature(), None);
+        assert_eq!(delta.humidity(), Some(&45));
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let warmer = Reading {
+            temperature: 22,
+            ..new.clone()
+        };
+        let composed = Reading::compose_delta(
+            Reading::delta(old.clone(), new.clone()).unwrap(),
+            Reading::delta(new, warmer.clone()).unwrap(),
+        );
+        assert_eq!(composed.present, 0b1011);
+        let mut applied = old;
+        applied.apply_delta(composed);
+        assert_eq!(applied, warmer);
+    }
+
+    #[test]
+    fn with_replace_patches_or_replaces() {
+        let old = Snapshot {
+            level: 1,
+            zones: vec![1],
+        };
+        let new = Snapshot {
+            level: 2,
+            zones: vec![1],
+        };
+        let delta = Snapshot::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta,
+            SnapshotDelta::Patch(SnapshotPatch {
+                level: Some(2),
+                zones_add: vec![],
+                zones_remove: vec![],
+            })
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let full = Snapshot {
+            level: 9,
+            zones: vec![2, 3],
+        };
+        let mut replaced = old.clone();
+        replaced.apply_delta(SnapshotDelta::Replace(full.clone()));
+        assert_eq!(replaced, full);
+
+        // A patch after a replacement is folded into the replacement value.
+        let patch = Snapshot::delta(full.clone(), Snapshot { level: 4, ..full }).unwrap();
+        let composed = Snapshot::compose_delta(SnapshotDelta::Replace(new), patch);
+        assert_eq!(
+            composed,
+            SnapshotDelta::Replace(Snapshot {
+                level: 4,
+                zones: vec![1],
+            })
+        );
+    }
+
+    #[test]
+    fn scalar_impls_from_macro_nest() {
+        let old = Hvac {
+            mode: Mode::Heat,
+            label: None,
+        };
+        let new = Hvac {
+            mode: Mode::Cool,
+            label: Some(Label("upstairs".into())),
+        };
+        let delta = Hvac::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.mode, Some(Mode::Cool));
+        assert_eq!(Hvac::change_count(&old, &new, usize::MAX), 2);
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+        assert!(Hvac::delta(old.clone(), old).is_none());
+    }
+
+    #[test]
+    fn coarse_fields_patch_or_replace_by_granularity() {
+        let old = Room {
+            settings: Settings {
+                volume: 1,
+                muted: false,
+            },
+            name: "den".into(),
+        };
+        let new = Room {
+            settings: Settings {
+                volume: 2,
+                muted: false,
+            },
+            ..old.clone()
+        };
+        let fine = Room::delta(old.clone(), new.clone()).unwrap();
+        match &fine.settings {
+            Some(Granular::Patch(patch)) => {
+                assert_eq!(patch.volume, Some(2));
+                assert_eq!(patch.muted, None);
+            }
+            _ => panic!("expected a nested patch"),
+        }
+        let coarse = Room::delta_at(old.clone(), new.clone(), Granularity::Coarse).unwrap();
+        match &coarse.settings {
+            Some(Granular::Replace(settings)) => assert_eq!(settings, &new.settings),
+            _ => panic!("expected a replacement"),
+        }
+        assert!(Room::delta_at(old.clone(), old.clone(), Granularity::Coarse).is_none());
+
+        for delta in [fine, coarse] {
+            let mut applied = old.clone();
+            applied.apply_delta(delta);
+            assert_eq!(applied, new);
+        }
+    }
+
+    #[test]
+    fn conflicts_list_fields_both_deltas_modify() {
+        let base = Flags {
+            raw: 0b0011,
+            permissions: Permissions::READ,
+        };
+        let a = Flags::delta(
+            base.clone(),
+            Flags {
+                raw: 0b0001,
+                permissions: Permissions::READ | Permissions::WRITE,
+            },
+        )
+        .unwrap();
+        let b = Flags::delta(
+            base.clone(),
+            Flags {
+                raw: 0b0111,
+                permissions: Permissions::READ | Permissions::WRITE,
+            },
+        )
+        .unwrap();
+        // The raw deltas touch different bits.
+        assert_eq!(a.conflicts_with(&b), vec!["permissions"]);
+
+        let c = Settings::delta(
+            Settings::default(),
+            Settings {
+                volume: 1,
+                muted: false,
+            },
+        )
+        .unwrap();
+        let d = Settings::delta(
+            Settings::default(),
+            Settings {
+                volume: 0,
+                muted: true,
+            },
+        )
+        .unwrap();
+        assert!(c.conflicts_with(&d).is_empty());
+        assert_eq!(c.conflicts_with(&c), vec!["volume"]);
+    }
+
+    #[test]
+    fn unordered_scalar_ignores_order_but_replaces_whole() {
+        let old = Schedule {
+            days: vec![1, 3, 5],
+        };
+        let reordered = Schedule {
+            days: vec![5, 1, 3],
+        };
+        assert!(Schedule::delta(old.clone(), reordered.clone()).is_none());
+        assert_eq!(Schedule::change_count(&old, &reordered, usize::MAX), 0);
+
+        let new = Schedule {
+            days: vec![3, 1, 6],
+        };
+        let delta = Schedule::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.days, Some(vec![3, 1, 6]));
+        assert_eq!(Schedule::change_count(&old, &new, usize::MAX), 1);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn generic_nested_delta_fields() {
+        let old = GenericNested {
+            inner: NewType(1),
+            maybe: None,
+            count: 1,
+        };
+        let new = GenericNested {
+            inner: NewType(2),
+            maybe: Some(NewType(3)),
+            count: 1,
+        };
+        let delta: GenericNestedDelta<NewType> =
+            ComputeDelta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.count, None);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn const_generic_parameters_are_forwarded() {
+        let old: Frames<i16> = Frames {
+            buffer: Buffer {
+                data: [1, 2],
+                len: 2,
+            },
+            samples: [0, 0],
+        };
+        let new = Frames {
+            buffer: Buffer {
+                data: [1, 3],
+                len: 2,
+            },
+            ..old.clone()
+        };
+        let delta: FramesDelta<i16, 2> = Frames::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.samples, None);
+        assert!(delta.changed_flags().buffer);
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn delta_from_default_only_holds_changed_fields() {
+        let delta = Settings::delta_from_default(Settings {
+            volume: 0,
+            muted: true,
+        })
+        .unwrap();
+        assert_eq!(delta.volume, None);
+        assert_eq!(delta.muted, Some(true));
+        assert!(Settings::delta_from_default(Settings::default()).is_none());
+    }
+
+    #[test]
+    fn scalar_collection_replaces_whole_collection() {
+        let old = ScalarCollection {
+            tags: vec!["a".to_string(), "b".to_string()],
+        };
+        let new = ScalarCollection {
+            tags: vec!["b".to_string(), "c".to_string()],
+        };
+        let mut applied = ScalarCollection { tags: vec![] };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert_eq!(delta.tags, Some(vec!["b".to_string(), "c".to_string()]));
+        applied.apply_delta(delta);
+        assert_eq!(applied.tags, vec!["b".to_string(), "c".to_string()]);
+    }
+
+    #[test]
+    fn macro_generated_fields_do_not_collide() {
+        let old = MacroGenerated {
+            old: 1,
+            new: 2,
+            delta: false,
+            delta_is_some: false,
+            v: 0,
+            items: vec![1],
+        };
+        let new = MacroGenerated {
+            old: 1,
+            new: 3,
+            delta: true,
+            delta_is_some: true,
+            v: 0,
+            items: vec![2],
+        };
+        let mut applied = old.clone();
+        let delta = ComputeDelta::delta(old, new.clone()).unwrap();
+        assert!(delta.old.is_none());
+        assert_eq!(delta.new, Some(3));
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn output_override_uses_handwritten_type() {
+        let old = Handwritten {
+            name: "a".to_string(),
+            tags: vec![1],
+        };
+        let new = Handwritten {
+            name: "b".to_string(),
+            tags: vec![1, 2],
+        };
+        let mut applied = old.clone();
+        let delta: HandwrittenDelta = ComputeDelta::delta(old, new.clone()).unwrap();
+        assert!(delta.renamed());
+        assert_eq!(delta.tags_add, vec![2]);
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let a = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2, 3],
+        };
+        let b = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(3),
+            unordered: vec![2, 3, 4],
+        };
+        let c = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(5),
+            unordered: vec![3, 5],
+        };
+        let mut applied = a.clone();
+        let first = ComputeDelta::delta(a, b.clone()).unwrap();
+        let second = ComputeDelta::delta(b, c.clone()).unwrap();
+        let composed = AllFieldTypes::compose_delta(first, second);
+        assert_eq!(composed.unordered_add, vec![5]);
+        assert_eq!(composed.unordered_remove, vec![1, 2]);
+        applied.apply_delta(composed);
+        assert_eq!(applied, c);
+    }
+
+    #[test]
+    fn ordered_fields_use_splices() {
+        let old = OrderedList {
+            items: vec!['a', 'b', 'c', 'd'],
+            queue: vec![1, 2].into(),
+        };
+        let new = OrderedList {
+            items: vec!['a', 'x', 'd'],
+            queue: vec![1, 2].into(),
+        };
+        let mut applied = old.clone();
+        let delta = ComputeDelta::delta(old, new.clone()).unwrap();
+        assert_eq!(
+            delta.items,
+            vec![Splice {
+                start: 1,
+                delete_count: 2,
+                insert: vec!['x'],
+            }]
+        );
+        assert!(delta.queue.is_empty());
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn bitset_fields_keep_concurrent_bits() {
+        let old = Flags {
+            raw: 0b01,
+            permissions: Permissions::READ,
+        };
+        let new = Flags {
+            raw: 0b10,
+            permissions: Permissions::READ | Permissions::WRITE,
+        };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert_eq!(delta.permissions_set, Permissions::WRITE);
+        assert!(delta.permissions_clear.is_empty());
+        let mut concurrent = Flags {
+            raw: 0b101,
+            permissions: Permissions::READ | Permissions::ADMIN,
+        };
+        concurrent.apply_delta(delta);
+        assert_eq!(
+            concurrent,
+            Flags {
+                raw: 0b110,
+                permissions: Permissions::all(),
+            }
+        );
+    }
+
+    #[test]
+    fn delta_iterates_over_changed_fields() {
+        let old = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2],
+        };
+        let new = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(4),
+            unordered: vec![2, 3],
+        };
+        let changes = ComputeDelta::delta(old, new)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                AllFieldTypesChange::Delta(NewTypeDelta { field_0: Some(4) }),
+                AllFieldTypesChange::Unordered {
+                    add: vec![3],
+                    remove: vec![1],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn default_type_parameters_are_carried_over() {
+        let old: DefaultTypeParameter = DefaultTypeParameter {
+            value: 1,
+            values: vec![1],
+        };
+        let new = DefaultTypeParameter {
+            value: 2,
+            values: vec![1, 2],
+        };
+        let mut applied = old.clone();
+        let delta: DefaultTypeParameterDelta = ComputeDelta::delta(old, new.clone()).unwrap();
+        assert_eq!(delta.values_add, vec![2u32]);
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn float_fields_ignore_drift() {
+        let old = Measurement {
+            temperature: 20.0,
+            level: f32::NAN,
+        };
+        let drifted = Measurement {
+            temperature: 20.001,
+            level: f32::NAN,
+        };
+        assert!(ComputeDelta::delta(old.clone(), drifted).is_none());
+        let changed = Measurement {
+            temperature: 21.0,
+            level: f32::NAN,
+        };
+        let delta = ComputeDelta::delta(old, changed).unwrap();
+        assert_eq!(delta.temperature, Some(21.0));
+        assert!(delta.level.is_none());
+    }
+
+    #[test]
+    fn option_patch_on_missing_value() {
+        let some = Settings {
+            volume: 1,
+            muted: false,
+        };
+        let old = OptionalSettings {
+            dropped: Some(some.clone()),
+            created: Some(some.clone()),
+        };
+        let new = OptionalSettings {
+            dropped: Some(Settings { volume: 5, ..some }),
+            created: Some(Settings { volume: 5, ..some }),
+        };
+        let delta = ComputeDelta::delta(old, new).unwrap();
+        assert!(matches!(delta.created, Some(OptionDelta::Patch(_))));
+        let mut missing = OptionalSettings {
+            dropped: None,
+            created: None,
+        };
+        missing.apply_delta(delta);
+        assert_eq!(missing.dropped, None);
+        assert_eq!(
+            missing.created,
+            Some(Settings {
+                volume: 5,
+                muted: false,
+            })
+        );
+    }
+
+    #[test]
+    fn bounded_delta_falls_back_to_full_value() {
+        let old = AllFieldTypes {
+            scalar: 1,
+            delta: NewType(3),
+            unordered: vec![1, 2, 3],
+        };
+        let new = AllFieldTypes {
+            scalar: 2,
+            delta: NewType(4),
+            unordered: vec![4, 5, 6],
+        };
+        assert_eq!(AllFieldTypes::change_count(&old, &new, usize::MAX), 8);
+        match ComputeDelta::delta_bounded(old.clone(), new.clone(), 8) {
+            DeltaOrFull::Delta(delta) => assert_eq!(delta.scalar, Some(2)),
+            _ => panic!("expected a delta"),
+        }
+        match ComputeDelta::delta_bounded(old.clone(), new.clone(), 7) {
+            DeltaOrFull::Full(full) => assert_eq!(full, new),
+            _ => panic!("expected a full value"),
+        }
+        assert!(matches!(
+            ComputeDelta::delta_bounded(old.clone(), old, 0),
+            DeltaOrFull::Unchanged
+        ));
+    }
+
+    #[test]
+    fn max_encoded_size_sums_delta_fields() {
+        // flag: 1 + 1, level: 1 + 5, mask: 1 + 1, inner: 1 + (1 + 5)
+        assert_eq!(CompactDelta::MAX_ENCODED_SIZE, 17);
+        assert_eq!(<Option<CompactInnerDelta>>::MAX_ENCODED_SIZE, 7);
+    }
+
+    #[test]
+    fn configurable_crate_path() {
+        use through_facade::Reexported;
+        let old = Reexported {
+            name: "a".to_string(),
+            items: vec![1],
+            inner: None,
+        };
+        let new = Reexported {
+            name: "b".to_string(),
+            items: vec![1, 2],
+            inner: Some(Settings::default()),
+        };
+        let mut applied = old.clone();
+        applied.apply_delta(ComputeDelta::delta(old, new.clone()).unwrap());
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn keyed_fields_add_remove_and_patch_by_key() {
+        let child = |id, label| Child { id, label };
+        let old = Household {
+            members: vec![child(1, "a"), child(2, "b"), child(3, "c")],
+        };
+        let new = Household {
+            members: vec![child(3, "c"), child(2, "B"), child(4, "d")],
+        };
+        assert_eq!(Household::change_count(&old, &new, usize::MAX), 3);
+        let delta = Household::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta.members,
+            KeyedVecDelta {
+                remove: vec![1],
+                patch: vec![(
+                    2,
+                    ChildDelta {
+                        id: None,
+                        label: Some("B"),
+                    }
+                )],
+                add: vec![child(4, "d")],
+            }
+        );
+        let mut applied = old;
+        applied.apply_delta(delta);
+        // Only membership and contents are tracked, not order.
+        assert_eq!(
+            applied.members,
+            vec![child(2, "B"), child(3, "c"), child(4, "d")]
+        );
+    }
+
+    #[test]
+    fn strategy_fields_use_their_strategy() {
+        let a = Strategies {
+            zones: vec![1, 2],
+            settings: Settings::default(),
+            name: "a".into(),
+        };
+        let b = Strategies {
+            zones: vec![2, 3],
+            settings: Settings {
+                volume: 4,
+                muted: false,
+            },
+            ..a.clone()
+        };
+        let c = Strategies {
+            zones: vec![3],
+            name: "c".into(),
+            ..b.clone()
+        };
+        let first = Strategies::delta(a.clone(), b.clone()).unwrap();
+        assert_eq!(
+            first.zones,
+            Some(strategy::UnorderedDelta {
+                add: vec![3],
+                remove: vec![1],
+            })
+        );
+        assert_eq!(first.name, None);
+        assert_eq!(Strategies::change_count(&a, &b, usize::MAX), 3);
+        let second = Strategies::delta(b, c.clone()).unwrap();
+        let mut applied = a;
+        applied.apply_delta(Strategies::compose_delta(first, second));
+        assert_eq!(applied, c);
+    }
+
+    #[test]
+    fn keyed_ordered_fields_move_and_patch() {
+        let child = |id, label| Child { id, label };
+        let old = Parent {
+            children: vec![child(1, "a"), child(2, "b"), child(3, "c"), child(4, "d")],
+        };
+        let new = Parent {
+            children: vec![child(3, "c"), child(1, "A"), child(5, "e"), child(2, "b")],
+        };
+        let mut applied = old.clone();
+        assert_eq!(Parent::change_count(&old, &new, usize::MAX), 4);
+        let delta = ComputeDelta::delta(old, new.clone()).unwrap();
+        assert_eq!(
+            delta.children,
+            vec![
+                KeyedOp::Remove { index: 3 },
+                KeyedOp::Move { from: 2, to: 0 },
+                KeyedOp::Insert {
+                    index: 2,
+                    value: child(5, "e"),
+                },
+                KeyedOp::Patch {
+                    index: 1,
+                    delta: ChildDelta {
+                        id: None,
+                        label: Some("A"),
+                    },
+                },
+            ]
+        );
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn split_chunks_apply_like_the_whole_delta() {
+        let old = Playlist {
+            name: "old".into(),
+            favorites: vec![1, 2, 3],
+            tracks: vec![1, 2, 3],
+        };
+        let new = Playlist {
+            name: "new".into(),
+            favorites: vec![3, 4, 5, 6],
+            tracks: vec![1, 7, 8, 9, 10, 11, 3],
+        };
+        let chunks = Playlist::delta(old.clone(), new.clone()).unwrap().split(2);
+        assert_eq!(chunks.len(), 6);
+        assert_eq!(chunks[0].name.as_deref(), Some("new"));
+        assert!(chunks[1..].iter().all(|chunk| chunk.name.is_none()));
+        let mut applied = old;
+        for chunk in chunks {
+            let items = chunk.favorites_add.len()
+                + chunk.favorites_remove.len()
+                + chunk
+                    .tracks
+                    .iter()
+                    .map(|splice| splice.insert.len().max(1))
+                    .sum::<usize>();
+            assert!(items <= 2);
+            applied.apply_delta(chunk);
+        }
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn non_exhaustive_deltas_are_built_with_setters() {
+        let mut furnace = Furnace {
+            setpoint: 68,
+            settings: Settings {
+                volume: 1,
+                muted: false,
+            },
+            schedules: vec![1, 2],
+            modes: 0b01,
+        };
+        let empty = FurnaceDelta::default();
+        assert!(empty.setpoint.is_none() && empty.schedules_add.is_empty());
+        let delta = FurnaceDelta::default()
+            .with_setpoint(72)
+            .with_settings(Settings {
+                volume: 1,
+                muted: true,
+            })
+            .with_schedules_remove(vec![1])
+            .with_modes_set(0b10);
+        furnace.apply_delta(delta);
+        assert_eq!(furnace.setpoint, 72);
+        assert!(furnace.settings.muted);
+        assert_eq!(furnace.schedules, vec![2]);
+        assert_eq!(furnace.modes, 0b11);
+    }
+
+    #[test]
+    fn monotonic_fields_ignore_stale_values() {
+        let beacon = |last_seen, min_rssi, name: &str| Beacon {
+            last_seen,
+            min_rssi,
+            name: name.into(),
+        };
+        let old = beacon(10, -40, "a");
+        let newer = Beacon::delta(old.clone(), beacon(20, -60, "b")).unwrap();
+        let stale = Beacon::delta(old.clone(), beacon(15, -50, "c")).unwrap();
+        let mut applied = old.clone();
+        applied.apply_delta(newer);
+        applied.apply_delta(stale);
+        assert_eq!(applied, beacon(20, -60, "c"));
+
+        let newer = Beacon::delta(old.clone(), beacon(20, -60, "b")).unwrap();
+        let stale = Beacon::delta(old.clone(), beacon(15, -50, "c")).unwrap();
+        let mut composed = old;
+        composed.apply_delta(Beacon::compose_delta(newer, stale));
+        assert_eq!(composed, applied);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_deltas_apply() {
+        use arbitrary::{Arbitrary, Unstructured};
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        let mut doorbell = Doorbell {
+            volume: 3,
+            contacts: vec![1, 2],
+            chime: None,
+        };
+        let mut applied = 0;
+        while !u.is_empty() {
+            let delta = DoorbellDelta::arbitrary(&mut u).unwrap();
+            doorbell.apply_delta(delta);
+            applied += 1;
+        }
+        assert!(applied > 1);
+    }
+
+    #[test]
+    fn from_delta_requires_every_scalar_field() {
+        let contact = Contact {
+            name: "Ada".into(),
+            phone: Some("555".into()),
+            groups: vec![1, 2],
+        };
+        let delta = Contact::delta_from_default(contact.clone()).unwrap();
+        assert_eq!(Contact::from_delta(delta), Some(contact.clone()));
+        let renamed = Contact {
+            name: "Grace".into(),
+            ..contact.clone()
+        };
+        let partial = Contact::delta(contact, renamed).unwrap();
+        assert_eq!(Contact::from_delta(partial), None);
+    }
+
+    #[test]
+    fn project_keeps_changes_under_the_paths() {
+        use project::{DeltaPath, Project};
+        let old = Nursery {
+            climate: Climate {
+                temperature: 20,
+                humidity: 40,
+            },
+            lights: false,
+            occupants: vec![1],
+        };
+        let new = Nursery {
+            climate: Climate {
+                temperature: 22,
+                humidity: 45,
+            },
+            lights: true,
+            occupants: vec![1, 2],
+        };
+        let delta = Nursery::delta(old, new).unwrap();
+        let projected = delta.project(&["climate.humidity", "occupants"].map(DeltaPath::from));
+        assert_eq!(
+            projected,
+            NurseryDelta {
+                climate: Some(ClimateDelta {
+                    temperature: None,
+                    humidity: Some(45),
+                }),
+                lights: None,
+                occupants_add: vec![2],
+                occupants_remove: vec![],
+            }
+        );
+        assert_eq!(delta.project(&[]).lights, None);
+        assert_eq!(delta.project(&["climate".into()]).climate, delta.climate);
+    }
+
+    #[test]
+    fn trait_object_fields_are_skipped_or_use_a_strategy() {
+        let widget = |id, label: &'static str| Widget {
+            id,
+            on_click: Box::new(move |x| x + id),
+            label: Box::new(label),
+        };
+        let delta = Widget::delta(widget(1, "ok"), widget(2, "ok")).unwrap();
+        assert_eq!(delta.id, Some(2));
+        assert!(delta.label.is_none());
+        let mut applied = widget(1, "ok");
+        applied.apply_delta(Widget::delta(widget(1, "ok"), widget(1, "cancel")).unwrap());
+        assert_eq!(applied.label.to_string(), "cancel");
+        assert_eq!((applied.on_click)(1), 2);
+    }
+
+    #[test]
+    fn set_map_fields_send_per_key_member_changes() {
+        let policy = |scopes: &[(&str, &[&str])]| AccessPolicy {
+            scopes: scopes
+                .iter()
+                .map(|(role, scopes)| {
+                    let scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+                    (role.to_string(), scopes)
+                })
+                .collect(),
+            version: 1,
+        };
+        let old = policy(&[("admin", &["read", "write"]), ("guest", &["read"])]);
+        let new = policy(&[
+            ("admin", &["read", "write", "delete"]),
+            ("guest", &["read"]),
+        ]);
+        let delta = AccessPolicy::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta.scopes,
+            Some(SetMapDelta {
+                remove: vec![],
+                update: vec![(
+                    "admin".to_string(),
+                    strategy::UnorderedDelta {
+                        add: vec!["delete".to_string()],
+                        remove: vec![],
+                    }
+                )],
+            })
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+        assert_eq!(AccessPolicy::change_count(&old, &new, usize::MAX), 1);
+    }
+
+    #[test]
+    fn apply_order_overrides_declaration_order() {
+        let old = Valve {
+            name: "main".into(),
+            flow: 1,
+            pressure: 2,
+        };
+        let new = Valve {
+            name: "backup".into(),
+            flow: 3,
+            pressure: 4,
+        };
+        let delta = Valve::delta(old.clone(), new.clone()).unwrap();
+        let mut applied = old;
+        block_on(applied.apply_delta_async(delta));
+        assert_eq!(applied, new);
+        assert_eq!(
+            PERSISTED.with(|persisted| persisted.take()),
+            vec!["4", "3", "backup"]
+        );
+    }
+
+    #[test]
+    fn sorted_fields_send_added_and_removed_elements() {
+        let old = Calendar {
+            holidays: vec![1, 185, 359].into_iter().collect(),
+            setpoints: vec![(0, 18), (420, 21)].into_iter().collect(),
+        };
+        let new = Calendar {
+            holidays: vec![1, 147, 359].into_iter().collect(),
+            setpoints: vec![(0, 18), (420, 20)].into_iter().collect(),
+        };
+        let delta = Calendar::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta.holidays,
+            Some(strategy::UnorderedDelta {
+                add: vec![147],
+                remove: vec![185],
+            })
+        );
+        assert_eq!(
+            delta.setpoints,
+            Some(strategy::UnorderedDelta {
+                add: vec![(420, 20)],
+                remove: vec![(420, 21)],
+            })
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+        assert_eq!(Calendar::change_count(&old, &new, usize::MAX), 4);
+    }
+
+    #[test]
+    fn rename_distance_sends_small_edits_as_changes() {
+        let strings =
+            |items: &[&str]| -> Vec<String> { items.iter().map(|s| s.to_string()).collect() };
+        let old = Roster {
+            rooms: strings(&["Kitchen", "Living room", "Attic"]),
+            members: strings(&["ana", "bo"]).into_iter().collect(),
+        };
+        let new = Roster {
+            rooms: strings(&["Living Room", "Kitchen", "Garage"]),
+            members: strings(&["anna", "cy"]).into_iter().collect(),
+        };
+        let delta = Roster::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta.rooms,
+            Some(unordered::RenameDelta {
+                add: strings(&["Garage"]),
+                remove: strings(&["Attic"]),
+                changed: vec![("Living room".into(), "Living Room".into())],
+            })
+        );
+        assert_eq!(
+            delta.members,
+            Some(unordered::RenameDelta {
+                add: strings(&["cy"]),
+                remove: strings(&["bo"]),
+                changed: vec![("ana".into(), "anna".into())],
+            })
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(
+            applied.rooms,
+            strings(&["Kitchen", "Living Room", "Garage"])
+        );
+        assert_eq!(applied.members, new.members);
+    }
+
+    #[test]
+    fn compute_only_types_need_not_support_applying() {
+        let old = SensorReport {
+            station: "north".into(),
+            readings: Readings(vec![3, 5, 8]),
+        };
+        let new = SensorReport {
+            station: "north".into(),
+            readings: Readings(vec![5, 8, 13]),
+        };
+        assert_eq!(SensorReport::change_count(&old, &new, usize::MAX), 2);
+        let delta = SensorReport::delta(old, new).unwrap();
+        assert_eq!(delta.station, None);
+        assert_eq!(delta.readings_add, vec![13]);
+        assert_eq!(delta.readings_remove, vec![3]);
+    }
+
+    #[test]
+    fn deltas_apply_to_a_different_representation() {
+        let old = SensorReport {
+            station: "north".into(),
+            readings: Readings(vec![3, 5]),
+        };
+        let new = SensorReport {
+            station: "south".into(),
+            readings: Readings(vec![5, 8, 13]),
+        };
+        let mut display = SensorDisplay {
+            title: "Station north".into(),
+            reading_count: 2,
+        };
+        display.apply_delta(SensorReport::delta(old, new).unwrap());
+        assert_eq!(
+            display,
+            SensorDisplay {
+                title: "Station south".into(),
+                reading_count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn nested_wrappers_of_delta_types_forward_to_them() {
+        let child = |id, label| Box::new(Child { id, label });
+        let a = Floorplan {
+            rooms: vec![child(1, "den"), child(2, "hall")],
+            primary: Some(std::sync::Arc::new(Child {
+                id: 1,
+                label: "den",
+            })),
+            wings: std::rc::Rc::new(vec![Some(child(3, "east")), None]),
+        };
+        let b = Floorplan {
+            rooms: vec![child(1, "study"), child(2, "hall"), child(4, "porch")],
+            primary: Some(std::sync::Arc::new(Child {
+                id: 1,
+                label: "study",
+            })),
+            wings: std::rc::Rc::new(vec![Some(child(3, "east")), Some(child(5, "west"))]),
+        };
+        let c = Floorplan {
+            rooms: vec![child(1, "office")],
+            primary: None,
+            wings: std::rc::Rc::new(vec![Some(child(3, "north"))]),
+        };
+        let first = Floorplan::delta(a.clone(), b.clone()).unwrap();
+        let rooms = first.rooms.as_ref().unwrap();
+        assert_eq!(rooms.keep, 2);
+        assert_eq!(
+            rooms.patch,
+            vec![(
+                0,
+                ChildDelta {
+                    id: None,
+                    label: Some("study")
+                }
+            )]
+        );
+        assert_eq!(rooms.append, vec![child(4, "porch")]);
+        assert_eq!(Floorplan::change_count(&a, &b, usize::MAX), 4);
+        let mut applied = a.clone();
+        applied.apply_delta(first);
+        assert_eq!(applied, b);
+        let mut composed = a.clone();
+        composed.apply_delta(Floorplan::compose_delta(
+            Floorplan::delta(a, b.clone()).unwrap(),
+            Floorplan::delta(b, c.clone()).unwrap(),
+        ));
+        assert_eq!(composed, c);
+    }
+
+    #[test]
+    fn changed_paths_are_numbered_depth_first() {
+        assert_eq!(
+            NurseryDelta::paths(),
+            vec![
+                "climate",
+                "climate.temperature",
+                "climate.humidity",
+                "lights",
+                "occupants"
+            ]
+        );
+        let old = Nursery {
+            climate: Climate {
+                temperature: 20,
+                humidity: 40,
+            },
+            lights: false,
+            occupants: vec![1],
+        };
+        let new = Nursery {
+            climate: Climate {
+                temperature: 20,
+                humidity: 45,
+            },
+            lights: false,
+            occupants: vec![1, 2],
+        };
+        let delta = Nursery::delta(old, new).unwrap();
+        assert_eq!(delta.changed_path_ids(), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn sort_unordered_orders_added_and_removed_elements() {
+        let old = Badges {
+            earned: (0..20).collect(),
+            pinned: vec![9, 3, 7],
+        };
+        let new = Badges {
+            earned: (10..40).collect(),
+            pinned: vec![5, 1, 8, 3],
+        };
+        let first = Badges::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(first.earned_add, (20..40).collect::<Vec<_>>());
+        assert_eq!(first.earned_remove, (0..10).collect::<Vec<_>>());
+        assert_eq!(first.pinned_add, vec![1, 5, 8]);
+        assert_eq!(first.pinned_remove, vec![7, 9]);
+        let second = Badges::delta(
+            new,
+            Badges {
+                earned: (10..40).collect(),
+                pinned: vec![0],
+            },
+        )
+        .unwrap();
+        let composed = Badges::compose_delta(first, second);
+        assert_eq!(composed.pinned_add, vec![0]);
+        assert_eq!(composed.pinned_remove, vec![3, 7, 9]);
+    }
+
+    #[test]
+    fn has_changes_stops_at_the_first_difference() {
+        let climate = Climate {
+            temperature: 20,
+            humidity: 40,
+        };
+        let warmer = Climate {
+            temperature: 21,
+            ..climate.clone()
+        };
+        assert!(!Climate::has_changes(&climate, &climate));
+        assert!(Climate::has_changes(&climate, &warmer));
+        let nursery = Nursery {
+            climate,
+            lights: false,
+            occupants: vec![1, 2],
+        };
+        let reordered = Nursery {
+            occupants: vec![2, 1],
+            ..nursery.clone()
+        };
+        assert!(!Nursery::has_changes(&nursery, &reordered));
+        assert!(Nursery::has_changes(
+            &nursery,
+            &Nursery {
+                climate: warmer,
+                ..nursery.clone()
+            }
+        ));
+    }
+
+    #[test]
+    fn schema_describes_fields_and_nested_types() {
+        use schema::{FieldKind, Schema};
+        let schema = Nursery::delta_schema();
+        assert_eq!(schema.name, "Nursery");
+        let kinds: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|field| (field.name, field.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("climate", FieldKind::Delta),
+                ("lights", FieldKind::Scalar),
+                ("occupants", FieldKind::Unordered),
+            ]
+        );
+        assert_eq!(schema.field("occupants").unwrap().type_name, "Vec < u8 >");
+        let climate = schema.field("climate").unwrap().nested().unwrap();
+        assert!(std::ptr::eq(climate, Climate::delta_schema()));
+        assert!(climate.fields.iter().all(|field| field.nested.is_none()));
+    }
+
+    #[test]
+    fn apply_checked_rejects_deltas_of_other_states() {
+        let seen = std::time::Instant::now();
+        let a = Porch {
+            chime: 1,
+            contacts: vec![7],
+            seen,
+        };
+        let b = Porch {
+            chime: 2,
+            contacts: vec![7, 8],
+            seen,
+        };
+        let c = Porch {
+            chime: 3,
+            contacts: vec![8],
+            seen,
+        };
+        let first = Porch::delta(a.clone(), b.clone()).unwrap();
+        let second = Porch::delta(b.clone(), c.clone()).unwrap();
+        assert_eq!(first.base_fingerprint, a.fingerprint());
+        let replica = Porch {
+            seen: seen + std::time::Duration::from_secs(1),
+            ..a.clone()
+        };
+        assert_eq!(replica.fingerprint(), a.fingerprint());
+        let mut target = a.clone();
+        let error = target
+            .apply_checked(Porch::delta(b.clone(), c.clone()).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            error,
+            Error::BaseMismatch {
+                expected: b.fingerprint(),
+                found: a.fingerprint(),
+            }
+        );
+        assert_eq!(target, a);
+        let composed = Porch::compose_delta(first, second);
+        assert_eq!(target.apply_checked(composed), Ok(()));
+        assert_eq!(target, c);
+    }
+
+    #[test]
+    fn excluded_fields_are_left_out_of_deltas() {
+        let old = Account {
+            name: "a".into(),
+            created_at: 1,
+            internal_cache: vec![1],
+        };
+        let new = Account {
+            name: "b".into(),
+            created_at: 2,
+            internal_cache: vec![2],
+        };
+        let delta = Account::delta(old.clone(), new).unwrap();
+        assert_eq!(
+            delta,
+            AccountDelta {
+                name: Some("b".into())
+            }
+        );
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!((applied.created_at, applied.internal_cache), (1, vec![1]));
+        assert_eq!(
+            Account::delta(
+                Account {
+                    name: "a".into(),
+                    created_at: 1,
+                    internal_cache: vec![],
+                },
+                Account {
+                    name: "a".into(),
+                    created_at: 5,
+                    internal_cache: vec![5],
+                },
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn views_only_diff_their_fields() {
+        let old = Profile {
+            name: "ana".into(),
+            status: "away".into(),
+            permissions: vec!["read".into()],
+        };
+        let new = Profile {
+            name: "ana".into(),
+            status: "online".into(),
+            permissions: vec!["read".into(), "write".into()],
+        };
+        let public = Profile::delta_public(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            public,
+            ProfilePublicDelta {
+                name: None,
+                status: Some("online".into()),
+            }
+        );
+        let mut applied = old.clone();
+        applied.apply_delta_public(public);
+        assert_eq!(applied.status, "online");
+        assert_eq!(applied.permissions, old.permissions);
+
+        let mut updated = new.clone();
+        updated.status = "away".into();
+        assert_eq!(Profile::delta_public(old.clone(), updated), None);
+
+        let admin = Profile::delta_admin(old.clone(), new.clone()).unwrap();
+        let mut applied = old;
+        applied.apply_delta_admin(admin);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn encapsulated_deltas_are_read_through_getters() {
+        use encapsulated::{Lockbox, LockboxDelta};
+        let old = Lockbox {
+            code: 1234,
+            users: vec![1, 2],
+        };
+        let new = Lockbox {
+            code: 4321,
+            users: vec![2, 3],
+        };
+        let delta = Lockbox::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.code(), &Some(4321));
+        assert_eq!(delta.users_add(), &vec![3]);
+        assert_eq!(delta.users_remove(), &vec![1]);
+        let built = LockboxDelta::default()
+            .with_code(4321)
+            .with_users_add(vec![3])
+            .with_users_remove(vec![1]);
+        assert_eq!(built, delta);
+        let mut applied = old;
+        applied.apply_delta(built);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn duplicate_additions_follow_the_policy() {
+        let mut list = Guestlist {
+            invited: vec!["ana".into()],
+            arrivals: vec!["ana".into()],
+        };
+        list.apply_delta(GuestlistDelta {
+            invited_add: vec!["ana".into(), "bo".into(), "bo".into()],
+            invited_remove: vec![],
+            arrivals_add: vec!["ana".into()],
+            arrivals_remove: vec![],
+        });
+        assert_eq!(list.invited, vec!["ana".to_string(), "bo".to_string()]);
+        assert_eq!(list.arrivals, vec!["ana".to_string(), "ana".to_string()]);
+    }
+
+    #[test]
+    fn unchanged_collections_are_diffed_without_allocating() {
+        let child = |id, label| Child { id, label };
+        let old = Jukebox {
+            queued: vec![1, 2, 3].into(),
+            history: vec![4, 5, 6].into(),
+            requests: vec![child(1, "a"), child(2, "b")],
+            lineup: vec![child(3, "c"), child(4, "d")],
+        };
+        let new = old.clone();
+        let (count, allocations) =
+            allocations::counted(|| Jukebox::change_count(&old, &new, usize::MAX));
+        assert_eq!(count, 0);
+        assert_eq!(allocations, 0);
+        let (delta, allocations) = allocations::counted(|| Jukebox::delta(old, new));
+        assert!(delta.is_none());
+        assert_eq!(allocations, 0);
+
+        let old = Jukebox {
+            queued: vec![1, 2, 3].into(),
+            history: vec![4, 5, 6].into(),
+            requests: vec![child(1, "a"), child(2, "b")],
+            lineup: vec![child(3, "c"), child(4, "d")],
+        };
+        let mut new = old.clone();
+        new.queued.rotate_left(1);
+        new.requests.rotate_left(1);
+        let (delta, allocations) = allocations::counted(|| Jukebox::delta(old, new));
+        assert!(delta.is_none());
+        assert!(allocations > 0);
+    }
+
+    #[test]
+    fn redelivered_deltas_are_applied_once() {
+        let empty = Tally { visitors: vec![] };
+        let one = Tally {
+            visitors: vec!["ana".into()],
+        };
+        let two = Tally {
+            visitors: vec!["ana".into(), "bo".into()],
+        };
+        let first = Tally::delta(empty.clone(), one.clone()).unwrap();
+        let second = Tally::delta(one, two.clone()).unwrap();
+        assert_ne!(first.delta_id, second.delta_id);
+        let mut seen = std::collections::HashSet::new();
+        let mut target = empty;
+        assert!(target.apply_once(first.clone(), &mut seen));
+        assert!(!target.apply_once(first.clone(), &mut seen));
+        assert!(target.apply_once(second.clone(), &mut seen));
+        assert!(!target.apply_once(second.clone(), &mut seen));
+        assert_eq!(target, two);
+        let composed = Tally::compose_delta(first.clone(), second);
+        assert_ne!(composed.delta_id, first.delta_id);
+    }
+
+    #[test]
+    fn keyed_fields_patch_elements_in_place() {
+        let child = |id, label| Child { id, label };
+        let old = Daycare {
+            children: vec![child(1, "a"), child(2, "b"), child(3, "c")].into(),
+        };
+        let new = Daycare {
+            children: vec![child(1, "a"), child(2, "B"), child(3, "C")].into(),
+        };
+        let mut applied = old.clone();
+        let buffer = applied.children.as_slices().0.as_ptr();
+        applied.apply_delta(Daycare::delta(old, new.clone()).unwrap());
+        assert_eq!(applied, new);
+        assert_eq!(applied.children.as_slices().0.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn repr_is_carried_by_the_delta_struct() {
+        assert_eq!(std::mem::size_of::<DimmerDelta>(), 3);
+        let old = Dimmer {
+            level: 0,
+            on: false,
+        };
+        let new = Dimmer {
+            level: 40,
+            on: true,
+        };
+        let mut applied = old.clone();
+        applied.apply_delta(Dimmer::delta(old, new.clone()).unwrap());
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn delta_outputs_are_usable_generically() {
+        fn merged_fields<D: DeltaOutput>(first: D, second: D) -> Vec<&'static str> {
+            first.merge(second).changed_fields()
+        }
+        let a = Playlist {
+            name: "a".into(),
+            favorites: vec![1],
+            tracks: vec![1, 2],
+        };
+        let b = Playlist {
+            favorites: vec![1, 2],
+            ..a.clone()
+        };
+        let c = Playlist {
+            name: "c".into(),
+            ..b.clone()
+        };
+        let first = Playlist::delta(a.clone(), b.clone()).unwrap();
+        assert!(!first.is_empty());
+        assert_eq!(first.changed_fields(), vec!["favorites"]);
+        let second = Playlist::delta(b, c).unwrap();
+        assert_eq!(merged_fields(first, second), vec!["name", "favorites"]);
+        assert!(PlaylistDelta {
+            name: None,
+            favorites_add: vec![],
+            favorites_remove: vec![],
+            tracks: vec![],
+        }
+        .is_empty());
+    }
+
+    #[test]
+    fn associated_types_are_bounded_instead_of_their_parameter() {
+        let adapter = |reading, label| SensorAdapter::<Probe> {
+            reading,
+            config: Child { id: 1, label },
+            history: vec![reading],
+        };
+        let delta = SensorAdapter::delta(adapter(1, "a"), adapter(2, "a")).unwrap();
+        assert_eq!(delta.reading, Some(2));
+        assert!(delta.config.is_none());
+        assert_eq!(
+            (delta.history_add, delta.history_remove),
+            (vec![2], vec![1])
+        );
+        let mut applied = adapter(1, "a");
+        applied.apply_delta(SensorAdapter::delta(adapter(1, "a"), adapter(1, "b")).unwrap());
+        assert_eq!(applied.config.label, "b");
+    }
+
+    #[test]
+    fn grouped_fields_are_sent_together() {
+        let old = Drone::default();
+        let moved = Drone {
+            y: 3,
+            ..old.clone()
+        };
+        let delta = Drone::delta(old.clone(), moved.clone()).unwrap();
+        assert_eq!(
+            (delta.x, delta.y, delta.z, delta.battery),
+            (Some(0), Some(3), Some(0.0), None)
+        );
+        let drained = Drone {
+            battery: 50,
+            z: 0.25,
+            ..old.clone()
+        };
+        let delta = Drone::delta(old, drained).unwrap();
+        assert_eq!((delta.x, delta.y, delta.z), (None, None, None));
+    }
+
+    #[test]
+    fn delta_fields_are_enumerable_by_index() {
+        fn changed_row<D: DeltaOutput>(delta: &D) -> Vec<String> {
+            (0..D::FIELD_COUNT)
+                .map(|index| format!("{}={}", D::FIELDS[index], delta.is_field_changed(index)))
+                .collect()
+        }
+        assert_eq!(PlaylistDelta::FIELDS, &["name", "favorites", "tracks"]);
+        assert_eq!(PlaylistDelta::FIELD_COUNT, 3);
+        let delta = PlaylistDelta {
+            name: None,
+            favorites_add: vec![3],
+            favorites_remove: vec![],
+            tracks: vec![],
+        };
+        assert_eq!(
+            changed_row(&delta),
+            vec!["name=false", "favorites=true", "tracks=false"]
+        );
+        assert!(!delta.is_field_changed(PlaylistDelta::FIELD_COUNT));
+        assert_eq!(SnapshotPatch::FIELDS, &["level", "zones"]);
+        assert_eq!(SensorReportDelta::FIELDS, &["station", "readings"]);
+        assert_eq!(SensorReportDelta::FIELD_COUNT, 2);
+    }
+
+    fn connected(channels: Vec<u8>, retries: u8) -> Link {
+        Link::Connected {
+            address: "hub".to_string(),
+            channels,
+            retries,
+        }
+    }
+
+    #[test]
+    fn enum_variants_are_patched_or_replaced() {
+        let delta = Link::delta(connected(vec![1], 0), connected(vec![1, 2], 3)).unwrap();
+        assert_eq!(
+            delta,
+            LinkDelta::Connected(LinkConnectedDelta {
+                address: None,
+                channels_add: vec![2],
+                channels_remove: vec![],
+            })
+        );
+        let mut applied = connected(vec![1], 4);
+        applied.apply_delta(delta);
+        assert_eq!(applied, connected(vec![1, 2], 4));
+
+        assert_eq!(
+            Link::delta(connected(vec![1], 0), connected(vec![1], 5)),
+            None
+        );
+        assert_eq!(Link::delta(Link::Pairing(1), Link::Pairing(2)), None);
+        assert_eq!(Link::delta(Link::Idle, Link::Idle), None);
+        assert_eq!(Link::change_count(&Link::Idle, &Link::Pairing(1), 8), 1);
+        assert_eq!(
+            Link::change_count(&connected(vec![1], 0), &connected(vec![2], 0), 8),
+            2
+        );
+
+        let disconnected = Link::Disconnected("hub".to_string(), vec![3]);
+        let delta = Link::delta(connected(vec![1], 2), disconnected.clone()).unwrap();
+        assert_eq!(
+            delta,
+            LinkDelta::SetVariant(LinkVariant::Disconnected("hub".to_string(), vec![3]))
+        );
+        let mut applied = connected(vec![1], 2);
+        applied.apply_delta(delta);
+        assert_eq!(applied, disconnected);
+
+        let delta = Link::delta(Link::Idle, connected(vec![1], 2)).unwrap();
+        assert_eq!(
+            delta,
+            LinkDelta::SetVariant(LinkVariant::Connected {
+                address: "hub".to_string(),
+                channels: vec![1],
+            })
+        );
+        let mut applied = Link::Idle;
+        applied.apply_delta(delta);
+        assert_eq!(applied, connected(vec![1], 0));
+    }
+
+    #[test]
+    fn enum_patches_of_another_variant_are_skipped() {
+        let delta = Link::delta(connected(vec![], 0), connected(vec![1], 0)).unwrap();
+        let mut applied = Link::Idle;
+        applied.apply_delta(delta);
+        assert_eq!(applied, Link::Idle);
+    }
+
+    #[test]
+    fn enum_deltas_compose() {
+        let first = Link::delta(connected(vec![], 0), connected(vec![1], 0)).unwrap();
+        let second = Link::delta(connected(vec![1], 0), connected(vec![1, 2], 0)).unwrap();
+        let composed = Link::compose_delta(first, second);
+        let mut applied = connected(vec![], 7);
+        applied.apply_delta(composed);
+        assert_eq!(applied, connected(vec![1, 2], 7));
+
+        let first = Link::delta(Link::Idle, connected(vec![1], 0)).unwrap();
+        let second = Link::delta(connected(vec![1], 0), connected(vec![2], 0)).unwrap();
+        assert_eq!(
+            Link::compose_delta(first, second),
+            LinkDelta::SetVariant(LinkVariant::Connected {
+                address: "hub".to_string(),
+                channels: vec![2],
+            })
+        );
+
+        let first = Link::delta(Link::Idle, connected(vec![1], 0)).unwrap();
+        let second = Link::delta(connected(vec![1], 0), Link::Pairing(3)).unwrap();
+        assert_eq!(
+            Link::compose_delta(first, second),
+            LinkDelta::SetVariant(LinkVariant::Pairing(3))
+        );
+    }
+
+    #[test]
+    fn generic_enums_are_diffed() {
+        let delta = Sample::delta(Sample::Value(1u32), Sample::Value(2)).unwrap();
+        let mut applied = Sample::Value(1);
+        applied.apply_delta(delta);
+        assert_eq!(applied, Sample::Value(2));
+        let mut applied = Sample::Value(1);
+        applied.apply_delta(Sample::delta(Sample::Value(1), Sample::Missing).unwrap());
+        assert_eq!(applied, Sample::Missing);
+    }
+
+    #[test]
+    fn from_default_variants_carry_their_changes_from_the_default() {
+        let away = |message: &str, minutes| Presence::Away {
+            message: message.to_string(),
+            minutes,
+        };
+        let delta = Presence::delta(Presence::Home, away("", 30)).unwrap();
+        assert_eq!(
+            delta,
+            PresenceDelta::SetVariant(PresenceVariant::Away(Some(PresenceAwayDelta {
+                message: None,
+                minutes: Some(30),
+            })))
+        );
+        let mut applied = Presence::Home;
+        applied.apply_delta(delta);
+        assert_eq!(applied, away("", 30));
+
+        let delta = Presence::delta(Presence::Home, away("", 0)).unwrap();
+        assert_eq!(
+            delta,
+            PresenceDelta::SetVariant(PresenceVariant::Away(None))
+        );
+        let mut applied = Presence::Home;
+        applied.apply_delta(delta);
+        assert_eq!(applied, away("", 0));
+
+        let second = Presence::delta(away("", 0), away("lunch", 0)).unwrap();
+        let composed = Presence::compose_delta(
+            Presence::delta(Presence::Home, away("", 0)).unwrap(),
+            second,
+        );
+        let mut applied = Presence::Home;
+        applied.apply_delta(composed);
+        assert_eq!(applied, away("lunch", 0));
     }
 }
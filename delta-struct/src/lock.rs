@@ -0,0 +1,184 @@
+//! Diffing of fields behind a `Mutex` or `RwLock`, for `field_type =
+//! "locked"`, so shared state wrapping hot fields in locks can derive
+//! `Delta`. The inner values are compared like a scalar field, or with their
+//! own `Delta` impl with `inner = "delta"`.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::sync::{Mutex, RwLock};
+//!
+//! #[derive(Delta)]
+//! struct Device {
+//!     #[delta_struct(field_type = "locked")]
+//!     name: Mutex<String>,
+//!     #[delta_struct(field_type = "locked")]
+//!     readings: RwLock<Vec<u16>>,
+//! }
+//! ```
+//!
+//! Computing a delta takes the values out of the consumed locks, and
+//! applying one goes through the exclusive reference to the target, so
+//! neither waits on other threads. Counting changes holds both locks at
+//! once, taking them in address order so that counting `a` against `b` and
+//! `b` against `a` on different threads can't deadlock. A poisoned lock is
+//! used as is, since a delta either replaces its value or is computed from
+//! it.
+
+use crate::strategy::{DiffStrategy, NestedStrategy, ScalarStrategy};
+use std::marker::PhantomData;
+use std::sync::{Mutex, PoisonError, RwLock};
+
+/// A lock around a value, as std's locks don't share a trait.
+pub trait Lock {
+    type Inner;
+
+    /// Calls `f` with the value, holding the lock meanwhile.
+    fn with<R>(&self, f: impl FnOnce(&Self::Inner) -> R) -> R;
+
+    fn get_mut(&mut self) -> &mut Self::Inner;
+
+    fn into_inner(self) -> Self::Inner;
+}
+
+impl<T> Lock for Mutex<T> {
+    type Inner = T;
+
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        Mutex::get_mut(self).unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn into_inner(self) -> T {
+        Mutex::into_inner(self).unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Lock for RwLock<T> {
+    type Inner = T;
+
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        RwLock::get_mut(self).unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn into_inner(self) -> T {
+        RwLock::into_inner(self).unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Diffs the value behind a lock with the strategy `S`. See the
+/// [module docs](self).
+pub struct LockStrategy<S = ScalarStrategy>(PhantomData<S>);
+
+pub type NestedLockStrategy = LockStrategy<NestedStrategy>;
+
+impl<L: Lock, S: DiffStrategy<L::Inner>> DiffStrategy<L> for LockStrategy<S> {
+    type Delta = S::Delta;
+
+    fn diff(old: L, new: L) -> Option<S::Delta> {
+        S::diff(old.into_inner(), new.into_inner())
+    }
+
+    fn apply(target: &mut L, delta: S::Delta) {
+        S::apply(target.get_mut(), delta);
+    }
+
+    fn change_count(old: &L, new: &L, limit: usize) -> usize {
+        // Locking the same mutex twice would deadlock.
+        if std::ptr::eq(old, new) {
+            return 0;
+        }
+        if (old as *const L) < (new as *const L) {
+            old.with(|old| new.with(|new| S::change_count(old, new, limit)))
+        } else {
+            new.with(|new| old.with(|old| S::change_count(old, new, limit)))
+        }
+    }
+
+    fn compose(first: S::Delta, second: S::Delta) -> S::Delta {
+        S::compose(first, second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+    use std::sync::{Mutex, RwLock};
+
+    #[derive(Clone, Debug, Default, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Zone {
+        name: String,
+        setpoint: u8,
+    }
+
+    #[derive(Debug, Default, Delta)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Hub {
+        #[delta_struct(field_type = "locked")]
+        mode: Mutex<String>,
+        #[delta_struct(field_type = "locked", inner = "delta")]
+        zone: RwLock<Zone>,
+    }
+
+    #[test]
+    fn locked_values_are_diffed() {
+        let hub = |mode: &str, setpoint| Hub {
+            mode: Mutex::new(mode.to_string()),
+            zone: RwLock::new(Zone {
+                name: "den".to_string(),
+                setpoint,
+            }),
+        };
+        let (old, new) = (hub("home", 20), hub("away", 18));
+        assert_eq!(Hub::change_count(&old, &new, usize::MAX), 2);
+        assert_eq!(Hub::change_count(&old, &old, usize::MAX), 0);
+        let delta = Hub::delta(old, new).unwrap();
+        assert_eq!(
+            delta,
+            HubDelta {
+                mode: Some("away".to_string()),
+                zone: Some(ZoneDelta {
+                    name: None,
+                    setpoint: Some(18),
+                }),
+            }
+        );
+        let mut target = hub("home", 20);
+        target.apply_delta(delta);
+        assert_eq!(*target.mode.lock().unwrap(), "away");
+        assert_eq!(target.zone.read().unwrap().setpoint, 18);
+        assert!(Hub::delta(hub("home", 20), hub("home", 20)).is_none());
+    }
+
+    #[test]
+    fn opposite_counts_take_locks_in_the_same_order() {
+        use std::sync::Arc;
+
+        let hub = |mode: &str| Hub {
+            mode: Mutex::new(mode.to_string()),
+            zone: RwLock::default(),
+        };
+        let (a, b) = (Arc::new(hub("home")), Arc::new(hub("away")));
+        let threads: Vec<_> = (0..2)
+            .map(|i| {
+                let (a, b) = (a.clone(), b.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        let (old, new) = if i == 0 { (&a, &b) } else { (&b, &a) };
+                        assert_eq!(Hub::change_count(old, new, usize::MAX), 1);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! MongoDB update documents from deltas, for collections storing the
+//! derived structs as documents. Delta structs deriving with
+//! `#[delta_struct(mongodb)]` implement [`UpdateDocument`]:
+//!
+//! - a changed scalar field is `$set`, or `$unset` if it serializes to
+//!   `null`,
+//! - an `unordered` field `$pull`s its removals and `$addToSet`s its
+//!   additions, so the array is updated with set semantics,
+//! - a nested `delta` field updates the dotted paths under it, or is `$set`
+//!   as a whole when it is replaced.
+//!
+//! MongoDB rejects a `$pull` and an `$addToSet` of the same path in one
+//! update, so a delta that does both is turned into two updates, to be run
+//! in order. Requires the `mongodb` feature.
+
+use crate::{ComputeDelta, Delta, Granular, OptionDelta};
+use serde::Serialize;
+use std::fmt;
+
+pub use bson::{Bson, Document};
+
+/// Why a field of a delta could not be converted to BSON, as
+/// [`Error::Encode`](crate::Error::Encode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    path: String,
+    message: String,
+}
+
+impl Error {
+    /// The dotted path of the field that could not be converted.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "field `{}`: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The operators of an update, collected across the fields of a delta.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Update {
+    set: Document,
+    unset: Document,
+    pull: Document,
+    add_to_set: Document,
+}
+
+impl Update {
+    /// Sets `path` to `value`, or unsets it if `value` is `null`.
+    pub fn set<T: Serialize + ?Sized>(&mut self, path: String, value: &T) -> Result<(), Error> {
+        match to_bson(&path, value)? {
+            Bson::Null => self.unset.insert(path, ""),
+            value => self.set.insert(path, value),
+        };
+        Ok(())
+    }
+
+    /// Unsets `path`.
+    pub fn unset(&mut self, path: String) {
+        self.unset.insert(path, "");
+    }
+
+    /// Removes every element equal to one of `items` from the array at
+    /// `path`.
+    pub fn pull<T: Serialize>(&mut self, path: String, items: &[T]) -> Result<(), Error> {
+        if !items.is_empty() {
+            let items = to_bson(&path, items)?;
+            self.pull.insert(path, bson::doc! { "$in": items });
+        }
+        Ok(())
+    }
+
+    /// Adds each of `items` to the array at `path`, unless it is already
+    /// present.
+    pub fn add_to_set<T: Serialize>(&mut self, path: String, items: &[T]) -> Result<(), Error> {
+        if !items.is_empty() {
+            let items = to_bson(&path, items)?;
+            self.add_to_set.insert(path, bson::doc! { "$each": items });
+        }
+        Ok(())
+    }
+
+    /// The update documents to run in order, none if nothing changed.
+    pub fn into_documents(self) -> Vec<Document> {
+        let Update {
+            set,
+            unset,
+            pull,
+            add_to_set,
+        } = self;
+        let (later, now): (Vec<_>, Vec<_>) = add_to_set
+            .into_iter()
+            .partition(|(path, _)| pull.contains_key(path));
+        let mut first = Document::new();
+        for (operator, fields) in [
+            ("$set", set),
+            ("$unset", unset),
+            ("$pull", pull),
+            ("$addToSet", now.into_iter().collect()),
+        ] {
+            if !fields.is_empty() {
+                first.insert(operator, fields);
+            }
+        }
+        let mut documents = vec![];
+        if !first.is_empty() {
+            documents.push(first);
+        }
+        if !later.is_empty() {
+            let later: Document = later.into_iter().collect();
+            documents.push(bson::doc! { "$addToSet": later });
+        }
+        documents
+    }
+}
+
+fn to_bson<T: Serialize + ?Sized>(path: &str, value: &T) -> Result<Bson, Error> {
+    bson::to_bson(value).map_err(|e| Error {
+        path: path.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// A delta that can be stored as a MongoDB update.
+pub trait UpdateDocument {
+    /// Adds the delta's changes to `update`, below the dotted `prefix`.
+    fn push_update(&self, prefix: &str, update: &mut Update) -> Result<(), Error>;
+
+    /// The update documents applying the delta to a stored document, to be
+    /// run in order.
+    fn to_update_documents(&self) -> Result<Vec<Document>, Error> {
+        let mut update = Update::default();
+        self.push_update("", &mut update)?;
+        Ok(update.into_documents())
+    }
+}
+
+/// A set value is stored whole and a cleared one is unset.
+impl<T> UpdateDocument for OptionDelta<T>
+where
+    T: ComputeDelta + Serialize,
+    T::Output: UpdateDocument,
+{
+    fn push_update(&self, prefix: &str, update: &mut Update) -> Result<(), Error> {
+        match self {
+            OptionDelta::Patch(patch) => patch.push_update(prefix, update),
+            OptionDelta::Set(value) => update.set(prefix.to_string(), value),
+            OptionDelta::Clear => {
+                update.unset(prefix.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A replacement is stored whole.
+impl<T> UpdateDocument for Granular<T>
+where
+    T: Delta + Serialize,
+    T::Output: UpdateDocument,
+{
+    fn push_update(&self, prefix: &str, update: &mut Update) -> Result<(), Error> {
+        match self {
+            Granular::Patch(patch) => patch.push_update(prefix, update),
+            Granular::Replace(value) => update.set(prefix.to_string(), value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize)]
+    #[delta_struct(mongodb)]
+    struct Speaker {
+        volume: u8,
+        muted: bool,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize)]
+    #[delta_struct(mongodb)]
+    struct Room {
+        name: Option<String>,
+        #[delta_struct(field_type = "unordered")]
+        tags: Vec<String>,
+        #[delta_struct(field_type = "delta")]
+        speaker: Speaker,
+        #[delta_struct(field_type = "delta")]
+        backup: Option<Speaker>,
+    }
+
+    fn room(name: Option<&str>, tags: &[&str], volume: u8) -> Room {
+        Room {
+            name: name.map(String::from),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            speaker: Speaker {
+                volume,
+                muted: false,
+            },
+            backup: None,
+        }
+    }
+
+    #[test]
+    fn deltas_become_update_operators() {
+        let old = room(Some("den"), &["a", "b"], 3);
+        let mut new = room(None, &["b", "c"], 5);
+        new.backup = Some(Speaker {
+            volume: 1,
+            muted: true,
+        });
+        let documents = Room::delta(old, new)
+            .unwrap()
+            .to_update_documents()
+            .unwrap();
+        assert_eq!(
+            documents,
+            vec![
+                doc! {
+                    "$set": {
+                        "speaker.volume": 5,
+                        "backup": { "volume": 1, "muted": true },
+                    },
+                    "$unset": { "name": "" },
+                    "$pull": { "tags": { "$in": ["a"] } },
+                },
+                doc! { "$addToSet": { "tags": { "$each": ["c"] } } }
+            ]
+        );
+    }
+
+    #[test]
+    fn additions_alone_fit_one_update() {
+        let old = room(Some("den"), &["a"], 3);
+        let new = room(Some("den"), &["a", "b"], 3);
+        assert_eq!(
+            Room::delta(old, new)
+                .unwrap()
+                .to_update_documents()
+                .unwrap(),
+            vec![doc! { "$addToSet": { "tags": { "$each": ["b"] } } }]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! A process wide hook called by every derived `delta` and `apply_delta`,
+//! e.g. to export metrics on delta sizes and diff latency without wrapping
+//! each call site.
+//!
+//! Nothing is timed while no observer is set.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// The call being observed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Delta,
+    Apply,
+}
+
+/// A single observed call.
+#[derive(Copy, Clone, Debug)]
+pub struct Observation {
+    pub operation: Operation,
+    /// `std::any::type_name` of the deriving type.
+    pub type_name: &'static str,
+    /// The number of fields that changed, or that had a change applied.
+    pub fields_changed: usize,
+    pub duration: Duration,
+}
+
+pub type Observer = fn(&Observation);
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static OBSERVER: RwLock<Option<Observer>> = RwLock::new(None);
+
+/// Replaces the observer, or removes it with `None`.
+pub fn set_delta_observer(observer: Option<Observer>) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = observer;
+    ACTIVE.store(observer.is_some(), Ordering::Release);
+}
+
+/// Called by generated code before doing any work.
+#[doc(hidden)]
+pub fn start() -> Option<Instant> {
+    if ACTIVE.load(Ordering::Acquire) {
+        Some(Instant::now())
+    } else {
+        None
+    }
+}
+
+/// Called by generated code once done, with the result of [`start`].
+#[doc(hidden)]
+pub fn finish(
+    started: Option<Instant>,
+    operation: Operation,
+    type_name: &'static str,
+    fields_changed: usize,
+) {
+    let started = match started {
+        Some(started) => started,
+        None => return,
+    };
+    let observer = *OBSERVER.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(observer) = observer {
+        observer(&Observation {
+            operation,
+            type_name,
+            fields_changed,
+            duration: started.elapsed(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_delta_observer, Observation, Operation};
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Delta)]
+    struct Observed {
+        a: u8,
+        b: u8,
+        #[delta_struct(field_type = "unordered")]
+        c: Vec<u8>,
+    }
+
+    static SEEN: Mutex<Vec<(Operation, usize)>> = Mutex::new(Vec::new());
+
+    fn record(observation: &Observation) {
+        // Other tests run deltas concurrently.
+        if observation.type_name.ends_with("::Observed") {
+            SEEN.lock()
+                .unwrap()
+                .push((observation.operation, observation.fields_changed));
+        }
+    }
+
+    #[test]
+    fn observer_sees_delta_and_apply() {
+        let old = Observed {
+            a: 1,
+            b: 2,
+            c: vec![1],
+        };
+        let new = Observed {
+            a: 1,
+            b: 3,
+            c: vec![2],
+        };
+        set_delta_observer(Some(record));
+        let delta = Observed::delta(old.clone(), new).unwrap();
+        let mut applied = old;
+        applied.apply_delta(delta);
+        set_delta_observer(None);
+        let _ = Observed::delta(applied.clone(), applied);
+        assert_eq!(
+            *SEEN.lock().unwrap(),
+            vec![(Operation::Delta, 2), (Operation::Apply, 2)]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Applying deltas at most once, for transports that deliver messages at
+//! least once. Structs deriving with `#[delta_struct(delta_id)]` stamp
+//! each delta with a `delta_id` from [`next_id`] and get
+//! `apply_once(&mut self, delta, seen)`, which skips deltas whose ID is
+//! already in `seen`.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// The IDs of the deltas that have been applied.
+pub trait IdSet {
+    /// Records `id`, returning whether it is new.
+    fn insert(&mut self, id: u64) -> bool;
+}
+
+impl<S: BuildHasher> IdSet for HashSet<u64, S> {
+    fn insert(&mut self, id: u64) -> bool {
+        HashSet::insert(self, id)
+    }
+}
+
+impl IdSet for BTreeSet<u64> {
+    fn insert(&mut self, id: u64) -> bool {
+        BTreeSet::insert(self, id)
+    }
+}
+
+/// The IDs of the last `capacity` deltas, for when redeliveries only ever
+/// trail the original by a bounded number of messages and remembering
+/// every ID would grow without bound.
+#[derive(Clone, Debug)]
+pub struct RecentIds {
+    capacity: usize,
+    ids: VecDeque<u64>,
+}
+
+impl RecentIds {
+    pub fn new(capacity: usize) -> Self {
+        RecentIds {
+            capacity,
+            ids: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl IdSet for RecentIds {
+    fn insert(&mut self, id: u64) -> bool {
+        if self.ids.contains(&id) {
+            return false;
+        }
+        if self.ids.len() == self.capacity {
+            self.ids.pop_front();
+        }
+        if self.capacity > 0 {
+            self.ids.push_back(id);
+        }
+        true
+    }
+}
+
+/// A new ID for a delta. IDs count up from a random start, so they never
+/// repeat within a process and are unlikely to collide across processes.
+pub fn next_id() -> u64 {
+    static START: OnceLock<u64> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let start = START.get_or_init(|| RandomState::new().build_hasher().finish());
+    start.wrapping_add(COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_ids_forget_the_oldest() {
+        let mut seen = RecentIds::new(2);
+        assert!(seen.insert(1));
+        assert!(seen.insert(2));
+        assert!(!seen.insert(1));
+        assert!(seen.insert(3));
+        assert!(seen.insert(1));
+        assert!(!seen.insert(3));
+    }
+
+    #[test]
+    fn ids_are_distinct() {
+        let ids: HashSet<u64> = (0..1000).map(|_| next_id()).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+}
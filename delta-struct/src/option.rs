@@ -0,0 +1,176 @@
+//! `Delta` for `Option<T>` where `T` itself implements `Delta`.
+
+use crate::{ApplyDelta, ComputeDelta, Delta};
+use std::fmt;
+
+/// The delta of an `Option<T>` holding a nested delta type.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OptionDelta<T: ComputeDelta> {
+    /// The value was present before and after; apply the inner delta.
+    Patch(T::Output),
+    /// The value is now present, replacing whatever was there.
+    Set(T),
+    /// The value is now absent.
+    Clear,
+}
+
+impl<T: ComputeDelta> ComputeDelta for Option<T> {
+    type Output = OptionDelta<T>;
+
+    fn delta(old: Self, new: Self) -> Option<Self::Output> {
+        match (old, new) {
+            (Some(old), Some(new)) => T::delta(old, new).map(OptionDelta::Patch),
+            (None, Some(new)) => Some(OptionDelta::Set(new)),
+            (Some(_), None) => Some(OptionDelta::Clear),
+            (None, None) => None,
+        }
+    }
+
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+        match (old, new) {
+            (Some(old), Some(new)) => T::change_count(old, new, limit),
+            (None, None) => 0,
+            _ => 1,
+        }
+    }
+}
+
+impl<T: Delta> ApplyDelta<OptionDelta<T>> for Option<T> {
+    /// A `Patch` applied to `None` is dropped; use `create_if_missing` on
+    /// the field to build the value from `T::default()` instead.
+    fn apply_delta(&mut self, delta: OptionDelta<T>) {
+        match delta {
+            OptionDelta::Patch(patch) => {
+                if let Some(value) = self {
+                    value.apply_delta(patch);
+                }
+            }
+            OptionDelta::Set(value) => *self = Some(value),
+            OptionDelta::Clear => *self = None,
+        }
+    }
+
+    fn compose_delta(first: OptionDelta<T>, second: OptionDelta<T>) -> OptionDelta<T> {
+        match (first, second) {
+            (OptionDelta::Patch(first), OptionDelta::Patch(second)) => {
+                OptionDelta::Patch(T::compose_delta(first, second))
+            }
+            (OptionDelta::Set(mut value), OptionDelta::Patch(patch)) => {
+                value.apply_delta(patch);
+                OptionDelta::Set(value)
+            }
+            (OptionDelta::Clear, OptionDelta::Patch(_)) => OptionDelta::Clear,
+            (_, second) => second,
+        }
+    }
+}
+
+/// Applies `delta` to `target`, starting from `T::default()` when a patch
+/// arrives for a missing value. Used by fields marked `create_if_missing`.
+pub fn apply_or_default<T: Delta + Default>(target: &mut Option<T>, delta: OptionDelta<T>) {
+    if let (None, OptionDelta::Patch(_)) = (&target, &delta) {
+        *target = Some(T::default());
+    }
+    target.apply_delta(delta);
+}
+
+/// Composes two deltas with the semantics of [`apply_or_default`].
+pub fn compose_or_default<T: Delta + Default>(
+    first: OptionDelta<T>,
+    second: OptionDelta<T>,
+) -> OptionDelta<T> {
+    match (first, second) {
+        (OptionDelta::Clear, OptionDelta::Patch(patch)) => {
+            let mut value = T::default();
+            value.apply_delta(patch);
+            OptionDelta::Set(value)
+        }
+        (first, second) => Option::<T>::compose_delta(first, second),
+    }
+}
+
+impl<T: ComputeDelta> Clone for OptionDelta<T>
+where
+    T: Clone,
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            OptionDelta::Patch(patch) => OptionDelta::Patch(patch.clone()),
+            OptionDelta::Set(value) => OptionDelta::Set(value.clone()),
+            OptionDelta::Clear => OptionDelta::Clear,
+        }
+    }
+}
+
+impl<T: ComputeDelta> fmt::Debug for OptionDelta<T>
+where
+    T: fmt::Debug,
+    T::Output: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionDelta::Patch(patch) => f.debug_tuple("Patch").field(patch).finish(),
+            OptionDelta::Set(value) => f.debug_tuple("Set").field(value).finish(),
+            OptionDelta::Clear => f.write_str("Clear"),
+        }
+    }
+}
+
+impl<T: ComputeDelta> PartialEq for OptionDelta<T>
+where
+    T: PartialEq,
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (OptionDelta::Patch(a), OptionDelta::Patch(b)) => a == b,
+            (OptionDelta::Set(a), OptionDelta::Set(b)) => a == b,
+            (OptionDelta::Clear, OptionDelta::Clear) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: ComputeDelta> arbitrary::Arbitrary<'a> for OptionDelta<T>
+where
+    T: arbitrary::Arbitrary<'a>,
+    T::Output: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.choose_index(3)? {
+            0 => OptionDelta::Patch(u.arbitrary()?),
+            1 => OptionDelta::Set(u.arbitrary()?),
+            _ => OptionDelta::Clear,
+        })
+    }
+}
+
+/// Serializes the `Option<Option<T>>` delta member of a scalar `Option`
+/// field, for `#[delta_struct(serde)]`. Generated deltas skip the member
+/// when it is `None`, so a present `null` reads back as a cleared field
+/// rather than an unchanged one.
+#[cfg(feature = "serde")]
+pub mod nullable {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
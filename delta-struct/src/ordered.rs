@@ -0,0 +1,445 @@
+use crate::Delta;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+
+/// A single edit to an ordered collection, with the semantics of
+/// JavaScript's `Array.prototype.splice`: remove `delete_count` elements
+/// starting at `start`, then insert `insert` at `start`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Splice<T> {
+    pub start: usize,
+    pub delete_count: usize,
+    pub insert: Vec<T>,
+}
+
+impl<T: PartialEq> Splice<T> {
+    /// Computes the splices that turn `old` into `new`, in the order they
+    /// must be applied. Returns no splices when the two are equal.
+    pub fn diff(old: Vec<T>, new: Vec<T>) -> Vec<Splice<T>> {
+        let prefix = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let delete_count = old.len() - prefix - suffix;
+        let insert_end = new.len() - suffix;
+        if delete_count == 0 && insert_end == prefix {
+            return vec![];
+        }
+        let insert = new
+            .into_iter()
+            .skip(prefix)
+            .take(insert_end - prefix)
+            .collect();
+        vec![Splice {
+            start: prefix,
+            delete_count,
+            insert,
+        }]
+    }
+}
+
+/// Counts the elements deleted and inserted by the splice turning `old` into
+/// `new`, without building it.
+pub fn change_count<'a, C, T>(old: &'a C, new: &'a C) -> usize
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq + 'a,
+{
+    if crate::unordered::in_order_eq(old, new) {
+        return 0;
+    }
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (old.len() - prefix - suffix) + (new.len() - prefix - suffix)
+}
+
+impl<T> Splice<T> {
+    /// Splits the splice into splices inserting at most `max_items` elements
+    /// each, which applied in order are equivalent to it.
+    pub fn split(self, max_items: usize) -> Vec<Splice<T>> {
+        let max_items = max_items.max(1);
+        if self.insert.len() <= max_items {
+            return vec![self];
+        }
+        let mut splices = vec![];
+        let mut start = self.start;
+        let mut delete_count = self.delete_count;
+        let mut insert = self.insert.into_iter().peekable();
+        while insert.peek().is_some() {
+            let part: Vec<T> = insert.by_ref().take(max_items).collect();
+            let inserted = part.len();
+            splices.push(Splice {
+                start,
+                delete_count,
+                insert: part,
+            });
+            start += inserted;
+            delete_count = 0;
+        }
+        splices
+    }
+
+    /// Applies the splice to `target`. Like `Array.prototype.splice`, a
+    /// `start` or `delete_count` past the end of `target` is clamped.
+    pub fn apply(self, target: &mut Vec<T>) {
+        let start = self.start.min(target.len());
+        let end = start.saturating_add(self.delete_count).min(target.len());
+        target.splice(start..end, self.insert);
+    }
+}
+
+/// A single edit to an ordered collection whose elements are identified by
+/// a key. Applying a list of operations in order turns the old collection
+/// into the new one; indices refer to the collection as it is when the
+/// operation is applied.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyedOp<T: Delta> {
+    Remove { index: usize },
+    Insert { index: usize, value: T },
+    Move { from: usize, to: usize },
+    Patch { index: usize, delta: T::Output },
+}
+
+impl<T: Delta> KeyedOp<T> {
+    /// Computes the operations turning `old` into `new`, matching elements
+    /// by `key`. Elements only present in `old` are removed, elements only
+    /// present in `new` are inserted, elements that changed position are
+    /// moved, and elements that changed are patched with their own delta.
+    ///
+    /// Keys are expected to be unique within each collection.
+    pub fn diff<K, F>(old: Vec<T>, new: Vec<T>, key: F) -> Vec<KeyedOp<T>>
+    where
+        K: Eq + Hash,
+        F: Fn(&T) -> K,
+    {
+        let new_keys: HashSet<K> = new.iter().map(&key).collect();
+        let mut ops = vec![];
+        let mut current = vec![];
+        for (index, item) in old.into_iter().enumerate().rev() {
+            let item_key = key(&item);
+            if new_keys.contains(&item_key) {
+                current.push((item_key, Some(item)));
+            } else {
+                ops.push(KeyedOp::Remove { index });
+            }
+        }
+        current.reverse();
+        let mut new: Vec<Option<T>> = new.into_iter().map(Some).collect();
+        for (index, slot) in new.iter_mut().enumerate() {
+            let new_key = key(slot.as_ref().unwrap());
+            if current.get(index).map(|(k, _)| k) == Some(&new_key) {
+                continue;
+            }
+            match current.iter().position(|(k, _)| *k == new_key) {
+                Some(from) => {
+                    let moved = current.remove(from);
+                    current.insert(index, moved);
+                    ops.push(KeyedOp::Move { from, to: index });
+                }
+                None => {
+                    current.insert(index, (new_key, None));
+                    ops.push(KeyedOp::Insert {
+                        index,
+                        value: slot.take().unwrap(),
+                    });
+                }
+            }
+        }
+        for (index, ((_, old), new)) in current.into_iter().zip(new).enumerate() {
+            if let (Some(old), Some(new)) = (old, new) {
+                if let Some(delta) = T::delta(old, new) {
+                    ops.push(KeyedOp::Patch { index, delta });
+                }
+            }
+        }
+        ops
+    }
+
+    /// Applies the operation to `target`. Like [`Splice::apply`], it never
+    /// panics on a `target` other than the one it was computed from: an
+    /// insertion or move past the end of `target` goes at the end, and a
+    /// removal, move or patch of an element that isn't there is skipped.
+    pub fn apply(self, target: &mut Vec<T>) {
+        match self {
+            KeyedOp::Remove { index } => {
+                if index < target.len() {
+                    target.remove(index);
+                }
+            }
+            KeyedOp::Insert { index, value } => target.insert(index.min(target.len()), value),
+            KeyedOp::Move { from, to } => {
+                if from < target.len() {
+                    let item = target.remove(from);
+                    target.insert(to.min(target.len()), item);
+                }
+            }
+            KeyedOp::Patch { index, delta } => {
+                if let Some(item) = target.get_mut(index) {
+                    item.apply_delta(delta);
+                }
+            }
+        }
+    }
+}
+
+/// Counts the operations [`KeyedOp::diff`] would produce, with patched
+/// elements contributing their own change count.
+pub fn keyed_change_count<'a, C, T, K, F>(old: &'a C, new: &'a C, key: F, limit: usize) -> usize
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Delta + 'a,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let new_keys: HashSet<K> = new.iter().map(|item| key(item)).collect();
+    let mut current: Vec<(K, Option<&T>)> = vec![];
+    let mut count = 0;
+    for item in old {
+        let item_key = key(item);
+        if new_keys.contains(&item_key) {
+            current.push((item_key, Some(item)));
+        } else {
+            count += 1;
+        }
+    }
+    for (index, item) in new.iter().enumerate() {
+        if count > limit {
+            return count;
+        }
+        let new_key = key(item);
+        if current.get(index).map(|(k, _)| k) == Some(&new_key) {
+            continue;
+        }
+        count += 1;
+        match current.iter().position(|(k, _)| *k == new_key) {
+            Some(from) => {
+                let moved = current.remove(from);
+                current.insert(index, moved);
+            }
+            None => current.insert(index, (new_key, None)),
+        }
+    }
+    for ((_, old), new) in current.into_iter().zip(new) {
+        if count > limit {
+            return count;
+        }
+        if let Some(old) = old {
+            count += T::change_count(old, new, limit - count);
+        }
+    }
+    count
+}
+
+impl<T: Delta> Clone for KeyedOp<T>
+where
+    T: Clone,
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            KeyedOp::Remove { index } => KeyedOp::Remove { index: *index },
+            KeyedOp::Insert { index, value } => KeyedOp::Insert {
+                index: *index,
+                value: value.clone(),
+            },
+            KeyedOp::Move { from, to } => KeyedOp::Move {
+                from: *from,
+                to: *to,
+            },
+            KeyedOp::Patch { index, delta } => KeyedOp::Patch {
+                index: *index,
+                delta: delta.clone(),
+            },
+        }
+    }
+}
+
+impl<T: Delta> fmt::Debug for KeyedOp<T>
+where
+    T: fmt::Debug,
+    T::Output: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyedOp::Remove { index } => f.debug_struct("Remove").field("index", index).finish(),
+            KeyedOp::Insert { index, value } => f
+                .debug_struct("Insert")
+                .field("index", index)
+                .field("value", value)
+                .finish(),
+            KeyedOp::Move { from, to } => f
+                .debug_struct("Move")
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            KeyedOp::Patch { index, delta } => f
+                .debug_struct("Patch")
+                .field("index", index)
+                .field("delta", delta)
+                .finish(),
+        }
+    }
+}
+
+impl<T: Delta> PartialEq for KeyedOp<T>
+where
+    T: PartialEq,
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (KeyedOp::Remove { index: a }, KeyedOp::Remove { index: b }) => a == b,
+            (KeyedOp::Insert { index: a, value: c }, KeyedOp::Insert { index: b, value: d }) => {
+                a == b && c == d
+            }
+            (KeyedOp::Move { from: a, to: c }, KeyedOp::Move { from: b, to: d }) => {
+                a == b && c == d
+            }
+            (KeyedOp::Patch { index: a, delta: c }, KeyedOp::Patch { index: b, delta: d }) => {
+                a == b && c == d
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: Delta> arbitrary::Arbitrary<'a> for KeyedOp<T>
+where
+    T: arbitrary::Arbitrary<'a>,
+    T::Output: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.choose_index(4)? {
+            0 => KeyedOp::Remove {
+                index: u.arbitrary()?,
+            },
+            1 => KeyedOp::Insert {
+                index: u.arbitrary()?,
+                value: u.arbitrary()?,
+            },
+            2 => KeyedOp::Move {
+                from: u.arbitrary()?,
+                to: u.arbitrary()?,
+            },
+            _ => KeyedOp::Patch {
+                index: u.arbitrary()?,
+                delta: u.arbitrary()?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(old: Vec<i32>, new: Vec<i32>) -> Vec<Splice<i32>> {
+        let mut applied = old.clone();
+        let splices = Splice::diff(old, new.clone());
+        for splice in splices.clone() {
+            splice.apply(&mut applied);
+        }
+        assert_eq!(applied, new);
+        splices
+    }
+
+    #[test]
+    fn splice_covers_only_the_changed_middle() {
+        assert_eq!(
+            roundtrip(vec![1, 2, 3, 4, 5], vec![1, 2, 9, 5]),
+            vec![Splice {
+                start: 2,
+                delete_count: 2,
+                insert: vec![9],
+            }]
+        );
+        assert_eq!(roundtrip(vec![1, 2], vec![1, 2]), vec![]);
+        roundtrip(vec![], vec![1, 2]);
+        roundtrip(vec![1, 2], vec![]);
+        roundtrip(vec![1, 1, 1], vec![1, 1]);
+    }
+
+    #[test]
+    fn split_splices_apply_like_the_original() {
+        let splice = Splice {
+            start: 1,
+            delete_count: 2,
+            insert: vec![7, 8, 9, 10, 11],
+        };
+        let parts = splice.clone().split(2);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[2].start, 5);
+        let mut whole = vec![1, 2, 3, 4];
+        splice.apply(&mut whole);
+        let mut split = vec![1, 2, 3, 4];
+        for part in parts {
+            part.apply(&mut split);
+        }
+        assert_eq!(split, whole);
+    }
+
+    #[derive(Clone, Debug, crate::Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Zone {
+        id: u8,
+        armed: bool,
+    }
+
+    #[test]
+    fn keyed_ops_skip_what_a_stale_base_lacks() {
+        let zone = |id, armed| Zone { id, armed };
+        let old = vec![zone(1, false), zone(2, false), zone(3, false)];
+        let new = vec![zone(3, true), zone(2, false), zone(4, false)];
+        let ops = KeyedOp::diff(old, new, |zone| zone.id);
+        assert!(ops.iter().any(|op| matches!(op, KeyedOp::Move { .. })));
+        assert!(ops.iter().any(|op| matches!(op, KeyedOp::Patch { .. })));
+
+        let ids = |zones: &[Zone]| zones.iter().map(|zone| zone.id).collect::<Vec<_>>();
+        let mut empty = vec![];
+        for op in ops.clone() {
+            op.apply(&mut empty);
+        }
+        assert_eq!(ids(&empty), [4]);
+
+        let mut shorter = vec![zone(1, false)];
+        for op in ops {
+            op.apply(&mut shorter);
+        }
+        assert_eq!(ids(&shorter), [4]);
+
+        let mut duplicated = vec![zone(5, false)];
+        KeyedOp::Move { from: 3, to: 0 }.apply(&mut duplicated);
+        KeyedOp::Move { from: 0, to: 7 }.apply(&mut duplicated);
+        KeyedOp::Remove { index: 9 }.apply(&mut duplicated);
+        KeyedOp::Insert {
+            index: 9,
+            value: zone(6, false),
+        }
+        .apply(&mut duplicated);
+        assert_eq!(duplicated, vec![zone(5, false), zone(6, false)]);
+    }
+}
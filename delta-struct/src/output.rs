@@ -0,0 +1,56 @@
+use crate::{ApplyDelta, ComputeDelta};
+
+/// The operations every generated delta struct supports, for code generic
+/// over the deltas of any derived type, such as a log or a sync layer.
+///
+/// Implemented by `#[derive(Delta)]` for the delta structs it generates,
+/// except with `with_replace` or a custom `output`. It is sealed, so it
+/// can gain methods without breaking anyone.
+///
+/// ```
+/// use delta_struct::DeltaOutput;
+///
+/// fn describe<D: DeltaOutput>(delta: &D) -> String {
+///     if delta.is_empty() {
+///         "nothing changed".to_string()
+///     } else {
+///         format!("changed {}", delta.changed_fields().join(", "))
+///     }
+/// }
+/// ```
+pub trait DeltaOutput: Sized + Sealed {
+    /// The type the deltas are computed from and applied to.
+    type Target: ComputeDelta<Output = Self> + ApplyDelta<Self>;
+
+    /// The names of the fields, in declaration order, for table-driven code
+    /// such as exporters. Generated structs also have it as an inherent
+    /// const, along with `FIELD_COUNT`.
+    const FIELDS: &'static [&'static str];
+
+    const FIELD_COUNT: usize = Self::FIELDS.len();
+
+    /// Whether the delta changes nothing.
+    fn is_empty(&self) -> bool;
+
+    /// Whether the delta changes the field at `index` in
+    /// [`DeltaOutput::FIELDS`]. Out of range indices are unchanged.
+    fn is_field_changed(&self, index: usize) -> bool;
+
+    /// The names of the fields the delta changes, in declaration order.
+    fn changed_fields(&self) -> Vec<&'static str> {
+        (0..Self::FIELD_COUNT)
+            .filter(|index| self.is_field_changed(*index))
+            .map(|index| Self::FIELDS[index])
+            .collect()
+    }
+
+    /// Combines the delta with `next`, a delta following it, as
+    /// [`ApplyDelta::compose_delta`].
+    fn merge(self, next: Self) -> Self {
+        <Self::Target as ApplyDelta<Self>>::compose_delta(self, next)
+    }
+}
+
+/// Keeps [`DeltaOutput`] from being implemented outside generated code.
+#[doc(hidden)]
+pub trait Sealed {}
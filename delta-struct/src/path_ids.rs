@@ -0,0 +1,74 @@
+//! Small integer IDs for the field paths of a delta, so the paths a delta
+//! changes can be reported as varints instead of strings, e.g. in
+//! telemetry. Implemented for delta structs deriving with
+//! `#[delta_struct(path_ids)]`, which also get `paths()`, the table of
+//! dotted paths indexed by ID, and `changed_path_ids()`.
+//!
+//! Paths are numbered depth first in declaration order, each nested delta
+//! field followed by the paths under it. The IDs stay the same as long as
+//! fields are only ever added at the end of the outermost struct.
+
+use crate::{ComputeDelta, Delta, Granular, OptionDelta};
+
+pub trait PathIds {
+    /// The number of paths under the delta.
+    fn path_count() -> u32;
+
+    /// Pushes the dotted paths under the delta, below `prefix`, onto
+    /// `paths` in ID order.
+    fn push_paths(prefix: &str, paths: &mut Vec<String>);
+
+    /// Pushes the IDs of the paths the delta changes onto `ids`, numbering
+    /// its paths from `first`. A changed nested field is reported along
+    /// with the changed paths under it.
+    fn push_changed_path_ids(&self, first: u32, ids: &mut Vec<u32>);
+}
+
+/// The path of the field `name` below `prefix`.
+pub fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// A value being set or cleared only changes the field holding it.
+impl<T: ComputeDelta> PathIds for OptionDelta<T>
+where
+    T::Output: PathIds,
+{
+    fn path_count() -> u32 {
+        T::Output::path_count()
+    }
+
+    fn push_paths(prefix: &str, paths: &mut Vec<String>) {
+        T::Output::push_paths(prefix, paths);
+    }
+
+    fn push_changed_path_ids(&self, first: u32, ids: &mut Vec<u32>) {
+        if let OptionDelta::Patch(patch) = self {
+            patch.push_changed_path_ids(first, ids);
+        }
+    }
+}
+
+/// A replacement only changes the field holding it.
+impl<T: Delta> PathIds for Granular<T>
+where
+    T::Output: PathIds,
+{
+    fn path_count() -> u32 {
+        T::Output::path_count()
+    }
+
+    fn push_paths(prefix: &str, paths: &mut Vec<String>) {
+        T::Output::push_paths(prefix, paths);
+    }
+
+    fn push_changed_path_ids(&self, first: u32, ids: &mut Vec<u32>) {
+        if let Granular::Patch(patch) = self {
+            patch.push_changed_path_ids(first, ids);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Strategies for the persistent collections of the `im` crate, applying
+//! deltas with their in-place updates so the unchanged parts of a
+//! collection stay shared instead of being rebuilt. Requires the `im`
+//! feature.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Clone, Delta)]
+//! struct Directory {
+//!     #[delta_struct(strategy = "delta_struct::persistent::MapStrategy")]
+//!     names: im::HashMap<u32, String>,
+//!     #[delta_struct(strategy = "delta_struct::persistent::VectorStrategy")]
+//!     recent: im::Vector<u32>,
+//! }
+//! ```
+
+use crate::strategy::{DiffStrategy, UnorderedDelta};
+use im::{HashMap, OrdSet, Vector};
+use std::hash::Hash;
+
+/// The delta of an `im::HashMap`. Applying it removes the `remove` keys and
+/// then inserts the `insert` entries, replacing the values of keys that are
+/// already present.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct MapDelta<K, V> {
+    pub remove: Vec<K>,
+    pub insert: Vec<(K, V)>,
+}
+
+/// Diffs an `im::HashMap` per key. Maps sharing their root are equal
+/// without being compared.
+pub struct MapStrategy;
+
+impl<K, V> DiffStrategy<HashMap<K, V>> for MapStrategy
+where
+    K: Hash + Eq + Clone,
+    V: PartialEq + Clone,
+{
+    type Delta = MapDelta<K, V>;
+
+    fn diff(old: HashMap<K, V>, new: HashMap<K, V>) -> Option<MapDelta<K, V>> {
+        if old.ptr_eq(&new) {
+            return None;
+        }
+        let insert: Vec<(K, V)> = new
+            .iter()
+            .filter(|(key, value)| old.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let remove: Vec<K> = old
+            .keys()
+            .filter(|key| !new.contains_key(*key))
+            .cloned()
+            .collect();
+        if insert.is_empty() && remove.is_empty() {
+            None
+        } else {
+            Some(MapDelta { remove, insert })
+        }
+    }
+
+    fn apply(target: &mut HashMap<K, V>, delta: MapDelta<K, V>) {
+        for key in &delta.remove {
+            target.remove(key);
+        }
+        for (key, value) in delta.insert {
+            target.insert(key, value);
+        }
+    }
+
+    fn change_count(old: &HashMap<K, V>, new: &HashMap<K, V>, limit: usize) -> usize {
+        if old.ptr_eq(new) {
+            return 0;
+        }
+        let mut count = 0;
+        for (key, value) in new {
+            if old.get(key) != Some(value) {
+                count += 1;
+                if count > limit {
+                    return count;
+                }
+            }
+        }
+        count + old.keys().filter(|key| !new.contains_key(*key)).count()
+    }
+
+    fn compose(first: MapDelta<K, V>, second: MapDelta<K, V>) -> MapDelta<K, V> {
+        let MapDelta {
+            mut remove,
+            mut insert,
+        } = first;
+        insert.retain(|(key, _)| {
+            !second.remove.contains(key) && !second.insert.iter().any(|(k, _)| k == key)
+        });
+        for key in second.remove {
+            if !remove.contains(&key) {
+                remove.push(key);
+            }
+        }
+        insert.extend(second.insert);
+        MapDelta { remove, insert }
+    }
+}
+
+/// Diffs an `im::OrdSet` like an unordered field, inserting and removing
+/// elements in place.
+pub struct OrdSetStrategy;
+
+impl<T: Ord + Clone> DiffStrategy<OrdSet<T>> for OrdSetStrategy {
+    type Delta = UnorderedDelta<T>;
+
+    fn diff(old: OrdSet<T>, new: OrdSet<T>) -> Option<UnorderedDelta<T>> {
+        if old.ptr_eq(&new) {
+            return None;
+        }
+        let add: Vec<T> = new.iter().filter(|i| !old.contains(*i)).cloned().collect();
+        let remove: Vec<T> = old.iter().filter(|i| !new.contains(*i)).cloned().collect();
+        if add.is_empty() && remove.is_empty() {
+            None
+        } else {
+            Some(UnorderedDelta { add, remove })
+        }
+    }
+
+    fn apply(target: &mut OrdSet<T>, delta: UnorderedDelta<T>) {
+        for item in &delta.remove {
+            target.remove(item);
+        }
+        for item in delta.add {
+            target.insert(item);
+        }
+    }
+
+    fn change_count(old: &OrdSet<T>, new: &OrdSet<T>, limit: usize) -> usize {
+        if old.ptr_eq(new) {
+            return 0;
+        }
+        crate::unordered::change_count(old, new, limit)
+    }
+
+    fn compose(first: UnorderedDelta<T>, second: UnorderedDelta<T>) -> UnorderedDelta<T> {
+        <crate::strategy::UnorderedStrategy as DiffStrategy<Vec<T>>>::compose(first, second)
+    }
+}
+
+/// Diffs an `im::Vector` like an unordered field, treating it as a
+/// multiset. Removed elements are taken out where they are and added ones
+/// are pushed to the back.
+pub struct VectorStrategy;
+
+impl<T: PartialEq + Clone> DiffStrategy<Vector<T>> for VectorStrategy {
+    type Delta = UnorderedDelta<T>;
+
+    fn diff(old: Vector<T>, new: Vector<T>) -> Option<UnorderedDelta<T>> {
+        if old.ptr_eq(&new) {
+            return None;
+        }
+        <crate::strategy::UnorderedStrategy as DiffStrategy<Vector<T>>>::diff(old, new)
+    }
+
+    fn apply(target: &mut Vector<T>, delta: UnorderedDelta<T>) {
+        for item in &delta.remove {
+            if let Some(index) = target.index_of(item) {
+                target.remove(index);
+            }
+        }
+        for item in delta.add {
+            target.push_back(item);
+        }
+    }
+
+    fn change_count(old: &Vector<T>, new: &Vector<T>, limit: usize) -> usize {
+        if old.ptr_eq(new) {
+            return 0;
+        }
+        crate::unordered::change_count(old, new, limit)
+    }
+
+    fn compose(first: UnorderedDelta<T>, second: UnorderedDelta<T>) -> UnorderedDelta<T> {
+        <crate::strategy::UnorderedStrategy as DiffStrategy<Vec<T>>>::compose(first, second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_are_updated_in_place() {
+        let old: HashMap<u32, &str> = vec![(1, "den"), (2, "hall"), (3, "attic")]
+            .into_iter()
+            .collect();
+        let mut new = old.clone();
+        new.insert(2, "hallway");
+        new.remove(&3);
+        new.insert(4, "porch");
+        let delta = MapStrategy::diff(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.remove, vec![3]);
+        assert_eq!(MapStrategy::change_count(&old, &new, usize::MAX), 3);
+        let mut applied = old.clone();
+        MapStrategy::apply(&mut applied, delta);
+        assert_eq!(applied, new);
+        assert_eq!(MapStrategy::diff(new.clone(), new), None);
+    }
+
+    #[test]
+    fn map_compose_matches_sequential_apply() {
+        let a: HashMap<u32, u8> = vec![(1, 1), (2, 2)].into_iter().collect();
+        let b: HashMap<u32, u8> = vec![(2, 3), (3, 3)].into_iter().collect();
+        let c: HashMap<u32, u8> = vec![(1, 4), (3, 3)].into_iter().collect();
+        let delta = MapStrategy::compose(
+            MapStrategy::diff(a.clone(), b.clone()).unwrap(),
+            MapStrategy::diff(b, c.clone()).unwrap(),
+        );
+        let mut composed = a;
+        MapStrategy::apply(&mut composed, delta);
+        assert_eq!(composed, c);
+    }
+
+    #[test]
+    fn sets_and_vectors_apply_as_multisets() {
+        let old: OrdSet<u8> = vec![1, 2, 3].into_iter().collect();
+        let new: OrdSet<u8> = vec![2, 3, 4].into_iter().collect();
+        let mut applied = old.clone();
+        OrdSetStrategy::apply(
+            &mut applied,
+            OrdSetStrategy::diff(old, new.clone()).unwrap(),
+        );
+        assert_eq!(applied, new);
+
+        let old: Vector<u8> = vec![1, 2, 2, 3].into_iter().collect();
+        let new: Vector<u8> = vec![3, 2, 5].into_iter().collect();
+        let mut applied = old.clone();
+        VectorStrategy::apply(
+            &mut applied,
+            VectorStrategy::diff(old, new.clone()).unwrap(),
+        );
+        assert!(crate::unordered::multiset_eq(&applied, &new));
+    }
+}
@@ -0,0 +1,101 @@
+//! `Delta` for `Box<T>`, `Rc<T>` and `Arc<T>`, forwarding to `T`, so
+//! pointers to nested delta types can be nested themselves, as in
+//! `Vec<Box<T>>` or `Option<Arc<T>>`.
+//!
+//! Shared pointers are applied to with `make_mut`, which clones `T` if the
+//! value is shared.
+
+use crate::{ApplyDelta, ComputeDelta};
+use std::rc::Rc;
+use std::sync::Arc;
+
+impl<T: ComputeDelta> ComputeDelta for Box<T> {
+    type Output = T::Output;
+
+    fn delta(old: Self, new: Self) -> Option<T::Output> {
+        T::delta(*old, *new)
+    }
+
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+        T::change_count(old, new, limit)
+    }
+}
+
+impl<D, T: ApplyDelta<D>> ApplyDelta<D> for Box<T> {
+    fn apply_delta(&mut self, delta: D) {
+        (**self).apply_delta(delta);
+    }
+
+    fn compose_delta(first: D, second: D) -> D {
+        T::compose_delta(first, second)
+    }
+}
+
+macro_rules! shared_pointer_delta {
+    ($($pointer:ident),+) => {
+        $(
+            /// Values behind the same pointer are unchanged without being
+            /// compared.
+            impl<T: ComputeDelta + Clone> ComputeDelta for $pointer<T> {
+                type Output = T::Output;
+
+                fn delta(old: Self, new: Self) -> Option<T::Output> {
+                    if $pointer::ptr_eq(&old, &new) {
+                        return None;
+                    }
+                    T::delta($pointer::unwrap_or_clone(old), $pointer::unwrap_or_clone(new))
+                }
+
+                fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+                    if $pointer::ptr_eq(old, new) {
+                        return 0;
+                    }
+                    T::change_count(old, new, limit)
+                }
+            }
+
+            impl<D, T: ApplyDelta<D> + Clone> ApplyDelta<D> for $pointer<T> {
+                fn apply_delta(&mut self, delta: D) {
+                    $pointer::make_mut(self).apply_delta(delta);
+                }
+
+                fn compose_delta(first: D, second: D) -> D {
+                    T::compose_delta(first, second)
+                }
+            }
+        )+
+    };
+}
+
+shared_pointer_delta!(Rc, Arc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Delta;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Lock {
+        locked: bool,
+        battery: u8,
+    }
+
+    #[test]
+    fn shared_values_are_cloned_on_apply() {
+        let old = Arc::new(Lock {
+            locked: false,
+            battery: 90,
+        });
+        let new = Arc::new(Lock {
+            locked: true,
+            battery: 90,
+        });
+        assert_eq!(Arc::delta(old.clone(), old.clone()), None);
+        let delta = Arc::delta(old.clone(), new.clone()).unwrap();
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+        assert!(!old.locked);
+    }
+}
@@ -0,0 +1,114 @@
+//! Filtering a delta down to the changes under some fields, e.g. to send
+//! each subscriber only the part of the state it follows. Implemented for
+//! delta structs deriving with `#[delta_struct(project)]`.
+
+use crate::{Delta, Granular, OptionDelta};
+use std::fmt;
+
+/// A path to a field, as the names of the fields leading to it from the
+/// outermost struct. Tuple struct fields are named by their index.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeltaPath(Vec<String>);
+
+impl DeltaPath {
+    pub fn new<I, S>(segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        DeltaPath(segments.into_iter().map(Into::into).collect())
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+}
+
+/// Parses a dotted path such as `"settings.volume"`.
+impl From<&str> for DeltaPath {
+    fn from(path: &str) -> Self {
+        DeltaPath::new(path.split('.'))
+    }
+}
+
+impl fmt::Display for DeltaPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join("."))
+    }
+}
+
+pub trait Project {
+    /// Keeps only the changes at or under one of `paths`.
+    fn project(&self, paths: &[DeltaPath]) -> Self;
+}
+
+/// Which changes of a single field some paths select.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Selection {
+    None,
+    /// The field itself is selected, with everything under it.
+    All,
+    /// Only the given paths under the field are selected.
+    Nested(Vec<DeltaPath>),
+}
+
+/// Which changes of the field named `name` are selected by `paths`.
+pub fn select(paths: &[DeltaPath], name: &str) -> Selection {
+    let mut nested = vec![];
+    for path in paths {
+        match path.0.split_first() {
+            Some((first, [])) if first == name => return Selection::All,
+            Some((first, rest)) if first == name => nested.push(DeltaPath(rest.to_vec())),
+            _ => {}
+        }
+    }
+    if nested.is_empty() {
+        Selection::None
+    } else {
+        Selection::Nested(nested)
+    }
+}
+
+/// A value being set is kept whole if any path under it is selected.
+impl<T: Delta + Clone> Project for OptionDelta<T>
+where
+    T::Output: Project,
+{
+    fn project(&self, paths: &[DeltaPath]) -> Self {
+        match self {
+            OptionDelta::Patch(patch) => OptionDelta::Patch(patch.project(paths)),
+            OptionDelta::Set(value) => OptionDelta::Set(value.clone()),
+            OptionDelta::Clear => OptionDelta::Clear,
+        }
+    }
+}
+
+/// A replacement is kept whole if any path under it is selected.
+impl<T: Delta + Clone> Project for Granular<T>
+where
+    T::Output: Project,
+{
+    fn project(&self, paths: &[DeltaPath]) -> Self {
+        match self {
+            Granular::Patch(patch) => Granular::Patch(patch.project(paths)),
+            Granular::Replace(value) => Granular::Replace(value.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_splits_off_the_first_segment() {
+        let paths = ["a.b", "a.c.d", "e"].map(DeltaPath::from);
+        assert_eq!(
+            select(&paths, "a"),
+            Selection::Nested(vec![DeltaPath::from("b"), DeltaPath::from("c.d")])
+        );
+        assert_eq!(select(&paths, "e"), Selection::All);
+        assert_eq!(select(&paths, "b"), Selection::None);
+        assert_eq!(DeltaPath::from("a.c.d").to_string(), "a.c.d");
+    }
+}
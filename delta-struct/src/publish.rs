@@ -0,0 +1,162 @@
+use crate::Delta;
+use tokio::sync::{broadcast, watch};
+
+/// Broadcasts the deltas between successive states of a value to any
+/// number of [`DeltaSubscriber`]s, which keep replicas of it. Requires the
+/// `tokio` feature.
+///
+/// Subscribers start from a snapshot of the current state. One that falls
+/// more than `capacity` deltas behind catches up from the latest snapshot
+/// instead of the deltas it missed.
+pub struct DeltaPublisher<T: Delta> {
+    sequence: u64,
+    snapshot: watch::Sender<(u64, T)>,
+    deltas: broadcast::Sender<(u64, T::Output)>,
+}
+
+impl<T> DeltaPublisher<T>
+where
+    T: Delta + Clone,
+    T::Output: Clone,
+{
+    /// Creates a publisher of `initial`, buffering up to `capacity` deltas
+    /// for subscribers that haven't received them yet.
+    pub fn new(initial: T, capacity: usize) -> Self {
+        DeltaPublisher {
+            sequence: 0,
+            snapshot: watch::Sender::new((0, initial)),
+            deltas: broadcast::channel(capacity).0,
+        }
+    }
+
+    /// Broadcasts the delta from the current state to `new`, if there is
+    /// one, and makes `new` the current state. Returns whether anything
+    /// changed.
+    pub fn publish(&mut self, new: T) -> bool {
+        let old = self.snapshot.borrow().1.clone();
+        match T::delta(old, new.clone()) {
+            Some(delta) => {
+                self.sequence += 1;
+                // The snapshot is updated first, so a subscriber catching up
+                // never finds it older than a delta it has received.
+                self.snapshot.send_replace((self.sequence, new));
+                // Sending only fails if nobody is subscribed.
+                let _ = self.deltas.send((self.sequence, delta));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The current state, as of the last `publish`.
+    pub fn state(&self) -> T {
+        self.snapshot.borrow().1.clone()
+    }
+
+    /// Creates a subscriber replicating the current state.
+    pub fn subscribe(&self) -> DeltaSubscriber<T> {
+        let deltas = self.deltas.subscribe();
+        let snapshot = self.snapshot.subscribe();
+        let (sequence, state) = snapshot.borrow().clone();
+        DeltaSubscriber {
+            sequence,
+            state,
+            snapshot,
+            deltas,
+        }
+    }
+}
+
+/// A replica of the value of a [`DeltaPublisher`], kept up to date by
+/// applying the deltas it broadcasts.
+pub struct DeltaSubscriber<T: Delta> {
+    sequence: u64,
+    state: T,
+    snapshot: watch::Receiver<(u64, T)>,
+    deltas: broadcast::Receiver<(u64, T::Output)>,
+}
+
+impl<T> DeltaSubscriber<T>
+where
+    T: Delta + Clone,
+    T::Output: Clone,
+{
+    /// The replicated state.
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    /// Waits for the publisher's next change and applies it to the
+    /// replica. Returns `false` once the publisher has been dropped.
+    pub async fn changed(&mut self) -> bool {
+        loop {
+            match self.deltas.recv().await {
+                Ok((sequence, _)) if sequence <= self.sequence => {}
+                Ok((sequence, delta)) if sequence == self.sequence + 1 => {
+                    self.state.apply_delta(delta);
+                    self.sequence = sequence;
+                    return true;
+                }
+                // Deltas were missed, so the replica is rebuilt from the
+                // snapshot; any older deltas still buffered are skipped.
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let (sequence, state) = self.snapshot.borrow().clone();
+                    if sequence > self.sequence {
+                        self.sequence = sequence;
+                        self.state = state;
+                        return true;
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone)]")]
+    struct Lights {
+        level: u8,
+        #[delta_struct(field_type = "unordered")]
+        rooms: Vec<u8>,
+    }
+
+    fn lights(level: u8, rooms: &[u8]) -> Lights {
+        Lights {
+            level,
+            rooms: rooms.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_replicate_from_a_snapshot() {
+        let mut publisher = DeltaPublisher::new(lights(1, &[1]), 8);
+        assert!(publisher.publish(lights(2, &[1])));
+        let mut late = publisher.subscribe();
+        assert_eq!(late.state(), &lights(2, &[1]));
+        assert!(!publisher.publish(lights(2, &[1])));
+        assert!(publisher.publish(lights(2, &[1, 3])));
+        assert!(late.changed().await);
+        assert_eq!(late.state(), &publisher.state());
+        drop(publisher);
+        assert!(!late.changed().await);
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_catch_up_from_the_snapshot() {
+        let mut publisher = DeltaPublisher::new(lights(0, &[]), 1);
+        let mut subscriber = publisher.subscribe();
+        for level in 1..=3 {
+            publisher.publish(lights(level, &[level]));
+        }
+        assert!(subscriber.changed().await);
+        assert_eq!(subscriber.state(), &lights(3, &[3]));
+        publisher.publish(lights(4, &[3]));
+        assert!(subscriber.changed().await);
+        assert_eq!(subscriber.state(), &lights(4, &[3]));
+    }
+}
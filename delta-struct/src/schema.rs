@@ -0,0 +1,72 @@
+//! A description of the fields of a delta struct that is available at
+//! runtime, for tooling that renders or edits the deltas of any derived
+//! type. Implemented for structs deriving with `#[delta_struct(schema)]`.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub trait Schema {
+    fn delta_schema() -> &'static DeltaSchema;
+}
+
+#[derive(Debug)]
+pub struct DeltaSchema {
+    /// The name of the struct the deltas are computed from.
+    pub name: &'static str,
+    pub fields: &'static [FieldSchema],
+}
+
+impl DeltaSchema {
+    pub fn field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct FieldSchema {
+    /// The field's name, or its index for tuple structs.
+    pub name: &'static str,
+    /// The field's type, as written in the struct.
+    pub type_name: &'static str,
+    pub kind: FieldKind,
+    /// The schema of a `delta` field's type.
+    pub nested: Option<fn() -> &'static DeltaSchema>,
+}
+
+impl FieldSchema {
+    pub fn nested(&self) -> Option<&'static DeltaSchema> {
+        self.nested.map(|schema| schema())
+    }
+}
+
+/// How a field is diffed, after its `field_type`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FieldKind {
+    Scalar,
+    Float,
+    Bitset,
+    Ordered,
+    Unordered,
+    UnorderedScalar,
+    Keyed,
+    Delta,
+    /// Diffed by a `DiffStrategy`, including the field types implemented
+    /// with one, such as `sorted` and `json`.
+    Strategy,
+}
+
+/// Wrappers of a nested type are described by its schema.
+macro_rules! forward_schema {
+    ($($wrapper:ident),+) => {
+        $(
+            impl<T: Schema> Schema for $wrapper<T> {
+                fn delta_schema() -> &'static DeltaSchema {
+                    T::delta_schema()
+                }
+            }
+        )+
+    };
+}
+
+forward_schema!(Option, Box, Rc, Arc, Vec);
@@ -0,0 +1,203 @@
+//! Per-key diffing of maps of sets, for `field_type = "set_map"` fields.
+//! A change to one member of one key's set is sent as that member alone,
+//! instead of replacing the key's whole set.
+
+use crate::strategy::{DiffStrategy, UnorderedDelta};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// The delta of a `HashMap<K, HashSet<V>>`. Applying it removes the
+/// `remove` keys with their sets, and then updates the sets of the `update`
+/// keys, inserting keys that aren't in the map yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SetMapDelta<K, V> {
+    pub remove: Vec<K>,
+    /// Per key, the members removed from and then added to its set.
+    pub update: Vec<(K, UnorderedDelta<V>)>,
+}
+
+impl<K, V> SetMapDelta<K, V> {
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.update.is_empty()
+    }
+}
+
+impl<K, V> Default for SetMapDelta<K, V> {
+    fn default() -> Self {
+        SetMapDelta {
+            remove: vec![],
+            update: vec![],
+        }
+    }
+}
+
+/// Diffs a `HashMap<K, HashSet<V>>` per key, like `field_type = "set_map"`.
+pub struct SetMapStrategy;
+
+impl<K, V> DiffStrategy<HashMap<K, HashSet<V>>> for SetMapStrategy
+where
+    K: Eq + Hash,
+    V: Eq + Hash,
+{
+    type Delta = SetMapDelta<K, V>;
+
+    fn diff(
+        mut old: HashMap<K, HashSet<V>>,
+        new: HashMap<K, HashSet<V>>,
+    ) -> Option<SetMapDelta<K, V>> {
+        let mut delta = SetMapDelta::default();
+        for (key, mut members) in new {
+            match old.remove(&key) {
+                Some(previous) => {
+                    let remove: Vec<V> = previous
+                        .into_iter()
+                        .filter(|member| !members.remove(member))
+                        .collect();
+                    if !remove.is_empty() || !members.is_empty() {
+                        let add = members.into_iter().collect();
+                        delta.update.push((key, UnorderedDelta { add, remove }));
+                    }
+                }
+                None => {
+                    let add = members.into_iter().collect();
+                    delta.update.push((
+                        key,
+                        UnorderedDelta {
+                            add,
+                            remove: vec![],
+                        },
+                    ));
+                }
+            }
+        }
+        delta.remove = old.into_keys().collect();
+        if delta.is_empty() {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+
+    fn apply(target: &mut HashMap<K, HashSet<V>>, delta: SetMapDelta<K, V>) {
+        for key in delta.remove {
+            target.remove(&key);
+        }
+        for (key, UnorderedDelta { add, remove }) in delta.update {
+            let members = target.entry(key).or_default();
+            for member in remove {
+                members.remove(&member);
+            }
+            members.extend(add);
+        }
+    }
+
+    /// One change per member added to or removed from a set, and one per
+    /// key inserted or removed with an empty set.
+    fn change_count(
+        old: &HashMap<K, HashSet<V>>,
+        new: &HashMap<K, HashSet<V>>,
+        limit: usize,
+    ) -> usize {
+        let mut count = 0;
+        for (key, members) in new {
+            if count > limit {
+                return count;
+            }
+            count += match old.get(key) {
+                Some(previous) => members.symmetric_difference(previous).count(),
+                None => members.len().max(1),
+            };
+        }
+        count
+            + old
+                .iter()
+                .filter(|(key, _)| !new.contains_key(key))
+                .map(|(_, members)| members.len().max(1))
+                .sum::<usize>()
+    }
+
+    fn compose(first: SetMapDelta<K, V>, second: SetMapDelta<K, V>) -> SetMapDelta<K, V> {
+        let SetMapDelta {
+            mut remove,
+            mut update,
+        } = first;
+        for removed in second.remove {
+            update.retain(|(key, _)| *key != removed);
+            // `first` may have removed it and then inserted it again.
+            if !remove.contains(&removed) {
+                remove.push(removed);
+            }
+        }
+        for (key, second) in second.update {
+            match update.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, first)) => {
+                    first.add.retain(|member| !second.remove.contains(member));
+                    first.add.extend(second.add);
+                    first.remove.extend(second.remove);
+                }
+                None => update.push((key, second)),
+            }
+        }
+        SetMapDelta { remove, update }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&'static str, &[u8])]) -> HashMap<&'static str, HashSet<u8>> {
+        entries
+            .iter()
+            .map(|(key, members)| (*key, members.iter().copied().collect()))
+            .collect()
+    }
+
+    fn diff(
+        old: &HashMap<&'static str, HashSet<u8>>,
+        new: &HashMap<&'static str, HashSet<u8>>,
+    ) -> SetMapDelta<&'static str, u8> {
+        SetMapStrategy::diff(old.clone(), new.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let a = map(&[("admin", &[1, 2]), ("guest", &[1]), ("owner", &[3])]);
+        let b = map(&[("admin", &[2, 3]), ("owner", &[3]), ("viewer", &[])]);
+        let c = map(&[("admin", &[1, 3]), ("guest", &[4]), ("viewer", &[5])]);
+        let mut sequential = a.clone();
+        SetMapStrategy::apply(&mut sequential, diff(&a, &b));
+        SetMapStrategy::apply(&mut sequential, diff(&b, &c));
+        assert_eq!(sequential, c);
+        let mut composed = a.clone();
+        SetMapStrategy::apply(
+            &mut composed,
+            SetMapStrategy::compose(diff(&a, &b), diff(&b, &c)),
+        );
+        assert_eq!(composed, c);
+    }
+
+    #[test]
+    fn only_changed_members_are_sent() {
+        let old = map(&[("admin", &[1, 2, 3]), ("guest", &[1])]);
+        let new = map(&[("admin", &[1, 2, 4]), ("guest", &[1])]);
+        let delta = diff(&old, &new);
+        assert_eq!(
+            delta,
+            SetMapDelta {
+                remove: vec![],
+                update: vec![(
+                    "admin",
+                    UnorderedDelta {
+                        add: vec![4],
+                        remove: vec![3]
+                    }
+                )],
+            }
+        );
+        assert_eq!(SetMapStrategy::change_count(&old, &new, usize::MAX), 2);
+        assert_eq!(SetMapStrategy::diff(new.clone(), new), None);
+    }
+}
@@ -0,0 +1,71 @@
+/// An upper bound on the size of a value when encoded with `postcard`.
+///
+/// Implemented for the primitive types, `Option`, arrays and tuples, and
+/// generated for delta structs with `#[delta_struct(max_size)]`, so buffers
+/// for encoded deltas can be sized at compile time:
+///
+/// ```ignore
+/// let mut buf = [0u8; <ConfigDelta as MaxEncodedSize>::MAX_ENCODED_SIZE];
+/// ```
+///
+/// Types without a bound, such as `Vec` or `String`, do not implement it.
+pub trait MaxEncodedSize {
+    const MAX_ENCODED_SIZE: usize;
+}
+
+macro_rules! impl_max_encoded_size {
+    ($($ty:ty => $size:expr,)*) => {
+        $(
+            impl MaxEncodedSize for $ty {
+                const MAX_ENCODED_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+/// Bytes needed to varint encode a value of `bits` bits.
+const fn varint_size(bits: usize) -> usize {
+    bits.div_ceil(7)
+}
+
+impl_max_encoded_size! {
+    () => 0,
+    bool => 1,
+    u8 => 1,
+    i8 => 1,
+    u16 => varint_size(16),
+    i16 => varint_size(16),
+    u32 => varint_size(32),
+    i32 => varint_size(32),
+    u64 => varint_size(64),
+    i64 => varint_size(64),
+    u128 => varint_size(128),
+    i128 => varint_size(128),
+    usize => varint_size(64),
+    isize => varint_size(64),
+    f32 => 4,
+    f64 => 8,
+    // A length prefix followed by up to four bytes of UTF-8.
+    char => 5,
+}
+
+impl<T: MaxEncodedSize> MaxEncodedSize for Option<T> {
+    const MAX_ENCODED_SIZE: usize = 1 + T::MAX_ENCODED_SIZE;
+}
+
+impl<T: MaxEncodedSize, const N: usize> MaxEncodedSize for [T; N] {
+    const MAX_ENCODED_SIZE: usize = N * T::MAX_ENCODED_SIZE;
+}
+
+macro_rules! impl_max_encoded_size_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: MaxEncodedSize),+> MaxEncodedSize for ($($name,)+) {
+            const MAX_ENCODED_SIZE: usize = 0 $(+ $name::MAX_ENCODED_SIZE)+;
+        }
+    };
+}
+
+impl_max_encoded_size_tuple!(A);
+impl_max_encoded_size_tuple!(A B);
+impl_max_encoded_size_tuple!(A B C);
+impl_max_encoded_size_tuple!(A B C D);
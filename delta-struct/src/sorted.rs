@@ -0,0 +1,212 @@
+//! Linear diffing of collections that iterate in sorted order, such as
+//! `BTreeSet`s, `BTreeMap`s and sorted `Vec`s, for `field_type = "sorted"`
+//! fields. Unlike `field_type = "unordered"`, which matches every element
+//! against every other, both sides are walked in step.
+
+use crate::strategy::{DiffStrategy, UnorderedDelta};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter::{FromIterator, Peekable};
+
+/// A collection that iterates in sorted order.
+pub trait SortedCollection: IntoIterator + FromIterator<<Self as IntoIterator>::Item> {
+    /// Counts the elements added to and removed from `old` to get `new`.
+    fn change_count(old: &Self, new: &Self) -> usize;
+}
+
+impl<T: Ord> SortedCollection for Vec<T> {
+    fn change_count(old: &Self, new: &Self) -> usize {
+        let (remove, add) = walk(old, new);
+        remove.len() + add.len()
+    }
+}
+
+impl<T: Ord> SortedCollection for BTreeSet<T> {
+    fn change_count(old: &Self, new: &Self) -> usize {
+        let (remove, add) = walk(old, new);
+        remove.len() + add.len()
+    }
+}
+
+impl<K: Ord, V: Ord> SortedCollection for BTreeMap<K, V> {
+    fn change_count(old: &Self, new: &Self) -> usize {
+        let (remove, add) = walk(old, new);
+        remove.len() + add.len()
+    }
+}
+
+/// Diffs a sorted collection as the elements added and removed, like
+/// `field_type = "sorted"`. A `BTreeMap` entry whose value changed is
+/// removed and added again.
+pub struct SortedStrategy;
+
+impl<C, T> DiffStrategy<C> for SortedStrategy
+where
+    C: SortedCollection<Item = T>,
+    T: Ord,
+{
+    type Delta = UnorderedDelta<T>;
+
+    fn diff(old: C, new: C) -> Option<UnorderedDelta<T>> {
+        let (remove, add) = walk(old, new);
+        if add.is_empty() && remove.is_empty() {
+            None
+        } else {
+            Some(UnorderedDelta { add, remove })
+        }
+    }
+
+    fn apply(target: &mut C, delta: UnorderedDelta<T>) {
+        let UnorderedDelta {
+            mut add,
+            mut remove,
+        } = delta;
+        // Deltas computed by `diff` are already sorted, which `sort` only
+        // checks in linear time.
+        add.sort();
+        remove.sort();
+        let og = std::mem::replace(target, std::iter::empty().collect());
+        let mut remove = remove.into_iter().peekable();
+        let kept = og.into_iter().filter(|item| {
+            while remove.next_if(|removed| removed < item).is_some() {}
+            remove.next_if(|removed| removed == item).is_none()
+        });
+        *target = Merge(kept.peekable(), add.into_iter().peekable()).collect();
+    }
+
+    fn change_count(old: &C, new: &C, _limit: usize) -> usize {
+        C::change_count(old, new)
+    }
+
+    fn compose(first: UnorderedDelta<T>, second: UnorderedDelta<T>) -> UnorderedDelta<T> {
+        let UnorderedDelta {
+            add: mut first_add,
+            remove: mut first_remove,
+        } = first;
+        let UnorderedDelta {
+            add: mut second_add,
+            remove: mut second_remove,
+        } = second;
+        for items in [
+            &mut first_add,
+            &mut first_remove,
+            &mut second_add,
+            &mut second_remove,
+        ] {
+            items.sort();
+        }
+        // Elements `second` removes cancel out those `first` added.
+        let (first_add, second_remove) = walk(first_add, second_remove);
+        UnorderedDelta {
+            add: Merge(
+                first_add.into_iter().peekable(),
+                second_add.into_iter().peekable(),
+            )
+            .collect(),
+            remove: Merge(
+                first_remove.into_iter().peekable(),
+                second_remove.into_iter().peekable(),
+            )
+            .collect(),
+        }
+    }
+}
+
+/// Walks two sorted sequences in step, returning the elements only in
+/// `old` and those only in `new`, both sorted. Equal elements are matched
+/// one to one.
+fn walk<I, T>(old: I, new: I) -> (Vec<T>, Vec<T>)
+where
+    I: IntoIterator<Item = T>,
+    T: Ord,
+{
+    let mut old = old.into_iter().peekable();
+    let mut new = new.into_iter().peekable();
+    let (mut only_old, mut only_new) = (vec![], vec![]);
+    while let (Some(a), Some(b)) = (old.peek(), new.peek()) {
+        match a.cmp(b) {
+            Ordering::Less => only_old.extend(old.next()),
+            Ordering::Greater => only_new.extend(new.next()),
+            Ordering::Equal => {
+                old.next();
+                new.next();
+            }
+        }
+    }
+    only_old.extend(old);
+    only_new.extend(new);
+    (only_old, only_new)
+}
+
+/// Merges two sorted iterators into one.
+struct Merge<A: Iterator, B: Iterator>(Peekable<A>, Peekable<B>);
+
+impl<A, B, T> Iterator for Merge<A, B>
+where
+    A: Iterator<Item = T>,
+    B: Iterator<Item = T>,
+    T: Ord,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match (self.0.peek(), self.1.peek()) {
+            (Some(a), Some(b)) if b < a => self.1.next(),
+            (Some(_), _) => self.0.next(),
+            (None, _) => self.1.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff<C>(old: &C, new: &C) -> UnorderedDelta<C::Item>
+    where
+        C: SortedCollection + Clone,
+        C::Item: Ord,
+    {
+        SortedStrategy::diff(old.clone(), new.clone()).unwrap_or(UnorderedDelta {
+            add: vec![],
+            remove: vec![],
+        })
+    }
+
+    #[test]
+    fn diffs_sets_maps_and_sorted_vecs() {
+        let old: BTreeSet<u8> = vec![1, 3, 5].into_iter().collect();
+        let new: BTreeSet<u8> = vec![2, 3, 5, 8].into_iter().collect();
+        let delta = diff(&old, &new);
+        assert_eq!((&delta.add[..], &delta.remove[..]), (&[2, 8][..], &[1][..]));
+        let mut applied = old.clone();
+        SortedStrategy::apply(&mut applied, delta);
+        assert_eq!(applied, new);
+        assert_eq!(SortedStrategy::change_count(&old, &new, usize::MAX), 3);
+
+        let old: BTreeMap<&str, u8> = vec![("a", 1), ("b", 2)].into_iter().collect();
+        let new: BTreeMap<&str, u8> = vec![("a", 1), ("b", 3), ("c", 4)].into_iter().collect();
+        let delta = diff(&old, &new);
+        assert_eq!(delta.remove, vec![("b", 2)]);
+        let mut applied = old;
+        SortedStrategy::apply(&mut applied, delta);
+        assert_eq!(applied, new);
+
+        let old = vec![1, 1, 2, 4];
+        let new = vec![1, 2, 2, 3];
+        let mut applied = old.clone();
+        SortedStrategy::apply(&mut applied, diff(&old, &new));
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let a: Vec<u8> = vec![1, 2, 4, 6];
+        let b = vec![2, 3, 4, 7];
+        let c = vec![1, 3, 6, 7, 9];
+        let mut composed = a.clone();
+        let delta = <SortedStrategy as DiffStrategy<Vec<u8>>>::compose(diff(&a, &b), diff(&b, &c));
+        SortedStrategy::apply(&mut composed, delta);
+        assert_eq!(composed, c);
+    }
+}
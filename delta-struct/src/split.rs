@@ -0,0 +1,42 @@
+//! Support for the generated `split`, which breaks a delta with large
+//! collection changes into several smaller deltas, e.g. to stay under a
+//! message size limit.
+
+/// Distributes the collection elements of a delta over chunks of at most
+/// `max_items` elements, in order.
+pub struct Chunks<T, F> {
+    chunks: Vec<T>,
+    used: usize,
+    max_items: usize,
+    empty: F,
+}
+
+impl<T, F: Fn() -> T> Chunks<T, F> {
+    /// Starts with `first` as the first chunk. Further chunks are created
+    /// with `empty`.
+    pub fn new(first: T, max_items: usize, empty: F) -> Self {
+        Chunks {
+            chunks: vec![first],
+            used: 0,
+            max_items: max_items.max(1),
+            empty,
+        }
+    }
+
+    /// The chunk to put `items` more elements in, which is a new one if they
+    /// don't fit in the current one.
+    pub fn take(&mut self, items: usize) -> &mut T {
+        if self.used > 0 && self.used + items > self.max_items {
+            self.chunks.push((self.empty)());
+            self.used = 0;
+        }
+        self.used += items;
+        self.chunks
+            .last_mut()
+            .expect("there is always a first chunk")
+    }
+
+    pub fn finish(self) -> Vec<T> {
+        self.chunks
+    }
+}
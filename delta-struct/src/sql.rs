@@ -0,0 +1,169 @@
+//! Partial `UPDATE` statements from deltas, for tables whose columns are
+//! the fields of a struct. Delta structs deriving with
+//! `#[delta_struct(sql)]` get a `to_sql_update(&self, table)` returning an
+//! `UPDATE` of only the changed columns, with a `?` placeholder per value:
+//!
+//! ```ignore
+//! if let Some((sql, values)) = delta.to_sql_update("devices") {
+//!     // UPDATE "devices" SET "name" = ?, "volume" = ?
+//!     conn.execute(&format!("{} WHERE id = ?", sql), values_and_id)?;
+//! }
+//! ```
+//!
+//! The statement has no `WHERE` clause, so callers append one selecting the
+//! row and bind its values after the returned ones. The table and column
+//! names are quoted as identifiers, so they can't inject SQL, and a `.` in
+//! the table name separates a schema from the table. Requires the `sql`
+//! feature.
+
+use std::convert::TryFrom;
+
+/// A value bound to a placeholder of a generated statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// A field type stored in a single column.
+pub trait ToSqlValue {
+    fn to_sql_value(&self) -> SqlValue;
+}
+
+macro_rules! impl_to_sql_value {
+    ($($variant:ident($inner:ty) => $($ty:ty),+;)*) => {
+        $($(
+            impl ToSqlValue for $ty {
+                fn to_sql_value(&self) -> SqlValue {
+                    SqlValue::$variant(<$inner>::from(*self))
+                }
+            }
+        )+)*
+    };
+}
+
+impl_to_sql_value! {
+    Bool(bool) => bool;
+    Int(i64) => i8, i16, i32, i64, u8, u16, u32;
+    Float(f64) => f32, f64;
+}
+
+/// Values above `i64::MAX` don't fit the `Int` most databases store, and
+/// are bound as their decimal `Text` instead, which columns of a wider
+/// numeric type, e.g. Postgres' `NUMERIC`, convert back.
+impl ToSqlValue for u64 {
+    fn to_sql_value(&self) -> SqlValue {
+        i64::try_from(*self).map_or_else(|_| SqlValue::Text(self.to_string()), SqlValue::Int)
+    }
+}
+
+/// Like `u64`.
+impl ToSqlValue for usize {
+    fn to_sql_value(&self) -> SqlValue {
+        (*self as u64).to_sql_value()
+    }
+}
+
+impl ToSqlValue for String {
+    fn to_sql_value(&self) -> SqlValue {
+        SqlValue::Text(self.clone())
+    }
+}
+
+impl ToSqlValue for Vec<u8> {
+    fn to_sql_value(&self) -> SqlValue {
+        SqlValue::Bytes(self.clone())
+    }
+}
+
+/// `None` is stored as `NULL`.
+impl<T: ToSqlValue> ToSqlValue for Option<T> {
+    fn to_sql_value(&self) -> SqlValue {
+        self.as_ref().map_or(SqlValue::Null, T::to_sql_value)
+    }
+}
+
+/// `name` quoted as an SQL identifier, doubling the quotes within it.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The statement setting `columns`, in order.
+pub fn update(table: &str, columns: &[&str]) -> String {
+    let table: Vec<String> = table.split('.').map(quote_identifier).collect();
+    let set: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{} = ?", quote_identifier(column)))
+        .collect();
+    format!("UPDATE {} SET {}", table.join("."), set.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputeDelta, Delta};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(sql)]
+    struct Device {
+        name: String,
+        #[delta_struct(column = "vol")]
+        volume: u8,
+        room: Option<String>,
+        #[delta_struct(field_type = "float")]
+        offset: f32,
+    }
+
+    #[test]
+    fn updates_only_set_changed_columns() {
+        let old = Device {
+            name: "den".into(),
+            volume: 3,
+            room: Some("upstairs".into()),
+            offset: 0.5,
+        };
+        let new = Device {
+            name: "den".into(),
+            volume: 7,
+            room: None,
+            offset: 0.5,
+        };
+        let delta = Device::delta(old, new).unwrap();
+        assert_eq!(
+            delta.to_sql_update("devices"),
+            Some((
+                r#"UPDATE "devices" SET "vol" = ?, "room" = ?"#.to_string(),
+                vec![SqlValue::Int(7), SqlValue::Null],
+            ))
+        );
+        let empty = DeviceDelta {
+            name: None,
+            volume: None,
+            room: None,
+            offset: None,
+        };
+        assert_eq!(empty.to_sql_update("devices"), None);
+    }
+
+    #[test]
+    fn identifiers_are_quoted() {
+        assert_eq!(
+            update("main.devices", &["name", "a\" = 1; --"]),
+            r#"UPDATE "main"."devices" SET "name" = ?, "a"" = 1; --" = ?"#
+        );
+    }
+
+    #[test]
+    fn unsigned_integers_beyond_i64_are_bound_as_text() {
+        assert_eq!(7u64.to_sql_value(), SqlValue::Int(7));
+        assert_eq!(7usize.to_sql_value(), SqlValue::Int(7));
+        assert_eq!(
+            u64::MAX.to_sql_value(),
+            SqlValue::Text("18446744073709551615".to_string())
+        );
+    }
+}
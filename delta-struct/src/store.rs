@@ -0,0 +1,520 @@
+//! Persisting a value as a snapshot followed by the deltas applied since,
+//! so its latest state can be restored on startup.
+//!
+//! Values and deltas are stored as JSON. Where they are stored is up to a
+//! [`Backend`]: [`FileBackend`] keeps them in a directory, [`MemoryBackend`]
+//! is useful in tests, and `SledBackend` (with the `sled` feature) keeps
+//! them in a sled tree.
+//!
+//! Deltas should derive serde's traits with `#[delta_struct(serde)]`, which
+//! keeps a cleared `Option` field apart from an unchanged one in JSON.
+
+use crate::Delta;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The encoded snapshot and the deltas appended since, in order.
+pub struct Stored {
+    pub snapshot: Vec<u8>,
+    pub deltas: Vec<Vec<u8>>,
+}
+
+/// Storage for the encoded snapshot and deltas of a [`DeltaStore`].
+pub trait Backend {
+    type Error: std::error::Error + 'static;
+
+    /// Returns `None` if no snapshot was ever written.
+    fn load(&mut self) -> Result<Option<Stored>, Self::Error>;
+
+    /// Replaces the snapshot, discarding all deltas.
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> Result<(), Self::Error>;
+
+    fn append_delta(&mut self, delta: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<B: Backend + ?Sized> Backend for &mut B {
+    type Error = B::Error;
+
+    fn load(&mut self) -> Result<Option<Stored>, Self::Error> {
+        (**self).load()
+    }
+
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> Result<(), Self::Error> {
+        (**self).write_snapshot(snapshot)
+    }
+
+    fn append_delta(&mut self, delta: &[u8]) -> Result<(), Self::Error> {
+        (**self).append_delta(delta)
+    }
+}
+
+/// Why a [`DeltaStore`] operation failed.
+#[derive(Debug)]
+pub enum Error<E> {
+    Backend(E),
+    /// A value or delta couldn't be encoded, or a stored one decoded.
+    Codec(serde_json::Error),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Backend(e) => write!(f, "storage failed: {}", e),
+            Error::Codec(e) => write!(f, "encoding failed: {}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Backend(e) => Some(e),
+            Error::Codec(e) => Some(e),
+        }
+    }
+}
+
+/// A value persisted through a [`Backend`]. Every applied delta is stored
+/// before it is applied, and a new snapshot is written after every
+/// `snapshot_every` deltas.
+///
+/// Deltas are numbered, and a snapshot records the last one it includes,
+/// so deltas a backend still holds after a snapshot replaced them are
+/// skipped instead of applied twice.
+pub struct DeltaStore<T, B> {
+    value: T,
+    backend: B,
+    pending: usize,
+    snapshot_every: usize,
+    /// The number of the last stored delta.
+    sequence: u64,
+}
+
+/// A stored snapshot.
+#[derive(Serialize, Deserialize)]
+struct Snapshot<T> {
+    /// The number of the last delta `value` includes.
+    sequence: u64,
+    value: T,
+}
+
+/// A stored delta.
+#[derive(Serialize, Deserialize)]
+struct Entry<D> {
+    sequence: u64,
+    delta: D,
+}
+
+impl<T, B> DeltaStore<T, B>
+where
+    T: Delta + Serialize + DeserializeOwned,
+    T::Output: Serialize + DeserializeOwned,
+    B: Backend,
+{
+    /// Restores the latest state from `backend`, or starts from `initial`,
+    /// snapshotting it, if nothing was stored yet.
+    pub fn open(
+        mut backend: B,
+        snapshot_every: usize,
+        initial: impl FnOnce() -> T,
+    ) -> Result<Self, Error<B::Error>> {
+        let (value, pending, sequence) = match backend.load().map_err(Error::Backend)? {
+            Some(Stored { snapshot, deltas }) => {
+                let Snapshot {
+                    mut sequence,
+                    mut value,
+                } = serde_json::from_slice::<Snapshot<T>>(&snapshot).map_err(Error::Codec)?;
+                let mut pending = 0;
+                for entry in &deltas {
+                    let entry: Entry<T::Output> =
+                        serde_json::from_slice(entry).map_err(Error::Codec)?;
+                    if entry.sequence > sequence {
+                        value.apply_delta(entry.delta);
+                        sequence = entry.sequence;
+                        pending += 1;
+                    }
+                }
+                (value, pending, sequence)
+            }
+            None => {
+                let value = initial();
+                let snapshot = Snapshot {
+                    sequence: 0,
+                    value: &value,
+                };
+                let snapshot = serde_json::to_vec(&snapshot).map_err(Error::Codec)?;
+                backend.write_snapshot(&snapshot).map_err(Error::Backend)?;
+                (value, 0, 0)
+            }
+        };
+        Ok(DeltaStore {
+            value,
+            backend,
+            pending,
+            snapshot_every: snapshot_every.max(1),
+            sequence,
+        })
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Stores `delta`, then applies it.
+    pub fn apply(&mut self, delta: T::Output) -> Result<(), Error<B::Error>> {
+        let entry = Entry {
+            sequence: self.sequence + 1,
+            delta: &delta,
+        };
+        let encoded = serde_json::to_vec(&entry).map_err(Error::Codec)?;
+        self.backend
+            .append_delta(&encoded)
+            .map_err(Error::Backend)?;
+        self.value.apply_delta(delta);
+        self.sequence += 1;
+        self.pending += 1;
+        if self.pending >= self.snapshot_every {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Stores the delta to `new`, if any, and applies it.
+    pub fn update(&mut self, new: T) -> Result<(), Error<B::Error>>
+    where
+        T: Clone,
+    {
+        match T::delta(self.value.clone(), new) {
+            Some(delta) => self.apply(delta),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes a snapshot of the current value, discarding stored deltas.
+    pub fn snapshot(&mut self) -> Result<(), Error<B::Error>> {
+        let snapshot = Snapshot {
+            sequence: self.sequence,
+            value: &self.value,
+        };
+        let snapshot = serde_json::to_vec(&snapshot).map_err(Error::Codec)?;
+        self.backend
+            .write_snapshot(&snapshot)
+            .map_err(Error::Backend)?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// Keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend {
+    snapshot: Option<Vec<u8>>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for MemoryBackend {
+    type Error = std::convert::Infallible;
+
+    fn load(&mut self) -> Result<Option<Stored>, Self::Error> {
+        Ok(self.snapshot.clone().map(|snapshot| Stored {
+            snapshot,
+            deltas: self.deltas.clone(),
+        }))
+    }
+
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> Result<(), Self::Error> {
+        self.snapshot = Some(snapshot.to_vec());
+        self.deltas.clear();
+        Ok(())
+    }
+
+    fn append_delta(&mut self, delta: &[u8]) -> Result<(), Self::Error> {
+        self.deltas.push(delta.to_vec());
+        Ok(())
+    }
+}
+
+/// Keeps the snapshot in `snapshot.json` and the deltas, one per line, in
+/// `deltas.jsonl` inside a directory.
+#[derive(Clone, Debug)]
+pub struct FileBackend {
+    dir: PathBuf,
+}
+
+impl FileBackend {
+    /// Uses `dir`, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileBackend { dir })
+    }
+
+    fn snapshot_path(&self) -> PathBuf {
+        self.dir.join("snapshot.json")
+    }
+
+    fn deltas_path(&self) -> PathBuf {
+        self.dir.join("deltas.jsonl")
+    }
+}
+
+impl Backend for FileBackend {
+    type Error = io::Error;
+
+    fn load(&mut self) -> io::Result<Option<Stored>> {
+        let snapshot = match fs::read(self.snapshot_path()) {
+            Ok(snapshot) => snapshot,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let deltas = match fs::read(self.deltas_path()) {
+            Ok(mut lines) => {
+                // A crash while appending leaves a partial last line. Its
+                // delta was never applied, so it is cut off before anything
+                // is appended after it.
+                let complete = lines
+                    .iter()
+                    .rposition(|byte| *byte == b'\n')
+                    .map_or(0, |end| end + 1);
+                if complete < lines.len() {
+                    let file = OpenOptions::new().write(true).open(self.deltas_path())?;
+                    file.set_len(complete as u64)?;
+                    file.sync_all()?;
+                    lines.truncate(complete);
+                }
+                lines
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(<[u8]>::to_vec)
+                    .collect()
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        Ok(Some(Stored { snapshot, deltas }))
+    }
+
+    /// Replaces the snapshot atomically by renaming a temporary file over
+    /// it, so a crash leaves either the old or the new snapshot. The deltas
+    /// are only truncated afterwards, so a crash in between leaves deltas the
+    /// new snapshot already includes, which [`DeltaStore`] skips.
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> io::Result<()> {
+        let temporary = self.dir.join("snapshot.json.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(snapshot)?;
+        file.sync_all()?;
+        fs::rename(&temporary, self.snapshot_path())?;
+        File::create(self.deltas_path())?.sync_all()
+    }
+
+    fn append_delta(&mut self, delta: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.deltas_path())?;
+        let mut line = delta.to_vec();
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+}
+
+/// Keeps the snapshot and deltas in a sled tree.
+#[cfg(feature = "sled")]
+#[derive(Clone, Debug)]
+pub struct SledBackend {
+    tree: sled::Tree,
+    next: u64,
+}
+
+#[cfg(feature = "sled")]
+impl SledBackend {
+    const SNAPSHOT: &'static [u8] = b"snapshot";
+    const DELTA_PREFIX: &'static [u8] = b"delta/";
+
+    /// Uses `tree`, which shouldn't be shared with anything else.
+    pub fn new(tree: sled::Tree) -> sled::Result<Self> {
+        let next = match tree.scan_prefix(Self::DELTA_PREFIX).keys().next_back() {
+            Some(key) => Self::sequence(&key?) + 1,
+            None => 0,
+        };
+        Ok(SledBackend { tree, next })
+    }
+
+    fn sequence(key: &[u8]) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&key[Self::DELTA_PREFIX.len()..]);
+        u64::from_be_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "sled")]
+impl Backend for SledBackend {
+    type Error = sled::Error;
+
+    fn load(&mut self) -> sled::Result<Option<Stored>> {
+        let snapshot = match self.tree.get(Self::SNAPSHOT)? {
+            Some(snapshot) => snapshot.to_vec(),
+            None => return Ok(None),
+        };
+        // Keys are big endian sequence numbers, so they sort in order.
+        let deltas = self
+            .tree
+            .scan_prefix(Self::DELTA_PREFIX)
+            .values()
+            .map(|delta| delta.map(|delta| delta.to_vec()))
+            .collect::<sled::Result<_>>()?;
+        Ok(Some(Stored { snapshot, deltas }))
+    }
+
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> sled::Result<()> {
+        let mut batch = sled::Batch::default();
+        batch.insert(Self::SNAPSHOT, snapshot);
+        for key in self.tree.scan_prefix(Self::DELTA_PREFIX).keys() {
+            batch.remove(key?);
+        }
+        self.tree.apply_batch(batch)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    fn append_delta(&mut self, delta: &[u8]) -> sled::Result<()> {
+        let mut key = Self::DELTA_PREFIX.to_vec();
+        key.extend_from_slice(&self.next.to_be_bytes());
+        self.tree.insert(key, delta)?;
+        self.next += 1;
+        self.tree.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Clone, Debug, Delta, PartialEq, Serialize, Deserialize)]
+    #[delta_struct(serde)]
+    struct Garage {
+        open: bool,
+        #[delta_struct(field_type = "unordered")]
+        cars: Vec<String>,
+    }
+
+    fn closed() -> Garage {
+        Garage {
+            open: false,
+            cars: vec![],
+        }
+    }
+
+    /// Makes three changes, snapshotting after the second.
+    fn fill<B: Backend>(mut store: DeltaStore<Garage, B>) -> Garage
+    where
+        B::Error: fmt::Debug,
+    {
+        let sedan = Garage {
+            open: true,
+            cars: vec!["sedan".into()],
+        };
+        store.update(sedan.clone()).unwrap();
+        store
+            .update(Garage {
+                open: false,
+                ..sedan.clone()
+            })
+            .unwrap();
+        store
+            .update(Garage {
+                open: false,
+                cars: vec!["sedan".into(), "truck".into()],
+            })
+            .unwrap();
+        assert_eq!(store.pending, 1);
+        store.into_inner()
+    }
+
+    #[test]
+    fn memory_restores_snapshot_and_deltas() {
+        let mut backend = MemoryBackend::new();
+        let expected = fill(DeltaStore::open(&mut backend, 2, closed).unwrap());
+        let restored = DeltaStore::open(&mut backend, 2, closed).unwrap();
+        assert_eq!(*restored.get(), expected);
+        assert_eq!(restored.pending, 1);
+    }
+
+    #[test]
+    fn files_restore_snapshot_and_deltas() {
+        let dir = std::env::temp_dir().join(format!("delta-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let expected = fill(DeltaStore::open(FileBackend::new(&dir).unwrap(), 2, closed).unwrap());
+        let backend = FileBackend::new(&dir).unwrap();
+        let restored = DeltaStore::open(backend, 2, closed).unwrap();
+        assert_eq!(*restored.get(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_restores_snapshot_and_deltas() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree("garage").unwrap();
+        let expected =
+            fill(DeltaStore::open(SledBackend::new(tree.clone()).unwrap(), 2, closed).unwrap());
+        let restored = DeltaStore::open(SledBackend::new(tree).unwrap(), 2, closed).unwrap();
+        assert_eq!(*restored.get(), expected);
+    }
+
+    #[test]
+    fn files_survive_crashes_while_snapshotting_and_appending() {
+        let dir = std::env::temp_dir().join(format!("delta-store-crash-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let deltas = dir.join("deltas.jsonl");
+        let mut store = DeltaStore::open(FileBackend::new(&dir).unwrap(), 10, closed).unwrap();
+        let sedan = Garage {
+            open: true,
+            cars: vec!["sedan".into()],
+        };
+        store.update(sedan.clone()).unwrap();
+
+        // A crash after the new snapshot replaced the old one, but before
+        // the deltas it includes were truncated.
+        let included = fs::read(&deltas).unwrap();
+        store.snapshot().unwrap();
+        fs::write(&deltas, included).unwrap();
+        drop(store);
+        let mut store = DeltaStore::open(FileBackend::new(&dir).unwrap(), 10, closed).unwrap();
+        assert_eq!(*store.get(), sedan);
+        assert_eq!(store.pending, 0);
+
+        // A crash partway through appending a delta.
+        let truck = Garage {
+            cars: vec!["sedan".into(), "truck".into()],
+            ..sedan.clone()
+        };
+        store.update(truck.clone()).unwrap();
+        let mut torn = OpenOptions::new().append(true).open(&deltas).unwrap();
+        torn.write_all(br#"{"sequence":3,"delta":{"op"#).unwrap();
+        drop(store);
+        let mut store = DeltaStore::open(FileBackend::new(&dir).unwrap(), 10, closed).unwrap();
+        assert_eq!(*store.get(), truck);
+        store.update(closed()).unwrap();
+        drop(store);
+        let store = DeltaStore::open(FileBackend::new(&dir).unwrap(), 10, closed).unwrap();
+        assert_eq!(*store.get(), closed());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Custom per-field diffing with `#[delta_struct(strategy = "Type")]`.
+//!
+//! A strategy takes over how a single field is diffed, applied, counted and
+//! composed, while the derive still generates the delta struct around it.
+//! The field's member on the delta struct is an `Option` of the strategy's
+//! `Delta`.
+//!
+//! ```
+//! use delta_struct::{strategy::DiffStrategy, ComputeDelta, Delta};
+//!
+//! /// Only sends the difference between two counters.
+//! struct Increments;
+//!
+//! impl DiffStrategy<u64> for Increments {
+//!     type Delta = u64;
+//!
+//!     fn diff(old: u64, new: u64) -> Option<u64> {
+//!         new.checked_sub(old).filter(|added| *added > 0)
+//!     }
+//!
+//!     fn apply(target: &mut u64, delta: u64) {
+//!         *target += delta;
+//!     }
+//!
+//!     fn change_count(old: &u64, new: &u64, _limit: usize) -> usize {
+//!         (old != new) as usize
+//!     }
+//!
+//!     fn compose(first: u64, second: u64) -> u64 {
+//!         first + second
+//!     }
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Meter {
+//!     #[delta_struct(strategy = "Increments")]
+//!     total: u64,
+//! }
+//!
+//! let delta = Meter::delta(Meter { total: 40 }, Meter { total: 42 }).unwrap();
+//! assert_eq!(delta.total, Some(2));
+//! ```
+
+use crate::{Delta, Splice};
+
+/// How to diff and apply a field of type `T`.
+pub trait DiffStrategy<T> {
+    type Delta;
+
+    /// The delta from `old` to `new`, or `None` if there is no change.
+    fn diff(old: T, new: T) -> Option<Self::Delta>;
+
+    fn apply(target: &mut T, delta: Self::Delta);
+
+    /// Counts the changes between `old` and `new`, as for
+    /// [`ComputeDelta::change_count`].
+    fn change_count(old: &T, new: &T, limit: usize) -> usize;
+
+    /// Combines two consecutive deltas into one.
+    fn compose(first: Self::Delta, second: Self::Delta) -> Self::Delta;
+}
+
+/// Compares by `PartialEq` and replaces the value wholesale, like
+/// `field_type = "scalar"`.
+pub struct ScalarStrategy;
+
+impl<T: PartialEq> DiffStrategy<T> for ScalarStrategy {
+    type Delta = T;
+
+    fn diff(old: T, new: T) -> Option<T> {
+        if old != new {
+            Some(new)
+        } else {
+            None
+        }
+    }
+
+    fn apply(target: &mut T, delta: T) {
+        *target = delta;
+    }
+
+    fn change_count(old: &T, new: &T, _limit: usize) -> usize {
+        (old != new) as usize
+    }
+
+    fn compose(_first: T, second: T) -> T {
+        second
+    }
+}
+
+/// The elements added to and removed from a collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct UnorderedDelta<T> {
+    pub add: Vec<T>,
+    pub remove: Vec<T>,
+}
+
+/// Diffs a collection as a multiset, like `field_type = "unordered"`.
+pub struct UnorderedStrategy;
+
+impl<C, T> DiffStrategy<C> for UnorderedStrategy
+where
+    C: IntoIterator<Item = T> + std::iter::FromIterator<T>,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq,
+{
+    type Delta = UnorderedDelta<T>;
+
+    fn diff(old: C, new: C) -> Option<UnorderedDelta<T>> {
+        let mut add: Vec<T> = new.into_iter().collect();
+        let remove: Vec<T> = old
+            .into_iter()
+            .filter_map(|i| match add.iter().position(|a| a == &i) {
+                Some(index) => {
+                    add.remove(index);
+                    None
+                }
+                None => Some(i),
+            })
+            .collect();
+        if add.is_empty() && remove.is_empty() {
+            None
+        } else {
+            Some(UnorderedDelta { add, remove })
+        }
+    }
+
+    fn apply(target: &mut C, delta: UnorderedDelta<T>) {
+        let UnorderedDelta { add, mut remove } = delta;
+        let og = std::mem::replace(target, std::iter::empty().collect());
+        *target = og
+            .into_iter()
+            .filter_map(|i| match remove.iter().position(|a| a == &i) {
+                Some(index) => {
+                    remove.remove(index);
+                    None
+                }
+                None => Some(i),
+            })
+            .chain(add)
+            .collect();
+    }
+
+    fn change_count(old: &C, new: &C, limit: usize) -> usize {
+        crate::unordered::change_count(old, new, limit)
+    }
+
+    fn compose(first: UnorderedDelta<T>, second: UnorderedDelta<T>) -> UnorderedDelta<T> {
+        let UnorderedDelta {
+            mut add,
+            mut remove,
+        } = first;
+        for i in second.remove {
+            match add.iter().position(|a| a == &i) {
+                Some(index) => {
+                    add.remove(index);
+                }
+                None => remove.push(i),
+            }
+        }
+        add.extend(second.add);
+        UnorderedDelta { add, remove }
+    }
+}
+
+/// Diffs a collection as a list of splices, like `field_type = "ordered"`.
+pub struct OrderedStrategy;
+
+impl<C, T> DiffStrategy<C> for OrderedStrategy
+where
+    C: IntoIterator<Item = T> + std::iter::FromIterator<T>,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq,
+{
+    type Delta = Vec<Splice<T>>;
+
+    fn diff(old: C, new: C) -> Option<Vec<Splice<T>>> {
+        let splices = Splice::diff(old.into_iter().collect(), new.into_iter().collect());
+        if splices.is_empty() {
+            None
+        } else {
+            Some(splices)
+        }
+    }
+
+    fn apply(target: &mut C, delta: Vec<Splice<T>>) {
+        let og = std::mem::replace(target, std::iter::empty().collect());
+        let mut items: Vec<T> = og.into_iter().collect();
+        for splice in delta {
+            splice.apply(&mut items);
+        }
+        *target = items.into_iter().collect();
+    }
+
+    fn change_count(old: &C, new: &C, _limit: usize) -> usize {
+        crate::ordered::change_count(old, new)
+    }
+
+    fn compose(mut first: Vec<Splice<T>>, second: Vec<Splice<T>>) -> Vec<Splice<T>> {
+        first.extend(second);
+        first
+    }
+}
+
+/// Uses the field's own `Delta` impl, like `field_type = "delta"`.
+pub struct NestedStrategy;
+
+impl<T: Delta> DiffStrategy<T> for NestedStrategy {
+    type Delta = T::Output;
+
+    fn diff(old: T, new: T) -> Option<T::Output> {
+        T::delta(old, new)
+    }
+
+    fn apply(target: &mut T, delta: T::Output) {
+        target.apply_delta(delta);
+    }
+
+    fn change_count(old: &T, new: &T, limit: usize) -> usize {
+        T::change_count(old, new, limit)
+    }
+
+    fn compose(first: T::Output, second: T::Output) -> T::Output {
+        T::compose_delta(first, second)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::Delta;
+
+/// Decides the state two diverged replicas converge on.
+pub trait ConflictPolicy<T> {
+    fn resolve(&mut self, a: &T, b: &T) -> T;
+}
+
+impl<T, F: FnMut(&T, &T) -> T> ConflictPolicy<T> for F {
+    fn resolve(&mut self, a: &T, b: &T) -> T {
+        self(a, b)
+    }
+}
+
+/// Resolves every conflict in favor of the first replica.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PreferFirst;
+
+impl<T: Clone> ConflictPolicy<T> for PreferFirst {
+    fn resolve(&mut self, a: &T, _b: &T) -> T {
+        a.clone()
+    }
+}
+
+/// Resolves every conflict in favor of the second replica.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PreferSecond;
+
+impl<T: Clone> ConflictPolicy<T> for PreferSecond {
+    fn resolve(&mut self, _a: &T, b: &T) -> T {
+        b.clone()
+    }
+}
+
+/// [`reconcile_with`] [`PreferFirst`], so `b` is brought up to date with `a`.
+pub fn reconcile<T>(a: &mut T, b: &mut T) -> (Option<T::Output>, Option<T::Output>)
+where
+    T: Delta + Clone,
+    T::Output: Clone,
+{
+    reconcile_with(a, b, PreferFirst)
+}
+
+/// Makes two replicas converge on the state `policy` resolves them to,
+/// applying the delta from each replica to that state.
+///
+/// Returns the deltas applied to `a` and `b`, e.g. to send on to the
+/// replicas' peers. Replicas that are already equal are left alone without
+/// consulting `policy`.
+pub fn reconcile_with<T, P>(
+    a: &mut T,
+    b: &mut T,
+    mut policy: P,
+) -> (Option<T::Output>, Option<T::Output>)
+where
+    T: Delta + Clone,
+    T::Output: Clone,
+    P: ConflictPolicy<T>,
+{
+    if !T::has_changes(a, b) {
+        return (None, None);
+    }
+    let resolved = policy.resolve(a, b);
+    let to_a = T::delta(a.clone(), resolved.clone());
+    let to_b = T::delta(b.clone(), resolved);
+    if let Some(delta) = &to_a {
+        a.apply_delta_ref(delta);
+    }
+    if let Some(delta) = &to_b {
+        b.apply_delta_ref(delta);
+    }
+    (to_a, to_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Thermostat {
+        setpoint: u8,
+        #[delta_struct(field_type = "unordered")]
+        schedules: Vec<u8>,
+        #[delta_struct(skip)]
+        connected: bool,
+    }
+
+    #[test]
+    fn replicas_converge_on_the_resolved_state() {
+        let mut cloud = Thermostat {
+            setpoint: 70,
+            schedules: vec![1, 2],
+            connected: true,
+        };
+        let mut device = Thermostat {
+            setpoint: 68,
+            schedules: vec![2, 3],
+            connected: false,
+        };
+        let (to_cloud, to_device) =
+            reconcile_with(&mut cloud, &mut device, |a: &Thermostat, b: &Thermostat| {
+                let mut schedules = a.schedules.clone();
+                schedules.extend(b.schedules.iter().filter(|s| !a.schedules.contains(s)));
+                Thermostat {
+                    setpoint: b.setpoint,
+                    schedules,
+                    connected: false,
+                }
+            });
+        assert_eq!(
+            to_cloud,
+            Some(ThermostatDelta {
+                setpoint: Some(68),
+                schedules_add: vec![3],
+                schedules_remove: vec![],
+            })
+        );
+        assert_eq!(to_device.unwrap().schedules_add, vec![1]);
+        assert_eq!((cloud.setpoint, device.setpoint), (68, 68));
+        // Skipped fields are local to each replica.
+        assert!(cloud.connected && !device.connected);
+        assert_eq!(reconcile(&mut cloud, &mut device), (None, None));
+    }
+
+    #[test]
+    fn preferring_a_replica_only_updates_the_other() {
+        let mut a = Thermostat {
+            setpoint: 70,
+            schedules: vec![],
+            connected: true,
+        };
+        let mut b = Thermostat {
+            setpoint: 65,
+            schedules: vec![],
+            connected: true,
+        };
+        let (to_a, to_b) = reconcile_with(&mut a, &mut b, PreferSecond);
+        assert!(to_a.is_some() && to_b.is_none());
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,100 @@
+//! Support for `#[delta_struct(tagged_changes)]`, which serializes a delta
+//! as a list of change records instead of a struct of optional fields:
+//!
+//! ```json
+//! [{ "field": "name", "op": "set", "value": "porch" }]
+//! ```
+//!
+//! The ops per field type are `set` for scalar fields, `add` and `remove`
+//! for unordered fields, `splice` for ordered fields, `remove`, `patch` and
+//! `add` for keyed fields, `set` and `clear` for bitset masks, and `patch`
+//! for nested deltas.
+
+use crate::bitset::{self, BitSet};
+use serde::Serialize;
+
+/// A single serialized change.
+#[derive(Serialize)]
+pub struct Record<'a, V: ?Sized> {
+    /// The name of the field on the deriving struct.
+    pub field: &'static str,
+    pub op: &'static str,
+    pub value: &'a V,
+}
+
+/// `Some(items)` unless there are none.
+pub fn non_empty<T>(items: &Vec<T>) -> Option<&Vec<T>> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+/// `Some(mask)` unless no bits are set.
+pub fn non_empty_bits<T: BitSet>(mask: &T) -> Option<&T> {
+    if bitset::is_empty(*mask) {
+        None
+    } else {
+        Some(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ComputeDelta, Delta};
+    use serde_json::json;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(tagged_changes)]
+    struct Inner {
+        level: u8,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(tagged_changes)]
+    struct Device {
+        name: String,
+        #[delta_struct(field_type = "unordered")]
+        tags: Vec<String>,
+        #[delta_struct(field_type = "bitset")]
+        flags: u8,
+        #[delta_struct(field_type = "delta")]
+        inner: Inner,
+        unchanged: bool,
+    }
+
+    #[test]
+    fn serializes_one_record_per_change() {
+        let old = Device {
+            name: "lamp".into(),
+            tags: vec!["hall".into()],
+            flags: 0b01,
+            inner: Inner { level: 1 },
+            unchanged: true,
+        };
+        let new = Device {
+            name: "porch".into(),
+            tags: vec!["outside".into()],
+            flags: 0b10,
+            inner: Inner { level: 2 },
+            unchanged: true,
+        };
+        let delta = Device::delta(old, new).unwrap();
+        assert_eq!(
+            serde_json::to_value(&delta).unwrap(),
+            json!([
+                { "field": "name", "op": "set", "value": "porch" },
+                { "field": "tags", "op": "add", "value": ["outside"] },
+                { "field": "tags", "op": "remove", "value": ["hall"] },
+                { "field": "flags", "op": "set", "value": 2 },
+                { "field": "flags", "op": "clear", "value": 1 },
+                {
+                    "field": "inner",
+                    "op": "patch",
+                    "value": [{ "field": "level", "op": "set", "value": 2 }],
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Assertions for unit testing `Delta` implementations.
+//!
+//! ```
+//! use delta_struct::{assert_delta_roundtrip, testing::assert_no_delta, Delta};
+//!
+//! #[derive(Clone, Debug, Delta, PartialEq)]
+//! #[delta_struct(delta_leader = "#[derive(Debug)]")]
+//! struct Light {
+//!     on: bool,
+//!     level: u8,
+//! }
+//!
+//! let off = Light { on: false, level: 0 };
+//! let on = Light { on: true, level: 70 };
+//! assert_delta_roundtrip!(off.clone(), on);
+//! assert_no_delta(off);
+//! ```
+//!
+//! [`delta_of!`](crate::delta_of) lists the fields that differ between two
+//! struct literals, for types that don't derive `Delta`:
+//!
+//! ```
+//! struct Lock {
+//!     locked: bool,
+//!     battery: u8,
+//! }
+//!
+//! let changes = delta_struct::delta_of!(
+//!     Lock { locked: false, battery: 80 },
+//!     Lock { locked: true, battery: 80 },
+//! );
+//! assert_eq!(changes.to_string(), "locked: false -> true\n");
+//! ```
+
+use crate::Delta;
+use std::fmt::{self, Debug, Write};
+
+/// Asserts that applying the delta from `old` to `new` onto `old` gives
+/// `new`, and that there is no delta if they are equal.
+///
+/// On failure the message lists the lines of the `{:#?}` output that differ,
+/// which for derived `Debug` impls are the mismatching fields.
+#[track_caller]
+pub fn assert_roundtrip<T>(old: T, new: T)
+where
+    T: Delta + Clone + Debug + PartialEq,
+    T::Output: Debug,
+{
+    let delta = T::delta(old.clone(), new.clone());
+    let delta = match delta {
+        Some(delta) => delta,
+        None if old == new => return,
+        None => panic!(
+            "no delta was computed between unequal values\n{}",
+            field_diff(&old, &new)
+        ),
+    };
+    if old == new {
+        panic!("a delta was computed between equal values: {:#?}", delta);
+    }
+    let described = format!("{:#?}", delta);
+    let mut applied = old;
+    applied.apply_delta(delta);
+    if applied != new {
+        panic!(
+            "applying the delta did not produce the new value\n{}delta: {}",
+            field_diff(&new, &applied),
+            described
+        );
+    }
+}
+
+/// Asserts that there is no delta between `value` and a clone of itself.
+#[track_caller]
+pub fn assert_no_delta<T>(value: T)
+where
+    T: Delta + Clone,
+    T::Output: Debug,
+{
+    if let Some(delta) = T::delta(value.clone(), value) {
+        panic!("a delta was computed between equal values: {:#?}", delta);
+    }
+}
+
+/// The differing lines between the pretty printed `expected` and `actual`.
+fn field_diff<T: Debug>(expected: &T, actual: &T) -> String {
+    let expected = format!("{:#?}", expected);
+    let actual = format!("{:#?}", actual);
+    let expected_lines: Vec<_> = expected.lines().collect();
+    let actual_lines: Vec<_> = actual.lines().collect();
+    let mut out = String::new();
+    if expected_lines.len() != actual_lines.len() {
+        // The shapes differ, so lining up lines would be misleading.
+        let _ = writeln!(out, "expected: {}\nactual: {}", expected, actual);
+        return out;
+    }
+    for (e, a) in expected_lines.iter().zip(&actual_lines) {
+        if e != a {
+            let _ = writeln!(out, "- {}\n+ {}", e.trim(), a.trim());
+        }
+    }
+    out
+}
+
+/// A field that differs between two values, formatted with `Debug`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// The fields that differ between two values, as listed by
+/// [`delta_of!`](crate::delta_of). Displayed one `field: old -> new` line per
+/// change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changes(pub Vec<FieldChange>);
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Records `field` as changed unless `old` equals `new`.
+    pub fn compare<A, B>(&mut self, field: &'static str, old: &A, new: &B)
+    where
+        A: PartialEq<B> + Debug,
+        B: Debug,
+    {
+        if old != new {
+            self.0.push(FieldChange {
+                field,
+                old: format!("{:?}", old),
+                new: format!("{:?}", new),
+            });
+        }
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.0 {
+            writeln!(f, "{}: {} -> {}", change.field, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds both struct literals and returns the [`testing::Changes`]
+/// between them, comparing each field of the first with the field of the
+/// same name of the second. Neither type needs to implement `Delta`, only
+/// their fields `PartialEq` and `Debug`.
+///
+/// [`testing::Changes`]: crate::testing::Changes
+#[macro_export]
+macro_rules! delta_of {
+    (
+        $($old_ty:ident)::+ { $($field:ident : $old:expr),* $(,)? },
+        $($new_ty:ident)::+ { $($new_field:ident : $new:expr),* $(,)? } $(,)?
+    ) => {{
+        let old = $($old_ty)::+ { $($field: $old),* };
+        let new = $($new_ty)::+ { $($new_field: $new),* };
+        let mut changes = $crate::testing::Changes::default();
+        $(changes.compare(::std::stringify!($field), &old.$field, &new.$field);)*
+        changes
+    }};
+}
+
+/// Calls [`testing::assert_roundtrip`](crate::testing::assert_roundtrip).
+#[macro_export]
+macro_rules! assert_delta_roundtrip {
+    ($old:expr, $new:expr $(,)?) => {
+        $crate::testing::assert_roundtrip($old, $new)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_no_delta, assert_roundtrip};
+    use crate::{ApplyDelta, ComputeDelta, Delta};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    struct Thermostat {
+        target: i32,
+        #[delta_struct(field_type = "unordered")]
+        schedules: Vec<u8>,
+    }
+
+    /// Forgets to apply the target.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Broken {
+        target: i32,
+        mode: u8,
+    }
+
+    impl ComputeDelta for Broken {
+        type Output = i32;
+
+        fn delta(old: Self, new: Self) -> Option<i32> {
+            if old == new {
+                None
+            } else {
+                Some(new.target)
+            }
+        }
+
+        fn change_count(old: &Self, new: &Self, _: usize) -> usize {
+            (old != new) as usize
+        }
+    }
+
+    impl ApplyDelta<i32> for Broken {
+        fn apply_delta(&mut self, _: i32) {}
+
+        fn compose_delta(_: i32, second: i32) -> i32 {
+            second
+        }
+    }
+
+    #[test]
+    fn derived_impls_pass() {
+        let old = Thermostat {
+            target: 20,
+            schedules: vec![1, 2],
+        };
+        let new = Thermostat {
+            target: 22,
+            schedules: vec![2, 3],
+        };
+        assert_roundtrip(old.clone(), new);
+        assert_roundtrip(old.clone(), old.clone());
+        assert_delta_roundtrip!(old.clone(), old.clone());
+        assert_no_delta(old);
+    }
+
+    #[test]
+    #[should_panic(expected = "- target: 22,\n+ target: 20,\ndelta: 22")]
+    fn failures_list_mismatched_fields() {
+        assert_roundtrip(
+            Broken {
+                target: 20,
+                mode: 1,
+            },
+            Broken {
+                target: 22,
+                mode: 1,
+            },
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Mode(u8);
+
+    struct Hvac {
+        mode: Mode,
+        fan: bool,
+        name: &'static str,
+    }
+
+    #[test]
+    fn delta_of_lists_changed_fields() {
+        let changes = delta_of!(
+            Hvac {
+                mode: Mode(1),
+                fan: false,
+                name: "den",
+            },
+            Hvac {
+                mode: Mode(2),
+                fan: false,
+                name: "office",
+            },
+        );
+        assert_eq!(
+            changes.to_string(),
+            "mode: Mode(1) -> Mode(2)\nname: \"den\" -> \"office\"\n"
+        );
+        let unchanged = delta_of!(
+            Hvac {
+                mode: Mode(1),
+                fan: true,
+                name: "den",
+            },
+            Hvac {
+                name: "den",
+                fan: true,
+                mode: Mode(1),
+            }
+        );
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "a delta was computed between equal values")]
+    fn spurious_delta_fails() {
+        #[derive(Clone)]
+        struct Always;
+        impl ComputeDelta for Always {
+            type Output = ();
+            fn delta(_: Self, _: Self) -> Option<()> {
+                Some(())
+            }
+            fn change_count(_: &Self, _: &Self, _: usize) -> usize {
+                1
+            }
+        }
+        impl ApplyDelta<()> for Always {
+            fn apply_delta(&mut self, _: ()) {}
+            fn compose_delta(_: (), _: ()) {}
+        }
+        assert_no_delta(Always);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::Splice;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// A hash of a delta's contents, used by [`DeltaThrottle`] to recognize
+/// repeated deltas. Implemented for delta structs deriving with
+/// `#[delta_struct(content_hash)]`.
+pub trait ContentHash {
+    fn content_hash(&self) -> u64;
+}
+
+/// `Hash` for the members of `content_hash` deltas whose field types
+/// mention `f32` or `f64`. Floats are hashed by their bits, with every NaN
+/// alike and `-0.0` as `0.0`.
+#[doc(hidden)]
+pub trait FloatHash {
+    fn float_hash<H: Hasher>(&self, state: &mut H);
+}
+
+impl FloatHash for f32 {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        let value = if self.is_nan() {
+            f32::NAN
+        } else if *self == 0.0 {
+            0.0
+        } else {
+            *self
+        };
+        value.to_bits().hash(state);
+    }
+}
+
+impl FloatHash for f64 {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        let value = if self.is_nan() {
+            f64::NAN
+        } else if *self == 0.0 {
+            0.0
+        } else {
+            *self
+        };
+        value.to_bits().hash(state);
+    }
+}
+
+impl<T: FloatHash> FloatHash for Option<T> {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Some(value) => {
+                1u8.hash(state);
+                value.float_hash(state);
+            }
+            None => 0u8.hash(state),
+        }
+    }
+}
+
+impl<T: FloatHash> FloatHash for [T] {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for value in self {
+            value.float_hash(state);
+        }
+    }
+}
+
+impl<T: FloatHash> FloatHash for Vec<T> {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self[..].float_hash(state);
+    }
+}
+
+impl<T: FloatHash> FloatHash for Splice<T> {
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self.start.hash(state);
+        self.delete_count.hash(state);
+        self.insert.float_hash(state);
+    }
+}
+
+/// Suppresses deltas identical to the previously sent one, for devices that
+/// repeat the same state update.
+///
+/// A delta is only suppressed while it arrives within `window` of the last
+/// delta that was let through, so a repeated update is still sent once per
+/// window.
+pub struct DeltaThrottle<T> {
+    window: Duration,
+    last: Option<(u64, Instant)>,
+    suppressed: usize,
+    _delta: std::marker::PhantomData<fn(T)>,
+}
+
+impl<T: ContentHash> DeltaThrottle<T> {
+    pub fn new(window: Duration) -> Self {
+        DeltaThrottle {
+            window,
+            last: None,
+            suppressed: 0,
+            _delta: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns `delta` if it should be sent, or `None` if it repeats the
+    /// last sent delta within the window.
+    pub fn filter(&mut self, delta: T) -> Option<T> {
+        self.filter_at(delta, Instant::now())
+    }
+
+    /// [`DeltaThrottle::filter`] with an explicit current time.
+    pub fn filter_at(&mut self, delta: T, now: Instant) -> Option<T> {
+        let hash = delta.content_hash();
+        match self.last {
+            Some((last, sent))
+                if last == hash && now.saturating_duration_since(sent) < self.window =>
+            {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last = Some((hash, now));
+                Some(delta)
+            }
+        }
+    }
+
+    /// The number of deltas suppressed so far.
+    pub fn suppressed(&self) -> usize {
+        self.suppressed
+    }
+
+    /// Forgets the last sent delta, so the next one is always let through.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputeDelta, Delta};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(content_hash, delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Sensor {
+        level: u8,
+        #[delta_struct(field_type = "unordered")]
+        alarms: Vec<u16>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(content_hash)]
+    struct Gauge {
+        pressure: f64,
+        #[delta_struct(field_type = "float", epsilon = "0.5")]
+        temperature: f32,
+        #[delta_struct(field_type = "unordered")]
+        samples: Vec<f32>,
+    }
+
+    fn delta(level: u8) -> SensorDelta {
+        let old = Sensor {
+            level: 0,
+            alarms: vec![],
+        };
+        Sensor::delta(
+            old,
+            Sensor {
+                level,
+                alarms: vec![7],
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn repeats_are_suppressed_within_the_window() {
+        let start = Instant::now();
+        let mut throttle = DeltaThrottle::new(Duration::from_secs(10));
+        assert!(throttle.filter_at(delta(1), start).is_some());
+        assert!(throttle
+            .filter_at(delta(1), start + Duration::from_secs(5))
+            .is_none());
+        assert!(throttle
+            .filter_at(delta(2), start + Duration::from_secs(6))
+            .is_some());
+        assert!(throttle
+            .filter_at(delta(2), start + Duration::from_secs(17))
+            .is_some());
+        assert_eq!(throttle.suppressed(), 1);
+        assert_eq!(delta(3).content_hash(), delta(3).content_hash());
+        assert_ne!(delta(3).content_hash(), delta(4).content_hash());
+    }
+
+    #[test]
+    fn float_fields_hash_by_value() {
+        let hash = |pressure: f64, temperature: f32| {
+            let old = Gauge {
+                pressure: 1.0,
+                temperature: 1.0,
+                samples: vec![],
+            };
+            let new = Gauge {
+                pressure,
+                temperature,
+                samples: vec![temperature],
+            };
+            Gauge::delta(old, new).unwrap().content_hash()
+        };
+        assert_eq!(hash(2.0, 3.0), hash(2.0, 3.0));
+        assert_ne!(hash(2.0, 3.0), hash(2.5, 3.0));
+        assert_eq!(hash(f64::NAN, 3.0), hash(-f64::NAN, 3.0));
+        assert_eq!(hash(0.0, 3.0), hash(-0.0, 3.0));
+        assert_eq!(hash(2.0, 0.0), hash(2.0, -0.0));
+    }
+}
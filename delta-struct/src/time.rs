@@ -0,0 +1,97 @@
+//! Support for the `tolerance` option on time valued `scalar` fields, which
+//! ignores changes smaller than the tolerance.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time types whose values are some distance apart.
+pub trait Tolerance {
+    /// How far apart `self` and `other` are, in either direction.
+    fn distance(&self, other: &Self) -> Duration;
+}
+
+impl Tolerance for Duration {
+    fn distance(&self, other: &Self) -> Duration {
+        if self > other {
+            *self - *other
+        } else {
+            *other - *self
+        }
+    }
+}
+
+impl Tolerance for Instant {
+    fn distance(&self, other: &Self) -> Duration {
+        self.saturating_duration_since(*other)
+            .max(other.saturating_duration_since(*self))
+    }
+}
+
+impl Tolerance for SystemTime {
+    fn distance(&self, other: &Self) -> Duration {
+        self.duration_since(*other).unwrap_or_else(|e| e.duration())
+    }
+}
+
+/// `None` is infinitely far from any `Some`.
+impl<T: Tolerance> Tolerance for Option<T> {
+    fn distance(&self, other: &Self) -> Duration {
+        match (self, other) {
+            (Some(a), Some(b)) => a.distance(b),
+            (None, None) => Duration::ZERO,
+            _ => Duration::MAX,
+        }
+    }
+}
+
+/// Whether `old` and `new` are less than `tolerance_nanos` apart.
+pub fn within<T: Tolerance>(old: &T, new: &T, tolerance_nanos: u64) -> bool {
+    old.distance(new) < Duration::from_nanos(tolerance_nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::within;
+    use crate::{ComputeDelta, Delta};
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Heartbeat {
+        #[delta_struct(tolerance = "5ms")]
+        uptime: Duration,
+        #[delta_struct(tolerance = "1s")]
+        seen: Option<Instant>,
+    }
+
+    #[test]
+    fn small_changes_are_ignored() {
+        let now = Instant::now();
+        assert!(within(
+            &now,
+            &(now + Duration::from_millis(999)),
+            1_000_000_000
+        ));
+        assert!(!within(
+            &(now + Duration::from_secs(1)),
+            &now,
+            1_000_000_000
+        ));
+
+        let old = Heartbeat {
+            uptime: Duration::from_millis(100),
+            seen: Some(now),
+        };
+        let jitter = Heartbeat {
+            uptime: Duration::from_millis(104),
+            seen: Some(now + Duration::from_millis(10)),
+        };
+        assert!(Heartbeat::delta(old.clone(), jitter).is_none());
+
+        let later = Heartbeat {
+            uptime: Duration::from_millis(105),
+            seen: None,
+        };
+        let delta = Heartbeat::delta(old, later).unwrap();
+        assert_eq!(delta.uptime, Some(Duration::from_millis(105)));
+        assert_eq!(delta.seen, Some(None));
+    }
+}
@@ -0,0 +1,113 @@
+//! Recording deltas on `tracing` spans. Enabled per struct with
+//! `#[delta_struct(tracing)]`, which generates `record_into` on the delta
+//! struct.
+//!
+//! `tracing` only records fields a span declared when it was created:
+//!
+//! ```ignore
+//! let span = tracing::info_span!(
+//!     "apply",
+//!     delta.changed_fields = tracing::field::Empty,
+//!     delta.change_count = tracing::field::Empty,
+//! );
+//! delta.record_into(&span);
+//! ```
+
+pub use tracing::Span;
+
+/// The span field holding the comma separated names of the changed fields.
+pub const CHANGED_FIELDS: &str = "delta.changed_fields";
+/// The span field holding the number of changed fields.
+pub const CHANGE_COUNT: &str = "delta.change_count";
+
+/// Records the names of the `changed` fields and their count on `span`.
+pub fn record(span: &Span, changed: &[&'static str]) {
+    span.record(CHANGED_FIELDS, changed.join(",").as_str());
+    span.record(CHANGE_COUNT, changed.len() as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputeDelta, Delta};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(tracing)]
+    struct Lock {
+        locked: bool,
+        #[delta_struct(field_type = "unordered")]
+        codes: Vec<u16>,
+        battery: u8,
+    }
+
+    /// Collects every value recorded on any span.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(&'static str, String)>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn records_changed_field_names_and_count() {
+        let old = Lock {
+            locked: false,
+            codes: vec![1234],
+            battery: 80,
+        };
+        let new = Lock {
+            locked: true,
+            codes: vec![1234, 5678],
+            ..old.clone()
+        };
+        let delta = Lock::delta(old, new).unwrap();
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let span = tracing::info_span!(
+                "apply",
+                delta.changed_fields = tracing::field::Empty,
+                delta.change_count = tracing::field::Empty,
+            );
+            delta.record_into(&span);
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                (CHANGED_FIELDS, "\"locked,codes\"".to_string()),
+                (CHANGE_COUNT, "2".to_string()),
+            ]
+        );
+    }
+}
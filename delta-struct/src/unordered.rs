@@ -0,0 +1,302 @@
+//! Helpers for `field_type = "unordered"` and `"unordered_scalar"` fields.
+
+use crate::strategy::DiffStrategy;
+use std::fmt;
+use std::iter::FromIterator;
+
+/// Counts the elements added to and removed from `old` to get `new`,
+/// treating both as multisets. Stops counting once the count exceeds
+/// `limit`.
+pub fn change_count<'a, C, T>(old: &'a C, new: &'a C, limit: usize) -> usize
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq + 'a,
+{
+    if in_order_eq(old, new) {
+        return 0;
+    }
+    let mut unmatched: Vec<Option<&T>> = new.into_iter().map(Some).collect();
+    let mut count = 0;
+    for item in old {
+        match unmatched.iter_mut().find(|a| **a == Some(item)) {
+            Some(matched) => *matched = None,
+            None => {
+                count += 1;
+                if count > limit {
+                    return count;
+                }
+            }
+        }
+    }
+    count + unmatched.iter().filter(|a| a.is_some()).count()
+}
+
+/// Whether `old` and `new` hold equal elements in the same order, which an
+/// unchanged collection usually does. Checked without allocating, so the
+/// multiset comparisons only allocate once something changed.
+pub fn in_order_eq<'a, C, T>(old: &'a C, new: &'a C) -> bool
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq + 'a,
+{
+    old.into_iter().eq(new)
+}
+
+/// Whether `old` and `new` hold the same elements, ignoring order.
+pub fn multiset_eq<'a, C, T>(old: &'a C, new: &'a C) -> bool
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq + 'a,
+{
+    change_count(old, new, 0) == 0
+}
+
+/// The elements of `remove` that aren't in `collection`, formatted with
+/// `Debug`, treating both as multisets.
+pub fn missing_removals<'a, C, T>(collection: &'a C, remove: &[T]) -> Vec<String>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq + fmt::Debug + 'a,
+{
+    let mut present: Vec<Option<&T>> = collection.into_iter().map(Some).collect();
+    remove
+        .iter()
+        .filter(
+            |item| match present.iter_mut().find(|a| **a == Some(*item)) {
+                Some(matched) => {
+                    *matched = None;
+                    false
+                }
+                None => true,
+            },
+        )
+        .map(|item| format!("{:?}", item))
+        .collect()
+}
+
+/// Why a generated `try_apply_delta` failed, as [`Error::Apply`]: the delta
+/// removes elements that aren't present, so it was likely computed against
+/// a different base state. Nothing is applied in that case.
+///
+/// [`Error::Apply`]: crate::Error::Apply
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownRemovals {
+    /// Per field, the removed elements that weren't present, formatted with
+    /// `Debug`.
+    pub fields: Vec<(&'static str, Vec<String>)>,
+}
+
+impl fmt::Display for UnknownRemovals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the delta removes elements that aren't present:")?;
+        for (field, elements) in &self.fields {
+            write!(f, " `{}`: [{}]", field, elements.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownRemovals {}
+
+/// The delta of an unordered collection of strings, in which an element
+/// removed and a similar element added are paired up as a change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct RenameDelta<T> {
+    pub add: Vec<T>,
+    pub remove: Vec<T>,
+    /// Elements replaced by a similar one, as `(old, new)`. Applying the
+    /// delta replaces them in place.
+    pub changed: Vec<(T, T)>,
+}
+
+/// Diffs a collection of strings as a multiset, like `field_type =
+/// "unordered"`, pairing each removed element with the closest added one
+/// within `MAX_DISTANCE` character edits. Used for unordered fields with a
+/// `rename_distance`.
+///
+/// Renames count as a removal and an addition in `change_count`, like for
+/// plain unordered fields.
+pub struct RenameStrategy<const MAX_DISTANCE: usize>;
+
+impl<C, T, const MAX_DISTANCE: usize> DiffStrategy<C> for RenameStrategy<MAX_DISTANCE>
+where
+    C: IntoIterator<Item = T> + FromIterator<T>,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: AsRef<str> + PartialEq,
+{
+    type Delta = RenameDelta<T>;
+
+    fn diff(old: C, new: C) -> Option<RenameDelta<T>> {
+        let mut add: Vec<T> = new.into_iter().collect();
+        let mut remove: Vec<T> = vec![];
+        for item in old {
+            match add.iter().position(|a| *a == item) {
+                Some(index) => {
+                    add.remove(index);
+                }
+                None => remove.push(item),
+            }
+        }
+        let mut changed = vec![];
+        let mut unpaired = vec![];
+        for removed in remove {
+            let closest = add
+                .iter()
+                .enumerate()
+                .filter_map(|(i, added)| {
+                    let distance = edit_distance(removed.as_ref(), added.as_ref(), MAX_DISTANCE)?;
+                    Some((distance, i))
+                })
+                .min();
+            match closest {
+                Some((_, index)) => changed.push((removed, add.remove(index))),
+                None => unpaired.push(removed),
+            }
+        }
+        if add.is_empty() && unpaired.is_empty() && changed.is_empty() {
+            None
+        } else {
+            Some(RenameDelta {
+                add,
+                remove: unpaired,
+                changed,
+            })
+        }
+    }
+
+    fn apply(target: &mut C, delta: RenameDelta<T>) {
+        let RenameDelta {
+            add,
+            mut remove,
+            mut changed,
+        } = delta;
+        let og = std::mem::replace(target, std::iter::empty().collect());
+        let mut items = vec![];
+        for i in og {
+            if let Some(index) = remove.iter().position(|r| *r == i) {
+                remove.remove(index);
+            } else if let Some(index) = changed.iter().position(|(old, _)| *old == i) {
+                items.push(changed.remove(index).1);
+            } else {
+                items.push(i);
+            }
+        }
+        // Changes of elements that weren't present still add the new one.
+        items.extend(changed.into_iter().map(|(_, new)| new));
+        items.extend(add);
+        *target = items.into_iter().collect();
+    }
+
+    fn change_count(old: &C, new: &C, limit: usize) -> usize {
+        change_count(old, new, limit)
+    }
+
+    fn compose(first: RenameDelta<T>, second: RenameDelta<T>) -> RenameDelta<T> {
+        let RenameDelta {
+            mut add,
+            mut remove,
+            mut changed,
+        } = first;
+        for removed in second.remove {
+            if let Some(index) = add.iter().position(|a| *a == removed) {
+                add.remove(index);
+            } else if let Some(index) = changed.iter().position(|(_, new)| *new == removed) {
+                remove.push(changed.remove(index).0);
+            } else {
+                remove.push(removed);
+            }
+        }
+        for (old, new) in second.changed {
+            if let Some(added) = add.iter_mut().find(|a| **a == old) {
+                *added = new;
+            } else if let Some(change) = changed.iter_mut().find(|(_, n)| *n == old) {
+                change.1 = new;
+            } else {
+                changed.push((old, new));
+            }
+        }
+        add.extend(second.add);
+        RenameDelta {
+            add,
+            remove,
+            changed,
+        }
+    }
+}
+
+/// The number of single character insertions, deletions and substitutions
+/// turning `a` into `b`, or `None` if it exceeds `max`.
+pub fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|&min| min > max) {
+            return None;
+        }
+        previous = current;
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_character_edits() {
+        assert_eq!(edit_distance("kitchen", "kitchen", 2), Some(0));
+        assert_eq!(edit_distance("kitchen", "kitchens", 2), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance("", "abc", 2), None);
+    }
+
+    #[test]
+    fn rename_compose_matches_sequential_apply() {
+        type Strategy = RenameStrategy<2>;
+        let delta = |old: &Vec<&'static str>, new: &Vec<&'static str>| {
+            <Strategy as DiffStrategy<Vec<_>>>::diff(old.clone(), new.clone()).unwrap()
+        };
+        let a = vec!["den", "hall", "attic"];
+        let b = vec!["den 2", "hall", "porch"];
+        let c = vec!["den 3", "garage"];
+        let mut sequential = a.clone();
+        Strategy::apply(&mut sequential, delta(&a, &b));
+        Strategy::apply(&mut sequential, delta(&b, &c));
+        assert!(multiset_eq(&sequential, &c));
+        let mut composed = a.clone();
+        let delta = <Strategy as DiffStrategy<Vec<_>>>::compose(delta(&a, &b), delta(&b, &c));
+        Strategy::apply(&mut composed, delta);
+        assert!(multiset_eq(&composed, &c));
+    }
+
+    #[test]
+    fn counts_multiset_difference() {
+        assert_eq!(change_count(&vec![1, 2, 2], &vec![2, 3], usize::MAX), 3);
+        assert_eq!(change_count(&vec![1, 2], &vec![2, 1], usize::MAX), 0);
+        assert_eq!(change_count(&vec![1, 2, 3, 4], &vec![], 1), 2);
+    }
+
+    #[test]
+    fn missing_removals_respect_multiplicity() {
+        assert_eq!(missing_removals(&vec![1, 2], &[2, 2, 3]), vec!["2", "3"]);
+        assert!(missing_removals(&vec![1, 2, 2], &[2, 2]).is_empty());
+    }
+
+    #[test]
+    fn multiset_equality_ignores_order() {
+        assert!(multiset_eq(&vec![1, 2, 2], &vec![2, 1, 2]));
+        assert!(!multiset_eq(&vec![1, 2, 2], &vec![1, 2]));
+    }
+}
@@ -0,0 +1,280 @@
+//! Structural diffs of `serde_json::Value`s, for `field_type = "json"`
+//! fields holding free-form attribute bags. Objects are diffed per key, so
+//! changing one attribute only sends that attribute.
+//!
+//! Arrays are replaced wholesale unless the field is marked
+//! `arrays = "splice"`, in which case they are diffed like `ordered` fields.
+
+use crate::strategy::DiffStrategy;
+use crate::{ordered, Splice};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The delta of a `serde_json::Value`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ValueDelta {
+    /// Replaces the value.
+    Replace(Value),
+    /// Removes the `remove` keys of an object, and then changes the
+    /// `change` keys, inserting keys that aren't present. Applied to
+    /// anything but an object, it starts from an empty one.
+    Object {
+        remove: Vec<String>,
+        change: Vec<(String, ValueDelta)>,
+    },
+    /// Splices an array. Applied to anything but an array, it starts from
+    /// an empty one.
+    Array(Vec<Splice<Value>>),
+}
+
+/// Diffs a `serde_json::Value` per object key, replacing arrays wholesale.
+pub struct ValueStrategy;
+
+/// Diffs a `serde_json::Value` per object key, splicing arrays.
+pub struct SplicedValueStrategy;
+
+impl DiffStrategy<Value> for ValueStrategy {
+    type Delta = ValueDelta;
+
+    fn diff(old: Value, new: Value) -> Option<ValueDelta> {
+        diff(old, new, false)
+    }
+
+    fn apply(target: &mut Value, delta: ValueDelta) {
+        apply(target, delta)
+    }
+
+    fn change_count(old: &Value, new: &Value, limit: usize) -> usize {
+        change_count(old, new, limit, false)
+    }
+
+    fn compose(first: ValueDelta, second: ValueDelta) -> ValueDelta {
+        compose(first, second)
+    }
+}
+
+impl DiffStrategy<Value> for SplicedValueStrategy {
+    type Delta = ValueDelta;
+
+    fn diff(old: Value, new: Value) -> Option<ValueDelta> {
+        diff(old, new, true)
+    }
+
+    fn apply(target: &mut Value, delta: ValueDelta) {
+        apply(target, delta)
+    }
+
+    fn change_count(old: &Value, new: &Value, limit: usize) -> usize {
+        change_count(old, new, limit, true)
+    }
+
+    fn compose(first: ValueDelta, second: ValueDelta) -> ValueDelta {
+        compose(first, second)
+    }
+}
+
+fn diff(old: Value, new: Value, splice_arrays: bool) -> Option<ValueDelta> {
+    match (old, new) {
+        (Value::Object(mut old), Value::Object(new)) => {
+            let mut change = vec![];
+            for (key, value) in new {
+                match old.remove(&key) {
+                    Some(previous) => {
+                        if let Some(delta) = diff(previous, value, splice_arrays) {
+                            change.push((key, delta));
+                        }
+                    }
+                    None => change.push((key, ValueDelta::Replace(value))),
+                }
+            }
+            let remove: Vec<String> = old.into_iter().map(|(key, _)| key).collect();
+            if remove.is_empty() && change.is_empty() {
+                None
+            } else {
+                Some(ValueDelta::Object { remove, change })
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if splice_arrays => {
+            let splices = Splice::diff(old, new);
+            if splices.is_empty() {
+                None
+            } else {
+                Some(ValueDelta::Array(splices))
+            }
+        }
+        (old, new) => {
+            if old == new {
+                None
+            } else {
+                Some(ValueDelta::Replace(new))
+            }
+        }
+    }
+}
+
+fn apply(target: &mut Value, delta: ValueDelta) {
+    match delta {
+        ValueDelta::Replace(value) => *target = value,
+        ValueDelta::Object { remove, change } => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let object = target.as_object_mut().unwrap();
+            for key in remove {
+                object.remove(&key);
+            }
+            for (key, delta) in change {
+                apply(object.entry(key).or_insert(Value::Null), delta);
+            }
+        }
+        ValueDelta::Array(splices) => {
+            if !target.is_array() {
+                *target = Value::Array(vec![]);
+            }
+            let array = target.as_array_mut().unwrap();
+            for splice in splices {
+                splice.apply(array);
+            }
+        }
+    }
+}
+
+/// One change per changed leaf value, array splices counted as for
+/// `ordered` fields.
+fn change_count(old: &Value, new: &Value, limit: usize, splice_arrays: bool) -> usize {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut count = 0;
+            for (key, value) in new {
+                if count > limit {
+                    return count;
+                }
+                count += match old.get(key) {
+                    Some(previous) => change_count(previous, value, limit - count, splice_arrays),
+                    None => 1,
+                };
+            }
+            count + old.keys().filter(|key| !new.contains_key(*key)).count()
+        }
+        (Value::Array(old), Value::Array(new)) if splice_arrays => ordered::change_count(old, new),
+        (old, new) => (old != new) as usize,
+    }
+}
+
+fn compose(first: ValueDelta, second: ValueDelta) -> ValueDelta {
+    match (first, second) {
+        (_, ValueDelta::Replace(value)) => ValueDelta::Replace(value),
+        (ValueDelta::Replace(mut value), delta) => {
+            apply(&mut value, delta);
+            ValueDelta::Replace(value)
+        }
+        (
+            ValueDelta::Object {
+                mut remove,
+                mut change,
+            },
+            ValueDelta::Object {
+                remove: second_remove,
+                change: second_change,
+            },
+        ) => {
+            for removed in second_remove {
+                change.retain(|(key, _)| *key != removed);
+                // `first` may have removed it and then inserted it again.
+                if !remove.contains(&removed) {
+                    remove.push(removed);
+                }
+            }
+            for (key, delta) in second_change {
+                match change.iter().position(|(k, _)| *k == key) {
+                    Some(index) => {
+                        let (key, previous) = change.remove(index);
+                        change.insert(index, (key, compose(previous, delta)));
+                    }
+                    None => change.push((key, delta)),
+                }
+            }
+            ValueDelta::Object { remove, change }
+        }
+        (ValueDelta::Array(mut splices), ValueDelta::Array(second)) => {
+            splices.extend(second);
+            ValueDelta::Array(splices)
+        }
+        // Applying either kind to the other's result starts from an empty
+        // object or array, so `first` has no effect.
+        (_, second) => second,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn objects_are_diffed_per_key() {
+        let old = json!({ "name": "lamp", "attrs": { "color": "red", "level": 3 }, "tags": [1] });
+        let new = json!({ "name": "lamp", "attrs": { "color": "blue" }, "tags": [1, 2] });
+        let delta = ValueStrategy::diff(old.clone(), new.clone()).unwrap();
+        assert_eq!(
+            delta,
+            ValueDelta::Object {
+                remove: vec![],
+                change: vec![
+                    (
+                        "attrs".into(),
+                        ValueDelta::Object {
+                            remove: vec!["level".into()],
+                            change: vec![("color".into(), ValueDelta::Replace(json!("blue")))],
+                        }
+                    ),
+                    ("tags".into(), ValueDelta::Replace(json!([1, 2]))),
+                ],
+            }
+        );
+        let mut applied = old.clone();
+        ValueStrategy::apply(&mut applied, delta);
+        assert_eq!(applied, new);
+        assert_eq!(ValueStrategy::change_count(&old, &new, usize::MAX), 3);
+    }
+
+    #[test]
+    fn spliced_arrays_send_only_the_changed_elements() {
+        let old = json!({ "scenes": [1, 2, 3] });
+        let new = json!({ "scenes": [1, 4, 3] });
+        let delta = SplicedValueStrategy::diff(old.clone(), new.clone()).unwrap();
+        let mut applied = old;
+        SplicedValueStrategy::apply(&mut applied, delta.clone());
+        assert_eq!(applied, new);
+        assert_eq!(
+            delta,
+            ValueDelta::Object {
+                remove: vec![],
+                change: vec![(
+                    "scenes".into(),
+                    ValueDelta::Array(vec![Splice {
+                        start: 1,
+                        delete_count: 1,
+                        insert: vec![json!(4)],
+                    }])
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let a = json!({ "a": 1, "b": { "c": [1] }, "d": "x" });
+        let b = json!({ "b": { "c": [1, 2], "e": null }, "d": "y" });
+        let c = json!({ "a": 2, "b": { "e": true }, "d": ["z"] });
+        let mut composed = a.clone();
+        SplicedValueStrategy::apply(
+            &mut composed,
+            SplicedValueStrategy::compose(
+                SplicedValueStrategy::diff(a, b.clone()).unwrap(),
+                SplicedValueStrategy::diff(b, c.clone()).unwrap(),
+            ),
+        );
+        assert_eq!(composed, c);
+    }
+}
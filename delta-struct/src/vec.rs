@@ -0,0 +1,215 @@
+//! `Delta` for `Vec<T>` where `T` itself implements `Delta`, matching
+//! elements by position. Use a `keyed` field instead when elements move
+//! around.
+
+use crate::{ApplyDelta, ComputeDelta, Delta};
+use std::fmt;
+
+/// The delta of a `Vec<T>` of nested delta types. Applying it truncates the
+/// vector to `keep` elements, patches the elements at the `patch`
+/// positions, and then appends the `append` elements.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize, T::Output: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>, T::Output: serde::Deserialize<'de>"
+    ))
+)]
+pub struct VecDelta<T: ComputeDelta> {
+    /// The number of existing elements kept.
+    pub keep: usize,
+    pub patch: Vec<(usize, T::Output)>,
+    pub append: Vec<T>,
+}
+
+impl<T: ComputeDelta> ComputeDelta for Vec<T> {
+    type Output = VecDelta<T>;
+
+    fn delta(old: Self, new: Self) -> Option<VecDelta<T>> {
+        let old_len = old.len();
+        let keep = old_len.min(new.len());
+        let mut new = new.into_iter();
+        let patch: Vec<(usize, T::Output)> = old
+            .into_iter()
+            .zip(new.by_ref())
+            .enumerate()
+            .filter_map(|(i, (old, new))| Some((i, T::delta(old, new)?)))
+            .collect();
+        let append: Vec<T> = new.collect();
+        if keep == old_len && patch.is_empty() && append.is_empty() {
+            None
+        } else {
+            Some(VecDelta {
+                keep,
+                patch,
+                append,
+            })
+        }
+    }
+
+    /// One change per added or removed element, and the nested count for
+    /// the elements present in both.
+    fn change_count(old: &Self, new: &Self, limit: usize) -> usize {
+        let mut count = old.len().abs_diff(new.len());
+        for (old, new) in old.iter().zip(new) {
+            if count > limit {
+                return count;
+            }
+            count += T::change_count(old, new, limit - count);
+        }
+        count
+    }
+}
+
+impl<T: Delta> ApplyDelta<VecDelta<T>> for Vec<T> {
+    fn apply_delta(&mut self, delta: VecDelta<T>) {
+        self.truncate(delta.keep);
+        for (i, patch) in delta.patch {
+            if let Some(item) = self.get_mut(i) {
+                item.apply_delta(patch);
+            }
+        }
+        self.extend(delta.append);
+    }
+
+    fn compose_delta(first: VecDelta<T>, second: VecDelta<T>) -> VecDelta<T> {
+        let VecDelta {
+            mut keep,
+            mut patch,
+            mut append,
+        } = first;
+        if second.keep <= keep {
+            keep = second.keep;
+            patch.retain(|(i, _)| *i < keep);
+            append.clear();
+        } else {
+            append.truncate(second.keep - keep);
+        }
+        for (i, delta) in second.patch {
+            if i >= keep {
+                if let Some(item) = append.get_mut(i - keep) {
+                    item.apply_delta(delta);
+                }
+            } else if let Some(index) = patch.iter().position(|(p, _)| *p == i) {
+                let (p, previous) = patch.remove(index);
+                patch.insert(index, (p, T::compose_delta(previous, delta)));
+            } else {
+                patch.push((i, delta));
+            }
+        }
+        append.extend(second.append);
+        VecDelta {
+            keep,
+            patch,
+            append,
+        }
+    }
+}
+
+impl<T: ComputeDelta> Clone for VecDelta<T>
+where
+    T: Clone,
+    T::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        VecDelta {
+            keep: self.keep,
+            patch: self.patch.clone(),
+            append: self.append.clone(),
+        }
+    }
+}
+
+impl<T: ComputeDelta> fmt::Debug for VecDelta<T>
+where
+    T: fmt::Debug,
+    T::Output: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VecDelta")
+            .field("keep", &self.keep)
+            .field("patch", &self.patch)
+            .field("append", &self.append)
+            .finish()
+    }
+}
+
+impl<T: ComputeDelta> PartialEq for VecDelta<T>
+where
+    T: PartialEq,
+    T::Output: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.keep == other.keep && self.patch == other.patch && self.append == other.append
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: ComputeDelta> arbitrary::Arbitrary<'a> for VecDelta<T>
+where
+    T: arbitrary::Arbitrary<'a>,
+    T::Output: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(VecDelta {
+            keep: u.arbitrary()?,
+            patch: u.arbitrary()?,
+            append: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Step {
+        minute: u16,
+        level: u8,
+    }
+
+    fn steps(levels: &[u8]) -> Vec<Step> {
+        levels
+            .iter()
+            .enumerate()
+            .map(|(i, &level)| Step {
+                minute: i as u16 * 15,
+                level,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn elements_are_patched_by_position() {
+        let old = steps(&[10, 20, 30]);
+        let new = steps(&[10, 25]);
+        let delta = Vec::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta.keep, 2);
+        assert_eq!(delta.patch.len(), 1);
+        assert!(delta.append.is_empty());
+        assert_eq!(Vec::change_count(&old, &new, usize::MAX), 2);
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+        assert_eq!(Vec::delta(new.clone(), new), None);
+    }
+
+    #[test]
+    fn compose_matches_sequential_apply() {
+        let cases = [
+            (steps(&[1, 2]), steps(&[1, 3, 4, 5]), steps(&[6, 3, 7])),
+            (steps(&[1, 2, 3]), steps(&[4]), steps(&[4, 5, 6])),
+            (steps(&[1, 2, 3]), steps(&[1, 2, 3, 4]), steps(&[9])),
+        ];
+        for (a, b, c) in cases.iter() {
+            let first = Vec::delta(a.clone(), b.clone()).unwrap();
+            let second = Vec::delta(b.clone(), c.clone()).unwrap();
+            let mut composed = a.clone();
+            composed.apply_delta(Vec::compose_delta(first, second));
+            assert_eq!(&composed, c);
+        }
+    }
+}
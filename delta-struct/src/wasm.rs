@@ -0,0 +1,93 @@
+//! JavaScript bindings for delta structs, so a browser front-end compiled
+//! to WebAssembly computes and applies the same deltas as a Rust backend.
+//! Structs deriving with `#[delta_struct(wasm)]` get `to_js` and `from_js`
+//! on their delta struct, and two exported functions named after the
+//! struct in snake case, or after the prefix given with `wasm = "prefix"`:
+//!
+//! ```js
+//! const delta = foo_delta_compute(old, new); // null if nothing changed
+//! const updated = foo_delta_apply(old, delta);
+//! ```
+//!
+//! Values cross the boundary as plain JavaScript objects, in the shape of
+//! their JSON serialization, so the struct must implement `Serialize` and
+//! `Deserialize`. `wasm` implies `serde`. Requires the `wasm` feature.
+
+use crate::Delta;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[doc(hidden)]
+pub use wasm_bindgen;
+pub use wasm_bindgen::JsValue;
+
+/// Converts `value` into a JavaScript object.
+pub fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(value).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    js_sys::JSON::parse(&json)
+}
+
+/// Converts a JavaScript object into a `T`.
+pub fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, JsValue> {
+    let json = String::from(js_sys::JSON::stringify(value)?);
+    serde_json::from_str(&json).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// The delta from `old` to `new` as a JavaScript object, or `null` if they
+/// are equal. Implements the generated `_delta_compute`.
+pub fn compute<T>(old: &JsValue, new: &JsValue) -> Result<JsValue, JsValue>
+where
+    T: Delta + DeserializeOwned,
+    T::Output: Serialize,
+{
+    match T::delta(from_js(old)?, from_js(new)?) {
+        Some(delta) => to_js(&delta),
+        None => Ok(JsValue::NULL),
+    }
+}
+
+/// `target` with `delta` applied, as a JavaScript object. A `null` delta
+/// changes nothing. Implements the generated `_delta_apply`.
+pub fn apply<T>(target: &JsValue, delta: &JsValue) -> Result<JsValue, JsValue>
+where
+    T: Delta + Serialize + DeserializeOwned,
+    T::Output: DeserializeOwned,
+{
+    let mut target: T = from_js(target)?;
+    if !delta.is_null() {
+        target.apply_delta(from_js(delta)?);
+    }
+    to_js(&target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, crate::Delta, serde::Serialize, serde::Deserialize)]
+    #[delta_struct(wasm)]
+    struct Lamp {
+        brightness: u8,
+        color: String,
+    }
+
+    #[derive(Clone, crate::Delta, serde::Serialize, serde::Deserialize)]
+    #[delta_struct(wasm = "porch_light")]
+    struct PorchLight {
+        on: bool,
+    }
+
+    // `JsValue`s only exist inside a JavaScript host, so natively the
+    // bindings can only be checked for their signatures.
+    #[test]
+    fn bindings_are_exported() {
+        type Binding = fn(JsValue, JsValue) -> Result<JsValue, JsValue>;
+        let _: [Binding; 4] = [
+            lamp_delta_compute,
+            lamp_delta_apply,
+            porch_light_delta_compute,
+            porch_light_delta_apply,
+        ];
+        let _: fn(&LampDelta) -> Result<JsValue, JsValue> = LampDelta::to_js;
+        let _: fn(&JsValue) -> Result<PorchLightDelta, JsValue> = PorchLightDelta::from_js;
+    }
+}